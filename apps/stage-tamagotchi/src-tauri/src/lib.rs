@@ -30,6 +30,12 @@ pub fn run() {
     .plugin(tauri_plugin_window_state::Builder::default().build())
     .plugin(tauri_plugin_positioner::init())
     // Project AIRI plugins
+    .plugin(tauri_plugin_ipc_audio_capture::init())
+    .plugin(tauri_plugin_ipc_audio_kws::init())
+    .plugin(airi_audio_models::init())
+    .plugin(tauri_plugin_ipc_audio_preprocess::init())
+    .plugin(tauri_plugin_ipc_audio_prosody_ort::init())
+    .plugin(tauri_plugin_ipc_audio_speaker_ort::init())
     .plugin(tauri_plugin_ipc_audio_transcription_ort::init())
     .plugin(tauri_plugin_ipc_audio_tts_ort::init())
     .plugin(tauri_plugin_ipc_audio_vad_ort::init())
@@ -0,0 +1,84 @@
+import type { SpotterOptions, Wake } from './audio-kws'
+import type { AudioError } from './audio-models'
+import type { PreprocessOptions } from './audio-preprocess'
+import type { Prosody } from './audio-prosody'
+import type { DiarizationOptions, SpeakerMatch } from './audio-speaker'
+import type { GuardConfig, TranscriptionResult } from './audio-transcription'
+import type { SegmenterOptions } from './audio-vad'
+
+import { Channel, invoke } from '@tauri-apps/api/core'
+
+/** What the commands of the plugin reject with, switch on its `code` */
+export type { AudioError }
+
+export interface InputDevice {
+  name: string
+  isDefault: boolean
+  /** Format the device is opened with */
+  sampleRate: number
+  channels: number
+}
+
+/**
+ * An input device, the default one without `name`, or a WAV file played as if it was a device,
+ * as fast as possible unless `realtime`
+ */
+export type CaptureSource
+  = | { type: 'device', name?: string }
+    | { type: 'file', path: string, realtime?: boolean }
+
+export interface CaptureOptions {
+  /** Noise suppression and echo cancellation before VAD and transcription, both off by default */
+  preprocess?: PreprocessOptions
+  segmenter?: SegmenterOptions
+  /** Speech segments are transcribed with the default Whisper model unless `false` */
+  transcribe?: boolean
+  language?: string
+  guards?: GuardConfig
+  /** Stays idle until an enrolled keyword is said, only speech from then on is transcribed */
+  wakeWord?: SpotterOptions | null
+  /** How long speech is transcribed after the keyword or the latest transcript, 10000 by default */
+  awakeMs?: number
+  /** Transcripts are tagged with who said them, needs the speaker model loaded */
+  speakers?: DiarizationOptions | null
+  /** Transcripts come with pitch, loudness, speaking rate and, with an emotion model loaded, emotion */
+  prosody?: boolean
+}
+
+/** Positions are sample offsets at 16 kHz since the capture started, padding included */
+export type CaptureEvent
+  = | { type: 'started', sampleRate: number, channels: number }
+    | { type: 'speech-start', start: number }
+    | { type: 'speech-end', start: number, end: number }
+    | ({ type: 'wake' } & Wake)
+    | ({ type: 'transcript', start: number, end: number, speaker: SpeakerMatch | null, prosody: Prosody | null } & TranscriptionResult)
+    | { type: 'error', message: string }
+    | { type: 'stopped' }
+
+export async function listInputDevices(): Promise<InputDevice[]> {
+  return await invoke('plugin:ipc-audio-capture|list_input_devices')
+}
+
+/**
+ * Captures audio natively and runs it through the loaded VAD and Whisper models in Rust, only
+ * events and transcripts come back. Returns the capture id.
+ */
+export async function startCapture(
+  onEvent: (event: CaptureEvent) => void,
+  source?: CaptureSource,
+  options?: CaptureOptions,
+): Promise<number> {
+  const channel = new Channel<CaptureEvent>()
+  channel.onmessage = onEvent
+
+  return await invoke('plugin:ipc-audio-capture|start_capture', {
+    source,
+    options,
+    onEvent: channel,
+  })
+}
+
+/** The speech going on is still transcribed, `stopped` is the last event */
+export async function stopCapture(captureId: number): Promise<void> {
+  return await invoke('plugin:ipc-audio-capture|stop_capture', { captureId })
+}
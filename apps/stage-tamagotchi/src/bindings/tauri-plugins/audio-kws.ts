@@ -0,0 +1,78 @@
+import type { UnlistenFn } from '@tauri-apps/api/event'
+
+import type { AudioError } from './audio-models'
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+
+/** What the commands of the plugin reject with, switch on its `code` */
+export type { AudioError }
+
+export interface KeywordInfo {
+  name: string
+  /** Recordings the keyword was enrolled from */
+  samples: number
+  /** Mean distance up to which the keyword counts as said, calibrated from the recordings */
+  threshold: number
+  durationMs: number
+}
+
+export interface SpotterOptions {
+  /** Names of the keywords to listen for, all enrolled ones by default */
+  keywords?: string[] | null
+  /** 0.5 by default, which is the calibrated threshold */
+  minConfidence?: number
+  /** Time after a wake in which no other one is reported, 1500 by default */
+  refractoryMs?: number
+}
+
+/** Sample offsets at 16 kHz since the session or capture started */
+export interface Wake {
+  keyword: string
+  confidence: number
+  start: number
+  end: number
+}
+
+/**
+ * Enrolls a keyword from a few recordings of the user saying it once each, mono at `sampleRate`
+ * (16000 by default). Three to five recordings work best. Replaces the keyword of the same name.
+ */
+export async function enrollKeyword(name: string, recordings: Float32Array[], sampleRate?: number): Promise<KeywordInfo> {
+  return await invoke('plugin:ipc-audio-kws|enroll_keyword', {
+    name,
+    recordings: recordings.map(recording => Array.from(recording)),
+    sampleRate,
+  })
+}
+
+export async function listKeywords(): Promise<KeywordInfo[]> {
+  return await invoke('plugin:ipc-audio-kws|list_keywords')
+}
+
+export async function removeKeyword(name: string): Promise<void> {
+  return await invoke('plugin:ipc-audio-kws|remove_keyword', { name })
+}
+
+/** Starts a session that listens for the enrolled keywords in 16 kHz mono audio */
+export async function createKwsSession(options?: SpotterOptions): Promise<number> {
+  return await invoke('plugin:ipc-audio-kws|create_kws_session', { options })
+}
+
+/** Appends audio of any length, returns the keywords said in it */
+export async function pushKwsSamples(sessionId: number, samples: Float32Array): Promise<Wake[]> {
+  return await invoke('plugin:ipc-audio-kws|push_kws_samples', {
+    sessionId,
+    samples: Array.from(samples),
+  })
+}
+
+/** Returns the keyword that was still waiting to be reported */
+export async function destroyKwsSession(sessionId: number): Promise<Wake[]> {
+  return await invoke('plugin:ipc-audio-kws|destroy_kws_session', { sessionId })
+}
+
+/** Keywords said in any session or capture */
+export async function onWake(listener: (wake: Wake) => void): Promise<UnlistenFn> {
+  return await listen<Wake>('tauri-plugins:tauri-plugin-ipc-audio-kws:wake', event => listener(event.payload))
+}
@@ -0,0 +1,212 @@
+// @ts-nocheck
+
+// This file was generated by [tauri-specta](https://github.com/oscartbeaumont/tauri-specta). Do not edit this file manually.
+
+/** user-defined commands **/
+
+
+export const commands = {
+async getModelHubConfig() : Promise<Result<HubConfigView, AudioError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-models|get_model_hub_config") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Sets where models are downloaded from, for the downloads of every plugin that start afterwards.
+ */
+async setModelHubConfig(config: HubConfig) : Promise<Result<null, AudioError>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:ipc-audio-models|set_model_hub_config", { config }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+}
+}
+
+/** user-defined events **/
+
+
+
+/** user-defined constants **/
+
+
+
+/** user-defined types **/
+
+/**
+ * What the commands of the audio plugins reject with, `{ code, message }` in the frontend.
+ * 
+ * The code is stable and tells what the user can do about the failure, the message is only for
+ * showing and logging. Errors of the downloads carry theirs through [`anyhow::Error`], see
+ * [`AudioError::load_failed`].
+ */
+export type AudioError = 
+/**
+ * The model the command needs has to be loaded first
+ */
+{ code: "model-not-loaded"; message: string } | 
+/**
+ * The model files are there but do not work, reloading downloads them again
+ */
+{ code: "model-load-failed"; message: string } | 
+/**
+ * The hub could not be reached or did not send the file, retrying may help
+ */
+{ code: "download-failed"; message: string } | 
+/**
+ * The repository needs a hub token that is allowed to read it
+ */
+{ code: "unauthorized"; message: string } | 
+/**
+ * The model is not downloaded and offline mode is on
+ */
+{ code: "offline"; message: string } | 
+/**
+ * Stopped on request, by `cancel_model_download` or an interruption, nothing to report
+ */
+{ code: "cancelled"; message: string } | 
+/**
+ * The arguments of the command are wrong
+ */
+{ code: "invalid-input"; message: string } | 
+/**
+ * The session, job or voice asked for does not exist (anymore), or the model file on the hub
+ */
+{ code: "not-found"; message: string } | 
+/**
+ * Running the model failed on valid input
+ */
+{ code: "inference-failed"; message: string } | 
+/**
+ * Anything else, e.g. the app failing to talk to the frontend
+ */
+{ code: "internal"; message: string }
+export type DownloadPhase = "downloading" | "finished" | 
+/**
+ * Stopped by `cancel_model_download`, the partial file is removed
+ */
+"cancelled" | 
+/**
+ * The partial file is removed, the command that loads the model returns why
+ */
+"failed"
+/**
+ * Where models are downloaded from. Settings that are not given fall back to the environment
+ * variables the Hugging Face tools read: `HF_ENDPOINT`, `HF_TOKEN` (or the token
+ * `huggingface-cli login` saved), `HTTPS_PROXY` / `ALL_PROXY` and `HF_HUB_OFFLINE`.
+ */
+export type HubConfig = { 
+/**
+ * Base URL of the hub or of a mirror of it, e.g. `https://hf-mirror.com`
+ */
+endpoint?: string | null; 
+/**
+ * Access token for gated and private repositories. Left out when setting the configuration,
+ * the token set before is kept, an empty one removes it.
+ */
+token?: string | null; 
+/**
+ * `http://`, `socks5://` or `socks5h://` proxy, with `user:password@` when it needs one
+ */
+proxy?: string | null; 
+/**
+ * Only use models that are on disk already, never connect
+ */
+offline?: boolean }
+/**
+ * What the frontend gets to see of the [`HubConfig`], the token never leaves the app.
+ */
+export type HubConfigView = { endpoint: string | null; 
+/**
+ * Whether a token was set, not one of the environment
+ */
+hasToken: boolean; proxy: string | null; offline: boolean }
+/**
+ * Progress of the download of one model file, the payload of the `load-model-*-progress` events
+ * of every audio plugin.
+ */
+export type ModelDownloadProgress = { 
+/**
+ * Repository of the file, which `cancel_model_download` takes
+ */
+modelId: string; file: string; phase: DownloadPhase; downloadedBytes: number; 
+/**
+ * Not known when the server does not send the size
+ */
+totalBytes: number | null; 
+/**
+ * Percent of the file, 0 until the size is known
+ */
+progress: number; 
+/**
+ * Average since the download started
+ */
+bytesPerSecond: number; 
+/**
+ * Seconds left at the average speed, when the size is known
+ */
+etaSeconds: number | null }
+
+/** tauri-specta globals **/
+
+import {
+	invoke as TAURI_INVOKE,
+	Channel as TAURI_CHANNEL,
+} from "@tauri-apps/api/core";
+import * as TAURI_API_EVENT from "@tauri-apps/api/event";
+import { type WebviewWindow as __WebviewWindow__ } from "@tauri-apps/api/webviewWindow";
+
+type __EventObj__<T> = {
+	listen: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.listen<T>>;
+	once: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.once<T>>;
+	emit: null extends T
+		? (payload?: T) => ReturnType<typeof TAURI_API_EVENT.emit>
+		: (payload: T) => ReturnType<typeof TAURI_API_EVENT.emit>;
+};
+
+export type Result<T, E> =
+	| { status: "ok"; data: T }
+	| { status: "error"; error: E };
+
+function __makeEvents__<T extends Record<string, any>>(
+	mappings: Record<keyof T, string>,
+) {
+	return new Proxy(
+		{} as unknown as {
+			[K in keyof T]: __EventObj__<T[K]> & {
+				(handle: __WebviewWindow__): __EventObj__<T[K]>;
+			};
+		},
+		{
+			get: (_, event) => {
+				const name = mappings[event as keyof T];
+
+				return new Proxy((() => {}) as any, {
+					apply: (_, __, [window]: [__WebviewWindow__]) => ({
+						listen: (arg: any) => window.listen(name, arg),
+						once: (arg: any) => window.once(name, arg),
+						emit: (arg: any) => window.emit(name, arg),
+					}),
+					get: (_, command: keyof __EventObj__<any>) => {
+						switch (command) {
+							case "listen":
+								return (arg: any) => TAURI_API_EVENT.listen(name, arg);
+							case "once":
+								return (arg: any) => TAURI_API_EVENT.once(name, arg);
+							case "emit":
+								return (arg: any) => TAURI_API_EVENT.emit(name, arg);
+						}
+					},
+				});
+			},
+		},
+	);
+}
@@ -0,0 +1,48 @@
+import type { AudioError } from './audio-models'
+
+import { invoke } from '@tauri-apps/api/core'
+
+/** What the commands of the plugin reject with, switch on its `code` */
+export type { AudioError }
+
+/** Both stages are off by default */
+export interface PreprocessOptions {
+  /** Suppresses stationary background noise */
+  denoise?: boolean
+  /** Removes what was pushed as echo reference, 16 kHz audio only */
+  echoCancellation?: boolean
+}
+
+/**
+ * Audio that starts playing through the speakers now, interleaved at any rate. Sessions with echo
+ * cancellation remove it from what they record. TTS utterances are pushed by the TTS plugin.
+ */
+export async function pushEchoReference(samples: Float32Array, sampleRate: number, channels?: number): Promise<void> {
+  return await invoke('plugin:ipc-audio-preprocess|push_echo_reference', {
+    samples: Array.from(samples),
+    sampleRate,
+    channels,
+  })
+}
+
+/** Playback was cut off, nothing is played from now on */
+export async function stopEchoReference(): Promise<void> {
+  return await invoke('plugin:ipc-audio-preprocess|stop_echo_reference')
+}
+
+/** Starts a session for audio sent over IPC, `sampleRate` is 16000 by default */
+export async function createPreprocessSession(options: PreprocessOptions, sampleRate?: number): Promise<number> {
+  return await invoke('plugin:ipc-audio-preprocess|create_preprocess_session', { sampleRate, options })
+}
+
+/** Returns the processed audio of every complete block of 16 ms, the rest waits for the next call */
+export async function processSamples(sessionId: number, samples: Float32Array): Promise<number[]> {
+  return await invoke('plugin:ipc-audio-preprocess|process_samples', {
+    sessionId,
+    samples: Array.from(samples),
+  })
+}
+
+export async function destroyPreprocessSession(sessionId: number): Promise<void> {
+  return await invoke('plugin:ipc-audio-preprocess|destroy_preprocess_session', { sessionId })
+}
@@ -0,0 +1,93 @@
+import type { UnlistenFn } from '@tauri-apps/api/event'
+
+import type { AudioError, ModelDownloadProgress } from './audio-models'
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+
+/** What the commands of the plugin reject with, switch on its `code` */
+export type { AudioError }
+
+export interface PitchStats {
+  meanHz: number
+  medianHz: number
+  /** 5th and 95th percentiles */
+  minHz: number
+  maxHz: number
+  rangeSemitones: number
+  /** Standard deviation around the median, low for monotone speech */
+  stdSemitones: number
+  /** Slope of the last 500 ms of voiced speech, positive when the voice rises at the end */
+  finalSlopeStPerS: number
+  voicedRatio: number
+}
+
+/** Levels in dB relative to full scale, of the frames with speech */
+export interface EnergyStats {
+  meanDb: number
+  maxDb: number
+  stdDb: number
+}
+
+export interface SpeakingRate {
+  syllablesPerSecond: number
+  /** From the transcript, when one was given */
+  wordsPerSecond: number | null
+}
+
+export interface Emotion {
+  /** As the model names it, e.g. `ang`, `angry` or `frustrated` */
+  label: string
+  confidence: number
+}
+
+export interface Prosody {
+  /** Without the silence around the speech */
+  durationMs: number
+  pitch: PitchStats | null
+  energy: EnergyStats
+  speakingRate: SpeakingRate
+  /** When an emotion model is loaded */
+  emotion: Emotion | null
+  /** What stands out, e.g. `sounds angry` or `speaks quickly` */
+  cues: string[]
+}
+
+/**
+ * Loads a speech emotion classifier in the Transformers.js layout, `config.json` with `id2label`
+ * and an ONNX audio classification model, such as a fine-tuned wav2vec2. Replaces the loaded one.
+ */
+export async function loadEmotionModel(modelId: string, revision?: string, modelFile?: string): Promise<void> {
+  return await invoke('plugin:ipc-audio-prosody-ort|load_ort_model_emotion', { modelId, revision, modelFile })
+}
+
+/** Progress of each file of the emotion model that is downloaded */
+export async function onModelDownloadProgress(listener: (progress: ModelDownloadProgress) => void): Promise<UnlistenFn> {
+  return await listen<ModelDownloadProgress>('tauri-plugins:tauri-plugin-ipc-audio-prosody-ort:load-model-emotion-progress', event => listener(event.payload))
+}
+
+/** By the `modelId` of the progress, `loadEmotionModel` then rejects */
+export async function cancelModelDownload(modelId: string): Promise<boolean> {
+  return await invoke('plugin:ipc-audio-prosody-ort|cancel_model_download', { modelId })
+}
+
+export async function unloadEmotionModel(): Promise<void> {
+  return await invoke('plugin:ipc-audio-prosody-ort|unload_ort_model_emotion')
+}
+
+/** How a speech segment, mono at `sampleRate` (16000 by default), was said. The transcript gives the word rate. */
+export async function analyzeProsody(samples: Float32Array, sampleRate?: number, text?: string): Promise<Prosody> {
+  return await invoke('plugin:ipc-audio-prosody-ort|analyze_prosody', {
+    samples: Array.from(samples),
+    sampleRate,
+    text,
+  })
+}
+
+/** The cues as a note for a language model prompt, e.g. `(sounds angry, speaks quickly)`, or an empty string */
+export function describeProsody(prosody: Prosody | null | undefined): string {
+  if (!prosody?.cues.length)
+    return ''
+
+  return `(${prosody.cues.join(', ')})`
+}
@@ -0,0 +1,91 @@
+import type { UnlistenFn } from '@tauri-apps/api/event'
+
+import type { AudioError, ModelDownloadProgress } from './audio-models'
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+
+/** What the commands of the plugin reject with, switch on its `code` */
+export type { AudioError }
+
+export interface SpeakerInfo {
+  /** `speaker-<n>`, stays the same across restarts */
+  id: string
+  name: string
+  /** Recordings and segments the voice was enrolled from */
+  samples: number
+}
+
+export interface DiarizationOptions {
+  /** Cosine similarity from which a segment is the voice of a known or earlier speaker, 0.5 by default */
+  threshold?: number
+  /** Unknown voices told apart at most, segments go to the closest one after that */
+  maxSpeakers?: number | null
+}
+
+export interface SpeakerMatch {
+  /** Id of an enrolled speaker, or `unknown-<n>` for the n-th unknown voice of the session */
+  speakerId: string
+  /** Only enrolled speakers have one */
+  name: string | null
+  /** Cosine similarity to the voice the segment was matched to, 1 for a new voice */
+  similarity: number
+}
+
+/** Downloads the WeSpeaker embedding model on first use */
+export async function loadSpeakerModel(): Promise<void> {
+  return await invoke('plugin:ipc-audio-speaker-ort|load_ort_model_speaker')
+}
+
+/** Progress of each file of the WeSpeaker model that is downloaded */
+export async function onModelDownloadProgress(listener: (progress: ModelDownloadProgress) => void): Promise<UnlistenFn> {
+  return await listen<ModelDownloadProgress>('tauri-plugins:tauri-plugin-ipc-audio-speaker-ort:load-model-wespeaker-progress', event => listener(event.payload))
+}
+
+/** `loadSpeakerModel` rejects when its download is stopped. Resolves to whether it was downloading */
+export async function cancelModelDownload(modelId: string): Promise<boolean> {
+  return await invoke('plugin:ipc-audio-speaker-ort|cancel_model_download', { modelId })
+}
+
+/**
+ * Enrolls the voice in a few recordings of one person, mono at `sampleRate` (16000 by default).
+ * Enrolling a name again adds to its voice. A few seconds of speech per recording work best.
+ */
+export async function enrollSpeaker(name: string, recordings: Float32Array[], sampleRate?: number): Promise<SpeakerInfo> {
+  return await invoke('plugin:ipc-audio-speaker-ort|enroll_speaker', {
+    name,
+    recordings: recordings.map(recording => Array.from(recording)),
+    sampleRate,
+  })
+}
+
+export async function listSpeakers(): Promise<SpeakerInfo[]> {
+  return await invoke('plugin:ipc-audio-speaker-ort|list_speakers')
+}
+
+export async function removeSpeaker(speakerId: string): Promise<void> {
+  return await invoke('plugin:ipc-audio-speaker-ort|remove_speaker', { speakerId })
+}
+
+/** Starts a session that tells the speakers of its segments apart */
+export async function createDiarizationSession(options?: DiarizationOptions): Promise<number> {
+  return await invoke('plugin:ipc-audio-speaker-ort|create_diarization_session', { options })
+}
+
+/** Who said a speech segment, `null` for segments under 500 ms */
+export async function identifySpeaker(sessionId: number, samples: Float32Array, sampleRate?: number): Promise<SpeakerMatch | null> {
+  return await invoke('plugin:ipc-audio-speaker-ort|identify_speaker', {
+    sessionId,
+    samples: Array.from(samples),
+    sampleRate,
+  })
+}
+
+/** Enrolls an unknown voice of a session under `name`, its later segments match the enrolled speaker */
+export async function nameSpeaker(sessionId: number, speakerId: string, name: string): Promise<SpeakerInfo> {
+  return await invoke('plugin:ipc-audio-speaker-ort|name_speaker', { sessionId, speakerId, name })
+}
+
+export async function destroyDiarizationSession(sessionId: number): Promise<void> {
+  return await invoke('plugin:ipc-audio-speaker-ort|destroy_diarization_session', { sessionId })
+}
@@ -1,12 +1,61 @@
-import { invoke } from '@tauri-apps/api/core'
+import type { UnlistenFn } from '@tauri-apps/api/event'
+
+import type { AudioError, ModelDownloadProgress } from './audio-models'
+import type { PreprocessOptions } from './audio-preprocess'
+
+import { Channel, invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+
+/** What the commands of the plugin reject with, switch on its `code` */
+export type { AudioError }
+
+/**
+ * Precision of the model files. fp16 is only faster on GPUs, int8/uint8/q4 are smaller and faster
+ * on CPUs at some accuracy cost.
+ */
+export type SttModelVariant = 'fp32' | 'fp16' | 'int8' | 'uint8' | 'q4'
+
+/**
+ * `default` serves one-shot transcriptions, jobs and streaming finals. An optional, faster
+ * `partial` model produces the partial results of streaming sessions in between.
+ */
+export type SttModelSlot = 'default' | 'partial'
+
+export type SttModelState
+  = | { state: 'loading', modelId: string, variant: SttModelVariant }
+    | { state: 'ready', modelId: string, variant: SttModelVariant }
+    | { state: 'failed', modelId: string, variant: SttModelVariant, error: string }
+    | { state: 'unloaded' }
+
+export type SttModelStateEvent = { slot: SttModelSlot } & SttModelState
+
+export const MODEL_STATE_EVENT = 'tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:model-state'
+
+export interface SttModelVariantInfo {
+  variant: SttModelVariant
+  /** Approximate download size in bytes */
+  size: number
+  installed: boolean
+  /** Bytes the encoder and decoder of this variant take in the cache */
+  sizeOnDisk: number
+}
 
 export interface SttModelInfo {
   id: string
   name: string
+  /** Approximate fp32 download size in bytes */
   size: number
   accuracy: 'high' | 'medium' | 'low'
   speed: 'fast' | 'medium' | 'slow'
+  /** English-only models can not translate or transcribe other languages */
+  multilingual: boolean
+  variants: SttModelVariantInfo[]
+  /** Whether any variant can be loaded without a download */
   installed: boolean
+  /** Bytes the whole model takes in the cache, including partial downloads */
+  sizeOnDisk: number
+  /** Variants of this model that are currently loaded, and into which slot */
+  loaded: { slot: SttModelSlot, variant: SttModelVariant }[]
 }
 
 export async function listModels(): Promise<SttModelInfo[]> {
@@ -17,18 +66,181 @@ export async function listInstalledModels(): Promise<string[]> {
   return await invoke('plugin:ipc-audio-transcription-ort|list_installed_models')
 }
 
-export async function loadWhisperModel(modelType: string): Promise<void> {
+/** Deletes one variant of a model, or all of its files without `variant`. Returns the freed bytes */
+export async function deleteModel(modelId: string, variant?: SttModelVariant): Promise<number> {
+  return await invoke('plugin:ipc-audio-transcription-ort|delete_model', {
+    modelId,
+    variant,
+  })
+}
+
+/**
+ * Loads a model into a slot, replacing the model the slot had once the new one is ready.
+ * Transcriptions keep using the previous model in the meantime.
+ */
+export async function loadWhisperModel(modelType: string, variant?: SttModelVariant, slot?: SttModelSlot): Promise<void> {
   return await invoke('plugin:ipc-audio-transcription-ort|load_ort_model_whisper', {
     modelType,
+    variant,
+    slot,
   })
 }
 
+/** Progress of each file of the Whisper model that is downloaded */
+export async function onModelDownloadProgress(listener: (progress: ModelDownloadProgress) => void): Promise<UnlistenFn> {
+  return await listen<ModelDownloadProgress>('tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress', event => listener(event.payload))
+}
+
+/** Takes a model id of `listModels` or the `modelId` of the progress, the slot keeps its current model */
+export async function cancelModelDownload(modelId: string): Promise<boolean> {
+  return await invoke('plugin:ipc-audio-transcription-ort|cancel_model_download', { modelId })
+}
+
+export async function unloadWhisperModel(slot?: SttModelSlot): Promise<void> {
+  return await invoke('plugin:ipc-audio-transcription-ort|unload_model', { slot })
+}
+
+/** Hallucination checks, set a threshold to `null` to turn its check off */
+export interface GuardConfig {
+  /** Drops silence, defaults to 0.6 */
+  noSpeechThreshold?: number | null
+  /** Silence also needs the average log probability below this, defaults to -1 */
+  logprobThreshold?: number
+  /** Stops decoding loops after this many repeats, defaults to 3 */
+  maxNgramRepeats?: number | null
+  /** Drops repetitive text, defaults to 2.4 */
+  compressionRatioThreshold?: number | null
+  /** Phrases dropped when they are the whole transcript, replaces the defaults */
+  blocklist?: string[]
+}
+
+export type TranscriptionGuard = 'no-speech' | 'repetition' | 'compression-ratio' | 'blocklist'
+
+export interface TranscriptionResult {
+  /** Empty when a guard rejected the transcript */
+  text: string
+  /** The guard that changed the result */
+  guard: TranscriptionGuard | null
+  noSpeechProb: number | null
+  avgLogprob: number
+  compressionRatio: number
+}
+
+export interface TranscribeOptions {
+  task?: 'transcribe' | 'translate'
+  /** Text to condition the decoder with, e.g. previous context or character names */
+  initialPrompt?: string
+  /** Words or phrases to bias the transcript towards */
+  hotwords?: string[]
+  /** Text the transcript is forced to start with */
+  prefix?: string
+  guards?: GuardConfig
+}
+
+export interface PcmFormat {
+  /** Defaults to 16000 */
+  sampleRate?: number
+  /** Number of interleaved channels, defaults to 1 */
+  channels?: number
+}
+
 export async function transcribe(
   chunk: Float32Array,
-  language?: string
-): Promise<string> {
+  language?: string,
+  options?: TranscribeOptions & PcmFormat
+): Promise<TranscriptionResult> {
   return await invoke('plugin:ipc-audio-transcription-ort|ipc_audio_transcription', {
     chunk: Array.from(chunk),
     language: language || 'en',
+    ...options,
   })
 }
+
+/** Transcribes an encoded audio file (WAV, FLAC, MP3 or Ogg Vorbis) */
+export async function transcribeEncoded(
+  encoded: ArrayBuffer | Uint8Array,
+  language?: string,
+  options?: TranscribeOptions
+): Promise<TranscriptionResult> {
+  return await invoke('plugin:ipc-audio-transcription-ort|ipc_audio_transcription', {
+    encoded: Array.from(encoded instanceof Uint8Array ? encoded : new Uint8Array(encoded)),
+    language: language || 'en',
+    ...options,
+  })
+}
+
+export interface StreamingOptions extends Omit<TranscribeOptions, 'prefix' | 'guards'> {
+  language?: string
+  /** How much new audio has to arrive before the model runs again, defaults to 1000 */
+  partialIntervalMs?: number
+  /** How often text is confirmed when a partial model is loaded, defaults to 3000 */
+  finalIntervalMs?: number
+  /** Buffer length after which confirmed audio is dropped, defaults to 15 */
+  maxBufferSecs?: number
+}
+
+export type TranscriptionSessionEvent
+  = | { type: 'partial', text: string }
+    | { type: 'final', text: string, start: number, end: number }
+
+/** With `preprocess`, pushed audio is cleaned up before it is transcribed */
+export async function startSession(
+  onEvent: (event: TranscriptionSessionEvent) => void,
+  options?: StreamingOptions,
+  preprocess?: PreprocessOptions,
+): Promise<number> {
+  const channel = new Channel<TranscriptionSessionEvent>()
+  channel.onmessage = onEvent
+
+  return await invoke('plugin:ipc-audio-transcription-ort|start_session', {
+    onEvent: channel,
+    options,
+    preprocess,
+  })
+}
+
+/** Appends 16 kHz mono samples to a session */
+export async function pushAudio(sessionId: number, pcm: Float32Array): Promise<void> {
+  return await invoke('plugin:ipc-audio-transcription-ort|push_audio', {
+    sessionId,
+    pcm: Array.from(pcm),
+  })
+}
+
+/** Flushes the remaining audio and returns the full transcript of the session */
+export async function endSession(sessionId: number): Promise<string> {
+  return await invoke('plugin:ipc-audio-transcription-ort|end_session', { sessionId })
+}
+
+export interface TranscriptionJobOptions extends Omit<TranscribeOptions, 'prefix'> {
+  language?: string
+  /** Transcript files written next to the source, defaults to both */
+  formats?: ('json' | 'srt')[]
+}
+
+export type TranscriptionJobStatus
+  = | { status: 'queued' }
+    | { status: 'running', progress: number }
+    | { status: 'completed', outputs: string[] }
+    | { status: 'failed', error: string }
+    | { status: 'cancelled' }
+
+export type TranscriptionJobEvent = { jobId: number, path: string } & TranscriptionJobStatus
+
+export const TRANSCRIPTION_JOB_EVENT = 'tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:transcription-job'
+
+/** Queues an audio file for transcription, follow it with {@link TRANSCRIPTION_JOB_EVENT} */
+export async function enqueueTranscriptionJob(path: string, options?: TranscriptionJobOptions): Promise<number> {
+  return await invoke('plugin:ipc-audio-transcription-ort|enqueue_transcription_job', {
+    path,
+    options,
+  })
+}
+
+export async function cancelTranscriptionJob(jobId: number): Promise<void> {
+  return await invoke('plugin:ipc-audio-transcription-ort|cancel_transcription_job', { jobId })
+}
+
+export async function listTranscriptionJobs(): Promise<TranscriptionJobEvent[]> {
+  return await invoke('plugin:ipc-audio-transcription-ort|list_transcription_jobs')
+}
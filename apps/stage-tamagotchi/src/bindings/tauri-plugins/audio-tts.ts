@@ -1,4 +1,12 @@
+import type { UnlistenFn } from '@tauri-apps/api/event'
+
+import type { AudioError, ModelDownloadProgress } from './audio-models'
+
 import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+
+/** What the commands of the plugin reject with, switch on its `code` */
+export type { AudioError }
 
 export interface TtsModelInfo {
   id: string
@@ -23,6 +31,17 @@ export interface SynthesizeOptions {
   volume?: number
 }
 
+/** An utterance cut off by a barge-in or `interruptPlayback` */
+export interface TtsInterrupted {
+  utteranceId: string
+  text: string
+  /** Estimated from the time played, without the last word if it was only partly said */
+  spokenText: string
+  spokenMs: number
+  /** `null` when the utterance was still being synthesized */
+  durationMs: number | null
+}
+
 export async function listModels(): Promise<TtsModelInfo[]> {
   return await invoke('plugin:ipc-audio-tts-ort|list_models')
 }
@@ -39,16 +58,51 @@ export async function loadModel(modelId: string): Promise<void> {
   return await invoke('plugin:ipc-audio-tts-ort|load_model', { modelId })
 }
 
+/** Progress of each file of the TTS model that is downloaded */
+export async function onModelDownloadProgress(listener: (progress: ModelDownloadProgress) => void): Promise<UnlistenFn> {
+  return await listen<ModelDownloadProgress>('tauri-plugins:tauri-plugin-ipc-audio-tts-ort:load-model-progress', event => listener(event.payload))
+}
+
+/** Takes a model id of `listModels` or the `modelId` of the progress, `loadModel` then rejects with `cancelled` */
+export async function cancelModelDownload(modelId: string): Promise<boolean> {
+  return await invoke('plugin:ipc-audio-tts-ort|cancel_model_download', { modelId })
+}
+
+/**
+ * With an `utteranceId` the utterance can be interrupted: synthesis rejects with `cancelled` if it
+ * is interrupted before the audio is ready, and its playback should be reported with
+ * `playbackStarted` and `playbackFinished`.
+ */
 export async function synthesize(
   text: string,
   voiceId: string,
-  options?: SynthesizeOptions
+  options?: SynthesizeOptions,
+  utteranceId?: string,
 ): Promise<Uint8Array> {
   const result = await invoke('plugin:ipc-audio-tts-ort|synthesize', {
     text,
     voiceId,
     options,
+    utteranceId,
   }) as number[]
 
   return new Uint8Array(result)
 }
+
+export async function playbackStarted(utteranceId: string): Promise<void> {
+  return await invoke('plugin:ipc-audio-tts-ort|playback_started', { utteranceId })
+}
+
+export async function playbackFinished(utteranceId: string): Promise<void> {
+  return await invoke('plugin:ipc-audio-tts-ort|playback_finished', { utteranceId })
+}
+
+/** Interrupts every tracked utterance, as a barge-in does */
+export async function interruptPlayback(): Promise<TtsInterrupted[]> {
+  return await invoke('plugin:ipc-audio-tts-ort|interrupt_playback')
+}
+
+/** Called for every interrupted utterance, its playback should stop */
+export async function onInterrupted(listener: (interrupted: TtsInterrupted) => void): Promise<UnlistenFn> {
+  return await listen<TtsInterrupted>('tauri-plugins:tauri-plugin-ipc-audio-tts-ort:interrupted', event => listener(event.payload))
+}
@@ -0,0 +1,157 @@
+import type { UnlistenFn } from '@tauri-apps/api/event'
+
+import type { AudioError, ModelDownloadProgress } from './audio-models'
+import type { PreprocessOptions } from './audio-preprocess'
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+
+/** What the commands of the plugin reject with, switch on its `code` */
+export type { AudioError }
+
+export interface VadFrame {
+  /** Position of the first sample of the window since the session started */
+  start: number
+  probability: number
+}
+
+/** Same parameters and defaults as Silero's `get_speech_timestamps` */
+export interface SegmenterOptions {
+  /** Windows at or above this probability are speech, 0.5 by default */
+  threshold?: number
+  /** Speech only ends below this probability, `threshold - 0.15` by default */
+  negThreshold?: number | null
+  minSpeechDurationMs?: number
+  minSilenceDurationMs?: number
+  speechPadMs?: number
+  /** Longer segments are split, not limited by default */
+  maxSpeechDurationS?: number | null
+}
+
+/** Sample offsets since the session started, padding included */
+export type SpeechEvent
+  = | { type: 'speech-start', start: number }
+    | { type: 'speech-end', start: number, end: number }
+
+export interface VadPushResult {
+  frames: VadFrame[]
+  events: SpeechEvent[]
+}
+
+/** When speech in any session counts as the user talking over the assistant */
+export interface BargeInOptions {
+  /** `true` by default */
+  enabled?: boolean
+  /** Mean speech probability since the segment started, 0.7 by default */
+  minProbability?: number
+  /** Time since the segment started, padding included, 400 by default */
+  minDurationMs?: number
+}
+
+/** Reported once per speech segment, the TTS plugin stops speaking when it happens */
+export interface BargeIn {
+  /** Sample offset of the segment start in the session it was detected in */
+  start: number
+  durationMs: number
+  probability: number
+}
+
+/** Sample offsets at `SpeechSegments.sampleRate`, padding included */
+export interface SpeechTimestamp {
+  start: number
+  end: number
+}
+
+export interface SpeechSegments {
+  /** Always 16000, all audio is resampled to it */
+  sampleRate: number
+  segments: SpeechTimestamp[]
+  /** The speech without the silence in between, when `returnAudio` was set */
+  audio: number[] | null
+}
+
+export type SpeechAudioSource
+  = | { samples: Float32Array, sampleRate?: number, channels?: number }
+    | { path: string }
+
+/** `energy` is a simpler detector used when the Silero model cannot be loaded */
+export type VadBackendKind = 'silero' | 'energy'
+
+/** Loads Silero VAD, falling back to the energy detector. Returns the backend in use. */
+export async function loadSileroVadModel(): Promise<VadBackendKind> {
+  return await invoke('plugin:ipc-audio-vad-ort|load_ort_model_silero_vad')
+}
+
+/** Progress of each file of the Silero VAD model that is downloaded */
+export async function onModelDownloadProgress(listener: (progress: ModelDownloadProgress) => void): Promise<UnlistenFn> {
+  return await listen<ModelDownloadProgress>('tauri-plugins:tauri-plugin-ipc-audio-vad-ort:load-model-silero-vad-progress', event => listener(event.payload))
+}
+
+/** Stops the download of the model by the `modelId` of its progress. Resolves to whether it was downloading */
+export async function cancelModelDownload(modelId: string): Promise<boolean> {
+  return await invoke('plugin:ipc-audio-vad-ort|cancel_model_download', { modelId })
+}
+
+export async function getVadBackend(): Promise<VadBackendKind | null> {
+  return await invoke('plugin:ipc-audio-vad-ort|get_vad_backend')
+}
+
+/**
+ * Starts a session that keeps the model state in Rust, `sampleRate` is 8000 or 16000 (default).
+ * With `preprocess`, pushed audio is cleaned up before the model sees it.
+ */
+export async function createVadSession(
+  sampleRate?: number,
+  options?: SegmenterOptions,
+  preprocess?: PreprocessOptions,
+): Promise<number> {
+  return await invoke('plugin:ipc-audio-vad-ort|create_vad_session', { sampleRate, options, preprocess })
+}
+
+/**
+ * Appends audio of any length, returns one frame per completed window of 512 samples
+ * (256 at 8 kHz) and the speech segment boundaries they settled. Leftover samples are kept for
+ * the next call.
+ */
+export async function pushSamples(sessionId: number, samples: Float32Array): Promise<VadPushResult> {
+  return await invoke('plugin:ipc-audio-vad-ort|push_samples', {
+    sessionId,
+    samples: Array.from(samples),
+  })
+}
+
+export async function resetVadSession(sessionId: number): Promise<void> {
+  return await invoke('plugin:ipc-audio-vad-ort|reset_vad_session', { sessionId })
+}
+
+/** Options missing from `options` go back to their defaults */
+export async function setBargeInOptions(options: BargeInOptions): Promise<void> {
+  return await invoke('plugin:ipc-audio-vad-ort|set_barge_in_options', { options })
+}
+
+export async function onBargeIn(listener: (bargeIn: BargeIn) => void): Promise<UnlistenFn> {
+  return await listen<BargeIn>('tauri-plugins:tauri-plugin-ipc-audio-vad-ort:barge-in', event => listener(event.payload))
+}
+
+/** Returns the events that end the speech still going on */
+export async function destroyVadSession(sessionId: number): Promise<SpeechEvent[]> {
+  return await invoke('plugin:ipc-audio-vad-ort|destroy_vad_session', { sessionId })
+}
+
+/**
+ * Finds the speech in a whole recording, either interleaved PCM at any rate or an audio file.
+ * Segments less than a window apart are merged.
+ */
+export async function detectSpeechSegments(
+  source: SpeechAudioSource,
+  options?: SegmenterOptions,
+  returnAudio?: boolean,
+): Promise<SpeechSegments> {
+  return await invoke('plugin:ipc-audio-vad-ort|detect_speech_segments', {
+    ...('samples' in source
+      ? { samples: Array.from(source.samples), sampleRate: source.sampleRate, channels: source.channels }
+      : { path: source.path }),
+    options,
+    returnAudio,
+  })
+}
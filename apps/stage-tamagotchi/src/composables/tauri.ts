@@ -2,6 +2,7 @@ import type { InvokeArgs, InvokeOptions } from '@tauri-apps/api/core'
 import type { EventCallback, EventName, UnlistenFn } from '@tauri-apps/api/event'
 import type { Monitor } from '@tauri-apps/api/window'
 
+import type { ModelDownloadProgress } from '../bindings/tauri-plugins/audio-models'
 import type { InvokeMethods, InvokeMethodShape } from '../tauri/invoke'
 import type { KeyCode } from '../tauri/rdev'
 
@@ -62,10 +63,10 @@ export interface AiriTamagotchiEvents extends Events {
   'tauri-plugins:tauri-plugin-window-pass-through-on-hover:window-frame': WindowFrame
   'tauri-plugins:tauri-plugin-window-pass-through-on-hover:pass-through-enabled': boolean
 
-  // from tauri-plugin-ipc-audio-transcription-ort
-  'tauri-plugins:tauri-plugin-ipc-audio-vad-ort:load-model-silero-vad-progress': [boolean, string, number, number, number]
   // from tauri-plugin-ipc-audio-vad-ort
-  'tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress': [boolean, string, number, number, number]
+  'tauri-plugins:tauri-plugin-ipc-audio-vad-ort:load-model-silero-vad-progress': ModelDownloadProgress
+  // from tauri-plugin-ipc-audio-transcription-ort
+  'tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress': ModelDownloadProgress
 
   // from tauri-plugin-rdev
   'tauri-plugins:tauri-plugin-rdev:keydown': { time: { secs_since_epoch: number, nanos_since_epoch: number }, name: string, event_type: { KeyPress: KeyCode | { Unknown: number } } } // similar to 'keydown' events from DOM elements
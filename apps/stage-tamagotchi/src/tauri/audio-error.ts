@@ -0,0 +1,17 @@
+import type { AudioError } from '../bindings/tauri-plugins/audio-models'
+
+/** Whether a command of an audio plugin rejected with an {@link AudioError} */
+export function isAudioError(error: unknown): error is AudioError {
+  return typeof error === 'object'
+    && error !== null
+    && typeof (error as AudioError).code === 'string'
+    && typeof (error as AudioError).message === 'string'
+}
+
+/** What to show for whatever a command of an audio plugin rejected with */
+export function audioErrorMessage(error: unknown): string {
+  if (isAudioError(error) || error instanceof Error)
+    return error.message
+
+  return String(error)
+}
@@ -8,12 +8,57 @@ export interface InvokeMethods {
   'open_chat_window': { args: undefined, options: undefined, returns: void }
 
   // Plugin - Audio Transcription
-  'plugin:ipc-audio-transcription-ort|load_ort_model_whisper': { args: { modelType: 'base' | 'largev3' | 'tiny' | 'medium' }, options: undefined, returns: void }
-  'plugin:ipc-audio-transcription-ort|ipc_audio_transcription': { args: { chunk: number[], language: string }, options: undefined, returns: string }
+  'plugin:ipc-audio-transcription-ort|load_ort_model_whisper': { args: { modelType: string, variant?: 'fp32' | 'fp16' | 'int8' | 'uint8' | 'q4', slot?: 'default' | 'partial' }, options: undefined, returns: void }
+  'plugin:ipc-audio-transcription-ort|cancel_model_download': { args: { modelId: string }, options: undefined, returns: boolean }
+  'plugin:ipc-audio-transcription-ort|ipc_audio_transcription': { args: { chunk?: number[], sampleRate?: number, channels?: number, encoded?: number[], language: string, task?: 'transcribe' | 'translate', initialPrompt?: string, hotwords?: string[], prefix?: string, guards?: { noSpeechThreshold?: number | null, logprobThreshold?: number, maxNgramRepeats?: number | null, compressionRatioThreshold?: number | null, blocklist?: string[] } }, options: undefined, returns: { text: string, guard: 'no-speech' | 'repetition' | 'compression-ratio' | 'blocklist' | null, noSpeechProb: number | null, avgLogprob: number, compressionRatio: number } }
+
+  // Plugin - Audio Keyword Spotting
+  'plugin:ipc-audio-kws|enroll_keyword': { args: { name: string, recordings: number[][], sampleRate?: number }, options: undefined, returns: { name: string, samples: number, threshold: number, durationMs: number } }
+  'plugin:ipc-audio-kws|list_keywords': { args: undefined, options: undefined, returns: { name: string, samples: number, threshold: number, durationMs: number }[] }
+  'plugin:ipc-audio-kws|remove_keyword': { args: { name: string }, options: undefined, returns: void }
+  'plugin:ipc-audio-kws|create_kws_session': { args: { options?: { keywords?: string[] | null, minConfidence?: number, refractoryMs?: number } }, options: undefined, returns: number }
+  'plugin:ipc-audio-kws|push_kws_samples': { args: { sessionId: number, samples: number[] }, options: undefined, returns: { keyword: string, confidence: number, start: number, end: number }[] }
+  'plugin:ipc-audio-kws|destroy_kws_session': { args: { sessionId: number }, options: undefined, returns: { keyword: string, confidence: number, start: number, end: number }[] }
+
+  // Plugin - Audio Models
+  'plugin:ipc-audio-models|get_model_hub_config': { args: undefined, options: undefined, returns: { endpoint: string | null, hasToken: boolean, proxy: string | null, offline: boolean } }
+  'plugin:ipc-audio-models|set_model_hub_config': { args: { config: { endpoint?: string | null, token?: string | null, proxy?: string | null, offline?: boolean } }, options: undefined, returns: void }
+
+  // Plugin - Audio Preprocess
+  'plugin:ipc-audio-preprocess|push_echo_reference': { args: { samples: number[], sampleRate: number, channels?: number }, options: undefined, returns: void }
+  'plugin:ipc-audio-preprocess|stop_echo_reference': { args: undefined, options: undefined, returns: void }
+  'plugin:ipc-audio-preprocess|create_preprocess_session': { args: { sampleRate?: number, options: { denoise?: boolean, echoCancellation?: boolean } }, options: undefined, returns: number }
+  'plugin:ipc-audio-preprocess|process_samples': { args: { sessionId: number, samples: number[] }, options: undefined, returns: number[] }
+  'plugin:ipc-audio-preprocess|destroy_preprocess_session': { args: { sessionId: number }, options: undefined, returns: void }
+
+  // Plugin - Audio Prosody
+  'plugin:ipc-audio-prosody-ort|load_ort_model_emotion': { args: { modelId: string, revision?: string, modelFile?: string }, options: undefined, returns: void }
+  'plugin:ipc-audio-prosody-ort|cancel_model_download': { args: { modelId: string }, options: undefined, returns: boolean }
+  'plugin:ipc-audio-prosody-ort|unload_ort_model_emotion': { args: undefined, options: undefined, returns: void }
+  'plugin:ipc-audio-prosody-ort|analyze_prosody': { args: { samples: number[], sampleRate?: number, text?: string }, options: undefined, returns: { durationMs: number, pitch: { meanHz: number, medianHz: number, minHz: number, maxHz: number, rangeSemitones: number, stdSemitones: number, finalSlopeStPerS: number, voicedRatio: number } | null, energy: { meanDb: number, maxDb: number, stdDb: number }, speakingRate: { syllablesPerSecond: number, wordsPerSecond: number | null }, emotion: { label: string, confidence: number } | null, cues: string[] } }
+
+  // Plugin - Audio Speaker
+  'plugin:ipc-audio-speaker-ort|load_ort_model_speaker': { args: undefined, options: undefined, returns: void }
+  'plugin:ipc-audio-speaker-ort|cancel_model_download': { args: { modelId: string }, options: undefined, returns: boolean }
+  'plugin:ipc-audio-speaker-ort|enroll_speaker': { args: { name: string, recordings: number[][], sampleRate?: number }, options: undefined, returns: { id: string, name: string, samples: number } }
+  'plugin:ipc-audio-speaker-ort|list_speakers': { args: undefined, options: undefined, returns: { id: string, name: string, samples: number }[] }
+  'plugin:ipc-audio-speaker-ort|remove_speaker': { args: { speakerId: string }, options: undefined, returns: void }
+  'plugin:ipc-audio-speaker-ort|create_diarization_session': { args: { options?: { threshold?: number, maxSpeakers?: number | null } }, options: undefined, returns: number }
+  'plugin:ipc-audio-speaker-ort|identify_speaker': { args: { sessionId: number, samples: number[], sampleRate?: number }, options: undefined, returns: { speakerId: string, name: string | null, similarity: number } | null }
+  'plugin:ipc-audio-speaker-ort|name_speaker': { args: { sessionId: number, speakerId: string, name: string }, options: undefined, returns: { id: string, name: string, samples: number } }
+  'plugin:ipc-audio-speaker-ort|destroy_diarization_session': { args: { sessionId: number }, options: undefined, returns: void }
 
   // Plugin - Audio VAD
-  'plugin:ipc-audio-vad-ort|load_ort_model_silero_vad': { args: undefined, options: undefined, returns: void }
+  'plugin:ipc-audio-vad-ort|load_ort_model_silero_vad': { args: undefined, options: undefined, returns: 'silero' | 'energy' }
+  'plugin:ipc-audio-vad-ort|cancel_model_download': { args: { modelId: string }, options: undefined, returns: boolean }
+  'plugin:ipc-audio-vad-ort|get_vad_backend': { args: undefined, options: undefined, returns: 'silero' | 'energy' | null }
   'plugin:ipc-audio-vad-ort|ipc_audio_vad': { args: { inputData: { input: number[], sr: number, state: number[] } }, options: undefined, returns: number }
+  'plugin:ipc-audio-vad-ort|create_vad_session': { args: { sampleRate?: number, options?: { threshold?: number, negThreshold?: number | null, minSpeechDurationMs?: number, minSilenceDurationMs?: number, speechPadMs?: number, maxSpeechDurationS?: number | null }, preprocess?: { denoise?: boolean, echoCancellation?: boolean } }, options: undefined, returns: number }
+  'plugin:ipc-audio-vad-ort|push_samples': { args: { sessionId: number, samples: number[] }, options: undefined, returns: { frames: { start: number, probability: number }[], events: ({ type: 'speech-start', start: number } | { type: 'speech-end', start: number, end: number })[] } }
+  'plugin:ipc-audio-vad-ort|reset_vad_session': { args: { sessionId: number }, options: undefined, returns: void }
+  'plugin:ipc-audio-vad-ort|set_barge_in_options': { args: { options: { enabled?: boolean, minProbability?: number, minDurationMs?: number } }, options: undefined, returns: void }
+  'plugin:ipc-audio-vad-ort|destroy_vad_session': { args: { sessionId: number }, options: undefined, returns: ({ type: 'speech-start', start: number } | { type: 'speech-end', start: number, end: number })[] }
+  'plugin:ipc-audio-vad-ort|detect_speech_segments': { args: { samples?: number[], sampleRate?: number, channels?: number, path?: string, options?: { threshold?: number, negThreshold?: number | null, minSpeechDurationMs?: number, minSilenceDurationMs?: number, speechPadMs?: number, maxSpeechDurationS?: number | null }, returnAudio?: boolean }, options: undefined, returns: { sampleRate: number, segments: { start: number, end: number }[], audio: number[] | null } }
 
   // Plugin - Window Pass through on hover
   'plugin:window-pass-through-on-hover|start_tracing_cursor': { args: undefined, options: undefined, returns: void }
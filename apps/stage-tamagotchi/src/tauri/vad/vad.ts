@@ -1,10 +1,12 @@
 import type { BaseVAD, BaseVADConfig, VADEventCallback, VADEvents } from '@proj-airi/stage-ui/libs/audio/vad'
 
+import { audioErrorMessage } from '../audio-error'
 import { invoke } from '../invoke'
 
 export class VAD implements BaseVAD {
   private config: BaseVADConfig
-  private state: Float32Array = new Float32Array(2 * 1 * 128) // 2, 1, 128
+  private sessionId?: number
+  private lastProbability: number = 0
   private buffer: Float32Array
   private bufferPointer: number = 0
   private isRecording: boolean = false
@@ -34,13 +36,19 @@ export class VAD implements BaseVAD {
     try {
       this.emit('status', { type: 'info', message: 'Loading VAD model...' })
 
-      await invoke('plugin:ipc-audio-vad-ort|load_ort_model_silero_vad')
+      const backend = await invoke('plugin:ipc-audio-vad-ort|load_ort_model_silero_vad')
+      if (backend === 'energy')
+        this.emit('status', { type: 'warn', message: 'Silero VAD model is unavailable, using the energy detector' })
+      // The model state lives in the session, on the Rust side
+      this.sessionId = await invoke('plugin:ipc-audio-vad-ort|create_vad_session', {
+        sampleRate: this.config.sampleRate,
+      })
       this.isReady = true
 
       this.emit('status', { type: 'info', message: 'VAD model loaded successfully' })
     }
     catch (error) {
-      this.emit('status', { type: 'error', message: `Failed to load VAD model: ${error}` })
+      this.emit('status', { type: 'error', message: `Failed to load VAD model: ${audioErrorMessage(error)}` })
       throw error
     }
   }
@@ -130,21 +138,18 @@ export class VAD implements BaseVAD {
 
   private async detectSpeech(buffer: Float32Array): Promise<boolean> {
     // Use Rust backend for inference
-    const result = await (this.inferenceChain = this.inferenceChain.then(() =>
-      invoke('plugin:ipc-audio-vad-ort|ipc_audio_vad', {
-        inputData: {
-          input: Array.from(buffer),
-          sr: this.config.sampleRate,
-          state: Array.from(this.state),
-        },
+    const { frames } = await (this.inferenceChain = this.inferenceChain.then(() =>
+      invoke('plugin:ipc-audio-vad-ort|push_samples', {
+        sessionId: this.sessionId!,
+        samples: Array.from(buffer),
       }),
-    )) as { output: number[], state: number[] }
+    )) as { frames: { start: number, probability: number }[] }
 
-    // Update the state
-    this.state = new Float32Array(result.state)
-
-    // Get the speech probability
-    const speechProb = result.output[0]
+    // The session runs whole windows only, a short buffer may not complete one
+    const speechProb = frames.length > 0
+      ? Math.max(...frames.map(frame => frame.probability))
+      : this.lastProbability
+    this.lastProbability = speechProb
 
     this.emit('debug', {
       message: 'VAD score',
@@ -203,11 +208,32 @@ export class VAD implements BaseVAD {
       this.buffer = new Float32Array(this.config.maxBufferDuration * this.config.sampleRate)
       this.bufferPointer = 0
     }
+
+    // A session runs at one sample rate, swap it before the next inference
+    if (newConfig.sampleRate && this.sessionId !== undefined) {
+      const previousSessionId = this.sessionId
+      this.inferenceChain = this.inferenceChain.then(async () => {
+        this.sessionId = await invoke('plugin:ipc-audio-vad-ort|create_vad_session', {
+          sampleRate: this.config.sampleRate,
+        })
+        await invoke('plugin:ipc-audio-vad-ort|destroy_vad_session', { sessionId: previousSessionId })
+      })
+    }
   }
 
   public isCurrentlyRecording(): boolean {
     return this.isRecording
   }
+
+  public async destroy(): Promise<void> {
+    if (this.sessionId === undefined)
+      return
+
+    const sessionId = this.sessionId
+    this.sessionId = undefined
+    this.isReady = false
+    await invoke('plugin:ipc-audio-vad-ort|destroy_vad_session', { sessionId })
+  }
 }
 
 export async function createVAD(config?: Partial<BaseVADConfig>): Promise<VAD> {
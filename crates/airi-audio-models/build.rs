@@ -0,0 +1,5 @@
+const COMMANDS: &[&str] = &["get_model_hub_config", "set_model_hub_config"];
+
+fn main() {
+  tauri_plugin::Builder::new(COMMANDS).build();
+}
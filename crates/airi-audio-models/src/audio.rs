@@ -0,0 +1,190 @@
+use std::{fs::File, io::Cursor, path::Path};
+
+use anyhow::{Result, anyhow};
+use log::warn;
+use rubato::{FftFixedIn, Resampler};
+use symphonia::core::{
+  audio::SampleBuffer,
+  codecs::{CODEC_TYPE_NULL, DecoderOptions},
+  errors::Error as SymphoniaError,
+  formats::FormatOptions,
+  io::{MediaSource, MediaSourceStream},
+  meta::MetadataOptions,
+  probe::Hint,
+};
+
+const RESAMPLER_CHUNK_SIZE: usize = 1024;
+
+/// Interleaved PCM samples as they come out of a decoder.
+pub struct DecodedAudio {
+  pub samples:     Vec<f32>,
+  pub sample_rate: u32,
+  pub channels:    usize,
+}
+
+impl DecodedAudio {
+  /// Converts to mono at `sample_rate`, what the models take.
+  pub fn into_mono(
+    self,
+    sample_rate: usize,
+  ) -> Result<Vec<f32>> {
+    to_mono(self.samples, self.sample_rate, self.channels, sample_rate)
+  }
+}
+
+/// Decodes an encoded audio file (WAV, FLAC, MP3, Ogg Vorbis) held in memory.
+pub fn decode_bytes(bytes: Vec<u8>) -> Result<DecodedAudio> {
+  decode(Box::new(Cursor::new(bytes)), Hint::new())
+}
+
+/// Decodes an audio file from disk, the extension is used as a hint for the format.
+pub fn decode_file(path: &Path) -> Result<DecodedAudio> {
+  let file = File::open(path)?;
+
+  let mut hint = Hint::new();
+  if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+    hint.with_extension(extension);
+  }
+
+  decode(Box::new(file), hint)
+}
+
+fn decode(
+  source: Box<dyn MediaSource>,
+  hint: Hint,
+) -> Result<DecodedAudio> {
+  let stream = MediaSourceStream::new(source, Default::default());
+  let probed = symphonia::default::get_probe()
+    .format(
+      &hint,
+      stream,
+      &FormatOptions::default(),
+      &MetadataOptions::default(),
+    )
+    .map_err(|e| anyhow!("Unsupported audio format: {}", e))?;
+  let mut format = probed.format;
+
+  let track = format
+    .tracks()
+    .iter()
+    .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+    .ok_or_else(|| anyhow!("No audio track found"))?;
+  let track_id = track.id;
+  let mut sample_rate = track.codec_params.sample_rate;
+  let mut channels = track.codec_params.channels.map(|c| c.count());
+
+  let mut decoder =
+    symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+  let mut samples = Vec::new();
+  loop {
+    let packet = match format.next_packet() {
+      Ok(packet) => packet,
+      Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+      Err(e) => return Err(e.into()),
+    };
+    if packet.track_id() != track_id {
+      continue;
+    }
+
+    match decoder.decode(&packet) {
+      Ok(decoded) => {
+        let spec = *decoded.spec();
+        sample_rate = Some(spec.rate);
+        channels = Some(spec.channels.count());
+
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+      },
+      Err(SymphoniaError::DecodeError(e)) => {
+        // Corrupted packets are skipped, the same as most players do
+        warn!("Skipping undecodable audio packet: {}", e);
+      },
+      Err(e) => return Err(e.into()),
+    }
+  }
+
+  Ok(DecodedAudio {
+    samples,
+    sample_rate: sample_rate.ok_or_else(|| anyhow!("Unknown sample rate"))?,
+    channels: channels.ok_or_else(|| anyhow!("Unknown channel count"))?,
+  })
+}
+
+/// Downmixes interleaved samples to mono and resamples them to `to`.
+pub fn to_mono(
+  samples: Vec<f32>,
+  sample_rate: u32,
+  channels: usize,
+  to: usize,
+) -> Result<Vec<f32>> {
+  if sample_rate == 0 || channels == 0 {
+    return Err(anyhow!(
+      "Invalid audio format: {} Hz, {} channels",
+      sample_rate,
+      channels
+    ));
+  }
+
+  if samples.len() % channels != 0 {
+    return Err(anyhow!(
+      "Sample count {} is not a multiple of the channel count {}",
+      samples.len(),
+      channels
+    ));
+  }
+
+  let mono = downmix(samples, channels);
+  resample(mono, sample_rate as usize, to)
+}
+
+/// Averages interleaved channels into one.
+pub fn downmix(
+  samples: Vec<f32>,
+  channels: usize,
+) -> Vec<f32> {
+  if channels == 1 {
+    return samples;
+  }
+
+  samples
+    .chunks_exact(channels)
+    .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+    .collect()
+}
+
+/// Resamples mono audio, the output has exactly `len * to / from` samples (rounded up).
+pub fn resample(
+  samples: Vec<f32>,
+  from: usize,
+  to: usize,
+) -> Result<Vec<f32>> {
+  if from == to || samples.is_empty() {
+    return Ok(samples);
+  }
+
+  let mut resampler = FftFixedIn::<f32>::new(from, to, RESAMPLER_CHUNK_SIZE, 2, 1)?;
+  let expected_len = (samples.len() * to).div_ceil(from);
+  let delay = resampler.output_delay();
+
+  let mut output = Vec::with_capacity(expected_len + delay);
+  let mut remaining = samples.as_slice();
+  while remaining.len() >= resampler.input_frames_next() {
+    let (chunk, rest) = remaining.split_at(resampler.input_frames_next());
+    output.extend_from_slice(&resampler.process(&[chunk], None)?[0]);
+    remaining = rest;
+  }
+
+  // The last partial chunk is zero padded, then the resampler delay is flushed out
+  if !remaining.is_empty() {
+    output.extend_from_slice(&resampler.process_partial(Some(&[remaining]), None)?[0]);
+  }
+  while output.len() < expected_len + delay {
+    output.extend_from_slice(&resampler.process_partial::<&[f32]>(None, None)?[0]);
+  }
+
+  output.drain(..delay);
+  output.truncate(expected_len);
+  Ok(output)
+}
@@ -0,0 +1,96 @@
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+
+/// Used by every model download of the process, see [`set_hub_config`]
+static HUB_CONFIG: RwLock<HubConfig> = RwLock::new(HubConfig {
+  endpoint: None,
+  token:    None,
+  proxy:    None,
+  offline:  false,
+});
+
+/// Where models are downloaded from. Settings that are not given fall back to the environment
+/// variables the Hugging Face tools read: `HF_ENDPOINT`, `HF_TOKEN` (or the token
+/// `huggingface-cli login` saved), `HTTPS_PROXY` / `ALL_PROXY` and `HF_HUB_OFFLINE`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct HubConfig {
+  /// Base URL of the hub or of a mirror of it, e.g. `https://hf-mirror.com`
+  #[serde(default)]
+  pub endpoint: Option<String>,
+  /// Access token for gated and private repositories. Left out when setting the configuration,
+  /// the token set before is kept, an empty one removes it.
+  #[serde(default)]
+  pub token:    Option<String>,
+  /// `http://`, `socks5://` or `socks5h://` proxy, with `user:password@` when it needs one
+  #[serde(default)]
+  pub proxy:    Option<String>,
+  /// Only use models that are on disk already, never connect
+  #[serde(default)]
+  pub offline:  bool,
+}
+
+impl HubConfig {
+  /// The endpoint downloads go to, without a trailing slash.
+  pub fn resolved_endpoint(&self) -> String {
+    self
+      .endpoint
+      .clone()
+      .filter(|endpoint| !endpoint.trim().is_empty())
+      .or_else(|| std::env::var("HF_ENDPOINT").ok())
+      .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string())
+      .trim()
+      .trim_end_matches('/')
+      .to_string()
+  }
+
+  pub fn resolved_token(&self) -> Option<String> {
+    self
+      .token
+      .clone()
+      .or_else(|| std::env::var("HF_TOKEN").ok())
+      .filter(|token| !token.trim().is_empty())
+      .or_else(|| hf_hub::Cache::from_env().token())
+  }
+
+  pub fn is_offline(&self) -> bool {
+    self.offline
+      || std::env::var("HF_HUB_OFFLINE").is_ok_and(|value| matches!(value.as_str(), "1" | "true"))
+  }
+}
+
+/// What the frontend gets to see of the [`HubConfig`], the token never leaves the app.
+#[derive(Clone, Debug, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct HubConfigView {
+  pub endpoint:  Option<String>,
+  /// Whether a token was set, not one of the environment
+  pub has_token: bool,
+  pub proxy:     Option<String>,
+  pub offline:   bool,
+}
+
+impl From<HubConfig> for HubConfigView {
+  fn from(config: HubConfig) -> Self {
+    Self {
+      endpoint:  config.endpoint,
+      has_token: config.token.is_some(),
+      proxy:     config.proxy,
+      offline:   config.offline,
+    }
+  }
+}
+
+/// The configuration downloads currently use.
+pub fn hub_config() -> HubConfig {
+  HUB_CONFIG.read().unwrap().clone()
+}
+
+/// Applies to every download started afterwards, by all plugins.
+pub fn set_hub_config(config: HubConfig) {
+  *HUB_CONFIG.write().unwrap() = config;
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+use specta::Type;
+
+/// What the commands of the audio plugins reject with, `{ code, message }` in the frontend.
+///
+/// The code is stable and tells what the user can do about the failure, the message is only for
+/// showing and logging. Errors of the downloads carry theirs through [`anyhow::Error`], see
+/// [`AudioError::load_failed`].
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error, Serialize, Type)]
+#[serde(tag = "code", content = "message", rename_all = "kebab-case")]
+pub enum AudioError {
+  /// The model the command needs has to be loaded first
+  #[error("{0}")]
+  ModelNotLoaded(String),
+  /// The model files are there but do not work, reloading downloads them again
+  #[error("{0}")]
+  ModelLoadFailed(String),
+  /// The hub could not be reached or did not send the file, retrying may help
+  #[error("{0}")]
+  DownloadFailed(String),
+  /// The repository needs a hub token that is allowed to read it
+  #[error("{0}")]
+  Unauthorized(String),
+  /// The model is not downloaded and offline mode is on
+  #[error("{0}")]
+  Offline(String),
+  /// Stopped on request, by `cancel_model_download` or an interruption, nothing to report
+  #[error("{0}")]
+  Cancelled(String),
+  /// The arguments of the command are wrong
+  #[error("{0}")]
+  InvalidInput(String),
+  /// The session, job or voice asked for does not exist (anymore), or the model file on the hub
+  #[error("{0}")]
+  NotFound(String),
+  /// Running the model failed on valid input
+  #[error("{0}")]
+  InferenceFailed(String),
+  /// Anything else, e.g. the app failing to talk to the frontend
+  #[error("{0}")]
+  Internal(String),
+}
+
+impl AudioError {
+  /// Loading a model failed with `error`, which keeps its code when it comes from a download.
+  pub fn load_failed(error: anyhow::Error) -> Self {
+    Self::from_anyhow(error, Self::ModelLoadFailed)
+  }
+
+  /// Running a model failed with `error`, which keeps its code when it has one.
+  pub fn inference_failed(error: anyhow::Error) -> Self {
+    Self::from_anyhow(error, Self::InferenceFailed)
+  }
+
+  pub fn internal(error: impl std::fmt::Display) -> Self {
+    Self::Internal(error.to_string())
+  }
+
+  fn from_anyhow(
+    error: anyhow::Error,
+    fallback: fn(String) -> Self,
+  ) -> Self {
+    // The message keeps the context added on the way up
+    let message = format!("{:#}", error);
+    match error.downcast_ref::<Self>() {
+      Some(inner) => inner.with_message(message),
+      None => fallback(message),
+    }
+  }
+
+  fn with_message(
+    &self,
+    message: String,
+  ) -> Self {
+    match self {
+      Self::ModelNotLoaded(_) => Self::ModelNotLoaded(message),
+      Self::ModelLoadFailed(_) => Self::ModelLoadFailed(message),
+      Self::DownloadFailed(_) => Self::DownloadFailed(message),
+      Self::Unauthorized(_) => Self::Unauthorized(message),
+      Self::Offline(_) => Self::Offline(message),
+      Self::Cancelled(_) => Self::Cancelled(message),
+      Self::InvalidInput(_) => Self::InvalidInput(message),
+      Self::NotFound(_) => Self::NotFound(message),
+      Self::InferenceFailed(_) => Self::InferenceFailed(message),
+      Self::Internal(_) => Self::Internal(message),
+    }
+  }
+}
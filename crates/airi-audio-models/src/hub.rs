@@ -0,0 +1,491 @@
+use std::{
+  fs,
+  io::{Read, Write},
+  path::{Path, PathBuf},
+  sync::{
+    Arc,
+    Mutex,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+  },
+  time::Duration,
+};
+
+use anyhow::Result;
+use hf_hub::{Cache, CacheRepo, Repo, RepoType};
+use log::info;
+use ureq::{Agent, AgentBuilder, Proxy};
+use url::Url;
+
+use crate::{
+  AudioError,
+  config::{HubConfig, hub_config},
+  progress::{DownloadPhase, DownloadProgress, ProgressEmitter},
+};
+
+/// Files under `$AIRI_MODELS_DIR/<model id>/` are used instead of the cache, for models copied
+/// over by hand or exported locally
+pub const LOCAL_MODELS_DIR_ENV: &str = "AIRI_MODELS_DIR";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+/// A download that stalls this long fails, a cancelled one stops at the latest then
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Follows the hub's own redirects, e.g. of renamed repositories
+const MAX_HUB_REDIRECTS: usize = 5;
+
+/// Numbers the partial files of the process, downloads of the same file do not share one
+static PART_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Downloads in progress by model id, see [`cancel_download`]
+static DOWNLOADS: Mutex<Vec<(String, Arc<AtomicBool>)>> = Mutex::new(Vec::new());
+
+/// Cancels the downloads of `model_id` in progress, their partial files are removed and the model
+/// loads waiting on them fail. Returns whether there were any.
+pub fn cancel_download(model_id: &str) -> bool {
+  let downloads = DOWNLOADS.lock().unwrap();
+  let mut cancelled = false;
+  for (_, cancel) in downloads.iter().filter(|(id, _)| id == model_id) {
+    cancel.store(true, Ordering::Relaxed);
+    cancelled = true;
+  }
+  if cancelled {
+    info!("Canceling the download of {}", model_id);
+  }
+  cancelled
+}
+
+/// A download in [`DOWNLOADS`] for as long as it lives
+struct ActiveDownload {
+  cancel: Arc<AtomicBool>,
+}
+
+impl ActiveDownload {
+  fn start(model_id: &str) -> Self {
+    let cancel = Arc::new(AtomicBool::new(false));
+    DOWNLOADS
+      .lock()
+      .unwrap()
+      .push((model_id.to_string(), cancel.clone()));
+    Self { cancel }
+  }
+
+  fn is_cancelled(&self) -> bool {
+    self.cancel.load(Ordering::Relaxed)
+  }
+}
+
+impl Drop for ActiveDownload {
+  fn drop(&mut self) {
+    DOWNLOADS
+      .lock()
+      .unwrap()
+      .retain(|(_, cancel)| !Arc::ptr_eq(cancel, &self.cancel));
+  }
+}
+
+/// A model repository on the Hugging Face Hub at one revision, and where its files are found.
+///
+/// Files are looked up in the local models directory first, then in the Hugging Face cache
+/// (`HF_HOME`), and downloaded last, from the endpoint of the [`HubConfig`]. Downloads are stored
+/// in the cache the way the Hugging Face tools store them.
+pub struct ModelRepo {
+  model_id:  String,
+  revision:  String,
+  cache:     CacheRepo,
+  config:    HubConfig,
+  local_dir: Option<PathBuf>,
+  repo_path: PathBuf,
+}
+
+impl ModelRepo {
+  /// A repository downloaded with the configuration of the process, see
+  /// [`set_hub_config`](crate::set_hub_config).
+  pub fn new(
+    model_id: &str,
+    revision: &str,
+  ) -> Result<Self> {
+    Self::with_config(
+      model_id,
+      revision,
+      hub_config(),
+      Cache::from_env().path().clone(),
+    )
+  }
+
+  /// A repository downloaded with `config` into the Hugging Face cache at `cache_dir`.
+  pub fn with_config(
+    model_id: &str,
+    revision: &str,
+    config: HubConfig,
+    cache_dir: PathBuf,
+  ) -> Result<Self> {
+    let cache = Cache::new(cache_dir);
+    let repo = Repo::with_revision(model_id.to_string(), RepoType::Model, revision.to_string());
+    let repo_path = cache.path().join(repo.folder_name());
+    let local_dir = std::env::var_os(LOCAL_MODELS_DIR_ENV)
+      .map(|dir| PathBuf::from(dir).join(model_id))
+      .filter(|dir| dir.is_dir());
+
+    Ok(Self {
+      model_id: model_id.to_string(),
+      revision: revision.to_string(),
+      cache: cache.repo(repo),
+      config,
+      local_dir,
+      repo_path,
+    })
+  }
+
+  pub fn model_id(&self) -> &str {
+    &self.model_id
+  }
+
+  /// The folder of the repository in the Hugging Face cache, with every revision of it.
+  pub fn cache_path(&self) -> &Path {
+    &self.repo_path
+  }
+
+  /// The path of `file` when it is on disk already.
+  pub fn cached(
+    &self,
+    file: &str,
+  ) -> Option<PathBuf> {
+    self
+      .local_dir
+      .as_ref()
+      .map(|dir| dir.join(file))
+      .filter(|path| path.is_file())
+      .or_else(|| self.cache.get(file))
+  }
+
+  /// The path of `file` in the Hugging Face cache, leaving out the local models directory.
+  pub fn cached_download(
+    &self,
+    file: &str,
+  ) -> Option<PathBuf> {
+    self.cache.get(file)
+  }
+
+  /// Whether the hub answered that the revision does not have `file`, the last time it was asked.
+  pub fn is_missing(
+    &self,
+    file: &str,
+  ) -> bool {
+    let commit = fs::read_to_string(self.repo_path.join("refs").join(&self.revision))
+      .unwrap_or_else(|_| self.revision.clone());
+    self
+      .repo_path
+      .join(".no_exist")
+      .join(commit.trim())
+      .join(file)
+      .exists()
+  }
+
+  /// Like [`Self::get`] for files that only some revisions have, `None` when the hub answers that
+  /// this one does not. That answer is kept, the hub is not asked again. Any other failure is an
+  /// error.
+  pub fn get_optional(
+    &self,
+    file: &str,
+    emitter: impl ProgressEmitter + 'static,
+    event_name: &str,
+  ) -> Result<Option<PathBuf>> {
+    if let Some(path) = self.cached(file) {
+      return Ok(Some(path));
+    }
+    if self.is_missing(file) {
+      return Ok(None);
+    }
+
+    match self.download(file, emitter, event_name) {
+      Ok(path) => Ok(Some(path)),
+      Err(e) if matches!(e.downcast_ref(), Some(AudioError::NotFound(_))) => Ok(None),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// The path of `file`, downloaded with progress reported as `event_name` when it is not on disk.
+  pub fn get(
+    &self,
+    file: &str,
+    emitter: impl ProgressEmitter + 'static,
+    event_name: &str,
+  ) -> Result<PathBuf> {
+    match self.cached(file) {
+      Some(path) => Ok(path),
+      None => self.download(file, emitter, event_name),
+    }
+  }
+
+  /// Downloads `file` into the cache even when it is there already, to replace a broken copy.
+  pub fn download(
+    &self,
+    file: &str,
+    emitter: impl ProgressEmitter + 'static,
+    event_name: &str,
+  ) -> Result<PathBuf> {
+    if self.config.is_offline() {
+      return Err(
+        AudioError::Offline(format!(
+          "{} of {} is not downloaded and offline mode is on. Turn offline mode off, or copy the \
+           repository into {} or ${}/{}",
+          file,
+          self.model_id,
+          self.repo_path.display(),
+          LOCAL_MODELS_DIR_ENV,
+          self.model_id,
+        ))
+        .into(),
+      );
+    }
+
+    let endpoint = self.config.resolved_endpoint();
+    let mut url = Url::parse(&format!(
+      "{}/{}/resolve/{}/{}",
+      endpoint, self.model_id, self.revision, file
+    ))
+    .map_err(|e| AudioError::InvalidInput(format!("Invalid endpoint {}: {}", endpoint, e)))?;
+    info!(
+      "Downloading {} of {} from {}",
+      file, self.model_id, endpoint
+    );
+
+    let active = ActiveDownload::start(&self.model_id);
+    let cancelled = || {
+      anyhow::Error::from(AudioError::Cancelled(format!(
+        "Download of {} of {} was cancelled",
+        file, self.model_id
+      )))
+    };
+
+    // The hub answers with the commit and the hash of the file, then redirects large files to
+    // a CDN, which must not get the token
+    let hub = self.agent(0)?;
+    let token = self.config.resolved_token();
+    let mut redirects = 0;
+    let response = loop {
+      let mut request = hub.request_url("GET", &url);
+      if let Some(token) = &token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+      }
+      let response = match request.call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, response)) => {
+          // Remembered like the Hugging Face tools do, so that optional files are not asked for
+          // on every load
+          if let Some(commit) = response.header("X-Repo-Commit") {
+            let marker = self
+              .repo_path
+              .join(".no_exist")
+              .join(commit)
+              .join(file);
+            if let Some(parent) = marker.parent() {
+              let _ = fs::create_dir_all(parent);
+            }
+            let _ = fs::File::create(marker);
+            let _ = self.cache.create_ref(commit);
+          }
+          return Err(
+            self
+              .request_error(file, &endpoint, ureq::Error::Status(404, response))
+              .into(),
+          );
+        },
+        Err(e) => return Err(self.request_error(file, &endpoint, e).into()),
+      };
+
+      let location = match response.header("Location") {
+        Some(location) if (300..400).contains(&response.status()) => url.join(location)?,
+        _ => break response,
+      };
+      if location.host() != url.host() || redirects == MAX_HUB_REDIRECTS {
+        break response;
+      }
+      url = location;
+      redirects += 1;
+    };
+
+    let commit = response
+      .header("X-Repo-Commit")
+      .ok_or_else(|| {
+        AudioError::DownloadFailed(format!(
+          "{} did not name the commit of {}, is it a hub?",
+          endpoint, file
+        ))
+      })?
+      .to_string();
+    let etag = response
+      .header("X-Linked-Etag")
+      .or_else(|| response.header("ETag"))
+      .ok_or_else(|| {
+        AudioError::DownloadFailed(format!("{} did not send the hash of {}", endpoint, file))
+      })?
+      .trim_start_matches("W/")
+      .replace('"', "");
+
+    let response = match response.header("Location") {
+      Some(location) if (300..400).contains(&response.status()) => {
+        let location = url.join(location)?;
+        self
+          .agent(5)?
+          .request_url("GET", &location)
+          .call()
+          .map_err(|e| self.request_error(file, location.as_str(), e))?
+      },
+      _ => response,
+    };
+    if active.is_cancelled() {
+      return Err(cancelled());
+    }
+    let size = response
+      .header("Content-Length")
+      .and_then(|length| length.parse().ok());
+
+    let blob_path = self.repo_path.join("blobs").join(&etag);
+    fs::create_dir_all(self.repo_path.join("blobs"))?;
+    // Written under its own name and moved into place when complete, another download of the same
+    // file only replaces the blob with the same content
+    let part_path = self.repo_path.join("blobs").join(format!(
+      "{}.{}-{}.part",
+      etag,
+      std::process::id(),
+      PART_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut progress = DownloadProgress::start(emitter, event_name, &self.model_id, file, size);
+    let written = (|| {
+      let mut reader = response.into_reader();
+      let mut writer = fs::File::create(&part_path)?;
+      let mut buffer = vec![0; 64 * 1024];
+      loop {
+        if active.is_cancelled() {
+          return Err(cancelled());
+        }
+        let read = match reader.read(&mut buffer) {
+          Ok(0) => break,
+          Ok(read) => read,
+          // Cancelled while waiting for the connection
+          Err(_) if active.is_cancelled() => return Err(cancelled()),
+          Err(e) => {
+            return Err(
+              AudioError::DownloadFailed(format!("Download of {} was interrupted: {}", file, e))
+                .into(),
+            );
+          },
+        };
+        writer.write_all(&buffer[..read])?;
+        progress.update(read);
+      }
+      writer.flush()?;
+      Ok(())
+    })();
+    if let Err(e) = written {
+      let _ = fs::remove_file(&part_path);
+      progress.finish(if active.is_cancelled() {
+        DownloadPhase::Cancelled
+      } else {
+        DownloadPhase::Failed
+      });
+      return Err(e);
+    }
+    fs::rename(&part_path, &blob_path)?;
+
+    let pointer_path = self
+      .repo_path
+      .join("snapshots")
+      .join(&commit)
+      .join(file);
+    if let Some(parent) = pointer_path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    link_blob(&blob_path, &pointer_path, file, &etag)?;
+    self.cache.create_ref(&commit)?;
+    progress.finish(DownloadPhase::Finished);
+
+    Ok(pointer_path)
+  }
+
+  fn agent(
+    &self,
+    redirects: u32,
+  ) -> Result<Agent> {
+    let mut builder = AgentBuilder::new()
+      .timeout_connect(CONNECT_TIMEOUT)
+      .timeout_read(READ_TIMEOUT)
+      .redirects(redirects)
+      .try_proxy_from_env(true);
+    if let Some(proxy) = self
+      .config
+      .proxy
+      .as_deref()
+      .map(str::trim)
+      .filter(|proxy| !proxy.is_empty())
+    {
+      builder = builder.proxy(
+        Proxy::new(proxy)
+          .map_err(|e| AudioError::InvalidInput(format!("Invalid proxy {}: {}", proxy, e)))?,
+      );
+    }
+
+    Ok(builder.build())
+  }
+
+  fn request_error(
+    &self,
+    file: &str,
+    server: &str,
+    error: ureq::Error,
+  ) -> AudioError {
+    match error {
+      ureq::Error::Status(401 | 403, _) => AudioError::Unauthorized(format!(
+        "{} of {} needs access, set a token that is allowed to read it",
+        file, self.model_id
+      )),
+      ureq::Error::Status(404, _) => AudioError::NotFound(format!(
+        "{} of {} at {} does not exist on {}",
+        file, self.model_id, self.revision, server
+      )),
+      ureq::Error::Status(status, _) => AudioError::DownloadFailed(format!(
+        "{} answered {} for {} of {}",
+        server, status, file, self.model_id
+      )),
+      ureq::Error::Transport(e) => AudioError::DownloadFailed(format!(
+        "Could not reach {}: {}. Check the endpoint and proxy, or turn on offline mode to use \
+         downloaded models only",
+        server, e
+      )),
+    }
+  }
+}
+
+/// Makes `file` of the snapshot point to its blob with a relative link, like the Hugging Face
+/// tools do. The blob is moved there where links are not allowed.
+fn link_blob(
+  blob_path: &Path,
+  pointer_path: &Path,
+  file: &str,
+  etag: &str,
+) -> Result<()> {
+  if pointer_path.symlink_metadata().is_ok() {
+    fs::remove_file(pointer_path)?;
+  }
+
+  // From snapshots/<commit>/<file> back to the repository folder
+  let mut target = PathBuf::new();
+  for _ in 0..file.matches('/').count() + 2 {
+    target.push("..");
+  }
+  target.push("blobs");
+  target.push(etag);
+
+  #[cfg(unix)]
+  let linked = std::os::unix::fs::symlink(&target, pointer_path);
+  #[cfg(windows)]
+  let linked = std::os::windows::fs::symlink_file(&target, pointer_path);
+  match linked {
+    Ok(()) => {},
+    // Linked by another download of the same file in the meantime
+    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {},
+    Err(_) => fs::rename(blob_path, pointer_path)?,
+  }
+
+  Ok(())
+}
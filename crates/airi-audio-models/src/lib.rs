@@ -0,0 +1,175 @@
+//! What the audio plugins share to get their models running: finding model files in the
+//! Hugging Face cache, a local directory or a mirror, reporting download progress, and building
+//! ONNX Runtime sessions on the best execution provider available.
+//!
+//! It is a plugin as well, which keeps the download configuration of the app.
+
+use std::{
+  fs,
+  io::Write,
+  path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use log::{info, warn};
+#[cfg(debug_assertions)]
+use specta_typescript::Typescript;
+use tauri::{
+  Manager,
+  Runtime,
+  plugin::{Builder as PluginBuilder, TauriPlugin},
+};
+
+pub mod audio;
+mod config;
+mod error;
+mod hub;
+mod progress;
+mod session;
+
+pub use config::{HubConfig, HubConfigView, hub_config, set_hub_config};
+pub use error::AudioError;
+pub use hub::{LOCAL_MODELS_DIR_ENV, ModelRepo, cancel_download};
+pub use ort::session::builder::GraphOptimizationLevel;
+pub use progress::{DownloadPhase, ModelDownloadProgress, ProgressEmitter};
+pub use session::{ExecutionProvider, SessionOptions, create_session};
+
+/// Emitted by the VAD plugin to every window and plugin when someone starts talking over the
+/// assistant. The TTS plugin stops speaking when it receives it.
+pub const BARGE_IN_EVENT: &str = "tauri-plugins:tauri-plugin-ipc-audio-vad-ort:barge-in";
+
+/// Where the hub configuration is kept across restarts.
+struct AppDataHubConfig {
+  path: Option<PathBuf>,
+}
+
+impl AppDataHubConfig {
+  fn save(
+    &self,
+    config: &HubConfig,
+  ) -> Result<(), AudioError> {
+    let Some(path) = &self.path else {
+      return Ok(());
+    };
+
+    let write = || -> Result<()> {
+      if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+      }
+      // It holds the token, only the user may read it
+      let mut options = fs::OpenOptions::new();
+      options.write(true).create(true).truncate(true);
+      #[cfg(unix)]
+      std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+      let mut file = options.open(path)?;
+      // A file saved before keeps the permissions it was created with
+      #[cfg(unix)]
+      file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+      file.write_all(&serde_json::to_vec(config)?)?;
+      Ok(())
+    };
+    write()
+      .map_err(|e| AudioError::Internal(format!("Failed to save model hub configuration: {}", e)))
+  }
+}
+
+fn read_hub_config(path: &Path) -> Result<HubConfig> {
+  Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn get_model_hub_config() -> Result<HubConfigView, AudioError> {
+  Ok(hub_config().into())
+}
+
+/// Sets where models are downloaded from, for the downloads of every plugin that start afterwards.
+#[tauri::command]
+#[specta::specta]
+async fn set_model_hub_config<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  mut config: HubConfig,
+) -> Result<(), AudioError> {
+  // The frontend cannot read the token back to send it again
+  config.token = config
+    .token
+    .or_else(|| hub_config().token)
+    .filter(|token| !token.trim().is_empty());
+
+  if let Some(proxy) = config
+    .proxy
+    .as_deref()
+    .map(str::trim)
+    .filter(|proxy| !proxy.is_empty())
+  {
+    ureq::Proxy::new(proxy)
+      .map_err(|e| AudioError::InvalidInput(format!("Invalid proxy {}: {}", proxy, e)))?;
+  }
+
+  app.state::<AppDataHubConfig>().save(&config)?;
+  info!(
+    "Models are downloaded from {}{}",
+    config.resolved_endpoint(),
+    if config.is_offline() {
+      ", offline"
+    } else {
+      ""
+    }
+  );
+  set_hub_config(config);
+  Ok(())
+}
+
+const PLUGIN_NAME: &str = "ipc-audio-models";
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+  let builder = tauri_specta::Builder::<R>::new()
+    .plugin_name(PLUGIN_NAME)
+    .commands(tauri_specta::internal::command(
+      tauri::generate_handler![get_model_hub_config, set_model_hub_config],
+      specta::function::collect_functions![
+        get_model_hub_config,
+        set_model_hub_config::<tauri::Wry>,
+      ],
+    ))
+    // The payload of the download progress events and the errors of the audio plugins
+    .typ::<ModelDownloadProgress>()
+    .typ::<AudioError>();
+
+  #[cfg(debug_assertions)]
+  builder
+    .export(
+      Typescript::default().header("// @ts-nocheck\n"),
+      "../src/bindings/tauri-plugins/audio-models.ts",
+    )
+    .expect("Failed to export typescript bindings");
+
+  PluginBuilder::new(PLUGIN_NAME)
+    .setup(|app, _| {
+      info!("Initializing audio models plugin...");
+      let path = match app.path().app_data_dir() {
+        Ok(dir) => Some(dir.join("model-hub.json")),
+        Err(e) => {
+          warn!(
+            "No app data directory, the model hub configuration is not kept: {}",
+            e
+          );
+          None
+        },
+      };
+      if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+        match read_hub_config(path) {
+          Ok(config) => set_hub_config(config),
+          Err(e) => warn!(
+            "Failed to load model hub configuration from {}: {}",
+            path.display(),
+            e
+          ),
+        }
+      }
+      app.manage(AppDataHubConfig { path });
+      Ok(())
+    })
+    .invoke_handler(builder.invoke_handler())
+    .build()
+}
@@ -0,0 +1,151 @@
+use std::time::{Duration, Instant};
+
+use log::error;
+use serde::Serialize;
+use specta::Type;
+use tauri::{Emitter, Runtime};
+
+/// Downloads in progress are reported at most this often
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum DownloadPhase {
+  Downloading,
+  Finished,
+  /// Stopped by `cancel_model_download`, the partial file is removed
+  Cancelled,
+  /// The partial file is removed, the command that loads the model returns why
+  Failed,
+}
+
+/// Progress of the download of one model file, the payload of the `load-model-*-progress` events
+/// of every audio plugin.
+#[derive(Clone, Debug, PartialEq, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelDownloadProgress {
+  /// Repository of the file, which `cancel_model_download` takes
+  pub model_id:         String,
+  pub file:             String,
+  pub phase:            DownloadPhase,
+  #[specta(type = f64)]
+  pub downloaded_bytes: u64,
+  /// Not known when the server does not send the size
+  #[specta(type = Option<f64>)]
+  pub total_bytes:      Option<u64>,
+  /// Percent of the file, 0 until the size is known
+  pub progress:         f32,
+  /// Average since the download started
+  pub bytes_per_second: f64,
+  /// Seconds left at the average speed, when the size is known
+  pub eta_seconds:      Option<f64>,
+}
+
+pub trait ProgressEmitter: Send + Sync {
+  fn emit_progress(
+    &self,
+    event_name: &str,
+    progress: &ModelDownloadProgress,
+  );
+}
+
+impl<R: Runtime> ProgressEmitter for tauri::WebviewWindow<R> {
+  fn emit_progress(
+    &self,
+    event_name: &str,
+    progress: &ModelDownloadProgress,
+  ) {
+    if let Err(err) = self.emit(event_name, progress) {
+      error!("Failed to emit {}: {:?}", event_name, err);
+    }
+  }
+}
+
+/// Turns the bytes of one download into [`ModelDownloadProgress`] events.
+pub(crate) struct DownloadProgress<E: ProgressEmitter> {
+  emitter:    E,
+  event_name: String,
+  state:      ModelDownloadProgress,
+  started:    Instant,
+  reported:   Instant,
+}
+
+impl<E: ProgressEmitter> DownloadProgress<E> {
+  pub fn start(
+    emitter: E,
+    event_name: &str,
+    model_id: &str,
+    file: &str,
+    total_bytes: Option<u64>,
+  ) -> Self {
+    let now = Instant::now();
+    let progress = Self {
+      emitter,
+      event_name: event_name.to_string(),
+      state: ModelDownloadProgress {
+        model_id:         model_id.to_string(),
+        file:             file.to_string(),
+        phase:            DownloadPhase::Downloading,
+        downloaded_bytes: 0,
+        total_bytes:      total_bytes.filter(|total| *total > 0),
+        progress:         0.0,
+        bytes_per_second: 0.0,
+        eta_seconds:      None,
+      },
+      started: now,
+      reported: now,
+    };
+    progress.emit();
+    progress
+  }
+
+  pub fn update(
+    &mut self,
+    bytes: usize,
+  ) {
+    self.state.downloaded_bytes += bytes as u64;
+    if self.reported.elapsed() < REPORT_INTERVAL {
+      return;
+    }
+
+    self.measure();
+    self.reported = Instant::now();
+    self.emit();
+  }
+
+  pub fn finish(
+    mut self,
+    phase: DownloadPhase,
+  ) {
+    self.measure();
+    self.state.phase = phase;
+    if phase == DownloadPhase::Finished {
+      self.state.progress = 100.0;
+      self.state.eta_seconds = Some(0.0);
+    } else {
+      self.state.eta_seconds = None;
+    }
+    self.emit();
+  }
+
+  fn measure(&mut self) {
+    let elapsed = self.started.elapsed().as_secs_f64();
+    let state = &mut self.state;
+    state.bytes_per_second = if elapsed > 0.0 {
+      state.downloaded_bytes as f64 / elapsed
+    } else {
+      0.0
+    };
+    if let Some(total) = state.total_bytes {
+      state.progress = (state.downloaded_bytes as f32 / total as f32 * 100.0).min(100.0);
+      state.eta_seconds = (state.bytes_per_second > 0.0)
+        .then(|| total.saturating_sub(state.downloaded_bytes) as f64 / state.bytes_per_second);
+    }
+  }
+
+  fn emit(&self) {
+    self
+      .emitter
+      .emit_progress(&self.event_name, &self.state);
+  }
+}
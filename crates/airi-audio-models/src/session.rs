@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use anyhow::Result;
+use ort::{
+  execution_providers::{
+    CPUExecutionProvider,
+    CUDAExecutionProvider,
+    CoreMLExecutionProvider,
+    DirectMLExecutionProvider,
+    ExecutionProvider as _,
+    ExecutionProviderDispatch,
+  },
+  session::{Session, builder::GraphOptimizationLevel},
+};
+
+/// Where a session may run. Providers that are not available on the platform or in the ONNX
+/// Runtime build are skipped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionProvider {
+  Cuda,
+  CoreMl,
+  DirectMl,
+  Cpu,
+}
+
+impl ExecutionProvider {
+  /// GPUs and NPUs first, the CPU when none of them is there
+  pub const ACCELERATED: [Self; 4] = [Self::Cuda, Self::CoreMl, Self::DirectMl, Self::Cpu];
+
+  /// Whether the ONNX Runtime build supports the provider on this platform, which does not mean a
+  /// device for it is there.
+  pub fn is_available(self) -> bool {
+    match self {
+      Self::Cuda => CUDAExecutionProvider::default().is_available(),
+      Self::CoreMl => CoreMLExecutionProvider::default().is_available(),
+      Self::DirectMl => DirectMLExecutionProvider::default().is_available(),
+      Self::Cpu => CPUExecutionProvider::default().is_available(),
+    }
+    .unwrap_or(false)
+  }
+
+  fn dispatch(self) -> ExecutionProviderDispatch {
+    match self {
+      Self::Cuda => CUDAExecutionProvider::default()
+        .with_device_id(0)
+        .build(),
+      Self::CoreMl => CoreMLExecutionProvider::default().build(),
+      Self::DirectMl => DirectMLExecutionProvider::default()
+        .with_device_id(0)
+        .build(),
+      Self::Cpu => CPUExecutionProvider::default().build(),
+    }
+  }
+}
+
+/// How a session is built, the defaults suit most models.
+pub struct SessionOptions {
+  pub optimization_level:  GraphOptimizationLevel,
+  /// Threads a single operator may use, ONNX Runtime decides when not given
+  pub intra_threads:       Option<usize>,
+  /// Runs independent branches of the graph at the same time
+  pub parallel_execution:  bool,
+  /// Tried in this order
+  pub execution_providers: Vec<ExecutionProvider>,
+}
+
+impl Default for SessionOptions {
+  fn default() -> Self {
+    Self {
+      optimization_level:  GraphOptimizationLevel::Level3,
+      intra_threads:       None,
+      parallel_execution:  false,
+      execution_providers: ExecutionProvider::ACCELERATED.to_vec(),
+    }
+  }
+}
+
+/// Creates a session for the ONNX model at `model_path`.
+pub fn create_session(
+  model_path: &Path,
+  options: SessionOptions,
+) -> Result<Session> {
+  let mut builder = Session::builder()?
+    .with_optimization_level(options.optimization_level)?
+    .with_parallel_execution(options.parallel_execution)?;
+  if let Some(threads) = options.intra_threads {
+    builder = builder.with_intra_threads(threads)?;
+  }
+
+  let providers: Vec<_> = options
+    .execution_providers
+    .into_iter()
+    .map(ExecutionProvider::dispatch)
+    .collect();
+  Ok(
+    builder
+      .with_execution_providers(providers)?
+      .commit_from_file(model_path)?,
+  )
+}
@@ -0,0 +1,510 @@
+use std::{
+  fs,
+  io::{BufRead, BufReader, Write},
+  net::{TcpListener, TcpStream},
+  path::PathBuf,
+  sync::{Arc, Mutex},
+  thread,
+};
+
+use airi_audio_models::{
+  AudioError,
+  DownloadPhase,
+  HubConfig,
+  ModelDownloadProgress,
+  ModelRepo,
+  ProgressEmitter,
+  cancel_download,
+};
+
+const COMMIT: &str = "0123456789abcdef0123456789abcdef01234567";
+const CONFIG: &str = r#"{"model_type":"tiny"}"#;
+const MODEL: &[u8] = &[7; 200_000];
+const EVENT: &str = "test:load-model-progress";
+
+/// What the stand-in saw of a request
+#[derive(Clone, Debug)]
+struct Request {
+  path:          String,
+  authorization: Option<String>,
+}
+
+/// A stand-in for the hub and its CDN, serving the same files for every repository `org/<name>`
+/// but the gated `org/gated`: `config.json` right away and `onnx/model.onnx` through a redirect to
+/// another host, the way large files are. It also acts as an HTTP proxy, tunnelling to itself
+/// whatever host was asked for.
+struct Hub {
+  port:     u16,
+  requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Hub {
+  fn start() -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    let seen = requests.clone();
+    thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        let seen = seen.clone();
+        thread::spawn(move || serve(stream, port, seen));
+      }
+    });
+
+    Self { port, requests }
+  }
+
+  fn endpoint(&self) -> String {
+    format!("http://127.0.0.1:{}", self.port)
+  }
+
+  fn requests(&self) -> Vec<Request> {
+    self.requests.lock().unwrap().clone()
+  }
+}
+
+fn serve(
+  stream: TcpStream,
+  port: u16,
+  seen: Arc<Mutex<Vec<Request>>>,
+) {
+  let mut reader = BufReader::new(stream.try_clone().unwrap());
+  let mut stream = stream;
+  loop {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+      return;
+    }
+    let mut authorization = None;
+    loop {
+      let mut line = String::new();
+      reader.read_line(&mut line).unwrap();
+      let line = line.trim_end();
+      if line.is_empty() {
+        break;
+      }
+      if let Some((name, value)) = line.split_once(": ")
+        && name.eq_ignore_ascii_case("authorization")
+      {
+        authorization = Some(value.to_string());
+      }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    if method == "CONNECT" {
+      stream
+        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+        .unwrap();
+      continue;
+    }
+
+    let path = target
+      .strip_prefix("http://")
+      .and_then(|rest| rest.find('/').map(|index| &rest[index..]))
+      .unwrap_or(target)
+      .to_string();
+    seen.lock().unwrap().push(Request {
+      path: path.clone(),
+      authorization,
+    });
+
+    let etag_header = |etag: &str| format!("ETag: \"{}\"\r\n", etag);
+    let file = path
+      .strip_prefix("/org/")
+      .and_then(|rest| rest.split_once("/resolve/main/"));
+    let (status, headers, body): (&str, String, &[u8]) = match file {
+      Some(("gated", _)) => ("401 Unauthorized", String::new(), b""),
+      Some((_, "config.json")) => (
+        "200 OK",
+        format!(
+          "X-Repo-Commit: {}\r\n{}",
+          COMMIT,
+          etag_header("config-etag")
+        ),
+        CONFIG.as_bytes(),
+      ),
+      Some((_, "onnx/model.onnx")) => (
+        "302 Found",
+        format!(
+          "X-Repo-Commit: {}\r\nX-Linked-Etag: \"model-etag\"\r\n{}Location: http://localhost:{}/cdn/model-etag\r\n",
+          COMMIT,
+          etag_header("redirect-etag"),
+          port
+        ),
+        b"",
+      ),
+      _ if path == "/cdn/model-etag" => ("200 OK", String::new(), MODEL),
+      // Files the repository does not have, the hub still names the commit it looked in
+      Some(_) => (
+        "404 Not Found",
+        format!("X-Repo-Commit: {}\r\n", COMMIT),
+        b"",
+      ),
+      _ => ("404 Not Found", String::new(), b""),
+    };
+
+    let head = format!(
+      "HTTP/1.1 {}\r\n{}Content-Length: {}\r\n\r\n",
+      status,
+      headers,
+      body.len()
+    );
+    if stream.write_all(head.as_bytes()).is_err() || stream.write_all(body).is_err() {
+      return;
+    }
+  }
+}
+
+/// Progress as the frontend would get it
+#[derive(Clone, Default)]
+struct Events(Arc<Mutex<Vec<ModelDownloadProgress>>>);
+
+impl Events {
+  fn of(
+    &self,
+    file: &str,
+  ) -> Vec<ModelDownloadProgress> {
+    self
+      .0
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|progress| progress.file == file)
+      .cloned()
+      .collect()
+  }
+}
+
+impl ProgressEmitter for Events {
+  fn emit_progress(
+    &self,
+    event_name: &str,
+    progress: &ModelDownloadProgress,
+  ) {
+    assert_eq!(event_name, EVENT);
+    self.0.lock().unwrap().push(progress.clone());
+  }
+}
+
+/// Cancels downloads as soon as they start, the way the frontend would
+#[derive(Clone, Default)]
+struct CancelingEvents(Events);
+
+impl ProgressEmitter for CancelingEvents {
+  fn emit_progress(
+    &self,
+    event_name: &str,
+    progress: &ModelDownloadProgress,
+  ) {
+    self.0.emit_progress(event_name, progress);
+    if progress.phase == DownloadPhase::Downloading {
+      assert!(cancel_download(&progress.model_id));
+    }
+  }
+}
+
+fn cache_dir(name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("airi-audio-models-{}-{}", name, std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  dir
+}
+
+fn online(hub: &Hub) -> HubConfig {
+  HubConfig {
+    endpoint: Some(format!("{}/", hub.endpoint())),
+    token: Some("hf_test".to_string()),
+    ..Default::default()
+  }
+}
+
+#[test]
+fn downloads_from_the_configured_endpoint() {
+  let hub = Hub::start();
+  let cache = cache_dir("endpoint");
+  let repo = ModelRepo::with_config("org/tiny", "main", online(&hub), cache.clone()).unwrap();
+  let events = Events::default();
+
+  let config = repo
+    .get("config.json", events.clone(), EVENT)
+    .unwrap();
+  let model = repo
+    .get("onnx/model.onnx", events.clone(), EVENT)
+    .unwrap();
+  assert_eq!(fs::read_to_string(&config).unwrap(), CONFIG);
+  assert_eq!(fs::read(&model).unwrap(), MODEL);
+
+  // Laid out like the Hugging Face cache, so it is found without the hub
+  assert!(model.starts_with(repo.cache_path().join("snapshots").join(COMMIT)));
+  assert!(
+    repo
+      .cache_path()
+      .join("blobs/model-etag")
+      .is_file()
+  );
+  assert_eq!(
+    fs::read_to_string(repo.cache_path().join("refs/main")).unwrap(),
+    COMMIT
+  );
+  assert_eq!(repo.cached("onnx/model.onnx"), Some(model.clone()));
+
+  let requests = hub.requests();
+  assert_eq!(
+    requests
+      .iter()
+      .map(|request| request.path.as_str())
+      .collect::<Vec<_>>(),
+    [
+      "/org/tiny/resolve/main/config.json",
+      "/org/tiny/resolve/main/onnx/model.onnx",
+      "/cdn/model-etag"
+    ]
+  );
+  // The token goes to the hub only, not to the CDN
+  assert_eq!(requests[0].authorization.as_deref(), Some("Bearer hf_test"));
+  assert_eq!(requests[1].authorization.as_deref(), Some("Bearer hf_test"));
+  assert_eq!(requests[2].authorization, None);
+
+  let events = events.of("onnx/model.onnx");
+  let first = events.first().unwrap();
+  assert_eq!(first.model_id, "org/tiny");
+  assert_eq!(first.phase, DownloadPhase::Downloading);
+  assert_eq!(first.downloaded_bytes, 0);
+  assert_eq!(first.total_bytes, Some(MODEL.len() as u64));
+  let last = events.last().unwrap();
+  assert_eq!(last.phase, DownloadPhase::Finished);
+  assert_eq!(last.downloaded_bytes, MODEL.len() as u64);
+  assert_eq!(last.progress, 100.0);
+
+  // Cached files are not downloaded again
+  repo
+    .get("onnx/model.onnx", Events::default(), EVENT)
+    .unwrap();
+  assert_eq!(hub.requests().len(), 3);
+
+  let _ = fs::remove_dir_all(cache);
+}
+
+#[test]
+fn downloads_the_same_file_at_once() {
+  let hub = Hub::start();
+  let cache = cache_dir("concurrent");
+  let repo = Arc::new(
+    ModelRepo::with_config("org/concurrent", "main", online(&hub), cache.clone()).unwrap(),
+  );
+
+  // E.g. two plugins loading the same model, or a reload while it is still loading
+  let downloads: Vec<_> = (0..4)
+    .map(|_| {
+      let repo = repo.clone();
+      thread::spawn(move || repo.download("onnx/model.onnx", Events::default(), EVENT))
+    })
+    .collect();
+  for download in downloads {
+    let model = download.join().unwrap().unwrap();
+    assert_eq!(fs::read(&model).unwrap(), MODEL);
+  }
+
+  let blobs: Vec<_> = fs::read_dir(repo.cache_path().join("blobs"))
+    .unwrap()
+    .map(|entry| entry.unwrap().file_name())
+    .collect();
+  assert_eq!(blobs, ["model-etag"]);
+  assert_eq!(
+    fs::read(repo.cached("onnx/model.onnx").unwrap()).unwrap(),
+    MODEL
+  );
+
+  let _ = fs::remove_dir_all(cache);
+}
+
+#[test]
+fn offline_mode_only_uses_the_cache() {
+  let hub = Hub::start();
+  let cache = cache_dir("offline");
+  ModelRepo::with_config("org/tiny", "main", online(&hub), cache.clone())
+    .unwrap()
+    .get("config.json", Events::default(), EVENT)
+    .unwrap();
+  let requests = hub.requests().len();
+
+  let offline = HubConfig {
+    offline: true,
+    ..online(&hub)
+  };
+  let repo = ModelRepo::with_config("org/tiny", "main", offline, cache.clone()).unwrap();
+  assert!(
+    repo
+      .get("config.json", Events::default(), EVENT)
+      .is_ok()
+  );
+
+  let error = AudioError::load_failed(
+    repo
+      .get("onnx/model.onnx", Events::default(), EVENT)
+      .unwrap_err(),
+  );
+  assert!(
+    matches!(&error, AudioError::Offline(message) if message.contains("models--org--tiny")),
+    "{:?}",
+    error
+  );
+  assert_eq!(hub.requests().len(), requests);
+
+  let _ = fs::remove_dir_all(cache);
+}
+
+#[test]
+fn reports_missing_files_and_refused_access() {
+  let hub = Hub::start();
+  let cache = cache_dir("errors");
+
+  let repo = ModelRepo::with_config("org/tiny", "main", online(&hub), cache.clone()).unwrap();
+  let error = AudioError::load_failed(
+    repo
+      .get("tokenizer.json", Events::default(), EVENT)
+      .unwrap_err(),
+  );
+  assert!(
+    matches!(&error, AudioError::NotFound(message) if message.contains("does not exist")),
+    "{:?}",
+    error
+  );
+
+  let repo = ModelRepo::with_config("org/gated", "main", online(&hub), cache.clone()).unwrap();
+  let error = AudioError::load_failed(
+    repo
+      .get("config.json", Events::default(), EVENT)
+      .unwrap_err(),
+  );
+  assert!(matches!(error, AudioError::Unauthorized(_)), "{:?}", error);
+
+  let unreachable = HubConfig {
+    endpoint: Some("http://127.0.0.1:1".to_string()),
+    ..Default::default()
+  };
+  let repo = ModelRepo::with_config("org/tiny", "main", unreachable, cache.clone()).unwrap();
+  let error = AudioError::load_failed(
+    repo
+      .get("config.json", Events::default(), EVENT)
+      .unwrap_err(),
+  );
+  assert!(
+    matches!(&error, AudioError::DownloadFailed(message) if message.contains("Could not reach")),
+    "{:?}",
+    error
+  );
+
+  let _ = fs::remove_dir_all(cache);
+}
+
+#[test]
+fn remembers_the_files_a_repository_does_not_have() {
+  let hub = Hub::start();
+  let cache = cache_dir("optional");
+  let repo = ModelRepo::with_config("org/optional", "main", online(&hub), cache.clone()).unwrap();
+
+  let config = repo
+    .get_optional("config.json", Events::default(), EVENT)
+    .unwrap();
+  assert_eq!(config, repo.cached("config.json"));
+  assert!(config.is_some());
+
+  assert_eq!(
+    repo
+      .get_optional("onnx/model.onnx_data", Events::default(), EVENT)
+      .unwrap(),
+    None
+  );
+  assert!(repo.is_missing("onnx/model.onnx_data"));
+  assert!(!repo.is_missing("onnx/model.onnx"));
+
+  // Asked once only
+  let requests = hub.requests().len();
+  assert_eq!(
+    repo
+      .get_optional("onnx/model.onnx_data", Events::default(), EVENT)
+      .unwrap(),
+    None
+  );
+  assert_eq!(hub.requests().len(), requests);
+
+  // Failures other than a missing file are errors
+  let repo = ModelRepo::with_config("org/gated", "main", online(&hub), cache.clone()).unwrap();
+  let error = AudioError::load_failed(
+    repo
+      .get_optional("onnx/model.onnx_data", Events::default(), EVENT)
+      .unwrap_err(),
+  );
+  assert!(matches!(error, AudioError::Unauthorized(_)), "{:?}", error);
+
+  let _ = fs::remove_dir_all(cache);
+}
+
+#[test]
+fn downloads_through_the_proxy() {
+  let hub = Hub::start();
+  let cache = cache_dir("proxy");
+  // Only reachable through the proxy
+  let config = HubConfig {
+    endpoint: Some("http://hub.invalid".to_string()),
+    proxy: Some(hub.endpoint()),
+    ..Default::default()
+  };
+
+  let repo = ModelRepo::with_config("org/tiny", "main", config, cache.clone()).unwrap();
+  let path = repo
+    .get("config.json", Events::default(), EVENT)
+    .unwrap();
+  assert_eq!(fs::read_to_string(path).unwrap(), CONFIG);
+  assert_eq!(hub.requests().len(), 1);
+
+  let _ = fs::remove_dir_all(cache);
+}
+
+#[test]
+fn cancelled_downloads_leave_nothing_behind() {
+  let hub = Hub::start();
+  let cache = cache_dir("cancel");
+  // Not shared with the other tests, which would be cancelled as well
+  let repo = ModelRepo::with_config("org/cancel", "main", online(&hub), cache.clone()).unwrap();
+  let events = CancelingEvents::default();
+
+  let error = AudioError::load_failed(
+    repo
+      .get("onnx/model.onnx", events.clone(), EVENT)
+      .unwrap_err(),
+  );
+  assert!(matches!(error, AudioError::Cancelled(_)), "{:?}", error);
+  assert_eq!(
+    events
+      .0
+      .of("onnx/model.onnx")
+      .iter()
+      .map(|progress| progress.phase)
+      .collect::<Vec<_>>(),
+    [DownloadPhase::Downloading, DownloadPhase::Cancelled]
+  );
+  assert_eq!(
+    fs::read_dir(repo.cache_path().join("blobs"))
+      .unwrap()
+      .count(),
+    0
+  );
+  assert_eq!(repo.cached("onnx/model.onnx"), None);
+  assert!(!cancel_download("org/cancel"));
+
+  // Nothing is left to cancel the next download
+  let events = Events::default();
+  repo
+    .get("onnx/model.onnx", events.clone(), EVENT)
+    .unwrap();
+  assert_eq!(
+    events.of("onnx/model.onnx").last().unwrap().phase,
+    DownloadPhase::Finished
+  );
+
+  let _ = fs::remove_dir_all(cache);
+}
@@ -0,0 +1,5 @@
+const COMMANDS: &[&str] = &["list_input_devices", "start_capture", "stop_capture"];
+
+fn main() {
+  tauri_plugin::Builder::new(COMMANDS).build();
+}
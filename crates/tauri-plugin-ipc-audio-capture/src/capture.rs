@@ -0,0 +1,714 @@
+use std::{
+  path::PathBuf,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, Sender},
+  },
+  thread,
+  time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use log::{error, info, warn};
+use rubato::{FftFixedIn, Resampler};
+use serde::{Deserialize, Serialize};
+use tauri::{Runtime, ipc::Channel};
+use tauri_plugin_ipc_audio_kws::{KeywordSpotter, SpotterOptions, Wake};
+use tauri_plugin_ipc_audio_preprocess::{EchoReference, PreprocessOptions, Preprocessor};
+use tauri_plugin_ipc_audio_prosody_ort::Prosody;
+use tauri_plugin_ipc_audio_speaker_ort::{DiarizationOptions, Diarizer, SpeakerMatch};
+use tauri_plugin_ipc_audio_transcription_ort::{
+  GenerationConfig,
+  GuardConfig,
+  TranscriptionResult,
+};
+use tauri_plugin_ipc_audio_vad_ort::{SegmenterOptions, SpeechEvent, VadSession};
+
+use crate::sources::{AudioSource, WavSource};
+
+/// Everything is resampled to what the VAD and Whisper take
+pub const SAMPLE_RATE: usize = 16000;
+
+const RESAMPLER_CHUNK_SIZE: usize = 1024;
+/// How often the capture thread checks whether it was stopped
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to capture from.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum CaptureSource {
+  /// An input device by name, the default one when not given
+  Device { name: Option<String> },
+  /// A WAV file played as if it was a device, as fast as it can be processed unless `realtime`
+  File {
+    path:     PathBuf,
+    #[serde(default)]
+    realtime: bool,
+  },
+}
+
+impl Default for CaptureSource {
+  fn default() -> Self {
+    Self::Device { name: None }
+  }
+}
+
+impl CaptureSource {
+  fn open(&self) -> Result<Box<dyn AudioSource>> {
+    match self {
+      #[cfg(feature = "native-devices")]
+      Self::Device { name } => Ok(Box::new(crate::devices::DeviceSource::open(
+        name.as_deref(),
+      )?)),
+      #[cfg(not(feature = "native-devices"))]
+      Self::Device { .. } => Err(anyhow!(
+        "Built without the native-devices feature, only files can be captured"
+      )),
+      Self::File { path, realtime } => Ok(Box::new(WavSource::open(path, *realtime)?)),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CaptureOptions {
+  /// Noise suppression and echo cancellation before anything else sees the audio
+  pub preprocess: PreprocessOptions,
+  /// How speech segments are cut out of the audio
+  pub segmenter:  SegmenterOptions,
+  /// Speech segments are transcribed with the default Whisper model unless turned off
+  pub transcribe: bool,
+  pub language:   Option<String>,
+  pub guards:     GuardConfig,
+  /// Stays idle until an enrolled keyword is said, only speech from then on is transcribed
+  pub wake_word:  Option<SpotterOptions>,
+  /// How long speech is transcribed after the keyword or the latest transcribed segment
+  pub awake_ms:   u32,
+  /// Transcripts are tagged with who said them, enrolled speakers by name, others as unknown
+  pub speakers:   Option<DiarizationOptions>,
+  /// Transcripts come with how they were said: pitch, loudness, speaking rate and emotion
+  pub prosody:    bool,
+}
+
+impl Default for CaptureOptions {
+  fn default() -> Self {
+    Self {
+      preprocess: PreprocessOptions::default(),
+      segmenter:  SegmenterOptions::default(),
+      transcribe: true,
+      language:   None,
+      guards:     GuardConfig::default(),
+      wake_word:  None,
+      awake_ms:   10000,
+      speakers:   None,
+      prosody:    false,
+    }
+  }
+}
+
+/// Positions are sample offsets at 16 kHz since the capture started, padding included.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum CaptureEvent {
+  /// The source is open, with its own format before resampling
+  #[serde(rename_all = "camelCase")]
+  Started {
+    sample_rate: u32,
+    channels:    usize,
+  },
+  SpeechStart {
+    start: u64,
+  },
+  SpeechEnd {
+    start: u64,
+    end:   u64,
+  },
+  /// An enrolled keyword was said, in wake word mode
+  Wake(Wake),
+  /// A speech segment was transcribed, in the order they ended
+  Transcript {
+    start:   u64,
+    end:     u64,
+    /// Who said the segment, when speakers are identified and it was long enough to tell
+    speaker: Option<SpeakerMatch>,
+    /// How the segment was said, when prosody is analyzed
+    prosody: Option<Prosody>,
+    #[serde(flatten)]
+    result:  TranscriptionResult,
+  },
+  Error {
+    message: String,
+  },
+  /// The capture ended, stopped or because the source did
+  Stopped,
+}
+
+/// A running capture. Audio is read, resampled and run through the VAD on its own thread, and
+/// speech segments are transcribed on another one so the VAD never waits for Whisper.
+pub struct Capture {
+  stop:  Arc<AtomicBool>,
+  ended: Arc<AtomicBool>,
+}
+
+impl Capture {
+  /// Opens `source` and starts capturing from it, returns once the source is open. `on_end` is
+  /// called on the capture thread once `Stopped` was sent, however the capture ended.
+  pub fn start<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    source: CaptureSource,
+    options: CaptureOptions,
+    channel: Channel<CaptureEvent>,
+    on_end: impl FnOnce() + Send + 'static,
+  ) -> Result<Self> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let ended = Arc::new(AtomicBool::new(false));
+    let (opened, open_result) = mpsc::sync_channel(1);
+
+    let thread_stop = stop.clone();
+    let thread_ended = ended.clone();
+    thread::Builder::new()
+      .name("audio-capture".to_string())
+      .spawn(move || {
+        let mut source = match source.open() {
+          Ok(source) => {
+            let _ = opened.send(Ok(()));
+            source
+          },
+          Err(e) => {
+            let _ = opened.send(Err(e));
+            return;
+          },
+        };
+
+        if let Err(e) = run(&app, source.as_mut(), &options, &channel, &thread_stop) {
+          error!("Audio capture failed: {}", e);
+          send(
+            &channel,
+            CaptureEvent::Error {
+              message: e.to_string(),
+            },
+          );
+        }
+        send(&channel, CaptureEvent::Stopped);
+
+        thread_ended.store(true, Ordering::Relaxed);
+        on_end();
+      })?;
+
+    open_result
+      .recv()
+      .map_err(|_| anyhow!("Audio capture thread exited"))??;
+
+    Ok(Self { stop, ended })
+  }
+
+  /// Whether the capture thread is still going, it ends on its own with the source, an error or
+  /// the frontend going away.
+  pub fn is_running(&self) -> bool {
+    !self.ended.load(Ordering::Relaxed)
+  }
+
+  /// Stops reading from the source. Speech going on is ended and transcribed, then `Stopped` is
+  /// sent.
+  pub fn stop(&self) {
+    self.stop.store(true, Ordering::Relaxed);
+  }
+}
+
+fn send(
+  channel: &Channel<CaptureEvent>,
+  event: CaptureEvent,
+) -> bool {
+  match channel.send(event) {
+    Ok(()) => true,
+    Err(e) => {
+      error!("Failed to send capture event: {}", e);
+      false
+    },
+  }
+}
+
+fn run<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  source: &mut dyn AudioSource,
+  options: &CaptureOptions,
+  channel: &Channel<CaptureEvent>,
+  stop: &AtomicBool,
+) -> Result<()> {
+  let mut splitter = SpeechSplitter::new(
+    source.sample_rate(),
+    source.channels(),
+    options,
+    tauri_plugin_ipc_audio_preprocess::echo_reference(app)?,
+  )?;
+  if !send(
+    channel,
+    CaptureEvent::Started {
+      sample_rate: source.sample_rate(),
+      channels:    source.channels(),
+    },
+  ) {
+    return Ok(());
+  }
+  info!(
+    "Audio capture started: {} Hz, {} channels",
+    source.sample_rate(),
+    source.channels()
+  );
+
+  let mut gate = options
+    .wake_word
+    .clone()
+    .map(|spotter| WakeGate::new(spotter, options.awake_ms));
+
+  let (segments, receiver) = mpsc::channel();
+  let transcriber = options.transcribe.then(|| {
+    let app = app.clone();
+    let channel = channel.clone();
+    let diarizer = options.speakers.clone().map(Diarizer::new);
+    let prosody = options.prosody;
+    let config = GenerationConfig {
+      language: options.language.clone(),
+      guards: options.guards.clone(),
+      ..Default::default()
+    };
+    thread::spawn(move || run_transcriber(app, receiver, diarizer, prosody, config, channel))
+  });
+
+  let mut result = Ok(());
+  while !stop.load(Ordering::Relaxed) {
+    let chunk = match source.next_chunk(POLL_INTERVAL) {
+      Ok(Some(chunk)) => chunk,
+      Ok(None) => break,
+      Err(e) => {
+        result = Err(e);
+        break;
+      },
+    };
+    if chunk.is_empty() {
+      continue;
+    }
+
+    let samples = match splitter.prepare(&chunk) {
+      Ok(samples) => samples,
+      Err(e) => {
+        result = Err(e);
+        break;
+      },
+    };
+
+    // Keywords first, speech that ends in this chunk may have started with one
+    if let Some(gate) = &mut gate {
+      let wakes =
+        match tauri_plugin_ipc_audio_kws::push_to_spotter(app, &mut gate.spotter, &samples) {
+          Ok(wakes) => wakes,
+          Err(e) => {
+            result = Err(e.into());
+            break;
+          },
+        };
+      if !wake(gate, wakes, channel) {
+        break;
+      }
+    }
+
+    let pushed =
+      tauri_plugin_ipc_audio_vad_ort::push_to_session(app, splitter.vad_session(), &samples);
+    match pushed {
+      Ok(pushed) => {
+        if !handle_events(pushed.events, &mut splitter, &mut gate, &segments, channel) {
+          break;
+        }
+      },
+      Err(e) => {
+        result = Err(e.into());
+        break;
+      },
+    }
+  }
+
+  // Speech still going on ends with the audio, and is transcribed before `Stopped` is sent
+  if let Some(gate) = &mut gate {
+    let wakes = gate.spotter.finish();
+    wake(gate, wakes, channel);
+  }
+  let events = splitter.vad_session().finish();
+  handle_events(events, &mut splitter, &mut gate, &segments, channel);
+  drop(segments);
+  if let Some(transcriber) = transcriber {
+    let _ = transcriber.join();
+  }
+
+  info!("Audio capture stopped");
+  result
+}
+
+/// Wakes the gate up for the keywords said, and forwards them. Returns false when the frontend is
+/// gone.
+fn wake(
+  gate: &mut WakeGate,
+  wakes: Vec<Wake>,
+  channel: &Channel<CaptureEvent>,
+) -> bool {
+  for wake in wakes {
+    gate.wake(&wake);
+    if !send(channel, CaptureEvent::Wake(wake)) {
+      return false;
+    }
+  }
+
+  true
+}
+
+/// Forwards VAD events and queues the speech that ended for transcription, unless the wake word
+/// was not said. Returns false when the frontend is gone.
+fn handle_events(
+  events: Vec<SpeechEvent>,
+  splitter: &mut SpeechSplitter,
+  gate: &mut Option<WakeGate>,
+  segments: &Sender<(u64, u64, Vec<f32>)>,
+  channel: &Channel<CaptureEvent>,
+) -> bool {
+  for event in events {
+    let audio = splitter.cut(&event);
+    let event = match event {
+      SpeechEvent::SpeechStart { start } => CaptureEvent::SpeechStart { start },
+      SpeechEvent::SpeechEnd { start, end } => {
+        if let Some(audio) = audio
+          && gate
+            .as_mut()
+            .is_none_or(|gate| gate.admits(start, end))
+        {
+          // Nobody listens when transcription is turned off
+          let _ = segments.send((start, end, audio));
+        }
+        CaptureEvent::SpeechEnd { start, end }
+      },
+    };
+
+    if !send(channel, event) {
+      return false;
+    }
+  }
+
+  true
+}
+
+fn run_transcriber<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  receiver: Receiver<(u64, u64, Vec<f32>)>,
+  mut diarizer: Option<Diarizer>,
+  analyze_prosody: bool,
+  config: GenerationConfig,
+  channel: Channel<CaptureEvent>,
+) {
+  // Without a speaker model every segment fails the same way, the frontend hears it once
+  let mut identify_failed = false;
+  for (start, end, audio) in receiver {
+    // A segment whose speaker is not known is still transcribed
+    let speaker = match diarizer
+      .as_mut()
+      .map(|diarizer| tauri_plugin_ipc_audio_speaker_ort::identify(&app, diarizer, &audio))
+      .transpose()
+    {
+      Ok(speaker) => speaker.flatten(),
+      Err(e) => {
+        let message = format!("Failed to identify speaker: {}", e);
+        if identify_failed {
+          warn!("{}", message);
+        } else {
+          identify_failed = true;
+          if !send(&channel, CaptureEvent::Error { message }) {
+            return;
+          }
+        }
+        None
+      },
+    };
+
+    let result = match tauri_plugin_ipc_audio_transcription_ort::transcribe(&app, &audio, &config) {
+      Ok(result) => result,
+      Err(e) => {
+        let message = format!("Failed to transcribe speech: {}", e);
+        if !send(&channel, CaptureEvent::Error { message }) {
+          return;
+        }
+        continue;
+      },
+    };
+
+    // The transcript gives the word rate, a rejected one has no words
+    let text = Some(result.text.as_str()).filter(|text| !text.is_empty());
+    let prosody = match analyze_prosody
+      .then(|| tauri_plugin_ipc_audio_prosody_ort::analyze(&app, &audio, text))
+      .transpose()
+    {
+      Ok(prosody) => prosody,
+      Err(e) => {
+        let message = format!("Failed to analyze prosody: {}", e);
+        if !send(&channel, CaptureEvent::Error { message }) {
+          return;
+        }
+        None
+      },
+    };
+
+    let event = CaptureEvent::Transcript {
+      start,
+      end,
+      speaker,
+      prosody,
+      result,
+    };
+
+    if !send(&channel, event) {
+      return;
+    }
+  }
+}
+
+/// Lets speech through to transcription from the wake word on, for a while after the keyword
+/// and after every segment let through. Positions are sample offsets at 16 kHz.
+pub struct WakeGate {
+  spotter: KeywordSpotter,
+  awake:   u64,
+  /// Start of the latest keyword
+  woken:   Option<u64>,
+  /// Segments starting after this are not transcribed
+  until:   u64,
+}
+
+impl WakeGate {
+  pub fn new(
+    options: SpotterOptions,
+    awake_ms: u32,
+  ) -> Self {
+    Self {
+      spotter: KeywordSpotter::new(options),
+      awake:   SAMPLE_RATE as u64 * u64::from(awake_ms) / 1000,
+      woken:   None,
+      until:   0,
+    }
+  }
+
+  /// Wakes up for a keyword that was said.
+  pub fn wake(
+    &mut self,
+    wake: &Wake,
+  ) {
+    self.woken = Some(wake.start);
+    self.until = self.until.max(wake.end + self.awake);
+  }
+
+  /// Whether a segment that ended is transcribed, which keeps the gate open for longer.
+  pub fn admits(
+    &mut self,
+    start: u64,
+    end: u64,
+  ) -> bool {
+    // The segment the keyword was said in counts, those before it do not
+    let admitted = self
+      .woken
+      .is_some_and(|woken| end > woken && start <= self.until);
+    if admitted {
+      self.until = self.until.max(end + self.awake);
+    }
+    admitted
+  }
+}
+
+/// The part of a capture that needs no app: turns what the source reads into clean 16 kHz mono
+/// audio, and cuts the speech segments the VAD finds in it out again.
+pub struct SpeechSplitter {
+  channels:     usize,
+  resampler:    StreamResampler,
+  preprocessor: Preprocessor,
+  session:      VadSession,
+  recorder:     SpeechRecorder,
+}
+
+impl SpeechSplitter {
+  pub fn new(
+    sample_rate: u32,
+    channels: usize,
+    options: &CaptureOptions,
+    echo_reference: Arc<EchoReference>,
+  ) -> Result<Self> {
+    if channels == 0 {
+      return Err(anyhow!("Source has no channels"));
+    }
+
+    Ok(Self {
+      channels,
+      resampler: StreamResampler::new(sample_rate as usize, SAMPLE_RATE)?,
+      preprocessor: Preprocessor::new(SAMPLE_RATE as u32, &options.preprocess, echo_reference)?,
+      session: VadSession::new(SAMPLE_RATE as u32, &options.segmenter)?,
+      recorder: SpeechRecorder::new(&options.segmenter),
+    })
+  }
+
+  /// Downmixes, resamples and cleans up interleaved samples read from the source. The result is
+  /// what the VAD session is fed with.
+  pub fn prepare(
+    &mut self,
+    chunk: &[f32],
+  ) -> Result<Vec<f32>> {
+    let samples = self
+      .resampler
+      .process(&downmix(chunk, self.channels))?;
+    let samples = self.preprocessor.process(&samples);
+    self.recorder.extend(&samples);
+    Ok(samples)
+  }
+
+  pub fn vad_session(&mut self) -> &mut VadSession {
+    &mut self.session
+  }
+
+  /// Follows the segments the VAD session reports, returns the audio of the one that ended.
+  pub fn cut(
+    &mut self,
+    event: &SpeechEvent,
+  ) -> Option<Vec<f32>> {
+    match *event {
+      SpeechEvent::SpeechStart { start } => {
+        self.recorder.hold(start);
+        None
+      },
+      SpeechEvent::SpeechEnd { start, end } => Some(self.recorder.take(start, end)),
+    }
+  }
+}
+
+/// Keeps the recent 16 kHz audio, and all of it since the start of the current speech segment.
+struct SpeechRecorder {
+  samples: Vec<f32>,
+  /// Position of the first kept sample
+  offset:  u64,
+  /// Start of the current speech segment
+  hold:    Option<u64>,
+  /// Segment starts are reported this far back at most, minimum duration and padding included
+  keep:    usize,
+}
+
+impl SpeechRecorder {
+  fn new(options: &SegmenterOptions) -> Self {
+    let keep_ms = options.min_speech_duration_ms + options.speech_pad_ms + 1000;
+    Self {
+      samples: Vec::new(),
+      offset:  0,
+      hold:    None,
+      keep:    SAMPLE_RATE * keep_ms as usize / 1000,
+    }
+  }
+
+  fn extend(
+    &mut self,
+    samples: &[f32],
+  ) {
+    self.samples.extend_from_slice(samples);
+
+    let end = self.offset + self.samples.len() as u64;
+    let keep_from = self
+      .hold
+      .unwrap_or(end.saturating_sub(self.keep as u64));
+    // Trimmed in batches, not on every chunk
+    let excess = keep_from.saturating_sub(self.offset) as usize;
+    if excess > self.keep {
+      self.samples.drain(..excess);
+      self.offset += excess as u64;
+    }
+  }
+
+  fn hold(
+    &mut self,
+    start: u64,
+  ) {
+    self.hold = Some(start);
+  }
+
+  /// Returns the audio of a segment that ended, as much of it as was kept.
+  fn take(
+    &mut self,
+    start: u64,
+    end: u64,
+  ) -> Vec<f32> {
+    self.hold = None;
+
+    let position = |at: u64| (at.saturating_sub(self.offset) as usize).min(self.samples.len());
+    self.samples[position(start)..position(end)].to_vec()
+  }
+}
+
+/// Averages interleaved channels into one.
+pub fn downmix(
+  samples: &[f32],
+  channels: usize,
+) -> Vec<f32> {
+  if channels == 1 {
+    return samples.to_vec();
+  }
+
+  samples
+    .chunks_exact(channels)
+    .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+    .collect()
+}
+
+/// Resamples audio that arrives in chunks of any length. The resampler delay is dropped, so
+/// positions in the output match the input.
+pub struct StreamResampler {
+  resampler: Option<FftFixedIn<f32>>,
+  pending:   Vec<f32>,
+  delay:     usize,
+}
+
+impl StreamResampler {
+  pub fn new(
+    from: usize,
+    to: usize,
+  ) -> Result<Self> {
+    if from == 0 {
+      return Err(anyhow!("Invalid sample rate {}", from));
+    }
+
+    let resampler = (from != to)
+      .then(|| FftFixedIn::<f32>::new(from, to, RESAMPLER_CHUNK_SIZE, 2, 1))
+      .transpose()?;
+    let delay = resampler
+      .as_ref()
+      .map_or(0, |resampler| resampler.output_delay());
+
+    Ok(Self {
+      resampler,
+      pending: Vec::new(),
+      delay,
+    })
+  }
+
+  pub fn process(
+    &mut self,
+    samples: &[f32],
+  ) -> Result<Vec<f32>> {
+    let Some(resampler) = &mut self.resampler else {
+      return Ok(samples.to_vec());
+    };
+
+    self.pending.extend_from_slice(samples);
+    let mut output = Vec::new();
+    let mut consumed = 0;
+    while self.pending.len() - consumed >= resampler.input_frames_next() {
+      let chunk = &self.pending[consumed..consumed + resampler.input_frames_next()];
+      consumed += chunk.len();
+      output.extend_from_slice(&resampler.process(&[chunk], None)?[0]);
+    }
+    self.pending.drain(..consumed);
+
+    let skipped = self.delay.min(output.len());
+    self.delay -= skipped;
+    output.drain(..skipped);
+
+    Ok(output)
+  }
+}
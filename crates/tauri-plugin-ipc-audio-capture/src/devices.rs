@@ -0,0 +1,155 @@
+use std::{
+  sync::mpsc::{self, Receiver, RecvTimeoutError},
+  time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use cpal::{
+  FromSample,
+  Sample,
+  SampleFormat,
+  SizedSample,
+  Stream,
+  StreamConfig,
+  StreamError,
+  traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+use log::error;
+
+use crate::sources::{AudioSource, InputDevice};
+
+pub fn list_input_devices() -> Result<Vec<InputDevice>> {
+  let host = cpal::default_host();
+  let default_name = host
+    .default_input_device()
+    .and_then(|device| device.name().ok());
+
+  let mut devices = Vec::new();
+  for device in host.input_devices()? {
+    let Ok(name) = device.name() else {
+      continue;
+    };
+    // Devices that are listed but cannot be opened are left out
+    let Ok(config) = device.default_input_config() else {
+      continue;
+    };
+
+    devices.push(InputDevice {
+      is_default: default_name.as_ref() == Some(&name),
+      name,
+      sample_rate: config.sample_rate().0,
+      channels: config.channels(),
+    });
+  }
+
+  Ok(devices)
+}
+
+/// An input device opened with its default configuration.
+pub struct DeviceSource {
+  // Capturing stops when the stream is dropped
+  _stream:     Stream,
+  receiver:    Receiver<Vec<f32>>,
+  errors:      Receiver<String>,
+  sample_rate: u32,
+  channels:    usize,
+}
+
+impl DeviceSource {
+  /// Opens the device called `name`, or the default input device.
+  pub fn open(name: Option<&str>) -> Result<Self> {
+    let host = cpal::default_host();
+    let device = match name {
+      Some(name) => host
+        .input_devices()?
+        .find(|device| device.name().is_ok_and(|n| n == name))
+        .ok_or_else(|| anyhow!("Input device {} not found", name))?,
+      None => host
+        .default_input_device()
+        .ok_or_else(|| anyhow!("No input device available"))?,
+    };
+
+    let supported = device.default_input_config()?;
+    let config = supported.config();
+    let (sender, receiver) = mpsc::channel();
+    let (error_sender, errors) = mpsc::channel();
+
+    let stream = match supported.sample_format() {
+      SampleFormat::F32 => build_stream::<f32>(&device, &config, sender, error_sender),
+      SampleFormat::I16 => build_stream::<i16>(&device, &config, sender, error_sender),
+      SampleFormat::U16 => build_stream::<u16>(&device, &config, sender, error_sender),
+      SampleFormat::I32 => build_stream::<i32>(&device, &config, sender, error_sender),
+      SampleFormat::I8 => build_stream::<i8>(&device, &config, sender, error_sender),
+      SampleFormat::U8 => build_stream::<u8>(&device, &config, sender, error_sender),
+      format => Err(anyhow!("Unsupported sample format {}", format)),
+    }?;
+    stream.play()?;
+
+    Ok(Self {
+      _stream: stream,
+      receiver,
+      errors,
+      sample_rate: config.sample_rate.0,
+      channels: config.channels as usize,
+    })
+  }
+}
+
+fn build_stream<T>(
+  device: &cpal::Device,
+  config: &StreamConfig,
+  sender: mpsc::Sender<Vec<f32>>,
+  error_sender: mpsc::Sender<String>,
+) -> Result<Stream>
+where
+  T: SizedSample,
+  f32: FromSample<T>,
+{
+  let stream = device.build_input_stream(
+    config,
+    move |data: &[T], _| {
+      // The receiving end is gone once capturing stopped, the stream is about to be dropped
+      let _ = sender.send(
+        data
+          .iter()
+          .map(|&sample| f32::from_sample(sample))
+          .collect(),
+      );
+    },
+    move |e| {
+      error!("Input stream error: {}", e);
+      // Backend errors like overruns lose some audio, the stream keeps going
+      if matches!(e, StreamError::DeviceNotAvailable) {
+        let _ = error_sender.send(e.to_string());
+      }
+    },
+    None,
+  )?;
+
+  Ok(stream)
+}
+
+impl AudioSource for DeviceSource {
+  fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  fn channels(&self) -> usize {
+    self.channels
+  }
+
+  fn next_chunk(
+    &mut self,
+    timeout: Duration,
+  ) -> Result<Option<Vec<f32>>> {
+    if let Ok(e) = self.errors.try_recv() {
+      return Err(anyhow!("Input device failed: {}", e));
+    }
+
+    match self.receiver.recv_timeout(timeout) {
+      Ok(chunk) => Ok(Some(chunk)),
+      Err(RecvTimeoutError::Timeout) => Ok(Some(Vec::new())),
+      Err(RecvTimeoutError::Disconnected) => Ok(None),
+    }
+  }
+}
@@ -0,0 +1,179 @@
+use std::{
+  collections::HashMap,
+  sync::{
+    Mutex,
+    atomic::{AtomicU32, Ordering},
+  },
+};
+
+use airi_audio_models::AudioError;
+use log::info;
+use tauri::{
+  Manager,
+  Runtime,
+  WindowEvent,
+  ipc::Channel,
+  plugin::{Builder as PluginBuilder, TauriPlugin},
+};
+
+mod capture;
+#[cfg(feature = "native-devices")]
+mod devices;
+mod sources;
+
+pub use capture::{
+  CaptureEvent,
+  CaptureOptions,
+  CaptureSource,
+  SpeechSplitter,
+  StreamResampler,
+  WakeGate,
+  downmix,
+};
+pub use sources::{AudioSource, InputDevice, WavSource};
+
+use crate::capture::Capture;
+
+struct WindowCapture {
+  /// Label of the window that started the capture, which stops it by closing
+  window:  String,
+  capture: Capture,
+}
+
+#[derive(Default)]
+struct AppDataCaptures {
+  next_id:  AtomicU32,
+  captures: Mutex<HashMap<u32, WindowCapture>>,
+}
+
+impl AppDataCaptures {
+  /// Stops the captures of a window that closed, their events have nowhere to go anymore.
+  fn stop_window(
+    &self,
+    window: &str,
+  ) {
+    self
+      .captures
+      .lock()
+      .unwrap()
+      .retain(|capture_id, entry| {
+        if entry.window != window {
+          return true;
+        }
+
+        entry.capture.stop();
+        info!("Capture {} stopped, its window closed", capture_id);
+        false
+      });
+  }
+}
+
+#[cfg(feature = "native-devices")]
+#[tauri::command]
+async fn list_input_devices() -> Result<Vec<InputDevice>, AudioError> {
+  devices::list_input_devices()
+    .map_err(|e| AudioError::Internal(format!("Failed to list input devices: {}", e)))
+}
+
+/// Without native devices there is nothing to list, only files can be captured.
+#[cfg(not(feature = "native-devices"))]
+#[tauri::command]
+async fn list_input_devices() -> Result<Vec<InputDevice>, AudioError> {
+  Ok(Vec::new())
+}
+
+/// Starts capturing from `source`, the default input device when not given. Speech is detected
+/// with the loaded VAD and transcribed with the default Whisper model in-process, only events
+/// and transcripts are sent to `on_event`. Returns the capture id.
+#[tauri::command]
+async fn start_capture<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  window: tauri::WebviewWindow<R>,
+  source: Option<CaptureSource>,
+  options: Option<CaptureOptions>,
+  on_event: Channel<CaptureEvent>,
+) -> Result<u32, AudioError> {
+  let data = app.state::<AppDataCaptures>();
+  let capture_id = data.next_id.fetch_add(1, Ordering::Relaxed);
+
+  // A capture that ends on its own, with the source or an error, is forgotten by itself
+  let ended_app = app.clone();
+  let capture = Capture::start(
+    app.clone(),
+    source.unwrap_or_default(),
+    options.unwrap_or_default(),
+    on_event,
+    move || {
+      ended_app
+        .state::<AppDataCaptures>()
+        .captures
+        .lock()
+        .unwrap()
+        .remove(&capture_id);
+    },
+  )
+  .map_err(|e| AudioError::InvalidInput(format!("Failed to start capture: {}", e)))?;
+
+  // Checked under the lock `on_end` takes, a capture that already ended is not kept
+  let mut captures = data.captures.lock().unwrap();
+  if capture.is_running() {
+    captures.insert(
+      capture_id,
+      WindowCapture {
+        window: window.label().to_string(),
+        capture,
+      },
+    );
+  }
+
+  info!("Capture {} started", capture_id);
+  Ok(capture_id)
+}
+
+/// Stops a capture, the speech going on is still transcribed before `stopped` is sent. A capture
+/// that ended on its own already is left as it is.
+#[tauri::command]
+async fn stop_capture<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  capture_id: u32,
+) -> Result<(), AudioError> {
+  let data = app.state::<AppDataCaptures>();
+  let Some(entry) = data.captures.lock().unwrap().remove(&capture_id) else {
+    if capture_id >= data.next_id.load(Ordering::Relaxed) {
+      return Err(AudioError::NotFound(format!(
+        "Capture {} does not exist",
+        capture_id
+      )));
+    }
+    return Ok(());
+  };
+  entry.capture.stop();
+
+  info!("Capture {} stopped", capture_id);
+  Ok(())
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+  PluginBuilder::new("ipc-audio-capture")
+    .setup(|app, _| {
+      info!("Initializing audio capture plugin...");
+      app.manage(AppDataCaptures::default());
+      Ok(())
+    })
+    .on_window_ready(|window| {
+      let closed = window.clone();
+      window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+          closed
+            .state::<AppDataCaptures>()
+            .stop_window(closed.label());
+        }
+      });
+    })
+    .invoke_handler(tauri::generate_handler![
+      list_input_devices,
+      start_capture,
+      stop_capture
+    ])
+    .build()
+}
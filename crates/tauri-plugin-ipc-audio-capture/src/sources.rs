@@ -0,0 +1,116 @@
+use std::{
+  path::Path,
+  thread,
+  time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use hound::{SampleFormat, WavReader};
+use serde::Serialize;
+
+/// Chunks the WAV source hands out, about 20 ms at 48 kHz like a typical device buffer
+const WAV_CHUNK_FRAMES: usize = 960;
+
+/// An input device with the format it is opened with.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDevice {
+  pub name:        String,
+  pub is_default:  bool,
+  pub sample_rate: u32,
+  pub channels:    u16,
+}
+
+/// Where captured audio comes from: a device, or a file standing in for one. Sources are opened
+/// on the capture thread, device streams cannot move between threads on every platform.
+pub trait AudioSource {
+  fn sample_rate(&self) -> u32;
+
+  fn channels(&self) -> usize;
+
+  /// Waits up to `timeout` for the next interleaved samples. Returns an empty chunk on timeout
+  /// and `None` once the source ended.
+  fn next_chunk(
+    &mut self,
+    timeout: Duration,
+  ) -> Result<Option<Vec<f32>>>;
+}
+
+/// Plays a WAV file as if it was a microphone, for tests and for reproducing issues with a
+/// recording.
+pub struct WavSource {
+  samples:     Vec<f32>,
+  sample_rate: u32,
+  channels:    usize,
+  position:    usize,
+  /// Chunks are handed out no faster than they would be recorded, when set
+  realtime:    Option<Instant>,
+}
+
+impl WavSource {
+  pub fn open(
+    path: &Path,
+    realtime: bool,
+  ) -> Result<Self> {
+    let reader =
+      WavReader::open(path).map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+      SampleFormat::Float => reader
+        .into_samples::<f32>()
+        .collect::<Result<Vec<_>, _>>()?,
+      SampleFormat::Int => {
+        let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+        reader
+          .into_samples::<i32>()
+          .map(|sample| sample.map(|sample| sample as f32 / scale))
+          .collect::<Result<Vec<_>, _>>()?
+      },
+    };
+
+    Ok(Self {
+      samples,
+      sample_rate: spec.sample_rate,
+      channels: spec.channels as usize,
+      position: 0,
+      realtime: realtime.then(Instant::now),
+    })
+  }
+}
+
+impl AudioSource for WavSource {
+  fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  fn channels(&self) -> usize {
+    self.channels
+  }
+
+  fn next_chunk(
+    &mut self,
+    timeout: Duration,
+  ) -> Result<Option<Vec<f32>>> {
+    if self.position >= self.samples.len() {
+      return Ok(None);
+    }
+
+    if let Some(started) = self.realtime {
+      let frames = (self.position / self.channels) as u64;
+      let due = started + Duration::from_micros(frames * 1_000_000 / u64::from(self.sample_rate));
+      let wait = due.saturating_duration_since(Instant::now());
+      if wait > timeout {
+        thread::sleep(timeout);
+        return Ok(Some(Vec::new()));
+      }
+      thread::sleep(wait);
+    }
+
+    let end = (self.position + WAV_CHUNK_FRAMES * self.channels).min(self.samples.len());
+    let chunk = self.samples[self.position..end].to_vec();
+    self.position = end;
+
+    Ok(Some(chunk))
+  }
+}
@@ -0,0 +1,260 @@
+use std::{
+  f32::consts::PI,
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::Duration,
+};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use tauri_plugin_ipc_audio_capture::{
+  AudioSource,
+  CaptureOptions,
+  SpeechSplitter,
+  StreamResampler,
+  WavSource,
+  downmix,
+};
+use tauri_plugin_ipc_audio_preprocess::EchoReference;
+use tauri_plugin_ipc_audio_vad_ort::{EnergyDetector, SpeechEvent};
+
+/// A voice-like tone: 150 Hz and its harmonics up to 3 kHz.
+fn voice(
+  seconds: f32,
+  sample_rate: u32,
+) -> Vec<f32> {
+  (0..(seconds * sample_rate as f32) as usize)
+    .map(|i| {
+      let phase = 2.0 * PI * 150.0 * i as f32 / sample_rate as f32;
+      0.1
+        * (1..=20)
+          .map(|harmonic| (harmonic as f32 * phase).sin() / harmonic as f32)
+          .sum::<f32>()
+    })
+    .collect()
+}
+
+fn silence(
+  seconds: f32,
+  sample_rate: u32,
+) -> Vec<f32> {
+  vec![0.0; (seconds * sample_rate as f32) as usize]
+}
+
+/// Writes mono `samples` to every channel of a WAV file, 16-bit or float.
+fn write_wav(
+  name: &str,
+  sample_rate: u32,
+  channels: u16,
+  sample_format: SampleFormat,
+  samples: &[f32],
+) -> PathBuf {
+  let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+  let spec = WavSpec {
+    channels,
+    sample_rate,
+    bits_per_sample: if sample_format == SampleFormat::Float {
+      32
+    } else {
+      16
+    },
+    sample_format,
+  };
+
+  let mut writer = WavWriter::create(&path, spec).unwrap();
+  for &sample in samples {
+    for _ in 0..channels {
+      match sample_format {
+        SampleFormat::Float => writer.write_sample(sample).unwrap(),
+        SampleFormat::Int => writer
+          .write_sample((sample * i16::MAX as f32) as i16)
+          .unwrap(),
+      }
+    }
+  }
+  writer.finalize().unwrap();
+  path
+}
+
+/// Runs a file through the splitter the way a capture does, with the energy detector as VAD.
+fn split(
+  path: &Path,
+  options: &CaptureOptions,
+) -> Vec<(u64, u64, Vec<f32>)> {
+  let mut source = WavSource::open(path, false).unwrap();
+  let mut splitter = SpeechSplitter::new(
+    source.sample_rate(),
+    source.channels(),
+    options,
+    Arc::new(EchoReference::default()),
+  )
+  .unwrap();
+
+  // Events are followed as they come, the splitter only keeps the audio of speech it was told of
+  let mut segments = Vec::new();
+  let mut cut = |splitter: &mut SpeechSplitter, events: Vec<SpeechEvent>| {
+    for event in events {
+      if let (Some(audio), SpeechEvent::SpeechEnd { start, end }) = (splitter.cut(&event), event) {
+        segments.push((start, end, audio));
+      }
+    }
+  };
+
+  while let Some(chunk) = source
+    .next_chunk(Duration::from_millis(100))
+    .unwrap()
+  {
+    let samples = splitter.prepare(&chunk).unwrap();
+    let pushed = splitter
+      .vad_session()
+      .push(&EnergyDetector, &samples)
+      .unwrap();
+    cut(&mut splitter, pushed.events);
+  }
+  let events = splitter.vad_session().finish();
+  cut(&mut splitter, events);
+
+  segments
+}
+
+fn assert_near(
+  actual: u64,
+  expected: u64,
+) {
+  // Speech is found window by window, 512 samples at 16 kHz
+  assert!(
+    actual.abs_diff(expected) <= 1024,
+    "{actual} is not near {expected}"
+  );
+}
+
+#[test]
+fn splits_a_recording_into_its_speech() {
+  let samples = [
+    silence(1.0, 48000),
+    voice(1.5, 48000),
+    silence(1.0, 48000),
+    voice(0.8, 48000),
+    silence(0.7, 48000),
+  ]
+  .concat();
+  let path = write_wav("speech.wav", 48000, 2, SampleFormat::Int, &samples);
+
+  let segments = split(&path, &CaptureOptions::default());
+
+  // Positions are at 16 kHz, with 30 ms of padding on both sides
+  assert_eq!(segments.len(), 2);
+  assert_near(segments[0].0, 16000 - 480);
+  assert_near(segments[0].1, 40000 + 480);
+  assert_near(segments[1].0, 56000 - 480);
+  assert_near(segments[1].1, 68800 + 480);
+  for (start, end, audio) in &segments {
+    assert_eq!(audio.len() as u64, end - start);
+  }
+}
+
+#[test]
+fn cuts_the_speech_out_of_the_audio() {
+  // Far more silence in front than the recorder keeps around
+  let samples = [silence(6.0, 16000), voice(1.0, 16000), silence(1.0, 16000)].concat();
+  let path = write_wav("long-silence.wav", 16000, 1, SampleFormat::Float, &samples);
+
+  let segments = split(&path, &CaptureOptions::default());
+
+  assert_eq!(segments.len(), 1);
+  let (start, end, audio) = &segments[0];
+  assert_near(*start, 96000 - 480);
+  assert_near(*end, 112000 + 480);
+  // Neither resampled nor preprocessed, the segment is the recorded audio
+  assert_eq!(audio.as_slice(), &samples[*start as usize..*end as usize]);
+}
+
+#[test]
+fn finds_nothing_in_silence() {
+  let path = write_wav(
+    "silence.wav",
+    44100,
+    1,
+    SampleFormat::Int,
+    &silence(2.0, 44100),
+  );
+
+  assert!(split(&path, &CaptureOptions::default()).is_empty());
+}
+
+#[test]
+fn reads_a_wav_file_in_device_sized_chunks() {
+  let path = write_wav("chunks.wav", 48000, 2, SampleFormat::Int, &[0.5; 2000]);
+  let mut source = WavSource::open(&path, false).unwrap();
+  assert_eq!(source.sample_rate(), 48000);
+  assert_eq!(source.channels(), 2);
+
+  let mut lengths = Vec::new();
+  while let Some(chunk) = source.next_chunk(Duration::ZERO).unwrap() {
+    assert!(
+      chunk
+        .iter()
+        .all(|&sample| (sample - 0.5).abs() < 1e-4)
+    );
+    lengths.push(chunk.len());
+  }
+  assert_eq!(lengths, [1920, 1920, 160]);
+}
+
+#[test]
+fn rejects_a_source_without_channels() {
+  assert!(
+    SpeechSplitter::new(
+      16000,
+      0,
+      &CaptureOptions::default(),
+      Arc::new(EchoReference::default()),
+    )
+    .is_err()
+  );
+}
+
+#[test]
+fn downmixes_interleaved_channels() {
+  assert_eq!(downmix(&[1.0, 3.0, 2.0, 4.0], 2), [2.0, 3.0]);
+  assert_eq!(downmix(&[1.0, 2.0, 6.0], 3), [3.0]);
+  assert_eq!(downmix(&[1.0, 2.0], 1), [1.0, 2.0]);
+}
+
+#[test]
+fn resamples_chunks_of_any_length() {
+  let input: Vec<f32> = (0..96000)
+    .map(|i| (2.0 * PI * 1000.0 * i as f32 / 48000.0).sin())
+    .collect();
+
+  let mut whole = StreamResampler::new(48000, 16000).unwrap();
+  let whole = whole.process(&input).unwrap();
+
+  let mut chunked = StreamResampler::new(48000, 16000).unwrap();
+  let mut output = Vec::new();
+  let mut position = 0;
+  for length in [100, 333, 960, 1, 2048, 4097].iter().cycle() {
+    let end = (position + length).min(input.len());
+    output.extend(chunked.process(&input[position..end]).unwrap());
+    position = end;
+    if position == input.len() {
+      break;
+    }
+  }
+
+  assert_eq!(output, whole);
+  // Whatever does not fill a chunk of the resampler yet is held back
+  assert!(output.len() <= 32000 && output.len() > 32000 - 1024);
+  // The delay is dropped, sample `i` is at the time of input sample `3 * i`, give or take one
+  for (i, &sample) in output.iter().enumerate().skip(200) {
+    let expected = (2.0 * PI * 1000.0 * i as f32 / 16000.0).sin();
+    assert!((sample - expected).abs() < 0.15, "{i}: {sample} {expected}");
+  }
+}
+
+#[test]
+fn passes_audio_at_the_target_rate_through() {
+  let mut resampler = StreamResampler::new(16000, 16000).unwrap();
+  assert_eq!(resampler.process(&[0.1, 0.2]).unwrap(), [0.1, 0.2]);
+
+  assert!(StreamResampler::new(0, 16000).is_err());
+}
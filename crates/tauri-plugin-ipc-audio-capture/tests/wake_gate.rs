@@ -0,0 +1,63 @@
+use tauri_plugin_ipc_audio_capture::WakeGate;
+use tauri_plugin_ipc_audio_kws::{SpotterOptions, Wake};
+
+const SECOND: u64 = 16000;
+
+fn gate() -> WakeGate {
+  WakeGate::new(SpotterOptions::default(), 2000)
+}
+
+fn wake(
+  start: u64,
+  end: u64,
+) -> Wake {
+  Wake {
+    keyword: "hey".to_string(),
+    confidence: 0.8,
+    start,
+    end,
+  }
+}
+
+#[test]
+fn keeps_speech_out_until_woken() {
+  let mut gate = gate();
+  assert!(!gate.admits(0, SECOND));
+  assert!(!gate.admits(2 * SECOND, 3 * SECOND));
+}
+
+#[test]
+fn admits_the_segment_with_the_keyword_and_not_those_before() {
+  let mut gate = gate();
+  gate.wake(&wake(5 * SECOND, 6 * SECOND));
+
+  assert!(!gate.admits(2 * SECOND, 4 * SECOND));
+  // The keyword is said at the start of the request
+  assert!(gate.admits(5 * SECOND - SECOND / 10, 8 * SECOND));
+}
+
+#[test]
+fn stays_open_while_the_conversation_goes_on() {
+  let mut gate = gate();
+  gate.wake(&wake(5 * SECOND, 6 * SECOND));
+
+  // Speech starting within two seconds of the keyword, and of every admitted segment
+  assert!(gate.admits(7 * SECOND, 9 * SECOND));
+  assert!(gate.admits(10 * SECOND, 12 * SECOND));
+  assert!(gate.admits(13 * SECOND + SECOND / 2, 15 * SECOND));
+
+  // A pause longer than that closes the gate
+  assert!(!gate.admits(17 * SECOND + SECOND / 2, 19 * SECOND));
+  assert!(!gate.admits(20 * SECOND, 21 * SECOND));
+
+  // Until the keyword is said again
+  gate.wake(&wake(25 * SECOND, 26 * SECOND));
+  assert!(gate.admits(27 * SECOND, 28 * SECOND));
+}
+
+#[test]
+fn closes_after_a_keyword_without_a_request() {
+  let mut gate = gate();
+  gate.wake(&wake(5 * SECOND, 6 * SECOND));
+  assert!(!gate.admits(9 * SECOND, 10 * SECOND));
+}
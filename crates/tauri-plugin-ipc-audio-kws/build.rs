@@ -0,0 +1,12 @@
+const COMMANDS: &[&str] = &[
+  "enroll_keyword",
+  "list_keywords",
+  "remove_keyword",
+  "create_kws_session",
+  "push_kws_samples",
+  "destroy_kws_session",
+];
+
+fn main() {
+  tauri_plugin::Builder::new(COMMANDS).build();
+}
@@ -0,0 +1,101 @@
+use std::ops::Range;
+
+use crate::features::distance;
+
+/// A matched segment may be spoken this much faster or slower than the template
+const MAX_TEMPO_RATIO: f32 = 2.0;
+
+/// Where a template matched in a sequence of frames.
+#[derive(Clone, Copy, Debug)]
+pub struct Alignment {
+  /// First and one past the last matched frame
+  pub start:    usize,
+  pub end:      usize,
+  /// Mean cosine distance along the warping path
+  pub distance: f32,
+}
+
+#[derive(Clone, Copy)]
+struct Cell {
+  cost:   f32,
+  length: u32,
+  start:  usize,
+}
+
+impl Cell {
+  fn mean(&self) -> f32 {
+    self.cost / self.length as f32
+  }
+}
+
+/// Subsequence dynamic time warping: the best match of the whole `template` with any part of
+/// `frames` that ends within `ends`. Segments spoken more than twice as fast or slow as the
+/// template are not considered.
+pub fn best_alignment(
+  template: &[Vec<f32>],
+  frames: &[Vec<f32>],
+  ends: Range<usize>,
+) -> Option<Alignment> {
+  if template.is_empty() || frames.is_empty() {
+    return None;
+  }
+
+  let unreachable = Cell {
+    cost:   f32::INFINITY,
+    length: 1,
+    start:  0,
+  };
+  let mut previous = vec![unreachable; template.len()];
+  let mut current = vec![unreachable; template.len()];
+  let mut best: Option<Alignment> = None;
+
+  for (j, frame) in frames.iter().enumerate().take(ends.end) {
+    for (i, row) in template.iter().enumerate() {
+      let cost = distance(row, frame);
+      let from = if i == 0 {
+        // The template starts at this frame, or held its first frame since an earlier one
+        let fresh = Cell {
+          cost:   0.0,
+          length: 0,
+          start:  j,
+        };
+        let held = previous[0];
+        if (held.cost + cost) / ((held.length + 1) as f32) < cost {
+          held
+        } else {
+          fresh
+        }
+      } else {
+        [previous[i - 1], current[i - 1], previous[i]]
+          .into_iter()
+          .min_by(|a, b| a.mean().total_cmp(&b.mean()))
+          .unwrap()
+      };
+
+      current[i] = Cell {
+        cost:   from.cost + cost,
+        length: from.length + 1,
+        start:  from.start,
+      };
+    }
+
+    let last = current[template.len() - 1];
+    let segment = (j + 1 - last.start) as f32;
+    let tempo = segment / template.len() as f32;
+    if ends.contains(&j)
+      && last.cost.is_finite()
+      && (1.0 / MAX_TEMPO_RATIO..=MAX_TEMPO_RATIO).contains(&tempo)
+      && best.is_none_or(|best| last.mean() < best.distance)
+    {
+      best = Some(Alignment {
+        start:    last.start,
+        end:      j + 1,
+        distance: last.mean(),
+      });
+    }
+
+    std::mem::swap(&mut previous, &mut current);
+  }
+
+  best
+}
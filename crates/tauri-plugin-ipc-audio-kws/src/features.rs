@@ -0,0 +1,187 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use ndarray::Array2;
+use realfft::{RealFftPlanner, RealToComplex};
+use tauri_plugin_ipc_audio_transcription_ort::mel_filters;
+
+/// Keywords are matched in 16 kHz audio, what Whisper takes
+pub const SAMPLE_RATE: usize = 16000;
+/// Frames are 10 ms apart, the same as Whisper's
+pub const FRAME_SAMPLES: usize = 160;
+/// Every frame is the spectrum of 25 ms of audio starting at it
+pub const WINDOW_SAMPLES: usize = 400;
+/// The mel bins of most Whisper models, keywords do not need the 128 of large-v3
+const MEL_BINS: usize = 80;
+/// Mel bins this far below the loudest one of their frame, in log10 power, are clamped to it
+const DYNAMIC_RANGE: f32 = 8.0;
+/// Frames this far below the loudest one, in log10 mel power, are silence around a recording
+const TRIM_RANGE: f32 = 3.0;
+
+/// Cepstral coefficients kept, the spectral envelope without the harmonics of the voice
+const CEPSTRA: usize = 12;
+
+/// Turns audio into frames of features that can be compared by cosine distance. Every frame
+/// only depends on its own window of audio, so a stream can be turned into frames as it comes.
+pub struct FeatureExtractor {
+  mel_filters: Array2<f32>,
+  window:      Vec<f32>,
+  fft:         Arc<dyn RealToComplex<f32>>,
+  /// DCT-II basis of coefficients 1 to [`CEPSTRA`], the first one is only the loudness
+  dct:         Vec<Vec<f32>>,
+}
+
+impl Default for FeatureExtractor {
+  fn default() -> Self {
+    Self {
+      mel_filters: mel_filters(SAMPLE_RATE, WINDOW_SAMPLES, MEL_BINS),
+      // Periodic Hann window, the same as Whisper's
+      window:      (0..WINDOW_SAMPLES)
+        .map(|i| 0.5 * (1.0 - (2.0 * PI * i as f32 / WINDOW_SAMPLES as f32).cos()))
+        .collect(),
+      fft:         RealFftPlanner::<f32>::new().plan_fft_forward(WINDOW_SAMPLES),
+      dct:         (1..=CEPSTRA)
+        .map(|k| {
+          (0..MEL_BINS)
+            .map(|n| (PI * k as f32 * (n as f32 + 0.5) / MEL_BINS as f32).cos())
+            .collect()
+        })
+        .collect(),
+    }
+  }
+}
+
+impl FeatureExtractor {
+  /// One frame per 10 ms of 16 kHz audio, for every window of [`WINDOW_SAMPLES`] that fits.
+  /// Every frame holds the cepstral coefficients of the log-mel spectrum but the first, so
+  /// loudness does not matter, scaled to unit length.
+  pub fn frames(
+    &self,
+    audio: &[f32],
+  ) -> Vec<Vec<f32>> {
+    windows(audio)
+      .map(|window| self.frame(window))
+      .collect()
+  }
+
+  /// The frame of the [`WINDOW_SAMPLES`] of audio starting at `window`.
+  pub fn frame(
+    &self,
+    window: &[f32],
+  ) -> Vec<f32> {
+    self.cepstra(&self.log_mel(window))
+  }
+
+  /// Same as [`FeatureExtractor::frames`] without the quiet frames before and after the loudest
+  /// part, for enrollment recordings.
+  pub fn trimmed_frames(
+    &self,
+    audio: &[f32],
+  ) -> Vec<Vec<f32>> {
+    let log_mels: Vec<Vec<f32>> = windows(audio)
+      .map(|window| self.log_mel(window))
+      .collect();
+    let loudness: Vec<f32> = log_mels
+      .iter()
+      .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+      .collect();
+    let loudest = loudness
+      .iter()
+      .fold(f32::NEG_INFINITY, |acc, &v| acc.max(v));
+    let threshold = loudest - TRIM_RANGE;
+
+    let Some(last) = loudness.iter().rposition(|&v| v >= threshold) else {
+      return Vec::new();
+    };
+    let first = loudness
+      .iter()
+      .position(|&v| v >= threshold)
+      .unwrap_or(0);
+    log_mels[first..=last]
+      .iter()
+      .map(|log_mel| self.cepstra(log_mel))
+      .collect()
+  }
+
+  /// The log10 mel power spectrum of one window.
+  fn log_mel(
+    &self,
+    window: &[f32],
+  ) -> Vec<f32> {
+    let mut input: Vec<f32> = window
+      .iter()
+      .zip(&self.window)
+      .map(|(sample, window)| sample * window)
+      .collect();
+    input.resize(WINDOW_SAMPLES, 0.0);
+    let mut spectrum = self.fft.make_output_vec();
+    // The output buffer is made by the plan itself and the input is resized to it
+    self
+      .fft
+      .process(&mut input, &mut spectrum)
+      .expect("FFT buffers have the planned lengths");
+
+    let power: Vec<f32> = spectrum
+      .iter()
+      .map(|bin| bin.norm_sqr())
+      .collect();
+    let log_mel: Vec<f32> = self
+      .mel_filters
+      .outer_iter()
+      .map(|filter| {
+        filter
+          .iter()
+          .zip(&power)
+          .map(|(f, p)| f * p)
+          .sum::<f32>()
+          .max(1e-10)
+          .log10()
+      })
+      .collect();
+
+    let floor = log_mel
+      .iter()
+      .fold(f32::NEG_INFINITY, |acc, &v| acc.max(v))
+      - DYNAMIC_RANGE;
+    log_mel
+      .into_iter()
+      .map(|x| x.max(floor))
+      .collect()
+  }
+
+  fn cepstra(
+    &self,
+    log_mel: &[f32],
+  ) -> Vec<f32> {
+    let mut frame: Vec<f32> = self
+      .dct
+      .iter()
+      .map(|basis| {
+        basis
+          .iter()
+          .zip(log_mel)
+          .map(|(b, x)| b * x)
+          .sum()
+      })
+      .collect();
+    let norm = frame.iter().map(|x| x * x).sum::<f32>().sqrt();
+    // Silent frames are flat, they stay all zeros and match nothing
+    if norm > f32::EPSILON {
+      frame.iter_mut().for_each(|x| *x /= norm);
+    }
+    frame
+  }
+}
+
+fn windows(audio: &[f32]) -> impl Iterator<Item = &[f32]> {
+  (0..(audio.len() + 1).saturating_sub(WINDOW_SAMPLES))
+    .step_by(FRAME_SAMPLES)
+    .map(|start| &audio[start..start + WINDOW_SAMPLES])
+}
+
+/// Cosine distance between two frames of unit length, 0 for the same spectrum and up to 2.
+pub fn distance(
+  a: &[f32],
+  b: &[f32],
+) -> f32 {
+  1.0 - a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>()
+}
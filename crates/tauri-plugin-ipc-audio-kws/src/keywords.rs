@@ -0,0 +1,142 @@
+use std::ops::Range;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  dtw::{Alignment, best_alignment},
+  features::{FRAME_SAMPLES, FeatureExtractor, SAMPLE_RATE},
+};
+
+/// Used for keywords enrolled from a single recording, there is nothing to calibrate against
+const DEFAULT_THRESHOLD: f32 = 0.25;
+/// The threshold is this much above the distance between the enrolled recordings, which is how
+/// far apart the keyword is when said by the same person
+const THRESHOLD_MARGIN: f32 = 1.3;
+const MIN_THRESHOLD: f32 = 0.1;
+const MAX_THRESHOLD: f32 = 0.4;
+/// Recordings shorter than this after trimming silence are rejected
+const MIN_TEMPLATE_FRAMES: usize = 20;
+
+/// A keyword enrolled from a few recordings of the user saying it, matched by the distance to
+/// the closest of them.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Keyword {
+  pub name:      String,
+  /// Features of every recording, silence trimmed
+  pub templates: Vec<Vec<Vec<f32>>>,
+  /// Mean cosine distance up to which the keyword counts as said
+  pub threshold: f32,
+}
+
+/// What the frontend gets to see of a keyword.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordInfo {
+  pub name:        String,
+  pub samples:     usize,
+  pub threshold:   f32,
+  /// Mean duration of the recordings without their silence
+  pub duration_ms: u64,
+}
+
+impl Keyword {
+  /// Enrolls a keyword from recordings of 16 kHz mono audio, each with the keyword said once.
+  pub fn enroll(
+    extractor: &FeatureExtractor,
+    name: &str,
+    recordings: &[Vec<f32>],
+  ) -> Result<Self> {
+    if name.trim().is_empty() {
+      return Err(anyhow!("Keyword name is empty"));
+    }
+    if recordings.is_empty() {
+      return Err(anyhow!("No recordings of keyword {}", name));
+    }
+
+    let templates = recordings
+      .iter()
+      .enumerate()
+      .map(|(i, recording)| {
+        let frames = extractor.trimmed_frames(recording);
+        if frames.len() < MIN_TEMPLATE_FRAMES {
+          return Err(anyhow!(
+            "Recording {} of keyword {} has less than {} ms of sound",
+            i + 1,
+            name,
+            MIN_TEMPLATE_FRAMES * FRAME_SAMPLES * 1000 / SAMPLE_RATE
+          ));
+        }
+        Ok(frames)
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    Ok(Self {
+      name: name.to_string(),
+      threshold: calibrate(&templates),
+      templates,
+    })
+  }
+
+  /// The closest match of any recording with `frames` that ends within `ends`.
+  pub fn detect(
+    &self,
+    frames: &[Vec<f32>],
+    ends: Range<usize>,
+  ) -> Option<Alignment> {
+    self
+      .templates
+      .iter()
+      .filter_map(|template| best_alignment(template, frames, ends.clone()))
+      .min_by(|a, b| a.distance.total_cmp(&b.distance))
+  }
+
+  /// 1 for a perfect match, 0.5 at the threshold, 0 at twice the threshold and beyond.
+  pub fn confidence(
+    &self,
+    distance: f32,
+  ) -> f32 {
+    (1.0 - distance / (2.0 * self.threshold)).clamp(0.0, 1.0)
+  }
+
+  /// The longest template, matches are looked for in this many frames and some more.
+  pub fn max_frames(&self) -> usize {
+    self
+      .templates
+      .iter()
+      .map(Vec::len)
+      .max()
+      .unwrap_or(0)
+  }
+
+  pub fn info(&self) -> KeywordInfo {
+    let frames: usize = self.templates.iter().map(Vec::len).sum();
+    KeywordInfo {
+      name:        self.name.clone(),
+      samples:     self.templates.len(),
+      threshold:   self.threshold,
+      duration_ms: (frames * FRAME_SAMPLES * 1000 / SAMPLE_RATE / self.templates.len().max(1))
+        as u64,
+    }
+  }
+}
+
+/// The threshold from how far the recordings are from each other.
+pub fn calibrate(templates: &[Vec<Vec<f32>>]) -> f32 {
+  let distances: Vec<f32> = templates
+    .iter()
+    .enumerate()
+    .flat_map(|(i, a)| {
+      templates[i + 1..]
+        .iter()
+        .filter_map(move |b| best_alignment(a, b, 0..b.len()))
+    })
+    .map(|alignment| alignment.distance)
+    .collect();
+  if distances.is_empty() {
+    return DEFAULT_THRESHOLD;
+  }
+
+  let mean = distances.iter().sum::<f32>() / distances.len() as f32;
+  (mean * THRESHOLD_MARGIN).clamp(MIN_THRESHOLD, MAX_THRESHOLD)
+}
@@ -0,0 +1,300 @@
+use std::{
+  collections::HashMap,
+  fs,
+  path::{Path, PathBuf},
+  sync::{
+    Arc,
+    Mutex,
+    atomic::{AtomicU32, Ordering},
+  },
+};
+
+use airi_audio_models::{AudioError, audio};
+use anyhow::Result;
+use log::{error, info, warn};
+use tauri::{
+  Emitter,
+  Manager,
+  Runtime,
+  plugin::{Builder as PluginBuilder, TauriPlugin},
+};
+
+mod dtw;
+mod features;
+mod keywords;
+mod spotter;
+
+pub use dtw::{Alignment, best_alignment};
+pub use features::FeatureExtractor;
+pub use keywords::{Keyword, KeywordInfo, calibrate};
+pub use spotter::{KeywordSpotter, SpotterOptions, Wake};
+
+/// Emitted for every keyword said in any session, with a [`Wake`].
+pub const WAKE_EVENT: &str = "tauri-plugins:tauri-plugin-ipc-audio-kws:wake";
+
+/// Enrolled keywords, kept in the app data directory across restarts.
+struct AppDataKeywords {
+  path:     Option<PathBuf>,
+  keywords: Mutex<Vec<Arc<Keyword>>>,
+}
+
+impl AppDataKeywords {
+  fn load(path: Option<PathBuf>) -> Self {
+    let keywords = path
+      .as_ref()
+      .filter(|path| path.exists())
+      .and_then(|path| match read_keywords(path) {
+        Ok(keywords) => Some(keywords),
+        Err(e) => {
+          warn!("Failed to load keywords from {}: {}", path.display(), e);
+          None
+        },
+      })
+      .unwrap_or_default();
+
+    Self {
+      path,
+      keywords: Mutex::new(keywords.into_iter().map(Arc::new).collect()),
+    }
+  }
+
+  fn save(
+    &self,
+    keywords: &[Arc<Keyword>],
+  ) -> Result<(), AudioError> {
+    let Some(path) = &self.path else {
+      return Ok(());
+    };
+
+    let keywords: Vec<&Keyword> = keywords.iter().map(Arc::as_ref).collect();
+    let write = || -> Result<()> {
+      if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+      }
+      fs::write(path, serde_json::to_vec(&keywords)?)?;
+      Ok(())
+    };
+    write().map_err(|e| AudioError::Internal(format!("Failed to save keywords: {}", e)))
+  }
+}
+
+fn read_keywords(path: &Path) -> Result<Vec<Keyword>> {
+  Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+#[derive(Default)]
+struct AppDataKwsSessions {
+  next_id:  AtomicU32,
+  sessions: Mutex<HashMap<u32, Arc<Mutex<KeywordSpotter>>>>,
+}
+
+impl AppDataKwsSessions {
+  fn get(
+    &self,
+    session_id: u32,
+  ) -> Result<Arc<Mutex<KeywordSpotter>>, AudioError> {
+    self
+      .sessions
+      .lock()
+      .unwrap()
+      .get(&session_id)
+      .cloned()
+      .ok_or_else(|| {
+        AudioError::NotFound(format!(
+          "Keyword spotting session {} does not exist",
+          session_id
+        ))
+      })
+  }
+}
+
+/// Enrolls a keyword from a few recordings of the user saying it once each, mono at any rate,
+/// replacing the keyword of the same name. Three to five recordings work best, the detection
+/// threshold is calibrated from how much they differ.
+#[tauri::command]
+async fn enroll_keyword<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  name: String,
+  recordings: Vec<Vec<f32>>,
+  sample_rate: Option<u32>,
+) -> Result<KeywordInfo, AudioError> {
+  let sample_rate = sample_rate.unwrap_or(features::SAMPLE_RATE as u32);
+  let recordings = recordings
+    .into_iter()
+    .map(|recording| audio::resample(recording, sample_rate as usize, features::SAMPLE_RATE))
+    .collect::<Result<Vec<_>>>()
+    .map_err(|e| AudioError::InvalidInput(format!("Failed to resample recordings: {}", e)))?;
+  let keyword = Keyword::enroll(&FeatureExtractor::default(), &name, &recordings)
+    .map_err(|e| AudioError::InvalidInput(format!("Failed to enroll keyword: {}", e)))?;
+  let info = keyword.info();
+
+  let data = app.state::<AppDataKeywords>();
+  let mut keywords = data.keywords.lock().unwrap();
+  keywords.retain(|existing| existing.name != keyword.name);
+  keywords.push(Arc::new(keyword));
+  data.save(&keywords)?;
+
+  info!(
+    "Keyword {} enrolled from {} recordings, threshold {:.3}",
+    info.name, info.samples, info.threshold
+  );
+  Ok(info)
+}
+
+#[tauri::command]
+async fn list_keywords<R: Runtime>(
+  app: tauri::AppHandle<R>
+) -> Result<Vec<KeywordInfo>, AudioError> {
+  Ok(
+    app
+      .state::<AppDataKeywords>()
+      .keywords
+      .lock()
+      .unwrap()
+      .iter()
+      .map(|keyword| keyword.info())
+      .collect(),
+  )
+}
+
+#[tauri::command]
+async fn remove_keyword<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  name: String,
+) -> Result<(), AudioError> {
+  let data = app.state::<AppDataKeywords>();
+  let mut keywords = data.keywords.lock().unwrap();
+  let count = keywords.len();
+  keywords.retain(|keyword| keyword.name != name);
+  if keywords.len() == count {
+    return Err(AudioError::NotFound(format!(
+      "Keyword {} is not enrolled",
+      name
+    )));
+  }
+  data.save(&keywords)?;
+
+  info!("Keyword {} removed", name);
+  Ok(())
+}
+
+/// Starts a session that listens for the enrolled keywords in 16 kHz mono audio. Returns the
+/// session id.
+#[tauri::command]
+async fn create_kws_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  options: Option<SpotterOptions>,
+) -> Result<u32, AudioError> {
+  let data = app.state::<AppDataKwsSessions>();
+  let session_id = data.next_id.fetch_add(1, Ordering::Relaxed);
+  data.sessions.lock().unwrap().insert(
+    session_id,
+    Arc::new(Mutex::new(KeywordSpotter::new(options.unwrap_or_default()))),
+  );
+
+  info!("Keyword spotting session {} created", session_id);
+  Ok(session_id)
+}
+
+/// Appends audio of any length to a session, returns the keywords said in it. Every one is also
+/// emitted as a wake event.
+#[tauri::command]
+async fn push_kws_samples<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+  samples: Vec<f32>,
+) -> Result<Vec<Wake>, AudioError> {
+  let session = app
+    .state::<AppDataKwsSessions>()
+    .get(session_id)?;
+  let mut session = session.lock().unwrap();
+
+  push_to_spotter(&app, &mut session, &samples)
+}
+
+/// Returns the keyword that was still waiting to be reported.
+#[tauri::command]
+async fn destroy_kws_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+) -> Result<Vec<Wake>, AudioError> {
+  let session = app
+    .state::<AppDataKwsSessions>()
+    .sessions
+    .lock()
+    .unwrap()
+    .remove(&session_id)
+    .ok_or_else(|| {
+      AudioError::NotFound(format!(
+        "Keyword spotting session {} does not exist",
+        session_id
+      ))
+    })?;
+
+  let wakes = session.lock().unwrap().finish();
+  emit_wakes(&app, &wakes);
+
+  info!("Keyword spotting session {} destroyed", session_id);
+  Ok(wakes)
+}
+
+/// Pushes audio into a spotter with the enrolled keywords, for plugins that capture audio
+/// in-process instead of sending it over IPC. Emits [`WAKE_EVENT`] for every keyword said.
+pub fn push_to_spotter<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  spotter: &mut KeywordSpotter,
+  samples: &[f32],
+) -> Result<Vec<Wake>, AudioError> {
+  let keywords = app
+    .try_state::<AppDataKeywords>()
+    .ok_or_else(|| AudioError::Internal("Keyword spotting plugin is not initialized".to_string()))?
+    .keywords
+    .lock()
+    .unwrap()
+    .clone();
+
+  let wakes = spotter.push(&keywords, samples);
+  emit_wakes(app, &wakes);
+  Ok(wakes)
+}
+
+fn emit_wakes<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  wakes: &[Wake],
+) {
+  for wake in wakes {
+    info!(
+      "Keyword {} said with confidence {:.2}",
+      wake.keyword, wake.confidence
+    );
+    if let Err(e) = app.emit(WAKE_EVENT, wake) {
+      error!("Failed to emit wake event: {}", e);
+    }
+  }
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+  PluginBuilder::new("ipc-audio-kws")
+    .setup(|app, _| {
+      info!("Initializing keyword spotting plugin...");
+      let path = match app.path().app_data_dir() {
+        Ok(dir) => Some(dir.join("keywords.json")),
+        Err(e) => {
+          warn!("No app data directory, keywords are not kept: {}", e);
+          None
+        },
+      };
+      app.manage(AppDataKeywords::load(path));
+      app.manage(AppDataKwsSessions::default());
+      Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+      enroll_keyword,
+      list_keywords,
+      remove_keyword,
+      create_kws_session,
+      push_kws_samples,
+      destroy_kws_session
+    ])
+    .build()
+}
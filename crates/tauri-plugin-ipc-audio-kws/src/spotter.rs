@@ -0,0 +1,204 @@
+use std::{ops::Range, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  features::{FRAME_SAMPLES, FeatureExtractor, SAMPLE_RATE, WINDOW_SAMPLES},
+  keywords::Keyword,
+};
+
+/// Keywords are searched for every 100 ms, in matches that ended since the previous search
+const STEP_FRAMES: usize = 10;
+/// The search window fits the longest keyword said this many times slower than recorded
+const WINDOW_RATIO: usize = 2;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SpotterOptions {
+  /// Names of the keywords to listen for, all enrolled ones when not given
+  pub keywords:       Option<Vec<String>>,
+  /// Matches below this confidence are ignored, 0.5 is the threshold calibrated at enrollment
+  pub min_confidence: f32,
+  /// Time after a wake in which no other one is reported
+  pub refractory_ms:  u32,
+}
+
+impl Default for SpotterOptions {
+  fn default() -> Self {
+    Self {
+      keywords:       None,
+      min_confidence: 0.5,
+      refractory_ms:  1500,
+    }
+  }
+}
+
+/// A keyword was said, positions are sample offsets at 16 kHz since the session started.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Wake {
+  pub keyword:    String,
+  pub confidence: f32,
+  pub start:      u64,
+  pub end:        u64,
+}
+
+/// Listens for keywords in a stream of 16 kHz audio pushed in chunks of any length. The audio is
+/// turned into frames as it comes, only the frames are kept.
+pub struct KeywordSpotter {
+  extractor:   FeatureExtractor,
+  options:     SpotterOptions,
+  /// Audio from the start of the next frame on
+  pending:     Vec<f32>,
+  /// Frames of the recent audio, as many as the longest keyword needs
+  frames:      Vec<Vec<f32>>,
+  /// Index of the first kept frame since the session started
+  offset:      u64,
+  /// Kept frames that were not searched yet
+  unsearched:  usize,
+  /// The best match so far, reported once the following search does not find a better one
+  candidate:   Option<Wake>,
+  /// Matches starting before this position are not reported
+  quiet_until: u64,
+}
+
+impl KeywordSpotter {
+  pub fn new(options: SpotterOptions) -> Self {
+    Self {
+      extractor: FeatureExtractor::default(),
+      options,
+      pending: Vec::new(),
+      frames: Vec::new(),
+      offset: 0,
+      unsearched: 0,
+      candidate: None,
+      quiet_until: 0,
+    }
+  }
+
+  /// Searches every complete step of 100 ms in `samples` and whatever was left over from the
+  /// previous call. Returns the keywords that were said.
+  pub fn push(
+    &mut self,
+    keywords: &[Arc<Keyword>],
+    samples: &[f32],
+  ) -> Vec<Wake> {
+    let keywords: Vec<&Keyword> = keywords
+      .iter()
+      .map(Arc::as_ref)
+      .filter(|keyword| {
+        self
+          .options
+          .keywords
+          .as_ref()
+          .is_none_or(|names| names.contains(&keyword.name))
+      })
+      .collect();
+    let window = keywords
+      .iter()
+      .map(|keyword| keyword.max_frames())
+      .max()
+      .unwrap_or(0)
+      * WINDOW_RATIO
+      + STEP_FRAMES;
+
+    self.pending.extend_from_slice(samples);
+    let mut start = 0;
+    while start + WINDOW_SAMPLES <= self.pending.len() {
+      let frame = self
+        .extractor
+        .frame(&self.pending[start..start + WINDOW_SAMPLES]);
+      self.frames.push(frame);
+      self.unsearched += 1;
+      start += FRAME_SAMPLES;
+    }
+    self.pending.drain(..start);
+
+    let mut wakes = Vec::new();
+    while self.unsearched >= STEP_FRAMES {
+      let end = self.frames.len() - self.unsearched + STEP_FRAMES;
+      self.unsearched -= STEP_FRAMES;
+      if keywords.is_empty() {
+        continue;
+      }
+
+      let start = end.saturating_sub(window);
+      let best = self.search(&keywords, start..end);
+      wakes.extend(self.settle(best));
+    }
+
+    // Trimmed in batches, not on every chunk
+    let excess = (self.frames.len() - self.unsearched).saturating_sub(window);
+    if excess > window {
+      self.frames.drain(..excess);
+      self.offset += excess as u64;
+    }
+
+    wakes
+  }
+
+  /// Ends the audio, reporting the keyword that was waiting for a better match.
+  pub fn finish(&mut self) -> Vec<Wake> {
+    let wakes = self.settle(None).into_iter().collect();
+    self.reset();
+    wakes
+  }
+
+  /// Forgets the audio seen so far, as if the spotter was just created.
+  pub fn reset(&mut self) {
+    self.pending.clear();
+    self.frames.clear();
+    self.offset = 0;
+    self.unsearched = 0;
+    self.candidate = None;
+    self.quiet_until = 0;
+  }
+
+  /// The most confident keyword in the kept frames `range` that ended in its last step.
+  fn search(
+    &self,
+    keywords: &[&Keyword],
+    range: Range<usize>,
+  ) -> Option<Wake> {
+    let frames = &self.frames[range.clone()];
+    let ends = frames.len().saturating_sub(STEP_FRAMES)..frames.len();
+    let offset = (self.offset + range.start as u64) * FRAME_SAMPLES as u64;
+
+    keywords
+      .iter()
+      .filter_map(|keyword| {
+        let alignment = keyword.detect(frames, ends.clone())?;
+        Some(Wake {
+          keyword:    keyword.name.clone(),
+          confidence: keyword.confidence(alignment.distance),
+          start:      offset + (alignment.start * FRAME_SAMPLES) as u64,
+          end:        offset + (alignment.end * FRAME_SAMPLES) as u64,
+        })
+      })
+      .filter(|wake| {
+        wake.confidence >= self.options.min_confidence && wake.start >= self.quiet_until
+      })
+      .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+  }
+
+  /// Keeps the better of `best` and the candidate, and reports the candidate once the matches
+  /// stop improving on it.
+  fn settle(
+    &mut self,
+    best: Option<Wake>,
+  ) -> Option<Wake> {
+    match (best, &self.candidate) {
+      (Some(best), Some(candidate)) if best.confidence <= candidate.confidence => {},
+      (Some(best), _) => {
+        self.candidate = Some(best);
+        return None;
+      },
+      (None, _) => {},
+    }
+
+    let wake = self.candidate.take()?;
+    let refractory = u64::from(self.options.refractory_ms) * SAMPLE_RATE as u64 / 1000;
+    self.quiet_until = wake.end + refractory;
+    Some(wake)
+  }
+}
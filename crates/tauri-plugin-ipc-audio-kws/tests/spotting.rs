@@ -0,0 +1,283 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use tauri_plugin_ipc_audio_kws::{
+  FeatureExtractor,
+  Keyword,
+  KeywordSpotter,
+  SpotterOptions,
+  Wake,
+  best_alignment,
+  calibrate,
+};
+
+const SAMPLE_RATE: usize = 16000;
+
+/// xorshift32, the tests need reproducible noise and nothing more
+struct Noise(u32);
+
+impl Noise {
+  fn next(&mut self) -> f32 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 17;
+    self.0 ^= self.0 << 5;
+    self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+  }
+}
+
+/// First and second formant of a few vowels, in Hz
+const A: (f32, f32) = (700.0, 1200.0);
+const E: (f32, f32) = (500.0, 1900.0);
+const I: (f32, f32) = (300.0, 2300.0);
+const O: (f32, f32) = (500.0, 900.0);
+const U: (f32, f32) = (300.0, 800.0);
+
+/// A made-up word: the vowels one after another, 200 ms each at `tempo` 1, voiced at `pitch`.
+/// The harmonics are shaped by the formants, which is what tells vowels apart.
+fn word(
+  vowels: &[(f32, f32)],
+  pitch: f32,
+  tempo: f32,
+) -> Vec<f32> {
+  let vowel_samples = (0.2 * SAMPLE_RATE as f32 / tempo) as usize;
+  let mut noise = Noise(pitch as u32);
+  let mut phase = 0.0;
+  (0..vowels.len() * vowel_samples)
+    .map(|i| {
+      // Formants glide from one vowel to the next
+      let position = i as f32 / vowel_samples as f32;
+      let current = vowels[(position as usize).min(vowels.len() - 1)];
+      let next = vowels[(position as usize + 1).min(vowels.len() - 1)];
+      let blend = ((position.fract() - 0.7) / 0.3).clamp(0.0, 1.0);
+      let f1 = current.0 + (next.0 - current.0) * blend;
+      let f2 = current.1 + (next.1 - current.1) * blend;
+
+      phase += 2.0 * PI * pitch / SAMPLE_RATE as f32;
+      let voiced: f32 = (1..=30)
+        .map(|harmonic| {
+          let frequency = harmonic as f32 * pitch;
+          let resonance = (-((frequency - f1) / 150.0).powi(2)).exp()
+            + 0.7 * (-((frequency - f2) / 200.0).powi(2)).exp();
+          (0.05 + resonance) * (harmonic as f32 * phase).sin()
+        })
+        .sum();
+      // Breath fills the gaps between the harmonics, like in a real voice
+      0.05 * voiced + 0.01 * noise.next()
+    })
+    .collect()
+}
+
+/// `before` and `after` seconds of faint noise around `audio`.
+fn padded(
+  audio: &[f32],
+  before: f32,
+  after: f32,
+  seed: u32,
+) -> Vec<f32> {
+  let mut noise = Noise(seed);
+  let mut padded: Vec<f32> = (0..(before * SAMPLE_RATE as f32) as usize)
+    .map(|_| 0.001 * noise.next())
+    .collect();
+  padded.extend(audio.iter().map(|x| x + 0.001 * noise.next()));
+  padded.extend((0..(after * SAMPLE_RATE as f32) as usize).map(|_| 0.001 * noise.next()));
+  padded
+}
+
+/// White noise at a level like speech.
+fn noise(
+  seconds: f32,
+  seed: u32,
+) -> Vec<f32> {
+  let mut noise = Noise(seed);
+  (0..(seconds * SAMPLE_RATE as f32) as usize)
+    .map(|_| 0.3 * noise.next())
+    .collect()
+}
+
+/// Every frame of `frames` said `times` as slow.
+fn stretched(
+  frames: &[Vec<f32>],
+  times: usize,
+) -> Vec<Vec<f32>> {
+  frames
+    .iter()
+    .flat_map(|frame| std::iter::repeat_n(frame.clone(), times))
+    .collect()
+}
+
+fn enroll(name: &str) -> Keyword {
+  let recordings = [(110.0, 0.9), (125.0, 1.0), (140.0, 1.1)]
+    .map(|(pitch, tempo)| padded(&word(&[A, I, U], pitch, tempo), 0.3, 0.3, 1));
+  Keyword::enroll(&FeatureExtractor::default(), name, &recordings).unwrap()
+}
+
+/// Pushes `audio` in chunks of 50 ms and ends it.
+fn spot(
+  spotter: &mut KeywordSpotter,
+  keywords: &[Arc<Keyword>],
+  audio: &[f32],
+) -> Vec<Wake> {
+  let mut wakes = Vec::new();
+  for chunk in audio.chunks(800) {
+    wakes.extend(spotter.push(keywords, chunk));
+  }
+  wakes.extend(spotter.finish());
+  wakes
+}
+
+#[test]
+fn template_matches_itself_and_not_noise() {
+  let extractor = FeatureExtractor::default();
+  let template = extractor.trimmed_frames(&word(&[A, I, U], 120.0, 1.0));
+
+  // The ends may be skipped, they add nothing to a perfect match
+  let alignment = best_alignment(&template, &template, 0..template.len()).unwrap();
+  assert!(alignment.start <= 2 && alignment.end >= template.len() - 2);
+  assert!(alignment.distance < 1e-4, "distance {}", alignment.distance);
+
+  let noise = extractor.frames(&noise(2.0, 5));
+  let alignment = best_alignment(&template, &noise, 0..noise.len()).unwrap();
+  assert!(alignment.distance > 0.5, "distance {}", alignment.distance);
+}
+
+#[test]
+fn finds_the_template_in_longer_audio() {
+  let extractor = FeatureExtractor::default();
+  let said = word(&[A, I, U], 120.0, 1.0);
+  let template = extractor.trimmed_frames(&said);
+  let frames = extractor.frames(&padded(&said, 0.5, 0.5, 2));
+
+  let alignment = best_alignment(&template, &frames, 0..frames.len()).unwrap();
+  assert!(
+    alignment.start.abs_diff(50) <= 3,
+    "starts at {}",
+    alignment.start
+  );
+  assert!(
+    alignment.end.abs_diff(50 + template.len()) <= 3,
+    "ends at {}",
+    alignment.end
+  );
+
+  // Only matches ending in `ends` count
+  let before = best_alignment(&template, &frames, 0..50);
+  assert!(before.is_none_or(|before| before.distance > alignment.distance + 0.1));
+}
+
+#[test]
+fn matches_up_to_twice_as_slow() {
+  let extractor = FeatureExtractor::default();
+  let template = extractor.trimmed_frames(&word(&[A, I, U], 120.0, 1.0));
+
+  let twice = stretched(&template, 2);
+  let alignment = best_alignment(&template, &twice, 0..twice.len()).unwrap();
+  assert!(alignment.start <= 2 && alignment.end >= twice.len() - 2);
+  assert!(alignment.distance < 1e-4, "distance {}", alignment.distance);
+
+  // Slower than that, only part of it is matched
+  let thrice = stretched(&template, 3);
+  let alignment = best_alignment(&template, &thrice, 0..thrice.len()).unwrap();
+  assert!(alignment.end - alignment.start <= 2 * template.len());
+
+  assert!(best_alignment(&[], &twice, 0..twice.len()).is_none());
+}
+
+#[test]
+fn calibrates_from_the_distance_between_recordings() {
+  let extractor = FeatureExtractor::default();
+  let template = |pitch, tempo| extractor.trimmed_frames(&word(&[A, I, U], pitch, tempo));
+
+  // Nothing to compare a single recording with
+  assert_eq!(calibrate(&[template(120.0, 1.0)]), 0.25);
+  // Identical recordings still leave some room
+  assert_eq!(
+    calibrate(&[template(120.0, 1.0), template(120.0, 1.0)]),
+    0.1
+  );
+
+  let close = calibrate(&[template(120.0, 1.0), template(125.0, 1.0)]);
+  let apart = calibrate(&[template(110.0, 0.9), template(140.0, 1.1)]);
+  assert!(
+    close <= apart,
+    "{close} for close recordings, {apart} for others"
+  );
+  assert!((0.1..=0.4).contains(&apart), "threshold {apart}");
+
+  // Different words are further apart than the same word said differently
+  let other = extractor.trimmed_frames(&word(&[O, E, I], 120.0, 1.0));
+  let different = calibrate(&[template(120.0, 1.0), other]);
+  assert!(
+    different > apart,
+    "{different} for different words, {apart} for one"
+  );
+}
+
+#[test]
+fn rejects_recordings_without_enough_sound() {
+  let extractor = FeatureExtractor::default();
+  let short = padded(&word(&[A], 120.0, 2.0), 0.3, 0.3, 3);
+  assert!(Keyword::enroll(&extractor, "hey", &[short]).is_err());
+
+  let recording = word(&[A, I, U], 120.0, 1.0);
+  assert!(Keyword::enroll(&extractor, " ", std::slice::from_ref(&recording)).is_err());
+  assert!(Keyword::enroll(&extractor, "hey", &[]).is_err());
+}
+
+#[test]
+fn spots_the_keyword_once_where_it_was_said() {
+  let keywords = [Arc::new(enroll("hey"))];
+  let said = word(&[A, I, U], 118.0, 1.05);
+  let audio = padded(&said, 1.0, 1.5, 4);
+
+  let mut spotter = KeywordSpotter::new(SpotterOptions::default());
+  let wakes = spot(&mut spotter, &keywords, &audio);
+  assert_eq!(wakes.len(), 1, "{wakes:?}");
+  let wake = &wakes[0];
+  assert_eq!(wake.keyword, "hey");
+  assert!(wake.confidence > 0.5);
+  assert!(
+    wake.start.abs_diff(16000) < 1600,
+    "starts at {}",
+    wake.start
+  );
+  let end = 16000 + said.len() as u64;
+  assert!(wake.end.abs_diff(end) < 1600, "ends at {}", wake.end);
+}
+
+#[test]
+fn ignores_noise_and_other_words() {
+  let keywords = [Arc::new(enroll("hey"))];
+  let mut spotter = KeywordSpotter::new(SpotterOptions::default());
+
+  assert!(spot(&mut spotter, &keywords, &noise(3.0, 6)).is_empty());
+
+  let other = padded(&word(&[O, E, I], 125.0, 1.0), 1.0, 1.0, 7);
+  assert!(spot(&mut spotter, &keywords, &other).is_empty());
+}
+
+#[test]
+fn only_listens_for_the_keywords_asked_for() {
+  let keywords = [Arc::new(enroll("hey"))];
+  let audio = padded(&word(&[A, I, U], 125.0, 1.0), 1.0, 1.0, 8);
+
+  let mut spotter = KeywordSpotter::new(SpotterOptions {
+    keywords: Some(vec!["hello".to_string()]),
+    ..Default::default()
+  });
+  assert!(spot(&mut spotter, &keywords, &audio).is_empty());
+}
+
+#[test]
+fn stays_quiet_for_the_refractory_time() {
+  let keywords = [Arc::new(enroll("hey"))];
+  let said = padded(&word(&[A, I, U], 125.0, 1.0), 0.5, 0.5, 9);
+  let twice = [said.clone(), said].concat();
+
+  let mut spotter = KeywordSpotter::new(SpotterOptions::default());
+  assert_eq!(spot(&mut spotter, &keywords, &twice).len(), 1);
+
+  let mut spotter = KeywordSpotter::new(SpotterOptions {
+    refractory_ms: 0,
+    ..Default::default()
+  });
+  assert_eq!(spot(&mut spotter, &keywords, &twice).len(), 2);
+}
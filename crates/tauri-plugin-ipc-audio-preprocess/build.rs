@@ -0,0 +1,11 @@
+const COMMANDS: &[&str] = &[
+  "push_echo_reference",
+  "stop_echo_reference",
+  "create_preprocess_session",
+  "process_samples",
+  "destroy_preprocess_session",
+];
+
+fn main() {
+  tauri_plugin::Builder::new(COMMANDS).build();
+}
@@ -0,0 +1,172 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex, num_complex::Complex32};
+
+/// Frames are 32 ms long and overlap by half
+const FRAME_MS: usize = 32;
+/// The noise is estimated from the first frames alone, about 0.1 seconds
+const INIT_FRAMES: usize = 8;
+/// Smoothing of the power spectrum the noise is tracked from
+const POWER_SMOOTHING: f32 = 0.7;
+/// Bins below this ratio to the noise are treated as noise and averaged in
+const NOISE_ONLY_RATIO: f32 = 4.0;
+const NOISE_SMOOTHING: f32 = 0.95;
+/// The estimate drops quickly to quieter noise and creeps up otherwise, about 1.3 dB a second
+const NOISE_FALL: f32 = 0.3;
+const NOISE_CREEP: f32 = 1.005;
+/// Weight of the previous frame in the decision-directed a priori SNR, which keeps the gains
+/// from fluctuating into musical noise
+const DECISION_DIRECTED: f32 = 0.98;
+/// Noise is attenuated by 20 dB at most
+const GAIN_FLOOR: f32 = 0.1;
+
+/// Spectral noise suppression: a Wiener gain per bin from an adaptive estimate of stationary
+/// noise. Fans, hum and hiss go, speech and other changing sounds stay. Output is delayed by one
+/// hop.
+pub struct Denoiser {
+  fft:      Arc<dyn RealToComplex<f32>>,
+  ifft:     Arc<dyn ComplexToReal<f32>>,
+  hop_size: usize,
+  /// Square root of a periodic Hann window, applied before and after the transform
+  window:   Vec<f32>,
+  frame:    Vec<f32>,
+  overlap:  Vec<f32>,
+  power:    Vec<f32>,
+  noise:    Vec<f32>,
+  /// Power left in every bin of the previous frame after the gain
+  clean:    Vec<f32>,
+  frames:   usize,
+  time:     Vec<f32>,
+  spectrum: Vec<Complex32>,
+}
+
+impl Denoiser {
+  pub fn new(sample_rate: u32) -> Result<Self> {
+    if sample_rate == 0 {
+      return Err(anyhow!("Sample rate must be positive"));
+    }
+
+    let frame_size = (sample_rate as usize * FRAME_MS / 1000).next_power_of_two();
+    let hop_size = frame_size / 2;
+    let bins = frame_size / 2 + 1;
+    let mut planner = RealFftPlanner::<f32>::new();
+
+    Ok(Self {
+      fft: planner.plan_fft_forward(frame_size),
+      ifft: planner.plan_fft_inverse(frame_size),
+      hop_size,
+      window: (0..frame_size)
+        .map(|i| (0.5 - 0.5 * (2.0 * PI * i as f32 / frame_size as f32).cos()).sqrt())
+        .collect(),
+      frame: vec![0.0; frame_size],
+      overlap: vec![0.0; hop_size],
+      power: vec![0.0; bins],
+      noise: vec![0.0; bins],
+      clean: vec![0.0; bins],
+      frames: 0,
+      time: vec![0.0; frame_size],
+      spectrum: vec![Complex32::default(); bins],
+    })
+  }
+
+  /// Samples taken and returned at a time.
+  pub fn hop_size(&self) -> usize {
+    self.hop_size
+  }
+
+  pub fn process_hop(
+    &mut self,
+    input: &[f32],
+    output: &mut [f32],
+  ) {
+    assert!(input.len() == self.hop_size && output.len() == self.hop_size);
+
+    self.frame.copy_within(self.hop_size.., 0);
+    let frame_size = self.frame.len();
+    self.frame[frame_size - self.hop_size..].copy_from_slice(input);
+    for ((time, sample), window) in self
+      .time
+      .iter_mut()
+      .zip(&self.frame)
+      .zip(&self.window)
+    {
+      *time = sample * window;
+    }
+    self
+      .fft
+      .process(&mut self.time, &mut self.spectrum)
+      .expect("FFT buffers are sized for the plan");
+
+    self.frames += 1;
+    for bin in 0..self.spectrum.len() {
+      let gain = self.update_bin(bin, self.spectrum[bin].norm_sqr());
+      self.spectrum[bin] *= gain;
+    }
+
+    self.spectrum[0].im = 0.0;
+    let last = self.spectrum.len() - 1;
+    self.spectrum[last].im = 0.0;
+    self
+      .ifft
+      .process(&mut self.spectrum, &mut self.time)
+      .expect("FFT buffers are sized for the plan");
+
+    // Overlap-add, the squared window sums to one at half overlap
+    for (time, window) in self.time.iter_mut().zip(&self.window) {
+      *time *= window / frame_size as f32;
+    }
+    for ((output, overlap), time) in output
+      .iter_mut()
+      .zip(&self.overlap)
+      .zip(&self.time)
+    {
+      *output = overlap + time;
+    }
+    self
+      .overlap
+      .copy_from_slice(&self.time[self.hop_size..]);
+  }
+
+  /// Forgets the noise learned so far.
+  pub fn reset(&mut self) {
+    self.frame.fill(0.0);
+    self.overlap.fill(0.0);
+    self.power.fill(0.0);
+    self.noise.fill(0.0);
+    self.clean.fill(0.0);
+    self.frames = 0;
+  }
+
+  /// Updates the noise estimate of a bin, returns its gain.
+  fn update_bin(
+    &mut self,
+    bin: usize,
+    power: f32,
+  ) -> f32 {
+    let noise = &mut self.noise[bin];
+    if self.frames <= INIT_FRAMES {
+      self.power[bin] = power;
+      *noise += (power - *noise) / self.frames as f32;
+    } else {
+      self.power[bin] = POWER_SMOOTHING * self.power[bin] + (1.0 - POWER_SMOOTHING) * power;
+      let smoothed = self.power[bin];
+      if smoothed < *noise {
+        *noise += (smoothed - *noise) * NOISE_FALL;
+      } else if smoothed < NOISE_ONLY_RATIO * *noise {
+        *noise = NOISE_SMOOTHING * *noise + (1.0 - NOISE_SMOOTHING) * smoothed;
+      } else {
+        *noise *= NOISE_CREEP;
+      }
+    }
+
+    let noise = noise.max(f32::MIN_POSITIVE);
+    let posterior = power / noise;
+    let prior = DECISION_DIRECTED * self.clean[bin] / noise
+      + (1.0 - DECISION_DIRECTED) * (posterior - 1.0).max(0.0);
+    let gain = (prior / (1.0 + prior)).max(GAIN_FLOOR);
+    self.clean[bin] = gain * gain * power;
+
+    gain
+  }
+}
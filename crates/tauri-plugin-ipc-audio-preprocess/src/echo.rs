@@ -0,0 +1,227 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex, num_complex::Complex32};
+
+/// Samples the canceller takes at a time, 16 ms at 16 kHz
+pub const BLOCK_SIZE: usize = 256;
+const FFT_SIZE: usize = 2 * BLOCK_SIZE;
+const BINS: usize = BLOCK_SIZE + 1;
+/// Filter length in blocks, echoes arriving up to 256 ms after the reference are cancelled
+pub const DEFAULT_PARTITIONS: usize = 16;
+
+/// NLMS step size of the background filter
+const STEP_SIZE: f32 = 0.5;
+/// Smoothing of the reference energy over the filter length every bin is normalized by
+const POWER_SMOOTHING: f32 = 0.9;
+/// Share of the mean bin energy added to every bin. Voices leave bins between harmonics nearly
+/// empty, and the filter would blow up in them as the pitch moves.
+const REGULARIZATION_RATIO: f32 = 0.1;
+/// Keeps the normalization finite when the reference is silent
+const REGULARIZATION: f32 = 1e-6 * FFT_SIZE as f32;
+/// Reference blocks quieter than this, about -80 dBFS, do not adapt the filter
+const SILENT_REFERENCE_ENERGY: f32 = 1e-8 * BLOCK_SIZE as f32;
+/// Smoothing of the error energies the two filters are compared with
+const ERROR_SMOOTHING: f32 = 0.8;
+/// The background filter replaces the foreground one when it leaves clearly less echo, and is
+/// reset to it when it diverged, which near-end speech makes it do
+const COPY_RATIO: f32 = 0.7;
+const DIVERGED_RATIO: f32 = 2.0;
+
+/// Acoustic echo canceller, a partitioned block frequency domain adaptive filter. Two filters
+/// run side by side: the background one always adapts, and its coefficients are copied into the
+/// foreground one that produces the output only while it cancels more echo. Near-end speech
+/// makes the background filter diverge but leaves the output alone.
+pub struct EchoCanceller {
+  fft:              Arc<dyn RealToComplex<f32>>,
+  ifft:             Arc<dyn ComplexToReal<f32>>,
+  partitions:       usize,
+  /// The previous and the current reference block
+  reference:        Vec<f32>,
+  /// Spectra of the latest reference blocks, the newest first
+  spectra:          VecDeque<Vec<Complex32>>,
+  power:            Vec<f32>,
+  background:       Vec<Vec<Complex32>>,
+  foreground:       Vec<Vec<Complex32>>,
+  background_error: f32,
+  foreground_error: f32,
+  time:             Vec<f32>,
+  spectrum:         Vec<Complex32>,
+}
+
+impl EchoCanceller {
+  pub fn new(partitions: usize) -> Self {
+    let mut planner = RealFftPlanner::<f32>::new();
+    let partitions = partitions.max(1);
+
+    Self {
+      fft: planner.plan_fft_forward(FFT_SIZE),
+      ifft: planner.plan_fft_inverse(FFT_SIZE),
+      partitions,
+      reference: vec![0.0; FFT_SIZE],
+      spectra: (0..partitions)
+        .map(|_| vec![Complex32::default(); BINS])
+        .collect(),
+      power: vec![0.0; BINS],
+      background: vec![vec![Complex32::default(); BINS]; partitions],
+      foreground: vec![vec![Complex32::default(); BINS]; partitions],
+      background_error: 0.0,
+      foreground_error: 0.0,
+      time: vec![0.0; FFT_SIZE],
+      spectrum: vec![Complex32::default(); BINS],
+    }
+  }
+
+  /// Removes the echo of `reference` from `mic`, both [`BLOCK_SIZE`] samples recorded and played
+  /// at the same time.
+  pub fn process_block(
+    &mut self,
+    mic: &[f32],
+    reference: &[f32],
+    output: &mut [f32],
+  ) {
+    assert!(mic.len() == BLOCK_SIZE && reference.len() == BLOCK_SIZE && output.len() == BLOCK_SIZE);
+
+    // Overlap-save, every spectrum covers the previous block too
+    self.reference.copy_within(BLOCK_SIZE.., 0);
+    self.reference[BLOCK_SIZE..].copy_from_slice(reference);
+    let mut spectrum = self
+      .spectra
+      .pop_back()
+      .unwrap_or_else(|| vec![Complex32::default(); BINS]);
+    self.time.copy_from_slice(&self.reference);
+    self
+      .fft
+      .process(&mut self.time, &mut spectrum)
+      .expect("FFT buffers are sized for the plan");
+    self.spectra.push_front(spectrum);
+    for (bin, power) in self.power.iter_mut().enumerate() {
+      let energy: f32 = self
+        .spectra
+        .iter()
+        .map(|spectrum| spectrum[bin].norm_sqr())
+        .sum();
+      *power = POWER_SMOOTHING * *power + (1.0 - POWER_SMOOTHING) * energy;
+    }
+
+    let mut background_echo = [0.0; BLOCK_SIZE];
+    self.filter(true, &mut background_echo);
+    self.filter(false, output);
+
+    let mut background_error = [0.0; BLOCK_SIZE];
+    let mut background_energy = 0.0;
+    let mut foreground_energy = 0.0;
+    for i in 0..BLOCK_SIZE {
+      background_error[i] = mic[i] - background_echo[i];
+      output[i] = mic[i] - output[i];
+      background_energy += background_error[i] * background_error[i];
+      foreground_energy += output[i] * output[i];
+    }
+    self.background_error =
+      ERROR_SMOOTHING * self.background_error + (1.0 - ERROR_SMOOTHING) * background_energy;
+    self.foreground_error =
+      ERROR_SMOOTHING * self.foreground_error + (1.0 - ERROR_SMOOTHING) * foreground_energy;
+
+    let reference_energy: f32 = reference.iter().map(|x| x * x).sum();
+    if reference_energy < SILENT_REFERENCE_ENERGY {
+      return;
+    }
+
+    self.adapt(&background_error);
+    if self.background_error < COPY_RATIO * self.foreground_error {
+      self.foreground.clone_from(&self.background);
+      self.foreground_error = self.background_error;
+    } else if self.background_error > DIVERGED_RATIO * self.foreground_error {
+      self.background.clone_from(&self.foreground);
+      self.background_error = self.foreground_error;
+    }
+  }
+
+  /// Forgets the learned echo path and the reference played so far.
+  pub fn reset(&mut self) {
+    *self = Self::new(self.partitions);
+  }
+
+  /// The echo estimate for the current block.
+  fn filter(
+    &mut self,
+    background: bool,
+    output: &mut [f32],
+  ) {
+    let weights = if background {
+      &self.background
+    } else {
+      &self.foreground
+    };
+
+    self.spectrum.fill(Complex32::default());
+    for (weights, spectrum) in weights.iter().zip(&self.spectra) {
+      for ((sum, weight), bin) in self
+        .spectrum
+        .iter_mut()
+        .zip(weights)
+        .zip(spectrum)
+      {
+        *sum += weight * bin;
+      }
+    }
+
+    self.inverse_fft();
+    for (output, echo) in output.iter_mut().zip(&self.time[BLOCK_SIZE..]) {
+      *output = echo / FFT_SIZE as f32;
+    }
+  }
+
+  /// One NLMS step of the background filter, constrained to a linear convolution.
+  fn adapt(
+    &mut self,
+    error: &[f32],
+  ) {
+    self.time[..BLOCK_SIZE].fill(0.0);
+    self.time[BLOCK_SIZE..].copy_from_slice(error);
+    let mut error_spectrum = vec![Complex32::default(); BINS];
+    self
+      .fft
+      .process(&mut self.time, &mut error_spectrum)
+      .expect("FFT buffers are sized for the plan");
+
+    let floor =
+      REGULARIZATION_RATIO * self.power.iter().sum::<f32>() / BINS as f32 + REGULARIZATION;
+    for partition in 0..self.partitions {
+      let spectrum = &self.spectra[partition];
+      for bin in 0..BINS {
+        let normalization = self.power[bin] + floor;
+        self.spectrum[bin] =
+          spectrum[bin].conj() * error_spectrum[bin] * (STEP_SIZE / normalization);
+      }
+
+      // Coefficients past the block length would wrap around, they are dropped
+      self.inverse_fft();
+      self.time[BLOCK_SIZE..].fill(0.0);
+      for sample in &mut self.time[..BLOCK_SIZE] {
+        *sample /= FFT_SIZE as f32;
+      }
+      self
+        .fft
+        .process(&mut self.time, &mut self.spectrum)
+        .expect("FFT buffers are sized for the plan");
+
+      for (weight, step) in self.background[partition]
+        .iter_mut()
+        .zip(&self.spectrum)
+      {
+        *weight += step;
+      }
+    }
+  }
+
+  /// Transforms `spectrum` into `time`.
+  fn inverse_fft(&mut self) {
+    // The spectrum of a real signal has real DC and Nyquist bins, rounding errors aside
+    self.spectrum[0].im = 0.0;
+    self.spectrum[BINS - 1].im = 0.0;
+    self
+      .ifft
+      .process(&mut self.spectrum, &mut self.time)
+      .expect("FFT buffers are sized for the plan");
+  }
+}
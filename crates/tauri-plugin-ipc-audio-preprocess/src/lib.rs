@@ -0,0 +1,178 @@
+use std::{
+  collections::HashMap,
+  sync::{
+    Arc,
+    Mutex,
+    atomic::{AtomicU32, Ordering},
+  },
+};
+
+use airi_audio_models::AudioError;
+use log::info;
+use tauri::{
+  Manager,
+  Runtime,
+  plugin::{Builder as PluginBuilder, TauriPlugin},
+};
+
+mod denoise;
+mod echo;
+mod preprocessor;
+mod reference;
+
+pub use denoise::Denoiser;
+pub use echo::{BLOCK_SIZE, DEFAULT_PARTITIONS, EchoCanceller};
+pub use preprocessor::{PreprocessOptions, Preprocessor};
+pub use reference::EchoReference;
+
+#[derive(Default)]
+struct AppDataPreprocessSessions {
+  next_id:  AtomicU32,
+  sessions: Mutex<HashMap<u32, Arc<Mutex<Preprocessor>>>>,
+}
+
+/// Audio that starts playing through the speakers now, interleaved at any rate. Sessions with
+/// echo cancellation remove it from what they record.
+#[tauri::command]
+async fn push_echo_reference<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  samples: Vec<f32>,
+  sample_rate: u32,
+  channels: Option<u16>,
+) -> Result<(), AudioError> {
+  play_echo_reference(&app, samples, sample_rate, channels.unwrap_or(1))
+}
+
+/// Playback was cut off, nothing is played from now on.
+#[tauri::command]
+async fn stop_echo_reference<R: Runtime>(app: tauri::AppHandle<R>) -> Result<(), AudioError> {
+  echo_reference(&app)?.stop();
+  Ok(())
+}
+
+/// Starts a session for audio that is sent over IPC, to clean it up before the stateless VAD
+/// and transcription commands. `sample_rate` is 16000 when not given. Returns the session id.
+#[tauri::command]
+async fn create_preprocess_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  sample_rate: Option<u32>,
+  options: PreprocessOptions,
+) -> Result<u32, AudioError> {
+  let preprocessor = new_preprocessor(&app, sample_rate.unwrap_or(16000), &options)?;
+
+  let data = app.state::<AppDataPreprocessSessions>();
+  let session_id = data.next_id.fetch_add(1, Ordering::Relaxed);
+  data
+    .sessions
+    .lock()
+    .unwrap()
+    .insert(session_id, Arc::new(Mutex::new(preprocessor)));
+
+  info!("Preprocess session {} created", session_id);
+  Ok(session_id)
+}
+
+/// Returns the processed audio of every complete block of 16 ms, the rest is kept for the next
+/// call.
+#[tauri::command]
+async fn process_samples<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+  samples: Vec<f32>,
+) -> Result<Vec<f32>, AudioError> {
+  let session = app
+    .state::<AppDataPreprocessSessions>()
+    .sessions
+    .lock()
+    .unwrap()
+    .get(&session_id)
+    .cloned()
+    .ok_or_else(|| {
+      AudioError::NotFound(format!("Preprocess session {} does not exist", session_id))
+    })?;
+
+  Ok(session.lock().unwrap().process(&samples))
+}
+
+#[tauri::command]
+async fn destroy_preprocess_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+) -> Result<(), AudioError> {
+  app
+    .state::<AppDataPreprocessSessions>()
+    .sessions
+    .lock()
+    .unwrap()
+    .remove(&session_id)
+    .ok_or_else(|| {
+      AudioError::NotFound(format!("Preprocess session {} does not exist", session_id))
+    })?;
+
+  info!("Preprocess session {} destroyed", session_id);
+  Ok(())
+}
+
+/// The reference every session of the app cancels the echo of.
+pub fn echo_reference<R: Runtime>(
+  app: &tauri::AppHandle<R>
+) -> Result<Arc<EchoReference>, AudioError> {
+  app
+    .try_state::<Arc<EchoReference>>()
+    .map(|reference| reference.inner().clone())
+    .ok_or_else(|| AudioError::Internal("Audio preprocess plugin is not initialized".to_string()))
+}
+
+/// Feeds audio that starts playing now into the echo reference, for plugins that play audio
+/// themselves.
+pub fn play_echo_reference<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  samples: Vec<f32>,
+  sample_rate: u32,
+  channels: u16,
+) -> Result<(), AudioError> {
+  let samples = match channels {
+    0 => {
+      return Err(AudioError::InvalidInput(
+        "Channel count must be positive".to_string(),
+      ));
+    },
+    1 => samples,
+    channels => samples
+      .chunks(channels as usize)
+      .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+      .collect(),
+  };
+
+  echo_reference(app)?
+    .play(samples, sample_rate)
+    .map_err(|e| AudioError::InvalidInput(format!("Failed to play echo reference: {}", e)))
+}
+
+/// A preprocessor reading the app's echo reference, for sessions of other plugins.
+pub fn new_preprocessor<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  sample_rate: u32,
+  options: &PreprocessOptions,
+) -> Result<Preprocessor, AudioError> {
+  Preprocessor::new(sample_rate, options, echo_reference(app)?)
+    .map_err(|e| AudioError::InvalidInput(e.to_string()))
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+  PluginBuilder::new("ipc-audio-preprocess")
+    .setup(|app, _| {
+      info!("Initializing audio preprocess plugin...");
+      app.manage(Arc::new(EchoReference::default()));
+      app.manage(AppDataPreprocessSessions::default());
+      Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+      push_echo_reference,
+      stop_echo_reference,
+      create_preprocess_session,
+      process_samples,
+      destroy_preprocess_session
+    ])
+    .build()
+}
@@ -0,0 +1,162 @@
+use std::{sync::Arc, time::Instant};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  denoise::Denoiser,
+  echo::{BLOCK_SIZE, DEFAULT_PARTITIONS, EchoCanceller},
+  reference::{EchoReference, SAMPLE_RATE},
+};
+
+/// Recorded audio reaches a session some time after it was recorded, more so through a webview.
+/// The reference is read this much earlier than the clock says, so the echo always comes after
+/// it, within the length of the echo filter.
+const LATENCY_ALLOWANCE_MS: u64 = 64;
+
+/// Which stages run, both are off by default.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PreprocessOptions {
+  /// Suppresses stationary background noise
+  pub denoise:           bool,
+  /// Removes what is played through the echo reference, 16 kHz audio only
+  pub echo_cancellation: bool,
+}
+
+struct EchoStage {
+  canceller: EchoCanceller,
+  reference: Arc<EchoReference>,
+  /// Reference position of the next block, kept while the reference plays so the two stay
+  /// aligned sample by sample
+  cursor:    Option<u64>,
+  played:    Vec<f32>,
+}
+
+impl EchoStage {
+  fn process(
+    &mut self,
+    mic: &[f32],
+    recorded: Instant,
+    age: u64,
+    output: &mut [f32],
+  ) {
+    let position = self.cursor.unwrap_or_else(|| {
+      let allowance = LATENCY_ALLOWANCE_MS * u64::from(SAMPLE_RATE) / 1000;
+      self
+        .reference
+        .position(recorded)
+        .saturating_sub(age + allowance)
+    });
+
+    let played = self.reference.read(position, &mut self.played);
+    self
+      .canceller
+      .process_block(mic, &self.played, output);
+
+    // While nothing plays the position comes from the clock again, so drift does not build up
+    self.cursor = played.then_some(position + BLOCK_SIZE as u64);
+  }
+}
+
+/// Cleans recorded audio up before VAD and transcription: echo cancellation first, then noise
+/// suppression. Audio comes out in blocks of 16 ms, delayed by 16 ms more with denoising.
+pub struct Preprocessor {
+  block_size: usize,
+  echo:       Option<EchoStage>,
+  denoiser:   Option<Denoiser>,
+  /// Samples that do not fill a block yet
+  pending:    Vec<f32>,
+}
+
+impl Preprocessor {
+  pub fn new(
+    sample_rate: u32,
+    options: &PreprocessOptions,
+    reference: Arc<EchoReference>,
+  ) -> Result<Self> {
+    if options.echo_cancellation && sample_rate != SAMPLE_RATE {
+      return Err(anyhow!(
+        "Echo cancellation needs {} Hz audio, got {}",
+        SAMPLE_RATE,
+        sample_rate
+      ));
+    }
+
+    let denoiser = options
+      .denoise
+      .then(|| Denoiser::new(sample_rate))
+      .transpose()?;
+    let block_size = denoiser
+      .as_ref()
+      .map_or(BLOCK_SIZE, Denoiser::hop_size);
+
+    Ok(Self {
+      block_size,
+      echo: options.echo_cancellation.then(|| EchoStage {
+        canceller: EchoCanceller::new(DEFAULT_PARTITIONS),
+        reference,
+        cursor: None,
+        played: vec![0.0; BLOCK_SIZE],
+      }),
+      denoiser,
+      pending: Vec::with_capacity(block_size),
+    })
+  }
+
+  /// Processes audio that was just recorded. Returns every complete block, the rest waits for
+  /// the next call.
+  pub fn process(
+    &mut self,
+    samples: &[f32],
+  ) -> Vec<f32> {
+    self.process_at(samples, Instant::now())
+  }
+
+  /// Same as [`Preprocessor::process`], `recorded` is when the last of `samples` was recorded.
+  pub fn process_at(
+    &mut self,
+    samples: &[f32],
+    recorded: Instant,
+  ) -> Vec<f32> {
+    if self.echo.is_none() && self.denoiser.is_none() {
+      return samples.to_vec();
+    }
+
+    self.pending.extend_from_slice(samples);
+    let block_size = self.block_size;
+    let blocks = self.pending.len() / block_size;
+
+    let mut output = vec![0.0; blocks * block_size];
+    let mut cancelled = vec![0.0; block_size];
+    for (i, output) in output.chunks_exact_mut(block_size).enumerate() {
+      let mut block = &self.pending[i * block_size..(i + 1) * block_size];
+      if let Some(echo) = &mut self.echo {
+        // Samples recorded since the start of the block
+        let age = (self.pending.len() - i * block_size) as u64;
+        echo.process(block, recorded, age, &mut cancelled);
+        block = &cancelled;
+      }
+
+      match &mut self.denoiser {
+        Some(denoiser) => denoiser.process_hop(block, output),
+        None => output.copy_from_slice(block),
+      }
+    }
+
+    self.pending.drain(..blocks * block_size);
+    output
+  }
+
+  /// Forgets the audio seen so far, the echo path and the noise included.
+  pub fn reset(&mut self) {
+    self.pending.clear();
+    if let Some(echo) = &mut self.echo {
+      echo.canceller.reset();
+      echo.cursor = None;
+    }
+    if let Some(denoiser) = &mut self.denoiser {
+      denoiser.reset();
+    }
+  }
+}
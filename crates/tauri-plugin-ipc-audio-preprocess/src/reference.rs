@@ -0,0 +1,135 @@
+use std::{collections::VecDeque, sync::Mutex, time::Instant};
+
+use airi_audio_models::audio::resample;
+use anyhow::Result;
+
+/// The reference is kept at the rate echo cancellation runs at
+pub const SAMPLE_RATE: u32 = 16000;
+/// Played audio kept around for sessions that read it late, and the longest silence between two
+/// clips that is filled in rather than starting over
+const HISTORY_SAMPLES: u64 = 2 * SAMPLE_RATE as u64;
+
+/// What is coming out of the speakers, shared by every session that cancels its echo. Positions
+/// are samples since the reference was created, so sessions can line the audio they record up
+/// with it by the clock.
+pub struct EchoReference {
+  origin:   Instant,
+  timeline: Mutex<Timeline>,
+}
+
+struct Timeline {
+  /// Position of the first sample kept
+  start:   u64,
+  samples: VecDeque<f32>,
+}
+
+impl Default for EchoReference {
+  fn default() -> Self {
+    Self {
+      origin:   Instant::now(),
+      timeline: Mutex::new(Timeline {
+        start:   0,
+        samples: VecDeque::new(),
+      }),
+    }
+  }
+}
+
+impl EchoReference {
+  /// Position of the sample playing at `at`.
+  pub fn position(
+    &self,
+    at: Instant,
+  ) -> u64 {
+    at.saturating_duration_since(self.origin)
+      .as_micros() as u64
+      * u64::from(SAMPLE_RATE)
+      / 1_000_000
+  }
+
+  /// Audio that starts playing now, in mono at any rate.
+  pub fn play(
+    &self,
+    samples: Vec<f32>,
+    sample_rate: u32,
+  ) -> Result<()> {
+    let samples = resample(samples, sample_rate as usize, SAMPLE_RATE as usize)?;
+    self.play_at(&samples, Instant::now());
+    Ok(())
+  }
+
+  /// Audio at 16 kHz that starts playing at `at`, replacing what was still to be played.
+  pub fn play_at(
+    &self,
+    samples: &[f32],
+    at: Instant,
+  ) {
+    let position = self.position(at);
+    let mut timeline = self.timeline.lock().unwrap();
+
+    let end = timeline.start + timeline.samples.len() as u64;
+    if position < timeline.start || position > end + HISTORY_SAMPLES {
+      timeline.samples.clear();
+      timeline.start = position;
+    } else if position < end {
+      let kept = (position - timeline.start) as usize;
+      timeline.samples.truncate(kept);
+    } else {
+      let silence = (position - end) as usize;
+      timeline
+        .samples
+        .extend(std::iter::repeat_n(0.0, silence));
+    }
+    timeline.samples.extend(samples);
+
+    let expired = position
+      .saturating_sub(HISTORY_SAMPLES)
+      .saturating_sub(timeline.start)
+      .min(timeline.samples.len() as u64);
+    timeline.samples.drain(..expired as usize);
+    timeline.start += expired;
+  }
+
+  /// Nothing plays from now on, when playback was cut off.
+  pub fn stop(&self) {
+    self.stop_at(Instant::now());
+  }
+
+  pub fn stop_at(
+    &self,
+    at: Instant,
+  ) {
+    let position = self.position(at);
+    let mut timeline = self.timeline.lock().unwrap();
+
+    let kept = position.saturating_sub(timeline.start) as usize;
+    timeline.samples.truncate(kept);
+  }
+
+  /// Copies the audio played from `position` on, silence where nothing was. Returns whether
+  /// anything was played in that range.
+  pub fn read(
+    &self,
+    position: u64,
+    output: &mut [f32],
+  ) -> bool {
+    let timeline = self.timeline.lock().unwrap();
+
+    output.fill(0.0);
+    let mut played = false;
+    for (i, sample) in output.iter_mut().enumerate() {
+      let Some(index) = (position + i as u64).checked_sub(timeline.start) else {
+        continue;
+      };
+      match timeline.samples.get(index as usize) {
+        Some(&value) => {
+          *sample = value;
+          played = true;
+        },
+        None => break,
+      }
+    }
+
+    played
+  }
+}
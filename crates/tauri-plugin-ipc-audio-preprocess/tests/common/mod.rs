@@ -0,0 +1,19 @@
+/// xorshift32, the tests need reproducible noise and nothing more
+pub struct Noise(pub u32);
+
+impl Noise {
+  pub fn next(&mut self) -> f32 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 17;
+    self.0 ^= self.0 << 5;
+    self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+  }
+}
+
+pub fn energy(samples: &[f32]) -> f32 {
+  samples.iter().map(|x| x * x).sum()
+}
+
+pub fn db(ratio: f32) -> f32 {
+  10.0 * ratio.log10()
+}
@@ -0,0 +1,91 @@
+use std::f32::consts::PI;
+
+use common::{Noise, db, energy};
+use tauri_plugin_ipc_audio_preprocess::Denoiser;
+
+mod common;
+
+const SAMPLE_RATE: usize = 16000;
+
+/// Voiced bursts from 2 to 3 and from 4 to 5 seconds, silence elsewhere.
+fn speech_bursts(seconds: usize) -> Vec<f32> {
+  let mut phase = 0.0;
+  (0..seconds * SAMPLE_RATE)
+    .map(|i| {
+      let t = i as f32 / SAMPLE_RATE as f32;
+      if !(2.0..3.0).contains(&t) && !(4.0..5.0).contains(&t) {
+        return 0.0;
+      }
+
+      let pitch = 180.0 * (1.0 + 0.2 * (2.0 * PI * 0.7 * t).sin());
+      phase += 2.0 * PI * pitch / SAMPLE_RATE as f32;
+      let voiced: f32 = (1..=20)
+        .map(|harmonic| (harmonic as f32 * phase).sin() / harmonic as f32)
+        .sum();
+      0.2 * (0.6 + 0.4 * (2.0 * PI * 4.0 * t).sin()) * voiced
+    })
+    .collect()
+}
+
+fn denoise(
+  samples: &[f32],
+  sample_rate: u32,
+) -> Vec<f32> {
+  let mut denoiser = Denoiser::new(sample_rate).unwrap();
+  let hop = denoiser.hop_size();
+  let mut output = vec![0.0; samples.len() / hop * hop];
+  for (input, output) in samples
+    .chunks_exact(hop)
+    .zip(output.chunks_exact_mut(hop))
+  {
+    denoiser.process_hop(input, output);
+  }
+
+  // Output is a hop behind the input
+  output.drain(..hop);
+  output
+}
+
+#[test]
+fn suppresses_stationary_noise() {
+  let speech = speech_bursts(6);
+  let mut noise = Noise(3);
+  let noise: Vec<f32> = speech
+    .iter()
+    .map(|_| 0.1 * noise.next())
+    .collect();
+  let noisy: Vec<f32> = speech
+    .iter()
+    .zip(&noise)
+    .map(|(s, n)| s + n)
+    .collect();
+
+  let output = denoise(&noisy, SAMPLE_RATE as u32);
+
+  let silence = SAMPLE_RATE..2 * SAMPLE_RATE - SAMPLE_RATE / 10;
+  let reduction = db(energy(&noise[silence.clone()]) / energy(&output[silence]));
+  assert!(reduction > 16.0, "noise only reduced by {reduction:.1} dB");
+
+  // Speech comes through with a better signal to noise ratio
+  let burst = 2 * SAMPLE_RATE + SAMPLE_RATE / 10..3 * SAMPLE_RATE - SAMPLE_RATE / 10;
+  let snr_before = db(energy(&speech[burst.clone()]) / energy(&noise[burst.clone()]));
+  assert!(
+    (5.0..7.0).contains(&snr_before),
+    "SNR is {snr_before:.1} dB before denoising"
+  );
+  let residual: Vec<f32> = output[burst.clone()]
+    .iter()
+    .zip(&speech[burst.clone()])
+    .map(|(o, s)| o - s)
+    .collect();
+  let snr_after = db(energy(&speech[burst.clone()]) / energy(&residual));
+  let kept = db(energy(&output[burst.clone()]) / energy(&speech[burst]));
+  assert!(
+    snr_after > snr_before + 4.0,
+    "SNR went from {snr_before:.1} dB to {snr_after:.1} dB"
+  );
+  assert!(
+    (-2.0..1.0).contains(&kept),
+    "speech level changed by {kept:.1} dB"
+  );
+}
@@ -0,0 +1,194 @@
+use std::{
+  f32::consts::PI,
+  sync::Arc,
+  time::{Duration, Instant},
+};
+
+use common::{Noise, db, energy};
+use tauri_plugin_ipc_audio_preprocess::{
+  BLOCK_SIZE,
+  DEFAULT_PARTITIONS,
+  EchoCanceller,
+  EchoReference,
+  PreprocessOptions,
+  Preprocessor,
+};
+
+mod common;
+
+const SAMPLE_RATE: usize = 16000;
+
+/// Voiced sounds with a gliding pitch and syllable-like loudness, a stand-in for TTS output.
+fn speech_like(
+  seconds: f32,
+  base_pitch: f32,
+  seed: u32,
+) -> Vec<f32> {
+  let mut noise = Noise(seed);
+  let mut phase = 0.0;
+  (0..(seconds * SAMPLE_RATE as f32) as usize)
+    .map(|i| {
+      let t = i as f32 / SAMPLE_RATE as f32;
+      let pitch = base_pitch * (1.0 + 0.2 * (2.0 * PI * 0.7 * t).sin());
+      phase += 2.0 * PI * pitch / SAMPLE_RATE as f32;
+      let voiced: f32 = (1..=20)
+        .map(|harmonic| (harmonic as f32 * phase).sin() / harmonic as f32)
+        .sum();
+      let syllables = 0.6 + 0.4 * (2.0 * PI * 4.0 * t).sin();
+      0.2 * syllables * (voiced + 0.3 * noise.next())
+    })
+    .collect()
+}
+
+/// A room: the direct sound after 30 ms, then a decaying tail of reflections over 100 ms.
+fn echo_path() -> Vec<f32> {
+  let mut noise = Noise(7);
+  let delay = 480;
+  let tail = 1600;
+  let mut path = vec![0.0; delay + tail];
+  path[delay] = 0.5;
+  for i in 1..tail {
+    path[delay + i] = 0.2 * noise.next() * (-(i as f32) / 300.0).exp();
+  }
+  path
+}
+
+fn convolve(
+  signal: &[f32],
+  path: &[f32],
+) -> Vec<f32> {
+  let mut output = vec![0.0; signal.len()];
+  for (delay, tap) in path.iter().enumerate() {
+    for (output, sample) in output[delay..].iter_mut().zip(signal) {
+      *output += tap * sample;
+    }
+  }
+  output
+}
+
+fn cancel(
+  mic: &[f32],
+  reference: &[f32],
+) -> Vec<f32> {
+  let mut canceller = EchoCanceller::new(DEFAULT_PARTITIONS);
+  let mut output = vec![0.0; mic.len() / BLOCK_SIZE * BLOCK_SIZE];
+  for ((mic, reference), output) in mic
+    .chunks_exact(BLOCK_SIZE)
+    .zip(reference.chunks_exact(BLOCK_SIZE))
+    .zip(output.chunks_exact_mut(BLOCK_SIZE))
+  {
+    canceller.process_block(mic, reference, output);
+  }
+  output
+}
+
+#[test]
+fn cancels_echo_of_reference() {
+  let reference = speech_like(8.0, 160.0, 1);
+  let mic = convolve(&reference, &echo_path());
+  let output = cancel(&mic, &reference);
+
+  // Echo return loss enhancement once the filter converged
+  let converged = 5 * SAMPLE_RATE..output.len();
+  let erle = db(energy(&mic[converged.clone()]) / energy(&output[converged]));
+  assert!(erle > 22.0, "echo only reduced by {erle:.1} dB");
+}
+
+#[test]
+fn keeps_near_end_speech_during_double_talk() {
+  let reference = speech_like(10.0, 160.0, 1);
+  let echo = convolve(&reference, &echo_path());
+  let mut near_end = vec![0.0; reference.len()];
+  let talking = 6 * SAMPLE_RATE..9 * SAMPLE_RATE;
+  near_end[talking.clone()].copy_from_slice(&speech_like(3.0, 230.0, 2));
+  let mic: Vec<f32> = echo
+    .iter()
+    .zip(&near_end)
+    .map(|(e, n)| e + n)
+    .collect();
+
+  let output = cancel(&mic, &reference);
+
+  // The echo is louder than the user to begin with
+  let before = db(energy(&near_end[talking.clone()]) / energy(&echo[talking.clone()]));
+  assert!(before < 0.0, "near-end to echo ratio is {before:.1} dB");
+  let residual: Vec<f32> = output[talking.clone()]
+    .iter()
+    .zip(&near_end[talking.clone()])
+    .map(|(o, n)| o - n)
+    .collect();
+  let after = db(energy(&near_end[talking]) / energy(&residual));
+  assert!(
+    after > 17.0,
+    "near-end to echo ratio went from {before:.1} dB to {after:.1} dB"
+  );
+
+  // The echo after the double talk is still cancelled
+  let after_talking = 9 * SAMPLE_RATE + SAMPLE_RATE / 2..output.len();
+  let erle = db(energy(&mic[after_talking.clone()]) / energy(&output[after_talking]));
+  assert!(
+    erle > 22.0,
+    "echo only reduced by {erle:.1} dB after double talk"
+  );
+}
+
+#[test]
+fn cancels_echo_through_shared_reference() {
+  let reference = Arc::new(EchoReference::default());
+  let options = PreprocessOptions {
+    echo_cancellation: true,
+    ..Default::default()
+  };
+  let mut preprocessor = Preprocessor::new(16000, &options, reference.clone()).unwrap();
+
+  // Playback starts 0.5 seconds into the recording, which reaches the session 20 ms late in
+  // chunks of 100 ms
+  let start = Instant::now();
+  let played = speech_like(8.0, 160.0, 1);
+  let offset = SAMPLE_RATE / 2;
+  reference.play_at(&played, start + Duration::from_millis(500));
+
+  let echo = convolve(&played, &echo_path());
+  let mut mic = vec![0.0; offset + echo.len()];
+  mic[offset..].copy_from_slice(&echo);
+
+  let chunk = SAMPLE_RATE / 10;
+  let mut output = Vec::new();
+  for (i, samples) in mic.chunks(chunk).enumerate() {
+    let recorded = start + Duration::from_millis(100 * (i as u64 + 1) + 20);
+    output.extend(preprocessor.process_at(samples, recorded));
+  }
+
+  let converged = offset + 5 * SAMPLE_RATE..output.len();
+  let erle = db(energy(&mic[converged.clone()]) / energy(&output[converged]));
+  assert!(erle > 22.0, "echo only reduced by {erle:.1} dB");
+}
+
+#[test]
+fn stopped_reference_is_not_subtracted() {
+  let reference = Arc::new(EchoReference::default());
+  let options = PreprocessOptions {
+    echo_cancellation: true,
+    ..Default::default()
+  };
+  let mut preprocessor = Preprocessor::new(16000, &options, reference.clone()).unwrap();
+
+  // Playback is cut off right away, the user talks instead
+  let start = Instant::now();
+  reference.play_at(&speech_like(4.0, 160.0, 1), start);
+  reference.stop_at(start);
+
+  let near_end = speech_like(4.0, 230.0, 2);
+  let mut output = Vec::new();
+  for (i, samples) in near_end.chunks(SAMPLE_RATE / 10).enumerate() {
+    let recorded = start + Duration::from_millis(100 * (i as u64 + 1));
+    output.extend(preprocessor.process_at(samples, recorded));
+  }
+
+  let residual: Vec<f32> = output
+    .iter()
+    .zip(&near_end)
+    .map(|(o, n)| o - n)
+    .collect();
+  assert_eq!(energy(&residual), 0.0);
+}
@@ -0,0 +1,10 @@
+const COMMANDS: &[&str] = &[
+  "load_ort_model_emotion",
+  "cancel_model_download",
+  "unload_ort_model_emotion",
+  "analyze_prosody",
+];
+
+fn main() {
+  tauri_plugin::Builder::new(COMMANDS).build();
+}
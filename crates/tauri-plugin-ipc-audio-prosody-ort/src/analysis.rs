@@ -0,0 +1,371 @@
+use serde::Serialize;
+
+use crate::{
+  models::emotion::Emotion,
+  pitch::{FRAME_LENGTH, FRAME_SHIFT, PitchTracker, SAMPLE_RATE},
+};
+
+/// Frames this far below the loudest one are silence, their pitch is ignored
+const ACTIVE_RANGE_DB: f32 = 35.0;
+/// A syllable nucleus stands out of the dips around it by this much
+const SYLLABLE_DIP_DB: f32 = 2.0;
+/// The intensity contour is smoothed over 50 ms before syllables are counted
+const SMOOTHING_FRAMES: usize = 5;
+const SILENCE_DB: f32 = -100.0;
+
+/// Emotions the classifier is less sure of than this are not described
+const MIN_EMOTION_CONFIDENCE: f32 = 0.5;
+/// Syllable rates of conversational speech lie around 4 to 5 per second
+const FAST_SYLLABLES_PER_SECOND: f32 = 6.0;
+const SLOW_SYLLABLES_PER_SECOND: f32 = 2.5;
+/// Pitch deviations of read speech lie around 2 to 3 semitones
+const ANIMATED_STD_SEMITONES: f32 = 4.5;
+const FLAT_STD_SEMITONES: f32 = 1.2;
+/// Rates and pitch deviations of shorter segments say little
+const MIN_DESCRIBED_MS: u32 = 1000;
+/// A final rise this steep sounds like a question
+const RISING_ST_PER_S: f32 = 10.0;
+
+/// How something was said, measured on one speech segment.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Prosody {
+  /// Length of the segment without the silence around the speech
+  pub duration_ms:   u32,
+  /// `None` when nothing was voiced
+  pub pitch:         Option<PitchStats>,
+  pub energy:        EnergyStats,
+  pub speaking_rate: SpeakingRate,
+  /// When an emotion model is loaded
+  pub emotion:       Option<Emotion>,
+  /// What stands out, to tell a language model how it was said, e.g. `sounds angry`
+  pub cues:          Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PitchStats {
+  pub mean_hz:              f32,
+  pub median_hz:            f32,
+  /// 5th and 95th percentiles, octave jumps of the tracker do not reach them
+  pub min_hz:               f32,
+  pub max_hz:               f32,
+  /// Between the min and max, 12 semitones are an octave
+  pub range_semitones:      f32,
+  /// Standard deviation around the median, low for monotone speech
+  pub std_semitones:        f32,
+  /// Slope of the last 500 ms of voiced speech, positive when the voice rises at the end
+  pub final_slope_st_per_s: f32,
+  /// Share of the speech that is voiced
+  pub voiced_ratio:         f32,
+}
+
+/// Levels in dB relative to full scale, of the frames with speech.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnergyStats {
+  pub mean_db: f32,
+  pub max_db:  f32,
+  pub std_db:  f32,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeakingRate {
+  /// Syllables estimated from peaks of loudness in voiced speech
+  pub syllables_per_second: f32,
+  /// From the transcript, when one was given. CJK characters count as words.
+  pub words_per_second:     Option<f32>,
+}
+
+/// Measures pitch, loudness and speaking rate of 16 kHz mono speech segments.
+#[derive(Default)]
+pub struct Analyzer {
+  tracker: PitchTracker,
+}
+
+impl Analyzer {
+  /// Analyzes a segment, with its transcript for the word rate if there is one, and the emotion
+  /// a model heard in it.
+  pub fn analyze(
+    &self,
+    audio: &[f32],
+    text: Option<&str>,
+    emotion: Option<Emotion>,
+  ) -> Prosody {
+    let pitch = self.tracker.track(audio);
+    // Loudness of the middle 10 ms of every pitch frame
+    let levels: Vec<f32> = (0..pitch.len())
+      .map(|frame| {
+        let start = frame * FRAME_SHIFT + (FRAME_LENGTH - FRAME_SHIFT) / 2;
+        level_db(&audio[start..start + FRAME_SHIFT])
+      })
+      .collect();
+    let loudest = levels.iter().copied().fold(SILENCE_DB, f32::max);
+    let active: Vec<bool> = levels
+      .iter()
+      .map(|&level| level > SILENCE_DB && level >= loudest - ACTIVE_RANGE_DB)
+      .collect();
+
+    // Speech is what lies between the first and the last active frame
+    let (Some(first), Some(last)) = (
+      active.iter().position(|&active| active),
+      active.iter().rposition(|&active| active),
+    ) else {
+      return Prosody {
+        emotion,
+        ..Default::default()
+      };
+    };
+    let frames = first..last + 1;
+    let seconds = frames.len() as f32 * FRAME_SHIFT as f32 / SAMPLE_RATE as f32;
+
+    let voiced: Vec<Option<f32>> = frames
+      .clone()
+      .map(|frame| pitch[frame].filter(|_| active[frame]))
+      .collect();
+    let speech_levels: Vec<f32> = frames
+      .clone()
+      .filter(|&frame| active[frame])
+      .map(|frame| levels[frame])
+      .collect();
+
+    let mut prosody = Prosody {
+      duration_ms: (seconds * 1000.0).round() as u32,
+      pitch: pitch_stats(&voiced),
+      energy: energy_stats(&speech_levels),
+      speaking_rate: SpeakingRate {
+        syllables_per_second: count_syllables(&levels[frames], &voiced) as f32 / seconds,
+        words_per_second:     text
+          .map(count_words)
+          .filter(|&words| words > 0)
+          .map(|words| words as f32 / seconds),
+      },
+      emotion,
+      cues: Vec::new(),
+    };
+    prosody.cues = describe(&prosody);
+    prosody
+  }
+}
+
+fn describe(prosody: &Prosody) -> Vec<String> {
+  let mut cues = Vec::new();
+
+  if let Some(emotion) = &prosody.emotion
+    && emotion.confidence >= MIN_EMOTION_CONFIDENCE
+    && let Some(cue) = emotion_cue(&emotion.label)
+  {
+    cues.push(cue);
+  }
+
+  if prosody.duration_ms < MIN_DESCRIBED_MS {
+    return cues;
+  }
+
+  let rate = prosody.speaking_rate.syllables_per_second;
+  if rate > FAST_SYLLABLES_PER_SECOND {
+    cues.push("speaks quickly".to_string());
+  } else if rate < SLOW_SYLLABLES_PER_SECOND {
+    cues.push("speaks slowly".to_string());
+  }
+
+  if let Some(pitch) = &prosody.pitch {
+    if pitch.std_semitones > ANIMATED_STD_SEMITONES {
+      cues.push("sounds animated".to_string());
+    } else if pitch.std_semitones < FLAT_STD_SEMITONES {
+      cues.push("sounds flat".to_string());
+    }
+    if pitch.final_slope_st_per_s > RISING_ST_PER_S {
+      cues.push("voice rises at the end".to_string());
+    }
+  }
+
+  cues
+}
+
+/// Labels of the common emotion datasets, neutral ones describe nothing. Other labels are used as
+/// they are.
+fn emotion_cue(label: &str) -> Option<String> {
+  let label = label.trim().to_lowercase();
+  let adjective = match label.as_str() {
+    "neu" | "neutral" | "calm" | "oth" | "other" | "unknown" => return None,
+    "ang" | "anger" | "angry" => "angry",
+    "fru" | "frustration" | "frustrated" => "frustrated",
+    "hap" | "happiness" | "happy" | "joy" => "happy",
+    "exc" | "excitement" | "excited" => "excited",
+    "sad" | "sadness" => "sad",
+    "fea" | "fear" | "fearful" => "scared",
+    "dis" | "disgust" | "disgusted" => "disgusted",
+    "sur" | "surprise" | "surprised" => "surprised",
+    label => label,
+  };
+  Some(format!("sounds {}", adjective))
+}
+
+fn level_db(samples: &[f32]) -> f32 {
+  let power = samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32;
+  if power > 0.0 {
+    (10.0 * power.log10()).max(SILENCE_DB)
+  } else {
+    SILENCE_DB
+  }
+}
+
+fn semitones(
+  hz: f32,
+  reference: f32,
+) -> f32 {
+  12.0 * (hz / reference).log2()
+}
+
+fn percentile(
+  sorted: &[f32],
+  p: f32,
+) -> f32 {
+  sorted[((sorted.len() - 1) as f32 * p).round() as usize]
+}
+
+fn pitch_stats(voiced: &[Option<f32>]) -> Option<PitchStats> {
+  let mut sorted: Vec<f32> = voiced.iter().flatten().copied().collect();
+  if sorted.is_empty() {
+    return None;
+  }
+  sorted.sort_by(f32::total_cmp);
+
+  let median = percentile(&sorted, 0.5);
+  let min = percentile(&sorted, 0.05);
+  let max = percentile(&sorted, 0.95);
+  let variance = sorted
+    .iter()
+    .map(|&hz| semitones(hz, median).powi(2))
+    .sum::<f32>()
+    / sorted.len() as f32;
+
+  // Least squares line through the voiced frames of the last 500 ms
+  let tail = voiced
+    .len()
+    .saturating_sub(SAMPLE_RATE / 2 / FRAME_SHIFT);
+  let points: Vec<(f32, f32)> = voiced[tail..]
+    .iter()
+    .enumerate()
+    .filter_map(|(i, hz)| {
+      let seconds = i as f32 * FRAME_SHIFT as f32 / SAMPLE_RATE as f32;
+      Some((seconds, semitones((*hz)?, median)))
+    })
+    .collect();
+  let n = points.len() as f32;
+  let mean_t = points.iter().map(|(t, _)| t).sum::<f32>() / n;
+  let mean_s = points.iter().map(|(_, s)| s).sum::<f32>() / n;
+  let covariance: f32 = points
+    .iter()
+    .map(|(t, s)| (t - mean_t) * (s - mean_s))
+    .sum();
+  let spread: f32 = points
+    .iter()
+    .map(|(t, _)| (t - mean_t).powi(2))
+    .sum();
+
+  Some(PitchStats {
+    mean_hz:              sorted.iter().sum::<f32>() / sorted.len() as f32,
+    median_hz:            median,
+    min_hz:               min,
+    max_hz:               max,
+    range_semitones:      semitones(max, min),
+    std_semitones:        variance.sqrt(),
+    final_slope_st_per_s: if points.len() >= 2 && spread > 0.0 {
+      covariance / spread
+    } else {
+      0.0
+    },
+    voiced_ratio:         sorted.len() as f32 / voiced.len() as f32,
+  })
+}
+
+fn energy_stats(levels: &[f32]) -> EnergyStats {
+  if levels.is_empty() {
+    return EnergyStats::default();
+  }
+
+  let n = levels.len() as f32;
+  let mean = levels.iter().sum::<f32>() / n;
+  EnergyStats {
+    mean_db: mean,
+    max_db:  levels.iter().copied().fold(SILENCE_DB, f32::max),
+    std_db:  (levels
+      .iter()
+      .map(|l| (l - mean).powi(2))
+      .sum::<f32>()
+      / n)
+      .sqrt(),
+  }
+}
+
+/// Counts peaks of the smoothed intensity that are voiced, loud enough and separated from the
+/// previous peak by a dip, after de Jong and Wempe's syllable nuclei detection.
+fn count_syllables(
+  levels: &[f32],
+  voiced: &[Option<f32>],
+) -> usize {
+  let smoothed: Vec<f32> = (0..levels.len())
+    .map(|i| {
+      let window = &levels
+        [i.saturating_sub(SMOOTHING_FRAMES / 2)..(i + SMOOTHING_FRAMES / 2 + 1).min(levels.len())];
+      window.iter().sum::<f32>() / window.len() as f32
+    })
+    .collect();
+
+  let mut sorted = smoothed.clone();
+  sorted.sort_by(f32::total_cmp);
+  let loudest = sorted.last().copied().unwrap_or(SILENCE_DB);
+  let floor = percentile(&sorted, 0.5).max(loudest - ACTIVE_RANGE_DB + 10.0);
+
+  let mut count = 0;
+  let mut previous: Option<f32> = None;
+  let mut dip = f32::INFINITY;
+  for i in 1..smoothed.len().saturating_sub(1) {
+    dip = dip.min(smoothed[i]);
+    let peak = smoothed[i] > smoothed[i - 1] && smoothed[i] >= smoothed[i + 1];
+    if !peak || smoothed[i] < floor || voiced[i].is_none() {
+      continue;
+    }
+
+    let separated =
+      previous.is_none_or(|previous| previous.min(smoothed[i]) - dip >= SYLLABLE_DIP_DB);
+    if separated {
+      count += 1;
+      previous = Some(smoothed[i]);
+      dip = smoothed[i];
+    } else if previous.is_some_and(|previous| smoothed[i] > previous) {
+      // The same syllable, peaking higher
+      previous = Some(smoothed[i]);
+      dip = smoothed[i];
+    }
+  }
+
+  count
+}
+
+fn is_cjk(c: char) -> bool {
+  matches!(c,
+    '\u{3040}'..='\u{30ff}' // Kana
+    | '\u{3400}'..='\u{4dbf}'
+    | '\u{4e00}'..='\u{9fff}' // CJK ideographs
+    | '\u{ac00}'..='\u{d7af}' // Hangul syllables
+  )
+}
+
+/// Words separated by spaces, and every CJK character as one word.
+fn count_words(text: &str) -> usize {
+  text
+    .split_whitespace()
+    .map(|word| {
+      let cjk = word.chars().filter(|&c| is_cjk(c)).count();
+      let rest = word
+        .split(is_cjk)
+        .filter(|part| part.chars().any(char::is_alphanumeric))
+        .count();
+      cjk + rest
+    })
+    .sum()
+}
@@ -0,0 +1,131 @@
+use std::sync::{Arc, Mutex};
+
+use airi_audio_models::{AudioError, audio};
+use log::info;
+use tauri::{
+  Manager,
+  Runtime,
+  plugin::{Builder as PluginBuilder, TauriPlugin},
+};
+
+mod analysis;
+mod models;
+mod pitch;
+
+pub use analysis::{Analyzer, EnergyStats, PitchStats, Prosody, SpeakingRate};
+pub use models::emotion::{Emotion, EmotionClassifier};
+pub use pitch::{FRAME_SHIFT, PitchTracker, SAMPLE_RATE};
+
+/// Emotion models need a bit of speech, shorter segments are only measured
+const MIN_CLASSIFIED_SAMPLES: usize = SAMPLE_RATE / 4;
+
+#[derive(Default)]
+struct AppDataProsody {
+  analyzer:   Analyzer,
+  classifier: Mutex<Option<Arc<EmotionClassifier>>>,
+}
+
+/// Loads a speech emotion classifier from a Hugging Face repository in the Transformers.js
+/// layout, its emotion is then part of every analysis. Replaces the loaded one.
+#[tauri::command]
+async fn load_ort_model_emotion<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  window: tauri::WebviewWindow<R>,
+  model_id: String,
+  revision: Option<String>,
+  model_file: Option<String>,
+) -> Result<(), AudioError> {
+  info!("Loading models...");
+
+  let classifier = EmotionClassifier::new(
+    &model_id,
+    revision.as_deref().unwrap_or("main"),
+    model_file.as_deref().unwrap_or("onnx/model.onnx"),
+    window,
+  )
+  .map_err(|e| {
+    AudioError::load_failed(e.context(format!("Failed to load emotion model {}", model_id)))
+  })?;
+  *app
+    .state::<AppDataProsody>()
+    .classifier
+    .lock()
+    .unwrap() = Some(Arc::new(classifier));
+
+  info!("Emotion model {} loaded successfully", model_id);
+  Ok(())
+}
+
+/// Stops the download of the emotion model, `load_ort_model_emotion` then fails. Returns whether
+/// it was downloading.
+#[tauri::command]
+async fn cancel_model_download(model_id: String) -> Result<bool, AudioError> {
+  Ok(airi_audio_models::cancel_download(&model_id))
+}
+
+#[tauri::command]
+async fn unload_ort_model_emotion<R: Runtime>(app: tauri::AppHandle<R>) -> Result<(), AudioError> {
+  app
+    .state::<AppDataProsody>()
+    .classifier
+    .lock()
+    .unwrap()
+    .take();
+
+  info!("Emotion model unloaded");
+  Ok(())
+}
+
+/// Analyzes how a speech segment, mono at any rate, was said. The transcript gives the word rate.
+#[tauri::command]
+async fn analyze_prosody<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  samples: Vec<f32>,
+  sample_rate: Option<u32>,
+  text: Option<String>,
+) -> Result<Prosody, AudioError> {
+  let samples = audio::resample(
+    samples,
+    sample_rate.unwrap_or(SAMPLE_RATE as u32) as usize,
+    SAMPLE_RATE,
+  )
+  .map_err(|e| AudioError::InvalidInput(format!("Failed to resample segment: {}", e)))?;
+
+  analyze(&app, &samples, text.as_deref())
+}
+
+/// Analyzes a speech segment of 16 kHz mono audio, with the emotion model if one is loaded, for
+/// plugins that capture audio in-process.
+pub fn analyze<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  samples: &[f32],
+  text: Option<&str>,
+) -> Result<Prosody, AudioError> {
+  let data = app
+    .try_state::<AppDataProsody>()
+    .ok_or_else(|| AudioError::Internal("Prosody plugin is not initialized".to_string()))?;
+  let classifier = data.classifier.lock().unwrap().clone();
+  let emotion = classifier
+    .filter(|_| samples.len() >= MIN_CLASSIFIED_SAMPLES)
+    .map(|classifier| classifier.classify(samples))
+    .transpose()
+    .map_err(|e| AudioError::inference_failed(e.context("Failed to classify emotion")))?;
+
+  Ok(data.analyzer.analyze(samples, text, emotion))
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+  PluginBuilder::new("ipc-audio-prosody-ort")
+    .setup(|app, _| {
+      info!("Initializing prosody plugin...");
+      app.manage(AppDataProsody::default());
+      Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+      load_ort_model_emotion,
+      cancel_model_download,
+      unload_ort_model_emotion,
+      analyze_prosody
+    ])
+    .build()
+}
@@ -0,0 +1,179 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use airi_audio_models::{ModelRepo, SessionOptions, create_session};
+use anyhow::{Result, anyhow};
+use log::{debug, info};
+use ort::{session::Session, util::Mutex, value::Tensor};
+use serde::{Deserialize, Serialize};
+use tauri::Runtime;
+
+use crate::pitch::SAMPLE_RATE;
+
+/// Segments are classified from their first 10 seconds
+const MAX_SAMPLES: usize = 10 * SAMPLE_RATE;
+const PROGRESS_EVENT: &str =
+  "tauri-plugins:tauri-plugin-ipc-audio-prosody-ort:load-model-emotion-progress";
+
+/// The emotion a classifier hears in a segment.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Emotion {
+  /// As the model names it, e.g. `ang`, `angry` or `frustrated`
+  pub label:      String,
+  pub confidence: f32,
+}
+
+#[derive(Deserialize)]
+struct ClassifierConfig {
+  id2label: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct FeatureExtractorConfig {
+  #[serde(default = "default_normalize")]
+  do_normalize: bool,
+}
+
+fn default_normalize() -> bool {
+  true
+}
+
+/// A speech emotion classifier exported for Transformers.js: `config.json` with `id2label` and an
+/// ONNX model taking raw 16 kHz audio as `input_values` and returning `logits`, such as the
+/// wav2vec2 and HuBERT audio classification models.
+pub struct EmotionClassifier {
+  session:   Mutex<Session>,
+  labels:    Vec<String>,
+  normalize: bool,
+}
+
+impl EmotionClassifier {
+  pub fn new<R: Runtime>(
+    model_id: &str,
+    revision: &str,
+    model_file: &str,
+    window: tauri::WebviewWindow<R>,
+  ) -> Result<Self> {
+    let repo = ModelRepo::new(model_id, revision)?;
+    let get_or_download = |file: &str| repo.get(file, window.clone(), PROGRESS_EVENT);
+
+    let model_path = get_or_download(model_file)?;
+    let config: ClassifierConfig =
+      serde_json::from_slice(&fs::read(get_or_download("config.json")?)?)?;
+    let normalize = match get_or_download("preprocessor_config.json") {
+      Ok(path) => serde_json::from_slice::<FeatureExtractorConfig>(&fs::read(path)?)?.do_normalize,
+      Err(e) => {
+        debug!(
+          "No preprocessor config for {}, normalizing: {}",
+          model_id, e
+        );
+        true
+      },
+    };
+
+    let mut labels: Vec<(usize, String)> = config
+      .id2label
+      .into_iter()
+      .map(|(id, label)| Ok((id.parse()?, label)))
+      .collect::<Result<_>>()?;
+    labels.sort();
+    if labels
+      .iter()
+      .enumerate()
+      .any(|(index, (id, _))| index != *id)
+    {
+      return Err(anyhow!("Labels of {} are not numbered from 0", model_id));
+    }
+
+    Ok(Self {
+      session: Mutex::new(Self::create_optimized_session(&model_path)?),
+      labels: labels
+        .into_iter()
+        .map(|(_, label)| label)
+        .collect(),
+      normalize,
+    })
+  }
+
+  /// Create an optimized ONNX session with hardware acceleration
+  fn create_optimized_session(model_path: &Path) -> Result<Session> {
+    let session = create_session(
+      model_path,
+      SessionOptions {
+        intra_threads: Some(2),
+        ..Default::default()
+      },
+    )?;
+    info!("Emotion model loaded successfully");
+
+    Ok(session)
+  }
+
+  /// The most likely emotion of 16 kHz mono speech.
+  pub fn classify(
+    &self,
+    audio: &[f32],
+  ) -> Result<Emotion> {
+    let mut input = audio[..audio.len().min(MAX_SAMPLES)].to_vec();
+    if input.is_empty() {
+      return Err(anyhow!("No audio to classify"));
+    }
+    if self.normalize {
+      let n = input.len() as f32;
+      let mean = input.iter().sum::<f32>() / n;
+      let std = (input
+        .iter()
+        .map(|x| (x - mean).powi(2))
+        .sum::<f32>()
+        / n)
+        .sqrt();
+      input
+        .iter_mut()
+        .for_each(|x| *x = (*x - mean) / (std + 1e-7));
+    }
+
+    let n_samples = input.len();
+    let inputs = vec![(
+      "input_values",
+      Tensor::from_array((vec![1, n_samples], input))?.into_dyn(),
+    )];
+
+    let mut session = self.session.lock();
+    let outputs = session.run(inputs)?;
+    let logits = outputs.get("logits").ok_or_else(|| {
+      anyhow!(
+        "No logits output found in model. Available outputs: {:?}",
+        outputs.keys().collect::<Vec<_>>()
+      )
+    })?;
+    let (_shape, logits) = logits.try_extract_tensor::<f32>()?;
+    if logits.len() != self.labels.len() {
+      return Err(anyhow!(
+        "Model returned {} scores for {} labels",
+        logits.len(),
+        self.labels.len()
+      ));
+    }
+
+    // Softmax of the best one only
+    let max = logits
+      .iter()
+      .copied()
+      .fold(f32::NEG_INFINITY, f32::max);
+    let sum: f32 = logits
+      .iter()
+      .map(|logit| (logit - max).exp())
+      .sum();
+    let best = logits
+      .iter()
+      .enumerate()
+      .max_by(|(_, a), (_, b)| a.total_cmp(b))
+      .map(|(index, _)| index)
+      .unwrap_or_default();
+
+    Ok(Emotion {
+      label:      self.labels[best].clone(),
+      confidence: 1.0 / sum,
+    })
+  }
+}
@@ -0,0 +1,90 @@
+/// Frames are 40 ms every 10 ms at 16 kHz, long enough for two periods of the lowest voices
+pub const SAMPLE_RATE: usize = 16000;
+pub const FRAME_SHIFT: usize = 160;
+pub const FRAME_LENGTH: usize = 640;
+const MIN_F0: f32 = 60.0;
+const MAX_F0: f32 = 500.0;
+/// Dips of the cumulative mean normalized difference below this are periods, as in the YIN paper
+const YIN_THRESHOLD: f32 = 0.15;
+
+/// Estimates the fundamental frequency of 10 ms frames with YIN.
+pub struct PitchTracker {
+  min_lag: usize,
+  max_lag: usize,
+}
+
+impl Default for PitchTracker {
+  fn default() -> Self {
+    Self {
+      min_lag: (SAMPLE_RATE as f32 / MAX_F0).floor() as usize,
+      max_lag: (SAMPLE_RATE as f32 / MIN_F0).ceil() as usize,
+    }
+  }
+}
+
+impl PitchTracker {
+  /// The pitch in Hz of every frame of 16 kHz mono audio, `None` for unvoiced frames. Frame `i`
+  /// starts at sample `i * FRAME_SHIFT`.
+  pub fn track(
+    &self,
+    audio: &[f32],
+  ) -> Vec<Option<f32>> {
+    if audio.len() < FRAME_LENGTH {
+      return Vec::new();
+    }
+
+    let n_frames = 1 + (audio.len() - FRAME_LENGTH) / FRAME_SHIFT;
+    let window = FRAME_LENGTH - self.max_lag;
+    let mut difference = vec![0.0; self.max_lag + 1];
+
+    (0..n_frames)
+      .map(|frame| {
+        let samples = &audio[frame * FRAME_SHIFT..frame * FRAME_SHIFT + FRAME_LENGTH];
+
+        for (lag, difference) in difference.iter_mut().enumerate().skip(1) {
+          *difference = samples[..window]
+            .iter()
+            .zip(&samples[lag..lag + window])
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum();
+        }
+
+        // Cumulative mean normalized difference, in place
+        let mut sum = 0.0;
+        difference[0] = 1.0;
+        for (lag, difference) in difference.iter_mut().enumerate().skip(1) {
+          sum += *difference;
+          *difference = if sum > 0.0 {
+            *difference * lag as f32 / sum
+          } else {
+            1.0
+          };
+        }
+
+        let lag = self.period(&difference)?;
+        Some(SAMPLE_RATE as f32 / lag)
+      })
+      .collect()
+  }
+
+  /// The first dip below the threshold, refined between samples.
+  fn period(
+    &self,
+    difference: &[f32],
+  ) -> Option<f32> {
+    let mut lag = (self.min_lag..self.max_lag).find(|&lag| difference[lag] < YIN_THRESHOLD)?;
+    while lag + 1 < self.max_lag && difference[lag + 1] < difference[lag] {
+      lag += 1;
+    }
+
+    // Parabola through the dip and its neighbours
+    let (before, at, after) = (difference[lag - 1], difference[lag], difference[lag + 1]);
+    let curvature = before - 2.0 * at + after;
+    let shift = if curvature > f32::EPSILON {
+      (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+    } else {
+      0.0
+    };
+    Some(lag as f32 + shift)
+  }
+}
@@ -0,0 +1,162 @@
+use std::f32::consts::PI;
+
+use tauri_plugin_ipc_audio_prosody_ort::{Analyzer, Emotion, SAMPLE_RATE};
+
+/// A voice at `hz(t)` with a few harmonics, its loudness following `envelope(t)`.
+fn voice(
+  seconds: f32,
+  hz: impl Fn(f32) -> f32,
+  envelope: impl Fn(f32) -> f32,
+) -> Vec<f32> {
+  let mut phase = 0.0;
+  (0..(seconds * SAMPLE_RATE as f32) as usize)
+    .map(|i| {
+      let t = i as f32 / SAMPLE_RATE as f32;
+      phase += 2.0 * PI * hz(t) / SAMPLE_RATE as f32;
+      let sample: f32 = [(1.0, 0.5), (2.0, 0.3), (3.0, 0.2)]
+        .iter()
+        .map(|(harmonic, amplitude)| amplitude * (harmonic * phase).sin())
+        .sum();
+      0.5 * envelope(t) * sample
+    })
+    .collect()
+}
+
+/// Syllables `rate` times a second, the loudness dips to nothing between them.
+fn syllables(rate: f32) -> impl Fn(f32) -> f32 {
+  move |t| (PI * rate * t).sin().powi(2)
+}
+
+fn padded(audio: Vec<f32>) -> Vec<f32> {
+  let silence = vec![0.0; SAMPLE_RATE / 2];
+  [silence.clone(), audio, silence].concat()
+}
+
+#[test]
+fn measures_nothing_in_silence() {
+  let prosody = Analyzer::default().analyze(&vec![0.0; SAMPLE_RATE], Some("hello"), None);
+  assert_eq!(prosody.duration_ms, 0);
+  assert!(prosody.pitch.is_none());
+  assert!(prosody.cues.is_empty());
+}
+
+#[test]
+fn measures_the_speech_without_the_silence_around_it() {
+  let audio = padded(voice(2.0, |_| 150.0, syllables(4.0)));
+  let prosody = Analyzer::default().analyze(&audio, None, None);
+
+  assert!(
+    (1900..=2050).contains(&prosody.duration_ms),
+    "{} ms",
+    prosody.duration_ms
+  );
+  let pitch = prosody.pitch.unwrap();
+  assert!((pitch.median_hz - 150.0).abs() < 1.0, "{pitch:?}");
+  assert!((pitch.mean_hz - 150.0).abs() < 1.0, "{pitch:?}");
+  assert!(pitch.range_semitones < 0.1, "{pitch:?}");
+  assert!(pitch.voiced_ratio > 0.5, "{pitch:?}");
+  // Peaks at half of full scale and a half amplitude voice
+  assert!(
+    (-16.0..-8.0).contains(&prosody.energy.max_db),
+    "{:?}",
+    prosody.energy
+  );
+  assert!(prosody.energy.mean_db < prosody.energy.max_db);
+}
+
+#[test]
+fn counts_syllables() {
+  let analyzer = Analyzer::default();
+  for rate in [3.0, 4.0, 5.0] {
+    let prosody = analyzer.analyze(&padded(voice(2.0, |_| 150.0, syllables(rate))), None, None);
+    let counted = prosody.speaking_rate.syllables_per_second;
+    assert!((counted - rate).abs() < 0.6, "{counted} for {rate}");
+  }
+}
+
+#[test]
+fn describes_how_something_was_said() {
+  let analyzer = Analyzer::default();
+
+  let monotone = analyzer.analyze(&padded(voice(2.0, |_| 150.0, syllables(4.0))), None, None);
+  assert_eq!(monotone.cues, ["sounds flat"]);
+
+  let fast = analyzer.analyze(&padded(voice(2.0, |_| 150.0, syllables(8.0))), None, None);
+  assert_eq!(fast.cues, ["speaks quickly", "sounds flat"]);
+
+  let slow = analyzer.analyze(&padded(voice(2.0, |_| 150.0, syllables(1.5))), None, None);
+  assert_eq!(slow.cues, ["speaks slowly", "sounds flat"]);
+
+  // Swinging an octave up and down
+  let animated = analyzer.analyze(
+    &padded(voice(
+      2.0,
+      |t| 150.0 * 2f32.powf((2.0 * PI * t).sin()),
+      syllables(4.0),
+    )),
+    None,
+    None,
+  );
+  assert_eq!(animated.cues, ["sounds animated"]);
+}
+
+#[test]
+fn hears_a_question_in_a_rising_voice() {
+  // Level, then rising by 8 semitones over the last 400 ms
+  let hz = |t: f32| 150.0 * 2f32.powf((t - 1.6).max(0.0) * 20.0 / 12.0);
+  let prosody = Analyzer::default().analyze(&padded(voice(2.0, hz, syllables(4.0))), None, None);
+
+  let pitch = prosody.pitch.unwrap();
+  assert!(pitch.final_slope_st_per_s > 10.0, "{pitch:?}");
+  assert!(
+    prosody
+      .cues
+      .contains(&"voice rises at the end".to_string()),
+    "{:?}",
+    prosody.cues
+  );
+}
+
+#[test]
+fn counts_words_of_the_transcript() {
+  let analyzer = Analyzer::default();
+  let audio = padded(voice(2.0, |_| 150.0, syllables(4.0)));
+  let rate = |text| {
+    analyzer
+      .analyze(&audio, Some(text), None)
+      .speaking_rate
+      .words_per_second
+  };
+
+  let words = rate("the quick brown fox").unwrap();
+  assert!((words - 2.0).abs() < 0.1, "{words}");
+  // Every CJK character is a word, punctuation is not
+  let words = rate("今天 天气 — good!").unwrap();
+  assert!((words - 2.5).abs() < 0.1, "{words}");
+  assert_eq!(rate(" ... "), None);
+}
+
+#[test]
+fn describes_confident_emotions() {
+  let analyzer = Analyzer::default();
+  // Too short for the other cues
+  let audio = padded(voice(0.5, |_| 150.0, syllables(4.0)));
+  let emotion = |label: &str, confidence| {
+    analyzer
+      .analyze(
+        &audio,
+        None,
+        Some(Emotion {
+          label: label.to_string(),
+          confidence,
+        }),
+      )
+      .cues
+  };
+
+  assert_eq!(emotion("ang", 0.9), ["sounds angry"]);
+  assert_eq!(emotion("Happy", 0.6), ["sounds happy"]);
+  assert_eq!(emotion("bored", 0.7), ["sounds bored"]);
+  assert!(emotion("neutral", 0.9).is_empty());
+  assert!(emotion("ang", 0.3).is_empty());
+}
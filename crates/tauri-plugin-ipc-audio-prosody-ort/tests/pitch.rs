@@ -0,0 +1,100 @@
+use std::f32::consts::PI;
+
+use tauri_plugin_ipc_audio_prosody_ort::{FRAME_SHIFT, PitchTracker, SAMPLE_RATE};
+
+fn sine(
+  hz: f32,
+  seconds: f32,
+) -> Vec<f32> {
+  (0..(seconds * SAMPLE_RATE as f32) as usize)
+    .map(|i| 0.5 * (2.0 * PI * hz * i as f32 / SAMPLE_RATE as f32).sin())
+    .collect()
+}
+
+#[test]
+fn finds_the_frequency_of_a_sine() {
+  let tracker = PitchTracker::default();
+  for hz in [80.0, 150.0, 220.0, 310.5, 450.0] {
+    let pitch = tracker.track(&sine(hz, 0.5));
+    assert!(!pitch.is_empty());
+    for frame in pitch {
+      let frame = frame.unwrap_or_else(|| panic!("{hz} Hz is unvoiced"));
+      assert!((frame - hz).abs() < 0.5, "{frame} Hz for {hz} Hz");
+    }
+  }
+}
+
+#[test]
+fn finds_the_fundamental_of_a_voice_with_harmonics() {
+  // Harmonics louder than the fundamental, as in most voices
+  let audio: Vec<f32> = (0..SAMPLE_RATE / 2)
+    .map(|i| {
+      let t = i as f32 / SAMPLE_RATE as f32;
+      [(1.0, 0.3), (2.0, 0.5), (3.0, 0.4), (4.0, 0.2)]
+        .iter()
+        .map(|(harmonic, amplitude)| amplitude * (2.0 * PI * 120.0 * harmonic * t).sin())
+        .sum()
+    })
+    .collect();
+
+  for frame in PitchTracker::default().track(&audio) {
+    let frame = frame.unwrap();
+    assert!((frame - 120.0).abs() < 0.5, "{frame} Hz");
+  }
+}
+
+#[test]
+fn has_no_pitch_in_silence_or_noise() {
+  let tracker = PitchTracker::default();
+  assert!(
+    tracker
+      .track(&vec![0.0; SAMPLE_RATE / 2])
+      .iter()
+      .all(Option::is_none)
+  );
+
+  let mut state = 1u32;
+  let noise: Vec<f32> = (0..SAMPLE_RATE / 2)
+    .map(|_| {
+      state ^= state << 13;
+      state ^= state >> 17;
+      state ^= state << 5;
+      state as f32 / u32::MAX as f32 - 0.5
+    })
+    .collect();
+  let pitch = tracker.track(&noise);
+  let voiced = pitch.iter().flatten().count();
+  assert!(
+    voiced * 10 < pitch.len(),
+    "{voiced} of {} frames voiced",
+    pitch.len()
+  );
+}
+
+#[test]
+fn tracks_frames_every_10_ms() {
+  let tracker = PitchTracker::default();
+  assert!(tracker.track(&sine(200.0, 0.03)).is_empty());
+
+  // One frame per shift after the first 40 ms
+  assert_eq!(tracker.track(&sine(200.0, 0.04)).len(), 1);
+  assert_eq!(
+    tracker.track(&sine(200.0, 1.0)).len(),
+    1 + (SAMPLE_RATE - 640) / FRAME_SHIFT
+  );
+}
+
+#[test]
+fn follows_the_pitch_as_it_changes() {
+  // 100 Hz for half a second, then 200 Hz
+  let mut audio = sine(100.0, 0.5);
+  let phase = audio.len();
+  audio.extend(
+    (0..SAMPLE_RATE / 2)
+      .map(|i| 0.5 * (2.0 * PI * 200.0 * (phase + i) as f32 / SAMPLE_RATE as f32).sin()),
+  );
+
+  let pitch = PitchTracker::default().track(&audio);
+  assert!((pitch[10].unwrap() - 100.0).abs() < 0.5);
+  assert!((pitch[pitch.len() - 10].unwrap() - 200.0).abs() < 0.5);
+}
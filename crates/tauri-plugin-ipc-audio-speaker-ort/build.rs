@@ -0,0 +1,15 @@
+const COMMANDS: &[&str] = &[
+  "load_ort_model_speaker",
+  "cancel_model_download",
+  "enroll_speaker",
+  "list_speakers",
+  "remove_speaker",
+  "create_diarization_session",
+  "identify_speaker",
+  "name_speaker",
+  "destroy_diarization_session",
+];
+
+fn main() {
+  tauri_plugin::Builder::new(COMMANDS).build();
+}
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  models::wespeaker::{normalize, similarity},
+  speakers::Speaker,
+};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DiarizationOptions {
+  /// Cosine similarity from which a segment is the voice of a known or earlier speaker
+  pub threshold:    f32,
+  /// Unknown voices told apart at most, segments go to the closest one after that
+  pub max_speakers: Option<usize>,
+}
+
+impl Default for DiarizationOptions {
+  fn default() -> Self {
+    Self {
+      threshold:    0.5,
+      max_speakers: None,
+    }
+  }
+}
+
+/// Who said a segment.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeakerMatch {
+  /// Id of an enrolled speaker, or `unknown-<n>` for the n-th unknown voice of the session
+  pub speaker_id: String,
+  /// Only enrolled speakers have one
+  pub name:       Option<String>,
+  /// Cosine similarity to the voice the segment was matched to, 1 for a new voice
+  pub similarity: f32,
+}
+
+/// An unknown voice, the sum of its embeddings.
+struct Cluster {
+  id:    String,
+  sum:   Vec<f32>,
+  count: usize,
+}
+
+impl Cluster {
+  fn centroid(&self) -> Vec<f32> {
+    normalize(self.sum.clone())
+  }
+}
+
+/// Tells the speakers of segments apart as they come: enrolled speakers are recognized by their
+/// voice, the others are clustered into unknown voices that keep their id for the session.
+pub struct Diarizer {
+  options:      DiarizationOptions,
+  clusters:     Vec<Cluster>,
+  next_unknown: usize,
+}
+
+impl Diarizer {
+  pub fn new(options: DiarizationOptions) -> Self {
+    Self {
+      options,
+      clusters: Vec::new(),
+      next_unknown: 1,
+    }
+  }
+
+  /// Matches the embedding of a segment to an enrolled speaker, or to a voice heard earlier in
+  /// the session, or to a new one.
+  pub fn assign(
+    &mut self,
+    speakers: &[Speaker],
+    embedding: &[f32],
+  ) -> SpeakerMatch {
+    let enrolled = speakers
+      .iter()
+      .map(|speaker| (speaker, similarity(&speaker.embedding, embedding)))
+      .max_by(|(_, a), (_, b)| a.total_cmp(b));
+    if let Some((speaker, similarity)) = enrolled
+      && similarity >= self.options.threshold
+    {
+      return SpeakerMatch {
+        speaker_id: speaker.id.clone(),
+        name: Some(speaker.name.clone()),
+        similarity,
+      };
+    }
+
+    let full = self
+      .options
+      .max_speakers
+      .is_some_and(|max| self.clusters.len() >= max.max(1));
+    let closest = self
+      .clusters
+      .iter_mut()
+      .map(|cluster| {
+        let similarity = similarity(&cluster.centroid(), embedding);
+        (cluster, similarity)
+      })
+      .max_by(|(_, a), (_, b)| a.total_cmp(b));
+    if let Some((cluster, similarity)) = closest
+      && (similarity >= self.options.threshold || full)
+    {
+      for (sum, value) in cluster.sum.iter_mut().zip(embedding) {
+        *sum += value;
+      }
+      cluster.count += 1;
+      return SpeakerMatch {
+        speaker_id: cluster.id.clone(),
+        name: None,
+        similarity,
+      };
+    }
+
+    let id = format!("unknown-{}", self.next_unknown);
+    self.next_unknown += 1;
+    self.clusters.push(Cluster {
+      id:    id.clone(),
+      sum:   embedding.to_vec(),
+      count: 1,
+    });
+    SpeakerMatch {
+      speaker_id: id,
+      name:       None,
+      similarity: 1.0,
+    }
+  }
+
+  /// Removes an unknown voice, returning its mean embedding and how many segments it is made of,
+  /// to enroll it under a name.
+  pub fn take_unknown(
+    &mut self,
+    speaker_id: &str,
+  ) -> Option<(Vec<f32>, usize)> {
+    let index = self
+      .clusters
+      .iter()
+      .position(|cluster| cluster.id == speaker_id)?;
+    let cluster = self.clusters.remove(index);
+    Some((cluster.centroid(), cluster.count))
+  }
+
+  /// Forgets the unknown voices heard so far.
+  pub fn reset(&mut self) {
+    self.clusters.clear();
+    self.next_unknown = 1;
+  }
+}
@@ -0,0 +1,148 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use realfft::{RealFftPlanner, RealToComplex};
+
+/// Kaldi fbank as WeSpeaker computes it: 25 ms frames every 10 ms at 16 kHz
+pub const SAMPLE_RATE: usize = 16000;
+pub const MEL_BINS: usize = 80;
+const FRAME_LENGTH: usize = 400;
+const FRAME_SHIFT: usize = 160;
+const FFT_SIZE: usize = 512;
+const PREEMPHASIS: f32 = 0.97;
+const LOW_FREQ: f32 = 20.0;
+/// WeSpeaker feeds Kaldi 16-bit sample values, not floats in [-1, 1]
+const SAMPLE_SCALE: f32 = 32768.0;
+
+/// Log mel filterbank energies, the input of the speaker embedding models.
+pub struct Fbank {
+  fft:     Arc<dyn RealToComplex<f32>>,
+  /// Hamming window, WeSpeaker's choice instead of Kaldi's default Povey window
+  window:  Vec<f32>,
+  /// `[MEL_BINS]` triangular filters over the FFT bins, each with its first bin
+  filters: Vec<(usize, Vec<f32>)>,
+}
+
+impl Default for Fbank {
+  fn default() -> Self {
+    Self {
+      fft:     RealFftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE),
+      window:  (0..FRAME_LENGTH)
+        .map(|i| 0.54 - 0.46 * (2.0 * PI * i as f32 / (FRAME_LENGTH - 1) as f32).cos())
+        .collect(),
+      filters: mel_filters(),
+    }
+  }
+}
+
+impl Fbank {
+  /// Features of 16 kHz mono audio, one frame of [`MEL_BINS`] per 10 ms, with the mean of
+  /// every bin over the whole audio subtracted. Empty for audio shorter than one frame.
+  pub fn compute(
+    &self,
+    audio: &[f32],
+  ) -> Vec<[f32; MEL_BINS]> {
+    if audio.len() < FRAME_LENGTH {
+      return Vec::new();
+    }
+
+    let n_frames = 1 + (audio.len() - FRAME_LENGTH) / FRAME_SHIFT;
+    let mut input = self.fft.make_input_vec();
+    let mut output = self.fft.make_output_vec();
+    let mut power = vec![0.0; output.len()];
+    let mut frames = Vec::with_capacity(n_frames);
+
+    for frame in 0..n_frames {
+      let samples = &audio[frame * FRAME_SHIFT..frame * FRAME_SHIFT + FRAME_LENGTH];
+      let mean = samples.iter().sum::<f32>() / FRAME_LENGTH as f32;
+
+      input.fill(0.0);
+      let mut previous = (samples[0] - mean) * SAMPLE_SCALE;
+      for (i, (input, &sample)) in input.iter_mut().zip(samples).enumerate() {
+        let sample = (sample - mean) * SAMPLE_SCALE;
+        // Kaldi pre-emphasizes the first sample against itself
+        let emphasized = sample - PREEMPHASIS * previous;
+        previous = sample;
+        *input = emphasized * self.window[i];
+      }
+
+      self
+        .fft
+        .process(&mut input, &mut output)
+        .expect("FFT buffers are made by the plan");
+      for (power, bin) in power.iter_mut().zip(&output) {
+        *power = bin.norm_sqr();
+      }
+
+      let mut energies = [0.0; MEL_BINS];
+      for (energy, (first, weights)) in energies.iter_mut().zip(&self.filters) {
+        let sum: f32 = weights
+          .iter()
+          .zip(&power[*first..])
+          .map(|(weight, power)| weight * power)
+          .sum();
+        *energy = sum.max(f32::EPSILON).ln();
+      }
+      frames.push(energies);
+    }
+
+    // Cepstral mean normalization, the embedding should not depend on the channel
+    let mut means = [0.0; MEL_BINS];
+    for frame in &frames {
+      for (mean, energy) in means.iter_mut().zip(frame) {
+        *mean += energy / n_frames as f32;
+      }
+    }
+    for frame in &mut frames {
+      for (energy, mean) in frame.iter_mut().zip(&means) {
+        *energy -= mean;
+      }
+    }
+
+    frames
+  }
+}
+
+fn mel(frequency: f32) -> f32 {
+  1127.0 * (1.0 + frequency / 700.0).ln()
+}
+
+/// Kaldi's filterbank: triangles evenly spaced on the mel scale between 20 Hz and Nyquist,
+/// without area normalization.
+fn mel_filters() -> Vec<(usize, Vec<f32>)> {
+  let bins = FFT_SIZE / 2;
+  let bin_width = SAMPLE_RATE as f32 / FFT_SIZE as f32;
+  let low = mel(LOW_FREQ);
+  let high = mel(SAMPLE_RATE as f32 / 2.0);
+  let spacing = (high - low) / (MEL_BINS + 1) as f32;
+
+  (0..MEL_BINS)
+    .map(|m| {
+      let left = low + m as f32 * spacing;
+      let center = left + spacing;
+      let right = center + spacing;
+
+      let weights: Vec<(usize, f32)> = (0..bins)
+        .filter_map(|bin| {
+          let mel = mel(bin as f32 * bin_width);
+          let weight = if mel > left && mel <= center {
+            (mel - left) / (center - left)
+          } else if mel > center && mel < right {
+            (right - mel) / (right - center)
+          } else {
+            return None;
+          };
+          Some((bin, weight))
+        })
+        .collect();
+
+      let first = weights.first().map_or(0, |&(bin, _)| bin);
+      (
+        first,
+        weights
+          .into_iter()
+          .map(|(_, weight)| weight)
+          .collect(),
+      )
+    })
+    .collect()
+}
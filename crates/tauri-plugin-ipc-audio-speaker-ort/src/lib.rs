@@ -0,0 +1,385 @@
+use std::{
+  collections::HashMap,
+  fs,
+  path::{Path, PathBuf},
+  sync::{
+    Arc,
+    Mutex,
+    atomic::{AtomicU32, Ordering},
+  },
+};
+
+use airi_audio_models::{AudioError, audio};
+use anyhow::Result;
+use log::{info, warn};
+use tauri::{
+  Manager,
+  Runtime,
+  plugin::{Builder as PluginBuilder, TauriPlugin},
+};
+
+mod diarization;
+mod fbank;
+mod models;
+mod speakers;
+
+pub use diarization::{DiarizationOptions, Diarizer, SpeakerMatch};
+pub use fbank::{Fbank, MEL_BINS};
+pub use models::wespeaker::Encoder;
+pub use speakers::{Speaker, SpeakerInfo};
+
+use crate::fbank::SAMPLE_RATE;
+
+/// Segments shorter than this do not tell voices apart reliably, they are not matched
+const MIN_SEGMENT_MS: usize = 500;
+
+#[derive(Default)]
+struct AppDataSpeakerEncoder {
+  encoder: Option<Arc<Encoder>>,
+}
+
+/// Enrolled speakers, kept in the app data directory across restarts.
+struct AppDataSpeakers {
+  path:     Option<PathBuf>,
+  speakers: Mutex<Vec<Speaker>>,
+}
+
+impl AppDataSpeakers {
+  fn load(path: Option<PathBuf>) -> Self {
+    let speakers = path
+      .as_ref()
+      .filter(|path| path.exists())
+      .and_then(|path| match read_speakers(path) {
+        Ok(speakers) => Some(speakers),
+        Err(e) => {
+          warn!("Failed to load speakers from {}: {}", path.display(), e);
+          None
+        },
+      })
+      .unwrap_or_default();
+
+    Self {
+      path,
+      speakers: Mutex::new(speakers),
+    }
+  }
+
+  fn save(
+    &self,
+    speakers: &[Speaker],
+  ) -> Result<(), AudioError> {
+    let Some(path) = &self.path else {
+      return Ok(());
+    };
+
+    let write = || -> Result<()> {
+      if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+      }
+      fs::write(path, serde_json::to_vec(speakers)?)?;
+      Ok(())
+    };
+    write().map_err(|e| AudioError::Internal(format!("Failed to save speakers: {}", e)))
+  }
+}
+
+fn read_speakers(path: &Path) -> Result<Vec<Speaker>> {
+  Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+#[derive(Default)]
+struct AppDataDiarizationSessions {
+  next_id:  AtomicU32,
+  sessions: Mutex<HashMap<u32, Arc<Mutex<Diarizer>>>>,
+}
+
+impl AppDataDiarizationSessions {
+  fn get(
+    &self,
+    session_id: u32,
+  ) -> Result<Arc<Mutex<Diarizer>>, AudioError> {
+    self
+      .sessions
+      .lock()
+      .unwrap()
+      .get(&session_id)
+      .cloned()
+      .ok_or_else(|| {
+        AudioError::NotFound(format!("Diarization session {} does not exist", session_id))
+      })
+  }
+}
+
+#[tauri::command]
+async fn load_ort_model_speaker<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  window: tauri::WebviewWindow<R>,
+) -> Result<(), AudioError> {
+  info!("Loading models...");
+
+  {
+    let data = app.state::<Mutex<AppDataSpeakerEncoder>>();
+    let data = data.lock().unwrap();
+    if data.encoder.is_some() {
+      info!("Speaker embedding model already loaded, skipping...");
+      return Ok(());
+    }
+  }
+
+  let encoder = Encoder::new(window)
+    .map_err(|e| AudioError::load_failed(e.context("Failed to load speaker embedding model")))?;
+  {
+    let data = app.state::<Mutex<AppDataSpeakerEncoder>>();
+    let mut data = data.lock().unwrap();
+    data.encoder = Some(Arc::new(encoder));
+  }
+
+  info!("Speaker embedding model loaded successfully");
+  Ok(())
+}
+
+/// Stops the download of the speaker embedding model, `load_ort_model_speaker` then fails.
+/// Returns whether it was downloading.
+#[tauri::command]
+async fn cancel_model_download(model_id: String) -> Result<bool, AudioError> {
+  Ok(airi_audio_models::cancel_download(&model_id))
+}
+
+/// Enrolls the voice in a few recordings of one person, mono at any rate, under `name`.
+/// Enrolling a name again adds to its voice. A few seconds of speech per recording work best.
+#[tauri::command]
+async fn enroll_speaker<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  name: String,
+  recordings: Vec<Vec<f32>>,
+  sample_rate: Option<u32>,
+) -> Result<SpeakerInfo, AudioError> {
+  if name.trim().is_empty() {
+    return Err(AudioError::InvalidInput(
+      "Speaker name is empty".to_string(),
+    ));
+  }
+  if recordings.is_empty() {
+    return Err(AudioError::InvalidInput(format!(
+      "No recordings of speaker {}",
+      name
+    )));
+  }
+
+  let encoder = encoder(&app)?;
+  let sample_rate = sample_rate.unwrap_or(SAMPLE_RATE as u32);
+  let mut sum = Vec::new();
+  for recording in &recordings {
+    let recording = audio::resample(recording.clone(), sample_rate as usize, SAMPLE_RATE)
+      .map_err(|e| AudioError::InvalidInput(format!("Failed to resample recording: {}", e)))?;
+    let embedding = encoder
+      .embed(&recording)
+      .map_err(|e| AudioError::inference_failed(e.context("Failed to embed recording")))?;
+    sum.resize(embedding.len(), 0.0);
+    for (sum, value) in sum.iter_mut().zip(&embedding) {
+      *sum += value;
+    }
+  }
+
+  let data = app.state::<AppDataSpeakers>();
+  let mut speakers = data.speakers.lock().unwrap();
+  let info = speakers::enroll(&mut speakers, &name, &sum, recordings.len());
+  data.save(&speakers)?;
+
+  info!("Speaker {} enrolled as {}", info.name, info.id);
+  Ok(info)
+}
+
+#[tauri::command]
+async fn list_speakers<R: Runtime>(
+  app: tauri::AppHandle<R>
+) -> Result<Vec<SpeakerInfo>, AudioError> {
+  Ok(
+    app
+      .state::<AppDataSpeakers>()
+      .speakers
+      .lock()
+      .unwrap()
+      .iter()
+      .map(Speaker::info)
+      .collect(),
+  )
+}
+
+#[tauri::command]
+async fn remove_speaker<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  speaker_id: String,
+) -> Result<(), AudioError> {
+  let data = app.state::<AppDataSpeakers>();
+  let mut speakers = data.speakers.lock().unwrap();
+  let count = speakers.len();
+  speakers.retain(|speaker| speaker.id != speaker_id);
+  if speakers.len() == count {
+    return Err(AudioError::NotFound(format!(
+      "Speaker {} is not enrolled",
+      speaker_id
+    )));
+  }
+  data.save(&speakers)?;
+
+  info!("Speaker {} removed", speaker_id);
+  Ok(())
+}
+
+/// Starts a session that tells the speakers of its segments apart. Returns the session id.
+#[tauri::command]
+async fn create_diarization_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  options: Option<DiarizationOptions>,
+) -> Result<u32, AudioError> {
+  let data = app.state::<AppDataDiarizationSessions>();
+  let session_id = data.next_id.fetch_add(1, Ordering::Relaxed);
+  data.sessions.lock().unwrap().insert(
+    session_id,
+    Arc::new(Mutex::new(Diarizer::new(options.unwrap_or_default()))),
+  );
+
+  info!("Diarization session {} created", session_id);
+  Ok(session_id)
+}
+
+/// Returns who said a speech segment, mono at any rate, or nothing when it is too short to tell.
+#[tauri::command]
+async fn identify_speaker<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+  samples: Vec<f32>,
+  sample_rate: Option<u32>,
+) -> Result<Option<SpeakerMatch>, AudioError> {
+  let samples = audio::resample(
+    samples,
+    sample_rate.unwrap_or(SAMPLE_RATE as u32) as usize,
+    SAMPLE_RATE,
+  )
+  .map_err(|e| AudioError::InvalidInput(format!("Failed to resample segment: {}", e)))?;
+  let session = app
+    .state::<AppDataDiarizationSessions>()
+    .get(session_id)?;
+  let mut session = session.lock().unwrap();
+
+  identify(&app, &mut session, &samples)
+}
+
+/// Enrolls an unknown voice of a session under `name`, its later segments are matched to the
+/// enrolled speaker.
+#[tauri::command]
+async fn name_speaker<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+  speaker_id: String,
+  name: String,
+) -> Result<SpeakerInfo, AudioError> {
+  if name.trim().is_empty() {
+    return Err(AudioError::InvalidInput(
+      "Speaker name is empty".to_string(),
+    ));
+  }
+
+  let session = app
+    .state::<AppDataDiarizationSessions>()
+    .get(session_id)?;
+  let (embedding, samples) = session
+    .lock()
+    .unwrap()
+    .take_unknown(&speaker_id)
+    .ok_or_else(|| {
+      AudioError::NotFound(format!(
+        "Session {} has no unknown speaker {}",
+        session_id, speaker_id
+      ))
+    })?;
+
+  let data = app.state::<AppDataSpeakers>();
+  let mut speakers = data.speakers.lock().unwrap();
+  let info = speakers::enroll(&mut speakers, &name, &embedding, samples);
+  data.save(&speakers)?;
+
+  info!("Speaker {} named {} as {}", speaker_id, info.name, info.id);
+  Ok(info)
+}
+
+#[tauri::command]
+async fn destroy_diarization_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+) -> Result<(), AudioError> {
+  app
+    .state::<AppDataDiarizationSessions>()
+    .sessions
+    .lock()
+    .unwrap()
+    .remove(&session_id)
+    .ok_or_else(|| {
+      AudioError::NotFound(format!("Diarization session {} does not exist", session_id))
+    })?;
+
+  info!("Diarization session {} destroyed", session_id);
+  Ok(())
+}
+
+fn encoder<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<Arc<Encoder>, AudioError> {
+  app
+    .try_state::<Mutex<AppDataSpeakerEncoder>>()
+    .ok_or_else(|| AudioError::Internal("Speaker plugin is not initialized".to_string()))?
+    .lock()
+    .unwrap()
+    .encoder
+    .clone()
+    .ok_or_else(|| AudioError::ModelNotLoaded("Speaker embedding model is not loaded".to_string()))
+}
+
+/// Finds who said a speech segment of 16 kHz mono audio with the loaded model and the enrolled
+/// speakers, for plugins that capture audio in-process. Nothing for segments too short to tell.
+pub fn identify<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  diarizer: &mut Diarizer,
+  samples: &[f32],
+) -> Result<Option<SpeakerMatch>, AudioError> {
+  if samples.len() < SAMPLE_RATE * MIN_SEGMENT_MS / 1000 {
+    return Ok(None);
+  }
+
+  let embedding = encoder(app)?
+    .embed(samples)
+    .map_err(|e| AudioError::inference_failed(e.context("Failed to embed segment")))?;
+  let speakers = app.state::<AppDataSpeakers>();
+  let speakers = speakers.speakers.lock().unwrap();
+
+  Ok(Some(diarizer.assign(&speakers, &embedding)))
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+  PluginBuilder::new("ipc-audio-speaker-ort")
+    .setup(|app, _| {
+      info!("Initializing speaker plugin...");
+      let path = match app.path().app_data_dir() {
+        Ok(dir) => Some(dir.join("speakers.json")),
+        Err(e) => {
+          warn!("No app data directory, speakers are not kept: {}", e);
+          None
+        },
+      };
+      app.manage(Mutex::new(AppDataSpeakerEncoder::default()));
+      app.manage(AppDataSpeakers::load(path));
+      app.manage(AppDataDiarizationSessions::default());
+      Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+      load_ort_model_speaker,
+      cancel_model_download,
+      enroll_speaker,
+      list_speakers,
+      remove_speaker,
+      create_diarization_session,
+      identify_speaker,
+      name_speaker,
+      destroy_diarization_session
+    ])
+    .build()
+}
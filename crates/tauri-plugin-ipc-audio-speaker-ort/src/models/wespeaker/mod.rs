@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use airi_audio_models::{ModelRepo, SessionOptions, create_session};
+use anyhow::{Result, anyhow};
+use log::info;
+use ort::{session::Session, util::Mutex, value::Tensor};
+use tauri::Runtime;
+
+use crate::fbank::{Fbank, MEL_BINS, SAMPLE_RATE};
+
+/// Embeddings of audio longer than this are computed from its first 20 seconds
+const MAX_SAMPLES: usize = 20 * SAMPLE_RATE;
+
+/// WeSpeaker ResNet34 trained on VoxCeleb, 256 values per embedding. Embeddings of the same
+/// voice have a cosine similarity well above those of different voices.
+pub struct Encoder {
+  session: Mutex<Session>,
+  fbank:   Fbank,
+}
+
+impl Encoder {
+  pub fn new<R: Runtime>(window: tauri::WebviewWindow<R>) -> Result<Self> {
+    let model_id = "Wespeaker/wespeaker-voxceleb-resnet34-LM";
+    let revision = "main";
+
+    let model_path = ModelRepo::new(model_id, revision)?.get(
+      "voxceleb_resnet34_LM.onnx",
+      window,
+      "tauri-plugins:tauri-plugin-ipc-audio-speaker-ort:load-model-wespeaker-progress",
+    )?;
+
+    Ok(Self {
+      session: Mutex::new(Self::create_optimized_session(&model_path)?),
+      fbank:   Fbank::default(),
+    })
+  }
+
+  /// Create an optimized ONNX session with hardware acceleration
+  fn create_optimized_session(model_path: &Path) -> Result<Session> {
+    let session = create_session(
+      model_path,
+      SessionOptions {
+        intra_threads: Some(2),
+        ..Default::default()
+      },
+    )?;
+    info!("Speaker embedding model loaded successfully");
+
+    Ok(session)
+  }
+
+  /// The embedding of the voice in 16 kHz mono audio, scaled to unit length.
+  pub fn embed(
+    &self,
+    audio: &[f32],
+  ) -> Result<Vec<f32>> {
+    let frames = self
+      .fbank
+      .compute(&audio[..audio.len().min(MAX_SAMPLES)]);
+    if frames.is_empty() {
+      return Err(anyhow!("Audio is too short for a speaker embedding"));
+    }
+
+    let n_frames = frames.len();
+    let features: Vec<f32> = frames.into_iter().flatten().collect();
+    let inputs = vec![(
+      "feats",
+      Tensor::from_array((vec![1, n_frames, MEL_BINS], features))?.into_dyn(),
+    )];
+
+    let mut session = self.session.lock();
+    let outputs = session.run(inputs)?;
+    let (_shape, embedding) = outputs[0].try_extract_tensor::<f32>()?;
+
+    Ok(normalize(embedding.to_vec()))
+  }
+}
+
+/// Scales `embedding` to unit length, so the dot product of two is their cosine similarity.
+pub fn normalize(mut embedding: Vec<f32>) -> Vec<f32> {
+  let norm = embedding
+    .iter()
+    .map(|x| x * x)
+    .sum::<f32>()
+    .sqrt();
+  if norm > f32::EPSILON {
+    embedding.iter_mut().for_each(|x| *x /= norm);
+  }
+  embedding
+}
+
+pub fn similarity(
+  a: &[f32],
+  b: &[f32],
+) -> f32 {
+  a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::wespeaker::normalize;
+
+/// A known voice, enrolled by name.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Speaker {
+  pub id:        String,
+  pub name:      String,
+  /// Mean embedding of everything enrolled, unit length
+  pub embedding: Vec<f32>,
+  /// Recordings and segments the embedding was averaged from
+  pub samples:   usize,
+}
+
+/// What the frontend gets to see of a speaker.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeakerInfo {
+  pub id:      String,
+  pub name:    String,
+  pub samples: usize,
+}
+
+impl Speaker {
+  pub fn info(&self) -> SpeakerInfo {
+    SpeakerInfo {
+      id:      self.id.clone(),
+      name:    self.name.clone(),
+      samples: self.samples,
+    }
+  }
+}
+
+/// Adds `samples` embeddings averaging to `embedding` to the speaker called `name`, enrolling
+/// them when they are new. Returns the speaker.
+pub fn enroll(
+  speakers: &mut Vec<Speaker>,
+  name: &str,
+  embedding: &[f32],
+  samples: usize,
+) -> SpeakerInfo {
+  if let Some(speaker) = speakers
+    .iter_mut()
+    .find(|speaker| speaker.name == name)
+  {
+    let total = (speaker.samples + samples) as f32;
+    let mean = speaker
+      .embedding
+      .iter()
+      .zip(embedding)
+      .map(|(known, new)| (known * speaker.samples as f32 + new * samples as f32) / total)
+      .collect();
+    speaker.embedding = normalize(mean);
+    speaker.samples += samples;
+    return speaker.info();
+  }
+
+  let next = speakers
+    .iter()
+    .filter_map(|speaker| {
+      speaker
+        .id
+        .strip_prefix("speaker-")?
+        .parse::<u32>()
+        .ok()
+    })
+    .max()
+    .map_or(1, |id| id + 1);
+  let speaker = Speaker {
+    id: format!("speaker-{}", next),
+    name: name.to_string(),
+    embedding: normalize(embedding.to_vec()),
+    samples,
+  };
+  let info = speaker.info();
+  speakers.push(speaker);
+  info
+}
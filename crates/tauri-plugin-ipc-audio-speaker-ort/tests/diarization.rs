@@ -0,0 +1,181 @@
+use tauri_plugin_ipc_audio_speaker_ort::{DiarizationOptions, Diarizer, Speaker};
+
+const DIMENSIONS: usize = 16;
+
+/// xorshift32, the tests need reproducible noise and nothing more
+struct Noise(u32);
+
+impl Noise {
+  fn next(&mut self) -> f32 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 17;
+    self.0 ^= self.0 << 5;
+    self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+  }
+}
+
+fn normalize(embedding: Vec<f32>) -> Vec<f32> {
+  let norm = embedding
+    .iter()
+    .map(|x| x * x)
+    .sum::<f32>()
+    .sqrt();
+  embedding.into_iter().map(|x| x / norm).collect()
+}
+
+/// An embedding of the voice `voice`: unit length along its own axis, with some noise that
+/// keeps segments of one voice at a similarity of about 0.9.
+fn segment(
+  voice: usize,
+  noise: &mut Noise,
+) -> Vec<f32> {
+  normalize(
+    (0..DIMENSIONS)
+      .map(|i| if i == voice { 1.0 } else { 0.0 } + 0.12 * noise.next())
+      .collect(),
+  )
+}
+
+fn speaker(
+  voice: usize,
+  name: &str,
+) -> Speaker {
+  Speaker {
+    id:        format!("speaker-{voice}"),
+    name:      name.to_string(),
+    embedding: segment(voice, &mut Noise(100 + voice as u32)),
+    samples:   3,
+  }
+}
+
+#[test]
+fn tells_unknown_voices_apart() {
+  let mut noise = Noise(1);
+  let mut diarizer = Diarizer::new(DiarizationOptions::default());
+
+  let ids: Vec<String> = [0, 0, 1, 0, 2, 1, 2]
+    .into_iter()
+    .map(|voice| {
+      diarizer
+        .assign(&[], &segment(voice, &mut noise))
+        .speaker_id
+    })
+    .collect();
+  assert_eq!(
+    ids,
+    [
+      "unknown-1",
+      "unknown-1",
+      "unknown-2",
+      "unknown-1",
+      "unknown-3",
+      "unknown-2",
+      "unknown-3"
+    ]
+  );
+}
+
+#[test]
+fn new_voices_match_themselves_and_later_ones_their_cluster() {
+  let mut noise = Noise(2);
+  let mut diarizer = Diarizer::new(DiarizationOptions::default());
+
+  let first = diarizer.assign(&[], &segment(0, &mut noise));
+  assert_eq!(first.similarity, 1.0);
+  assert_eq!(first.name, None);
+
+  let second = diarizer.assign(&[], &segment(0, &mut noise));
+  assert!(
+    (0.5..1.0).contains(&second.similarity),
+    "similarity {}",
+    second.similarity
+  );
+}
+
+#[test]
+fn recognizes_enrolled_speakers() {
+  let mut noise = Noise(3);
+  let speakers = [speaker(0, "Alice"), speaker(1, "Bob")];
+  let mut diarizer = Diarizer::new(DiarizationOptions::default());
+
+  let bob = diarizer.assign(&speakers, &segment(1, &mut noise));
+  assert_eq!(bob.speaker_id, "speaker-1");
+  assert_eq!(bob.name.as_deref(), Some("Bob"));
+
+  // Someone else is an unknown voice, even with speakers enrolled
+  let unknown = diarizer.assign(&speakers, &segment(2, &mut noise));
+  assert_eq!(unknown.speaker_id, "unknown-1");
+  assert_eq!(unknown.name, None);
+}
+
+#[test]
+fn threshold_decides_what_is_the_same_voice() {
+  let mut noise = Noise(4);
+  let mut strict = Diarizer::new(DiarizationOptions {
+    threshold: 0.99,
+    ..Default::default()
+  });
+  let first = strict.assign(&[], &segment(0, &mut noise));
+  let second = strict.assign(&[], &segment(0, &mut noise));
+  assert_ne!(first.speaker_id, second.speaker_id);
+
+  let mut lenient = Diarizer::new(DiarizationOptions {
+    threshold: -1.0,
+    ..Default::default()
+  });
+  let first = lenient.assign(&[], &segment(0, &mut noise));
+  let second = lenient.assign(&[], &segment(1, &mut noise));
+  assert_eq!(first.speaker_id, second.speaker_id);
+}
+
+#[test]
+fn assigns_to_the_closest_voice_past_max_speakers() {
+  let mut noise = Noise(5);
+  let mut diarizer = Diarizer::new(DiarizationOptions {
+    max_speakers: Some(2),
+    ..Default::default()
+  });
+
+  diarizer.assign(&[], &segment(0, &mut noise));
+  diarizer.assign(&[], &segment(1, &mut noise));
+  // Mostly the second voice, a bit of the first
+  let mixed = normalize(
+    segment(1, &mut noise)
+      .iter()
+      .zip(segment(2, &mut noise))
+      .zip(segment(0, &mut noise))
+      .map(|((b, c), a)| 0.3 * b + c + 0.1 * a)
+      .collect(),
+  );
+  let third = diarizer.assign(&[], &mixed);
+  assert_eq!(third.speaker_id, "unknown-2");
+  assert!(third.similarity < 0.5, "similarity {}", third.similarity);
+}
+
+#[test]
+fn takes_unknown_voices_out_to_enroll_them() {
+  let mut noise = Noise(6);
+  let mut diarizer = Diarizer::new(DiarizationOptions::default());
+  for voice in [0, 0, 0, 1] {
+    diarizer.assign(&[], &segment(voice, &mut noise));
+  }
+
+  let (embedding, count) = diarizer.take_unknown("unknown-1").unwrap();
+  assert_eq!(count, 3);
+  let norm = embedding
+    .iter()
+    .map(|x| x * x)
+    .sum::<f32>()
+    .sqrt();
+  assert!((norm - 1.0).abs() < 1e-5);
+  assert!(embedding[0] > 0.9, "{embedding:?}");
+  assert!(diarizer.take_unknown("unknown-1").is_none());
+
+  // The voice is new again, with a new id
+  let again = diarizer.assign(&[], &segment(0, &mut noise));
+  assert_eq!(again.speaker_id, "unknown-3");
+
+  diarizer.reset();
+  let after_reset = diarizer.assign(&[], &segment(1, &mut noise));
+  assert_eq!(after_reset.speaker_id, "unknown-1");
+}
@@ -0,0 +1,58 @@
+use serde::Deserialize;
+use tauri_plugin_ipc_audio_speaker_ort::{Fbank, MEL_BINS};
+
+/// Audio and what Kaldi makes of it with the options of WeSpeaker, see
+/// tests/fixtures/generate_fbank_reference.py
+#[derive(Deserialize)]
+struct Reference {
+  audio: Vec<f32>,
+  fbank: Vec<Vec<f32>>,
+}
+
+fn reference() -> Reference {
+  serde_json::from_str(include_str!("fixtures/fbank_reference.json")).unwrap()
+}
+
+#[test]
+fn matches_kaldi_fbank() {
+  let reference = reference();
+  let frames = Fbank::default().compute(&reference.audio);
+
+  assert_eq!(frames.len(), reference.fbank.len());
+  for (i, (frame, expected)) in frames.iter().zip(&reference.fbank).enumerate() {
+    assert_eq!(expected.len(), MEL_BINS);
+    for (bin, (actual, expected)) in frame.iter().zip(expected).enumerate() {
+      assert!(
+        (actual - expected).abs() < 1e-3,
+        "frame {i} bin {bin}: {actual} instead of {expected}"
+      );
+    }
+  }
+}
+
+#[test]
+fn needs_a_whole_frame() {
+  let fbank = Fbank::default();
+  assert!(fbank.compute(&[0.1; 399]).is_empty());
+  assert_eq!(fbank.compute(&[0.1; 400]).len(), 1);
+  // One more frame every 10 ms
+  assert_eq!(fbank.compute(&[0.1; 16000]).len(), 98);
+}
+
+#[test]
+fn does_not_depend_on_the_loudness() {
+  let reference = reference();
+  let fbank = Fbank::default();
+  let quieter: Vec<f32> = reference.audio.iter().map(|x| x * 0.25).collect();
+
+  // A gain is the same offset in every frame, which the mean normalization removes
+  for (loud, quiet) in fbank
+    .compute(&reference.audio)
+    .iter()
+    .zip(&fbank.compute(&quieter))
+  {
+    for (loud, quiet) in loud.iter().zip(quiet) {
+      assert!((loud - quiet).abs() < 1e-3);
+    }
+  }
+}
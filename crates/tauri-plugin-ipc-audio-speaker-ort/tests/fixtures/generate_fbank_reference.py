@@ -0,0 +1,127 @@
+"""Generates the features tests/fbank.rs compares `Fbank` with.
+
+`fbank` is `torchaudio.compliance.kaldi.fbank` written out in plain Python for the options
+WeSpeaker calls it with (wespeaker/cli/speaker.py, `compute_fbank`): 80 mel bins, 25 ms frames
+every 10 ms, no dither, a Hamming window, 16-bit sample values, and the mean of every bin
+subtracted afterwards. The other options keep their Kaldi defaults: DC offset removal,
+pre-emphasis of 0.97, a 512 point FFT, mel bins from 20 Hz to Nyquist and power spectra.
+
+    python3 tests/fixtures/generate_fbank_reference.py
+"""
+
+import json
+import math
+import struct
+from pathlib import Path
+
+HERE = Path(__file__).parent
+
+SAMPLE_RATE = 16000
+NUM_MEL_BINS = 80
+WINDOW_SIZE = 400
+WINDOW_SHIFT = 160
+PADDED_WINDOW_SIZE = 512
+PREEMPHASIS = 0.97
+LOW_FREQ = 20.0
+# torch.finfo(torch.float32).eps
+EPSILON = 1.1920928955078125e-07
+
+
+def f32(x):
+    return struct.unpack("f", struct.pack("f", x))[0]
+
+
+def signal(seconds):
+    """A vowel-like tone with a gliding pitch and some noise, as float32 values."""
+    state = 1
+    samples = []
+    for i in range(int(seconds * SAMPLE_RATE)):
+        # xorshift32
+        state ^= (state << 13) & 0xFFFFFFFF
+        state ^= state >> 17
+        state ^= (state << 5) & 0xFFFFFFFF
+        noise = state / 0xFFFFFFFF * 2.0 - 1.0
+
+        t = i / SAMPLE_RATE
+        pitch = 140.0 + 30.0 * t
+        voiced = sum(math.sin(2.0 * math.pi * k * pitch * t) / k for k in range(1, 15))
+        samples.append(f32(0.1 * voiced + 0.01 * noise))
+    return samples
+
+
+def mel_scale(freq):
+    return 1127.0 * math.log(1.0 + freq / 700.0)
+
+
+def mel_banks():
+    num_fft_bins = PADDED_WINDOW_SIZE // 2
+    fft_bin_width = SAMPLE_RATE / PADDED_WINDOW_SIZE
+    mel_low_freq = mel_scale(LOW_FREQ)
+    mel_high_freq = mel_scale(0.5 * SAMPLE_RATE)
+    mel_freq_delta = (mel_high_freq - mel_low_freq) / (NUM_MEL_BINS + 1)
+
+    banks = []
+    for b in range(NUM_MEL_BINS):
+        left_mel = mel_low_freq + b * mel_freq_delta
+        center_mel = mel_low_freq + (b + 1.0) * mel_freq_delta
+        right_mel = mel_low_freq + (b + 2.0) * mel_freq_delta
+        bank = []
+        for k in range(num_fft_bins):
+            mel = mel_scale(fft_bin_width * k)
+            up_slope = (mel - left_mel) / (center_mel - left_mel)
+            down_slope = (right_mel - mel) / (right_mel - center_mel)
+            bank.append(max(0.0, min(up_slope, down_slope)))
+        # The Nyquist bin gets no weight
+        bank.append(0.0)
+        banks.append(bank)
+    return banks
+
+
+def fbank(waveform):
+    waveform = [x * (1 << 15) for x in waveform]
+    window = [
+        0.54 - 0.46 * math.cos(2.0 * math.pi * n / (WINDOW_SIZE - 1)) for n in range(WINDOW_SIZE)
+    ]
+    banks = mel_banks()
+
+    num_frames = 1 + (len(waveform) - WINDOW_SIZE) // WINDOW_SHIFT
+    frames = []
+    for f in range(num_frames):
+        strided = waveform[f * WINDOW_SHIFT:f * WINDOW_SHIFT + WINDOW_SIZE]
+        mean = sum(strided) / WINDOW_SIZE
+        strided = [x - mean for x in strided]
+        # Replicate padding: the first sample is pre-emphasized against itself
+        offset = [strided[0]] + strided[:-1]
+        strided = [x - PREEMPHASIS * o for x, o in zip(strided, offset)]
+        strided = [x * w for x, w in zip(strided, window)]
+        strided += [0.0] * (PADDED_WINDOW_SIZE - WINDOW_SIZE)
+
+        spectrum = []
+        for k in range(PADDED_WINDOW_SIZE // 2 + 1):
+            re = 0.0
+            im = 0.0
+            for n, x in enumerate(strided):
+                angle = 2.0 * math.pi * k * n / PADDED_WINDOW_SIZE
+                re += x * math.cos(angle)
+                im -= x * math.sin(angle)
+            spectrum.append(re * re + im * im)
+
+        frames.append(
+            [math.log(max(sum(w * p for w, p in zip(bank, spectrum)), EPSILON)) for bank in banks]
+        )
+
+    # Cepstral mean normalization
+    means = [sum(frame[b] for frame in frames) / len(frames) for b in range(NUM_MEL_BINS)]
+    return [[x - m for x, m in zip(frame, means)] for frame in frames]
+
+
+def main():
+    audio = signal(0.25)
+    features = [[round(x, 5) for x in frame] for frame in fbank(audio)]
+    reference = {"audio": audio, "fbank": features}
+    with open(HERE / "fbank_reference.json", "w") as f:
+        json.dump(reference, f)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,36 @@
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use tauri_plugin_ipc_audio_transcription_ort::WhisperProcessor;
+
+const SAMPLE_RATE: usize = 16000;
+
+fn audio(seconds: usize) -> Vec<f32> {
+  (0..seconds * SAMPLE_RATE)
+    .map(|i| {
+      let t = i as f32 / SAMPLE_RATE as f32;
+      0.5 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+    })
+    .collect()
+}
+
+fn log_mel(c: &mut Criterion) {
+  let mut group = c.benchmark_group("log_mel");
+
+  for num_mel_bins in [80, 128] {
+    let processor = WhisperProcessor::new(num_mel_bins).unwrap();
+
+    // Streaming sessions send a few seconds, file transcription always full windows
+    for seconds in [1, 5, 30] {
+      let audio = audio(seconds);
+      group.bench_with_input(
+        BenchmarkId::new(format!("{num_mel_bins}_bins"), format!("{seconds}s")),
+        &audio,
+        |b, audio| b.iter(|| processor.process(black_box(audio))),
+      );
+    }
+  }
+
+  group.finish();
+}
+
+criterion_group!(benches, log_mel);
+criterion_main!(benches);
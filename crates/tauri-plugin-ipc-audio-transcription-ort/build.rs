@@ -1,8 +1,17 @@
 const COMMANDS: &[&str] = &[
   "load_ort_model_whisper",
+  "cancel_model_download",
+  "unload_model",
   "ipc_audio_transcription",
+  "start_session",
+  "push_audio",
+  "end_session",
+  "enqueue_transcription_job",
+  "cancel_transcription_job",
+  "list_transcription_jobs",
   "list_models",
   "list_installed_models",
+  "delete_model",
 ];
 
 fn main() {
@@ -0,0 +1,425 @@
+use std::{
+  collections::HashMap,
+  fmt::Write as _,
+  fs::OpenOptions,
+  io::{ErrorKind, Write as _},
+  path::{Path, PathBuf},
+  sync::{
+    Arc,
+    Mutex,
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    mpsc::{Receiver, Sender},
+  },
+  time::{Duration, Instant},
+};
+
+use airi_audio_models::{AudioError, audio};
+use anyhow::Result;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, Runtime};
+
+use crate::{
+  models::whisper::{
+    guards::GuardConfig,
+    whisper::{GenerationConfig, Task},
+    whisper_processor::SAMPLE_RATE,
+  },
+  slots::AppDataWhisperProcessor,
+};
+
+pub const TRANSCRIPTION_JOB_EVENT: &str =
+  "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:transcription-job";
+
+// Whisper looks at 30 seconds of audio at a time
+const WINDOW_SAMPLES: usize = 30 * SAMPLE_RATE;
+// Enough for a dense 30 second window, the decoder context caps it anyway
+const MAX_NEW_TOKENS: usize = 224;
+// Finished jobs stay listed this long, for the frontend to pick up how they ended
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+  Json,
+  Srt,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptionJobOptions {
+  pub language:       Option<String>,
+  pub task:           Option<Task>,
+  pub initial_prompt: Option<String>,
+  pub hotwords:       Option<Vec<String>>,
+  /// Transcript files written next to the source, both JSON and SRT when not set
+  pub formats:        Option<Vec<OutputFormat>>,
+  pub guards:         Option<GuardConfig>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum TranscriptionJobStatus {
+  Queued,
+  /// `progress` is the transcribed fraction of the audio, from 0 to 1.
+  Running {
+    progress: f32,
+  },
+  Completed {
+    outputs: Vec<PathBuf>,
+  },
+  Failed {
+    error: String,
+  },
+  Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptionJobEvent {
+  pub job_id: u32,
+  pub path:   PathBuf,
+  #[serde(flatten)]
+  pub status: TranscriptionJobStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptSegment {
+  pub start: f32,
+  pub end:   f32,
+  pub text:  String,
+}
+
+#[derive(Debug, Serialize)]
+struct Transcript {
+  text:     String,
+  language: Option<String>,
+  segments: Vec<TranscriptSegment>,
+}
+
+pub struct TranscriptionJob {
+  id:        u32,
+  path:      PathBuf,
+  options:   TranscriptionJobOptions,
+  cancelled: AtomicBool,
+  status:    Mutex<TranscriptionJobStatus>,
+  finished:  Mutex<Option<Instant>>,
+}
+
+impl TranscriptionJob {
+  fn event(&self) -> TranscriptionJobEvent {
+    TranscriptionJobEvent {
+      job_id: self.id,
+      path:   self.path.clone(),
+      status: self.status.lock().unwrap().clone(),
+    }
+  }
+
+  fn set_status<R: Runtime>(
+    &self,
+    app: &tauri::AppHandle<R>,
+    status: TranscriptionJobStatus,
+  ) {
+    if matches!(
+      status,
+      TranscriptionJobStatus::Completed { .. }
+        | TranscriptionJobStatus::Failed { .. }
+        | TranscriptionJobStatus::Cancelled
+    ) {
+      *self.finished.lock().unwrap() = Some(Instant::now());
+    }
+    *self.status.lock().unwrap() = status;
+
+    if let Err(err) = app.emit(TRANSCRIPTION_JOB_EVENT, self.event()) {
+      error!("Failed to emit transcription-job: {:?}", err);
+    }
+  }
+}
+
+/// Jobs are transcribed one at a time, in the order they were enqueued, by a single worker
+/// thread that shares the loaded model with the other commands.
+pub struct AppDataTranscriptionJobs {
+  next_id: AtomicU32,
+  jobs:    Mutex<HashMap<u32, Arc<TranscriptionJob>>>,
+  queue:   Sender<Arc<TranscriptionJob>>,
+}
+
+impl AppDataTranscriptionJobs {
+  /// Creates the job list and starts its worker thread.
+  pub fn new<R: Runtime>(app: tauri::AppHandle<R>) -> Self {
+    let (queue, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || run_worker(app, receiver));
+
+    Self {
+      next_id: AtomicU32::new(0),
+      jobs: Mutex::new(HashMap::new()),
+      queue,
+    }
+  }
+
+  pub fn enqueue<R: Runtime>(
+    &self,
+    app: &tauri::AppHandle<R>,
+    path: PathBuf,
+    options: TranscriptionJobOptions,
+  ) -> Result<u32, AudioError> {
+    if !path.is_file() {
+      return Err(AudioError::NotFound(format!(
+        "{} is not a file",
+        path.display()
+      )));
+    }
+
+    let job = Arc::new(TranscriptionJob {
+      id: self.next_id.fetch_add(1, Ordering::Relaxed),
+      path,
+      options,
+      cancelled: AtomicBool::new(false),
+      status: Mutex::new(TranscriptionJobStatus::Queued),
+      finished: Mutex::new(None),
+    });
+
+    self.remove_finished();
+    self
+      .jobs
+      .lock()
+      .unwrap()
+      .insert(job.id, job.clone());
+    job.set_status(app, TranscriptionJobStatus::Queued);
+    self
+      .queue
+      .send(job.clone())
+      .map_err(|_| AudioError::Internal("Transcription job worker is not running".to_string()))?;
+
+    Ok(job.id)
+  }
+
+  /// Stops a queued or running job, a running job stops after the window it is working on.
+  pub fn cancel(
+    &self,
+    job_id: u32,
+  ) -> Result<(), AudioError> {
+    let jobs = self.jobs.lock().unwrap();
+    let job = jobs.get(&job_id).ok_or_else(|| {
+      AudioError::NotFound(format!("Transcription job {} does not exist", job_id))
+    })?;
+
+    job.cancelled.store(true, Ordering::Relaxed);
+    Ok(())
+  }
+
+  pub fn list(&self) -> Vec<TranscriptionJobEvent> {
+    self.remove_finished();
+    let mut jobs: Vec<_> = self
+      .jobs
+      .lock()
+      .unwrap()
+      .values()
+      .map(|job| job.event())
+      .collect();
+    jobs.sort_by_key(|job| job.job_id);
+    jobs
+  }
+
+  /// Drops the jobs that finished more than [`FINISHED_JOB_TTL`] ago.
+  fn remove_finished(&self) {
+    self.jobs.lock().unwrap().retain(|_, job| {
+      job
+        .finished
+        .lock()
+        .unwrap()
+        .is_none_or(|finished| finished.elapsed() < FINISHED_JOB_TTL)
+    });
+  }
+}
+
+fn run_worker<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  receiver: Receiver<Arc<TranscriptionJob>>,
+) {
+  for job in receiver {
+    if job.cancelled.load(Ordering::Relaxed) {
+      job.set_status(&app, TranscriptionJobStatus::Cancelled);
+      continue;
+    }
+
+    info!(
+      "Transcription job {} started: {}",
+      job.id,
+      job.path.display()
+    );
+    job.set_status(&app, TranscriptionJobStatus::Running { progress: 0.0 });
+
+    let status = match run_job(&app, &job) {
+      Ok(Some(outputs)) => TranscriptionJobStatus::Completed { outputs },
+      Ok(None) => TranscriptionJobStatus::Cancelled,
+      Err(e) => {
+        error!("Transcription job {} failed: {}", job.id, e);
+        TranscriptionJobStatus::Failed {
+          error: e.to_string(),
+        }
+      },
+    };
+
+    info!("Transcription job {} finished: {:?}", job.id, status);
+    job.set_status(&app, status);
+  }
+}
+
+/// Transcribes the whole file, returns `None` when the job was cancelled midway.
+fn run_job<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  job: &TranscriptionJob,
+) -> Result<Option<Vec<PathBuf>>> {
+  let audio = audio::decode_file(&job.path)?.into_mono(SAMPLE_RATE)?;
+  let config = GenerationConfig {
+    language:          job.options.language.clone(),
+    task:              job.options.task.unwrap_or_default(),
+    return_timestamps: true,
+    max_new_tokens:    MAX_NEW_TOKENS,
+    initial_prompt:    job.options.initial_prompt.clone(),
+    hotwords:          job.options.hotwords.clone().unwrap_or_default(),
+    prefix:            None,
+    guards:            job.options.guards.clone().unwrap_or_default(),
+  };
+
+  let mut segments = Vec::new();
+  let mut seek = 0;
+  while seek < audio.len() {
+    if job.cancelled.load(Ordering::Relaxed) {
+      return Ok(None);
+    }
+
+    let end = (seek + WINDOW_SAMPLES).min(audio.len());
+    // Looked up for every window, a model swapped in while the job runs is used from then on
+    let mut window_segments = {
+      let loaded = app
+        .state::<AppDataWhisperProcessor>()
+        .default_model()?;
+      let mut processor = loaded.pipeline.lock().unwrap();
+
+      processor
+        .transcribe_segments(&audio[seek..end], &config)?
+        .segments
+    };
+
+    // The last segment of a window may be cut mid-word, transcribe it again with the next window
+    let mut next = end;
+    if end < audio.len() && window_segments.len() > 1 {
+      let last = window_segments.pop().unwrap();
+      next = seek + (last.start * SAMPLE_RATE as f32) as usize;
+    }
+
+    let offset = seek as f32 / SAMPLE_RATE as f32;
+    segments.extend(
+      window_segments
+        .into_iter()
+        .filter(|segment| !segment.text.trim().is_empty())
+        .map(|segment| TranscriptSegment {
+          start: offset + segment.start,
+          end:   offset + segment.end,
+          text:  segment.text.trim().to_string(),
+        }),
+    );
+
+    seek = next.max(seek + 1);
+    job.set_status(
+      app,
+      TranscriptionJobStatus::Running {
+        progress: seek.min(audio.len()) as f32 / audio.len() as f32,
+      },
+    );
+  }
+
+  let transcript = Transcript {
+    text: segments
+      .iter()
+      .map(|segment| segment.text.as_str())
+      .collect::<Vec<_>>()
+      .join(" "),
+    language: job.options.language.clone(),
+    segments,
+  };
+
+  let formats = job
+    .options
+    .formats
+    .clone()
+    .unwrap_or_else(|| vec![OutputFormat::Json, OutputFormat::Srt]);
+
+  formats
+    .into_iter()
+    .map(|format| write_transcript(&job.path, &transcript, format))
+    .collect::<Result<Vec<_>>>()
+    .map(Some)
+}
+
+/// Writes the transcript next to the source file, replacing its extension. Existing files are
+/// kept, the transcript then gets the first free numbered name: `talk-1.srt`, `talk-2.srt`, ...
+fn write_transcript(
+  source: &Path,
+  transcript: &Transcript,
+  format: OutputFormat,
+) -> Result<PathBuf> {
+  let (extension, contents) = match format {
+    OutputFormat::Json => ("json", serde_json::to_string_pretty(transcript)?),
+    OutputFormat::Srt => ("srt", to_srt(&transcript.segments)),
+  };
+
+  let stem = source
+    .file_stem()
+    .unwrap_or_default()
+    .to_string_lossy();
+  for number in 0.. {
+    let name = match number {
+      0 => format!("{}.{}", stem, extension),
+      _ => format!("{}-{}.{}", stem, number, extension),
+    };
+    let path = source.with_file_name(name);
+
+    // `create_new` fails instead of replacing a file, also one created since the last try
+    match OpenOptions::new()
+      .write(true)
+      .create_new(true)
+      .open(&path)
+    {
+      Ok(mut file) => {
+        file.write_all(contents.as_bytes())?;
+        return Ok(path);
+      },
+      Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+      Err(e) => return Err(e.into()),
+    }
+  }
+
+  unreachable!("ran out of transcript file names")
+}
+
+pub fn to_srt(segments: &[TranscriptSegment]) -> String {
+  let mut srt = String::new();
+  for (index, segment) in segments.iter().enumerate() {
+    let _ = writeln!(
+      srt,
+      "{}\n{} --> {}\n{}\n",
+      index + 1,
+      srt_timestamp(segment.start),
+      srt_timestamp(segment.end),
+      segment.text
+    );
+  }
+
+  srt
+}
+
+/// Formats seconds as `HH:MM:SS,mmm`, rounded to the millisecond. Hours get more digits past 99.
+pub fn srt_timestamp(seconds: f32) -> String {
+  // In f64, an f32 holds whole milliseconds only up to about 4 hours
+  let millis = (seconds.max(0.0) as f64 * 1000.0).round() as u64;
+  format!(
+    "{:02}:{:02}:{:02},{:03}",
+    millis / 3_600_000,
+    millis / 60_000 % 60,
+    millis / 1000 % 60,
+    millis % 1000
+  )
+}
@@ -1,186 +1,567 @@
-use std::sync::Mutex;
+use std::{
+  collections::HashMap,
+  sync::{
+    Arc,
+    Mutex,
+    atomic::{AtomicU32, Ordering},
+  },
+  time::{Duration, Instant},
+};
 
+use airi_audio_models::{AudioError, audio};
 use clap::ValueEnum;
 use log::info;
-use serde_json;
 use tauri::{
   Manager,
   Runtime,
+  ipc::Channel,
   plugin::{Builder as PluginBuilder, TauriPlugin},
 };
+use tauri_plugin_ipc_audio_preprocess::{PreprocessOptions, Preprocessor};
 
-mod helpers;
+mod jobs;
 mod models;
+mod slots;
+mod streaming;
+
+pub use jobs::{TranscriptSegment, srt_timestamp, to_srt};
+pub use models::whisper::{
+  guards::{Guard, GuardConfig, compression_ratio},
+  whisper::{
+    GenerationConfig,
+    ModelVariant,
+    Segment,
+    Task,
+    Transcription,
+    TranscriptionResult,
+    WhisperPipeline,
+  },
+  whisper_processor::{WhisperProcessor, mel_filters},
+};
+pub use streaming::{
+  StreamingOptions,
+  StreamingTranscriber,
+  Transcriber,
+  TranscriptionSessionEvent,
+};
+
+use crate::{
+  jobs::{AppDataTranscriptionJobs, TranscriptionJobEvent, TranscriptionJobOptions},
+  models::{
+    catalog::{self, ModelInfo},
+    new_whisper_processor,
+    whisper::{self, whisper::WhichModel, whisper_processor::SAMPLE_RATE},
+  },
+  slots::{AppDataWhisperProcessor, LoadedWhisper, ModelSlot, ModelState, emit_model_state},
+};
+
+/// Sessions that got no audio for this long are ended, for clients that never call `end_session`
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+struct TranscriptionSession {
+  transcriber:  StreamingTranscriber,
+  channel:      Channel<TranscriptionSessionEvent>,
+  /// Cleans pushed audio up before it is transcribed
+  preprocessor: Option<Preprocessor>,
+  last_used:    Instant,
+}
+
+impl TranscriptionSession {
+  fn send(
+    &self,
+    events: Vec<TranscriptionSessionEvent>,
+  ) -> Result<(), AudioError> {
+    for event in events {
+      self
+        .channel
+        .send(event)
+        .map_err(|e| AudioError::Internal(format!("Failed to send transcription event: {}", e)))?;
+    }
+
+    Ok(())
+  }
+}
 
 #[derive(Default)]
-struct AppDataWhisperProcessor {
-  whisper_processor: Option<models::whisper::whisper::WhisperPipeline>,
+struct AppDataTranscriptionSessions {
+  next_id:  AtomicU32,
+  sessions: Mutex<HashMap<u32, Arc<Mutex<TranscriptionSession>>>>,
 }
 
-use crate::models::{
-  new_whisper_processor,
-  whisper::{self, whisper::WhichModel},
-};
+impl AppDataTranscriptionSessions {
+  fn get(
+    &self,
+    session_id: u32,
+  ) -> Result<Arc<Mutex<TranscriptionSession>>, AudioError> {
+    self.remove_idle();
+    self
+      .sessions
+      .lock()
+      .unwrap()
+      .get(&session_id)
+      .cloned()
+      .ok_or_else(|| {
+        AudioError::NotFound(format!(
+          "Transcription session {} does not exist",
+          session_id
+        ))
+      })
+  }
+
+  /// Ends the sessions that got no audio within [`SESSION_IDLE_TIMEOUT`], checked whenever
+  /// sessions are started or used.
+  fn remove_idle(&self) {
+    self
+      .sessions
+      .lock()
+      .unwrap()
+      .retain(|session_id, session| {
+        // A session that is transcribing right now is not idle
+        let idle = session
+          .try_lock()
+          .is_ok_and(|session| session.last_used.elapsed() > SESSION_IDLE_TIMEOUT);
+        if idle {
+          info!(
+            "Transcription session {} ended after being idle",
+            session_id
+          );
+        }
+        !idle
+      });
+  }
+}
 
 #[tauri::command]
 async fn load_ort_model_whisper<R: Runtime>(
   app: tauri::AppHandle<R>,
   window: tauri::WebviewWindow<R>,
   model_type: Option<String>,
-) -> Result<(), String> {
-  info!("Loading models...");
+  variant: Option<ModelVariant>,
+  slot: Option<ModelSlot>,
+) -> Result<(), AudioError> {
+  let slot = slot.unwrap_or_default();
+  let model = WhichModel::from_id(
+    model_type
+      .unwrap_or_else(|| "medium".to_string())
+      .as_str(),
+  )?;
+  let variant = variant.unwrap_or_default();
 
-  {
-    let data = app.state::<Mutex<AppDataWhisperProcessor>>();
-    let data = data.lock().unwrap();
-    if data.whisper_processor.is_some() {
-      info!("Whisper model already loaded, skipping...");
-      return Ok(());
-    }
+  let data = app.state::<AppDataWhisperProcessor>();
+  if data.loaded(slot) == Some((model, variant)) {
+    info!("Whisper model already loaded, skipping...");
+    return Ok(());
   }
 
-  // Load the traditional whisper models first
-  match new_whisper_processor(
-    window,
-    Some(WhichModel::from_str(
-      model_type
-        .unwrap_or_else(|| "medium".to_string())
-        .as_str(),
-      true,
-    )?),
-  ) {
-    Ok(p) => {
-      let data = app.state::<Mutex<AppDataWhisperProcessor>>();
-      let mut data = data.lock().unwrap();
-      data.whisper_processor = Some(p);
+  info!(
+    "Loading {:?} {:?} into the {:?} slot...",
+    model, variant, slot
+  );
+  let generation = data.begin_load(slot);
+  emit_model_state(
+    &app,
+    slot,
+    ModelState::Loading {
+      model_id: model.id().to_string(),
+      variant,
+    },
+  );
+
+  // Downloading and building the sessions takes a while, the current model keeps serving
+  // transcriptions until the new one is ready
+  let result = tauri::async_runtime::spawn_blocking(move || {
+    new_whisper_processor(window, Some(model), variant)
+  })
+  .await
+  .map_err(AudioError::internal)?;
+
+  match result {
+    Ok(pipeline) => {
+      let loaded = LoadedWhisper {
+        model,
+        variant,
+        pipeline: Mutex::new(pipeline),
+      };
+
+      if !data.finish_load(slot, generation, loaded) {
+        info!("Loading {:?} was superseded by a newer request", model);
+        return Err(AudioError::Cancelled(format!(
+          "Loading {} was superseded by a newer request",
+          model.id()
+        )));
+      }
+
+      emit_model_state(
+        &app,
+        slot,
+        ModelState::Ready {
+          model_id: model.id().to_string(),
+          variant,
+        },
+      );
       info!("Whisper model loaded successfully");
+      Ok(())
     },
     Err(e) => {
-      let error_message = format!("Failed to load Whisper model: {}", e);
-      info!("{}", error_message);
-      return Err(error_message);
+      let error = AudioError::load_failed(e.context("Failed to load Whisper model"));
+      info!("{}", error);
+      emit_model_state(
+        &app,
+        slot,
+        ModelState::Failed {
+          model_id: model.id().to_string(),
+          variant,
+          error: error.to_string(),
+        },
+      );
+      Err(error)
     },
   }
+}
+
+/// Stops the downloads of a Whisper model being loaded, named like `load_ort_model_whisper` takes
+/// it or by the repository its progress events carry. The slot keeps its current model. Returns
+/// whether anything was downloading.
+#[tauri::command]
+async fn cancel_model_download(model_id: String) -> Result<bool, AudioError> {
+  let repo_id = WhichModel::from_id(&model_id)
+    .map(|model| model.model_and_revision().0.to_string())
+    .unwrap_or(model_id);
+  Ok(airi_audio_models::cancel_download(&repo_id))
+}
+
+#[tauri::command]
+async fn unload_model<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  slot: Option<ModelSlot>,
+) -> Result<(), AudioError> {
+  let slot = slot.unwrap_or_default();
+  if app
+    .state::<AppDataWhisperProcessor>()
+    .unload(slot)
+  {
+    info!("Unloaded the {:?} slot", slot);
+    emit_model_state(&app, slot, ModelState::Unloaded);
+  }
 
-  info!("All models loaded successfully");
   Ok(())
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn ipc_audio_transcription<R: Runtime>(
   app: tauri::AppHandle<R>,
-  chunk: Vec<f32>,
+  chunk: Option<Vec<f32>>,
+  sample_rate: Option<u32>,
+  channels: Option<u16>,
+  encoded: Option<Vec<u8>>,
   language: Option<String>,
-) -> Result<String, String> {
+  task: Option<whisper::whisper::Task>,
+  initial_prompt: Option<String>,
+  hotwords: Option<Vec<String>>,
+  prefix: Option<String>,
+  guards: Option<whisper::guards::GuardConfig>,
+) -> Result<whisper::whisper::TranscriptionResult, AudioError> {
   info!("Processing audio transcription...");
 
-  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
-
-  // Check if processor exists first
-  {
-    let data = data.lock().unwrap();
-    if data.whisper_processor.is_none() {
-      return Err("Whisper model is not loaded".to_string());
-    }
+  // Decoding and resampling happen before the model lock is taken
+  let audio = match (chunk, encoded) {
+    (Some(chunk), None) => audio::to_mono(
+      chunk,
+      sample_rate.unwrap_or(SAMPLE_RATE as u32),
+      channels.unwrap_or(1) as usize,
+      SAMPLE_RATE,
+    ),
+    (None, Some(encoded)) => audio::decode_bytes(encoded).and_then(|a| a.into_mono(SAMPLE_RATE)),
+    _ => {
+      return Err(AudioError::InvalidInput(
+        "Exactly one of `chunk` or `encoded` must be provided".to_string(),
+      ));
+    },
   }
+  .map_err(|e| AudioError::InvalidInput(format!("Failed to prepare audio: {}", e)))?;
 
-  // Then mutable borrow
-  let mut data = data.lock().unwrap();
-  let processor = data.whisper_processor.as_mut().unwrap();
+  let loaded = app
+    .state::<AppDataWhisperProcessor>()
+    .default_model()?;
+  let mut processor = loaded.pipeline.lock().unwrap();
 
-  let mut config = whisper::whisper::GenerationConfig::default();
-  config.language = language;
+  let config = whisper::whisper::GenerationConfig {
+    language,
+    task: task.unwrap_or_default(),
+    initial_prompt,
+    hotwords: hotwords.unwrap_or_default(),
+    prefix,
+    guards: guards.unwrap_or_default(),
+    ..Default::default()
+  };
 
   let transcription = processor
-    .transcribe(chunk.as_slice(), &config)
-    .map_err(|e| e.to_string())?;
+    .transcribe(audio.as_slice(), &config)
+    .map_err(AudioError::inference_failed)?;
 
-  info!("Transcription completed: {}", transcription);
+  match transcription.guard {
+    Some(guard) => info!(
+      "Transcription completed ({:?} guard): {}",
+      guard, transcription.text
+    ),
+    None => info!("Transcription completed: {}", transcription.text),
+  }
 
   Ok(transcription)
 }
 
 #[tauri::command]
-async fn list_models() -> Result<Vec<serde_json::Value>, String> {
-  Ok(vec![
-    serde_json::json!({
-      "id": "whisper-large-v3-turbo",
-      "name": "Whisper Large v3 Turbo",
-      "size": 1550000000,
-      "accuracy": "high",
-      "speed": "medium",
-      "installed": false,
-    }),
-    serde_json::json!({
-      "id": "whisper-large-v3",
-      "name": "Whisper Large v3",
-      "size": 1550000000,
-      "accuracy": "high",
-      "speed": "slow",
-      "installed": false,
-    }),
-    serde_json::json!({
-      "id": "whisper-medium",
-      "name": "Whisper Medium",
-      "size": 769000000,
-      "accuracy": "medium",
-      "speed": "medium",
-      "installed": false,
-    }),
-    serde_json::json!({
-      "id": "whisper-small",
-      "name": "Whisper Small",
-      "size": 244000000,
-      "accuracy": "medium",
-      "speed": "fast",
-      "installed": false,
-    }),
-    serde_json::json!({
-      "id": "whisper-base",
-      "name": "Whisper Base",
-      "size": 145000000,
-      "accuracy": "medium",
-      "speed": "fast",
-      "installed": false,
-    }),
-    serde_json::json!({
-      "id": "whisper-tiny",
-      "name": "Whisper Tiny",
-      "size": 39000000,
-      "accuracy": "low",
-      "speed": "fast",
-      "installed": false,
-    }),
-  ])
+async fn start_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  on_event: Channel<TranscriptionSessionEvent>,
+  options: Option<StreamingOptions>,
+  preprocess: Option<PreprocessOptions>,
+) -> Result<u32, AudioError> {
+  app
+    .state::<AppDataWhisperProcessor>()
+    .default_model()?;
+  let preprocessor = preprocess
+    .map(|preprocess| {
+      tauri_plugin_ipc_audio_preprocess::new_preprocessor(&app, SAMPLE_RATE as u32, &preprocess)
+    })
+    .transpose()?;
+
+  let sessions = app.state::<AppDataTranscriptionSessions>();
+  sessions.remove_idle();
+  let session_id = sessions.next_id.fetch_add(1, Ordering::Relaxed);
+  sessions.sessions.lock().unwrap().insert(
+    session_id,
+    Arc::new(Mutex::new(TranscriptionSession {
+      transcriber: StreamingTranscriber::new(options.unwrap_or_default()),
+      channel: on_event,
+      preprocessor,
+      last_used: Instant::now(),
+    })),
+  );
+
+  info!("Transcription session {} started", session_id);
+  Ok(session_id)
+}
+
+#[tauri::command]
+async fn push_audio<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+  pcm: Vec<f32>,
+) -> Result<(), AudioError> {
+  let session = app
+    .state::<AppDataTranscriptionSessions>()
+    .get(session_id)?;
+  let mut session = session.lock().unwrap();
+  session.last_used = Instant::now();
+
+  let pcm = match &mut session.preprocessor {
+    Some(preprocessor) => preprocessor.process(&pcm),
+    None => pcm,
+  };
+  if !session.transcriber.append(&pcm) {
+    return Ok(());
+  }
+
+  // With a separate partial model loaded, the default one only runs when finals are due
+  let data = app.state::<AppDataWhisperProcessor>();
+  let events = match data.get(ModelSlot::Partial) {
+    Some(partial) if !session.transcriber.final_due() => {
+      let mut processor = partial.pipeline.lock().unwrap();
+      session
+        .transcriber
+        .process_partial(&mut *processor)
+    },
+    _ => {
+      let loaded = data.default_model()?;
+      let mut processor = loaded.pipeline.lock().unwrap();
+      session.transcriber.process(&mut *processor)
+    },
+  }
+  .map_err(AudioError::inference_failed)?;
+
+  session.send(events)
+}
+
+#[tauri::command]
+async fn end_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+) -> Result<String, AudioError> {
+  let sessions = app.state::<AppDataTranscriptionSessions>();
+  let session = sessions
+    .sessions
+    .lock()
+    .unwrap()
+    .remove(&session_id)
+    .ok_or_else(|| {
+      AudioError::NotFound(format!(
+        "Transcription session {} does not exist",
+        session_id
+      ))
+    })?;
+  let mut session = session.lock().unwrap();
+
+  let events = {
+    let loaded = app
+      .state::<AppDataWhisperProcessor>()
+      .default_model()?;
+    let mut processor = loaded.pipeline.lock().unwrap();
+
+    session
+      .transcriber
+      .flush(&mut *processor)
+      .map_err(AudioError::inference_failed)?
+  };
+  session.send(events)?;
+
+  info!("Transcription session {} ended", session_id);
+  Ok(session.transcriber.transcript().to_string())
+}
+
+#[tauri::command]
+async fn enqueue_transcription_job<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  path: String,
+  options: Option<TranscriptionJobOptions>,
+) -> Result<u32, AudioError> {
+  let job_id = app.state::<AppDataTranscriptionJobs>().enqueue(
+    &app,
+    path.into(),
+    options.unwrap_or_default(),
+  )?;
+
+  info!("Transcription job {} enqueued", job_id);
+  Ok(job_id)
+}
+
+#[tauri::command]
+async fn cancel_transcription_job<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  job_id: u32,
+) -> Result<(), AudioError> {
+  app
+    .state::<AppDataTranscriptionJobs>()
+    .cancel(job_id)
+}
+
+#[tauri::command]
+async fn list_transcription_jobs<R: Runtime>(
+  app: tauri::AppHandle<R>
+) -> Result<Vec<TranscriptionJobEvent>, AudioError> {
+  Ok(app.state::<AppDataTranscriptionJobs>().list())
+}
+
+fn loaded_models<R: Runtime>(
+  app: &tauri::AppHandle<R>
+) -> Vec<(ModelSlot, WhichModel, ModelVariant)> {
+  let data = app.state::<AppDataWhisperProcessor>();
+
+  [ModelSlot::Default, ModelSlot::Partial]
+    .into_iter()
+    .filter_map(|slot| {
+      data
+        .loaded(slot)
+        .map(|(model, variant)| (slot, model, variant))
+    })
+    .collect()
+}
+
+#[tauri::command]
+async fn list_models<R: Runtime>(app: tauri::AppHandle<R>) -> Result<Vec<ModelInfo>, AudioError> {
+  let loaded = loaded_models(&app);
+
+  WhichModel::value_variants()
+    .iter()
+    .map(|&model| catalog::model_info(model, &loaded))
+    .collect::<anyhow::Result<_>>()
+    .map_err(AudioError::internal)
 }
 
 #[tauri::command]
 async fn list_installed_models<R: Runtime>(
   app: tauri::AppHandle<R>,
-) -> Result<Vec<String>, String> {
-  let data = app.state::<Mutex<AppDataWhisperProcessor>>();
-  let data = data.lock().unwrap();
+) -> Result<Vec<String>, AudioError> {
+  let loaded = loaded_models(&app);
+
+  Ok(
+    WhichModel::value_variants()
+      .iter()
+      .map(|&model| catalog::model_info(model, &loaded))
+      .collect::<anyhow::Result<Vec<_>>>()
+      .map_err(AudioError::internal)?
+      .into_iter()
+      .filter(|info| info.installed)
+      .map(|info| info.id)
+      .collect(),
+  )
+}
+
+#[tauri::command]
+async fn delete_model<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  model_id: String,
+  variant: Option<ModelVariant>,
+) -> Result<u64, AudioError> {
+  let model = WhichModel::from_id(&model_id)?;
 
-  let mut models = Vec::new();
-  if data.whisper_processor.is_some() {
-    models.push("whisper-base".to_string()); // Default loaded model
+  // The sessions keep the files open on Windows, and the model would be gone after a restart
+  if loaded_models(&app)
+    .iter()
+    .any(|&(_, loaded_model, loaded_variant)| {
+      loaded_model == model && variant.is_none_or(|v| v == loaded_variant)
+    })
+  {
+    return Err(AudioError::InvalidInput(format!(
+      "{} is currently loaded, unload it first",
+      model_id
+    )));
   }
 
-  Ok(models)
+  catalog::delete_model(model, variant).map_err(AudioError::internal)
+}
+
+/// Transcribes 16 kHz mono audio with the default model, for plugins that capture audio
+/// in-process instead of sending it over IPC.
+pub fn transcribe<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  audio: &[f32],
+  config: &GenerationConfig,
+) -> Result<TranscriptionResult, AudioError> {
+  let loaded = app
+    .state::<AppDataWhisperProcessor>()
+    .default_model()?;
+  let mut processor = loaded.pipeline.lock().unwrap();
+
+  processor
+    .transcribe(audio, config)
+    .map_err(AudioError::inference_failed)
 }
 
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
   PluginBuilder::new("ipc-audio-transcription-ort")
     .setup(|app, _| {
       info!("Initializing audio transcription plugin...");
-      app.manage(Mutex::new(AppDataWhisperProcessor::default()));
+      app.manage(AppDataWhisperProcessor::default());
+      app.manage(AppDataTranscriptionSessions::default());
+      app.manage(AppDataTranscriptionJobs::new(app.clone()));
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
       load_ort_model_whisper,
+      cancel_model_download,
+      unload_model,
       ipc_audio_transcription,
+      start_session,
+      push_audio,
+      end_session,
+      enqueue_transcription_job,
+      cancel_transcription_job,
+      list_transcription_jobs,
       list_models,
       list_installed_models,
+      delete_model,
     ])
     .build()
 }
@@ -0,0 +1,220 @@
+use std::{
+  collections::HashSet,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use airi_audio_models::ModelRepo;
+use anyhow::{Result, anyhow};
+use log::info;
+use serde::Serialize;
+
+use super::whisper::whisper::{Accuracy, ModelVariant, Speed, WhichModel};
+use crate::slots::ModelSlot;
+
+// Files every variant of a repository needs next to its encoder and decoder
+const SHARED_FILES: [&str; 3] = ["config.json", "tokenizer.json", "tokenizer_config.json"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelVariantInfo {
+  pub variant:      ModelVariant,
+  /// Approximate download size in bytes, estimated from the parameter count
+  pub size:         u64,
+  pub installed:    bool,
+  /// Bytes the encoder and decoder of this variant take on disk
+  pub size_on_disk: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadedVariant {
+  pub slot:    ModelSlot,
+  pub variant: ModelVariant,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+  pub id:           String,
+  pub name:         String,
+  /// Approximate download size in bytes of the default variant
+  pub size:         u64,
+  pub accuracy:     Accuracy,
+  pub speed:        Speed,
+  pub multilingual: bool,
+  pub variants:     Vec<ModelVariantInfo>,
+  /// Whether any variant is complete on disk and can be loaded without a download
+  pub installed:    bool,
+  /// Bytes the whole repository takes in the cache, including partial downloads
+  pub size_on_disk: u64,
+  /// Variants of this model that are currently loaded, and into which slot
+  pub loaded:       Vec<LoadedVariant>,
+}
+
+/// The encoder, decoder and external data files of `variant`.
+fn variant_files(
+  model: WhichModel,
+  variant: ModelVariant,
+) -> Vec<String> {
+  ["encoder_model", "decoder_model"]
+    .iter()
+    .map(|name| variant.onnx_file(name))
+    .chain(model.external_data_files(variant))
+    .collect()
+}
+
+fn file_size(path: &Path) -> u64 {
+  // Snapshot entries are symlinks into `blobs`, the metadata follows them
+  fs::metadata(path).map_or(0, |m| m.len())
+}
+
+fn dir_size(path: &Path) -> u64 {
+  let Ok(entries) = fs::read_dir(path) else {
+    return 0;
+  };
+
+  entries
+    .flatten()
+    .map(|entry| match entry.file_type() {
+      Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+      Ok(file_type) if file_type.is_file() => entry.metadata().map_or(0, |m| m.len()),
+      // Symlinks of the snapshots point to blobs that are counted already
+      _ => 0,
+    })
+    .sum()
+}
+
+/// Blobs the snapshot symlinks under `path` point to.
+fn linked_blobs(path: &Path) -> HashSet<PathBuf> {
+  let Ok(entries) = fs::read_dir(path) else {
+    return HashSet::new();
+  };
+
+  let mut blobs = HashSet::new();
+  for entry in entries.flatten() {
+    match entry.file_type() {
+      Ok(file_type) if file_type.is_dir() => blobs.extend(linked_blobs(&entry.path())),
+      Ok(file_type) if file_type.is_symlink() => {
+        if let Ok(blob_path) = fs::canonicalize(entry.path()) {
+          blobs.insert(blob_path);
+        }
+      },
+      _ => {},
+    }
+  }
+  blobs
+}
+
+fn variant_info(
+  repo: &ModelRepo,
+  model: WhichModel,
+  variant: ModelVariant,
+) -> ModelVariantInfo {
+  let external_data = model.external_data_files(variant);
+  // External data the hub does not have is not needed
+  let installed = variant_files(model, variant)
+    .iter()
+    .map(String::as_str)
+    .chain(SHARED_FILES)
+    .all(|file| {
+      repo.cached(file).is_some()
+        || external_data.iter().any(|data| data == file) && repo.is_missing(file)
+    });
+  let size_on_disk = variant_files(model, variant)
+    .iter()
+    .filter_map(|file| repo.cached(file))
+    .map(|path| file_size(&path))
+    .sum();
+
+  ModelVariantInfo {
+    variant,
+    size: variant.estimated_size(model),
+    installed,
+    size_on_disk,
+  }
+}
+
+/// Looks up which variants of `model` are on disk, in the local models directory or the Hugging
+/// Face cache, the same way loading it does.
+pub fn model_info(
+  model: WhichModel,
+  loaded: &[(ModelSlot, WhichModel, ModelVariant)],
+) -> Result<ModelInfo> {
+  let repo = model.repo()?;
+
+  let variants: Vec<_> = ModelVariant::ALL
+    .iter()
+    .map(|&variant| variant_info(&repo, model, variant))
+    .collect();
+
+  Ok(ModelInfo {
+    id: model.id().to_string(),
+    name: model.name().to_string(),
+    size: ModelVariant::default().estimated_size(model),
+    accuracy: model.accuracy(),
+    speed: model.speed(),
+    multilingual: model.is_multilingual(),
+    installed: variants.iter().any(|v| v.installed),
+    variants,
+    size_on_disk: dir_size(repo.cache_path()),
+    loaded: loaded
+      .iter()
+      .filter(|(_, loaded_model, _)| *loaded_model == model)
+      .map(|&(slot, _, variant)| LoadedVariant { slot, variant })
+      .collect(),
+  })
+}
+
+/// Removes one variant of `model` from the Hugging Face cache, or the whole repository when
+/// `variant` is not given. Files in the local models directory are the user's and stay. Returns
+/// the number of bytes freed.
+pub fn delete_model(
+  model: WhichModel,
+  variant: Option<ModelVariant>,
+) -> Result<u64> {
+  let repo = model.repo()?;
+  let repo_path = repo.cache_path();
+
+  let Some(variant) = variant else {
+    let freed = dir_size(repo_path);
+    if repo_path.exists() {
+      fs::remove_dir_all(repo_path)
+        .map_err(|e| anyhow!("Failed to delete {}: {}", repo_path.display(), e))?;
+    }
+
+    info!("Deleted {} ({} bytes)", model.id(), freed);
+    return Ok(freed);
+  };
+
+  let mut freed = 0;
+  let mut blobs = Vec::new();
+  for file in variant_files(model, variant) {
+    let Some(snapshot_path) = repo.cached_download(&file) else {
+      continue;
+    };
+
+    match fs::canonicalize(&snapshot_path) {
+      Ok(blob_path) if blob_path != snapshot_path => blobs.push(blob_path),
+      _ => freed += file_size(&snapshot_path),
+    }
+
+    fs::remove_file(&snapshot_path)
+      .map_err(|e| anyhow!("Failed to delete {}: {}", snapshot_path.display(), e))?;
+  }
+
+  // Remove the blobs as well, otherwise the space is not reclaimed, but only those no other
+  // snapshot links to: a file that did not change between revisions is stored once
+  let linked = linked_blobs(&repo_path.join("snapshots"));
+  for blob_path in blobs {
+    if linked.contains(&blob_path) || !blob_path.exists() {
+      continue;
+    }
+
+    freed += file_size(&blob_path);
+    fs::remove_file(&blob_path)
+      .map_err(|e| anyhow!("Failed to delete {}: {}", blob_path.display(), e))?;
+  }
+
+  info!("Deleted {} {:?} ({} bytes)", model.id(), variant, freed);
+  Ok(freed)
+}
@@ -0,0 +1,158 @@
+use std::io::Write;
+
+use flate2::{Compression, write::ZlibEncoder};
+use serde::{Deserialize, Serialize};
+
+// Longest token sequence checked for repetition loops
+const MAX_NGRAM: usize = 16;
+// Short interjections like "no, no, no" are fine, loops are only cut once they cover this many
+// tokens
+const MIN_REPETITION_SPAN: usize = 8;
+
+/// Phrases Whisper is known to produce on silence or noise, learned from subtitled videos.
+/// Compared after lowercasing and dropping punctuation.
+const DEFAULT_BLOCKLIST: &[&str] = &[
+  "thank you for watching",
+  "thanks for watching",
+  "thank you so much for watching",
+  "please subscribe",
+  "please like and subscribe",
+  "subtitles by the amaraorg community",
+  "ご視聴ありがとうございました",
+  "字幕by索兰娅",
+  "請不吝點贊 訂閱 轉發 打賞支持明鏡與點點欄目",
+  "untertitel der amaraorg community",
+  "sous-titrage société radio-canada",
+];
+
+/// Checks against hallucinations of greedy decoding, each one can be turned off. The defaults
+/// follow OpenAI's `transcribe()`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GuardConfig {
+  /// Drops the result when the probability of `<|nospeech|>` is above this and the average log
+  /// probability of the tokens is below [`Self::logprob_threshold`]
+  pub no_speech_threshold:         Option<f32>,
+  pub logprob_threshold:           f32,
+  /// Stops decoding once the output ends with a token sequence repeated more often than this in
+  /// a row, and keeps only its first occurrence
+  pub max_ngram_repeats:           Option<usize>,
+  /// Drops the result when its text compresses better than this ratio with zlib, which is what
+  /// loops of whole sentences look like
+  pub compression_ratio_threshold: Option<f32>,
+  /// Drops results that consist of one of these phrases only
+  pub blocklist:                   Vec<String>,
+}
+
+impl Default for GuardConfig {
+  fn default() -> Self {
+    Self {
+      no_speech_threshold:         Some(0.6),
+      logprob_threshold:           -1.0,
+      max_ngram_repeats:           Some(3),
+      compression_ratio_threshold: Some(2.4),
+      blocklist:                   DEFAULT_BLOCKLIST
+        .iter()
+        .map(|phrase| phrase.to_string())
+        .collect(),
+    }
+  }
+}
+
+/// Which guard changed the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Guard {
+  /// The audio is most likely silence, the text was dropped
+  NoSpeech,
+  /// Decoding was stopped in a loop, the text was cut after the first repetition
+  Repetition,
+  /// The text was too repetitive and dropped
+  CompressionRatio,
+  /// The text was a known hallucination and dropped
+  Blocklist,
+}
+
+impl GuardConfig {
+  /// Returns the length `tokens` should be cut to when they end in a repetition loop.
+  pub fn repetition_cut(
+    &self,
+    tokens: &[i64],
+  ) -> Option<usize> {
+    let max_repeats = self.max_ngram_repeats?;
+
+    (1..=MAX_NGRAM.min(tokens.len() / 2)).find_map(|n| {
+      let ngram = &tokens[tokens.len() - n..];
+      let repeats = tokens
+        .rchunks_exact(n)
+        .take_while(|chunk| *chunk == ngram)
+        .count();
+
+      (repeats > max_repeats && repeats * n >= MIN_REPETITION_SPAN)
+        .then(|| tokens.len() - (repeats - 1) * n)
+    })
+  }
+
+  pub fn is_no_speech(
+    &self,
+    no_speech_prob: Option<f32>,
+    avg_logprob: f32,
+  ) -> bool {
+    match (self.no_speech_threshold, no_speech_prob) {
+      (Some(threshold), Some(prob)) => prob > threshold && avg_logprob < self.logprob_threshold,
+      _ => false,
+    }
+  }
+
+  /// Checks the decoded text, returns the guard that rejects it if any.
+  pub fn check_text(
+    &self,
+    text: &str,
+  ) -> Option<Guard> {
+    if let Some(threshold) = self.compression_ratio_threshold
+      && compression_ratio(text) > threshold
+    {
+      return Some(Guard::CompressionRatio);
+    }
+
+    let normalized = normalize(text);
+    if !normalized.is_empty()
+      && self
+        .blocklist
+        .iter()
+        .any(|phrase| normalize(phrase) == normalized)
+    {
+      return Some(Guard::Blocklist);
+    }
+
+    None
+  }
+}
+
+/// Ratio of the UTF-8 length of `text` to its zlib compressed length.
+pub fn compression_ratio(text: &str) -> f32 {
+  let bytes = text.as_bytes();
+  if bytes.is_empty() {
+    return 0.0;
+  }
+
+  let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+  // Writing into a Vec does not fail
+  let compressed = encoder
+    .write_all(bytes)
+    .and_then(|_| encoder.finish())
+    .map_or(bytes.len(), |compressed| compressed.len());
+
+  bytes.len() as f32 / compressed as f32
+}
+
+fn normalize(text: &str) -> String {
+  text
+    .to_lowercase()
+    .chars()
+    .filter(|c| !c.is_ascii_punctuation() && !matches!(c, '。' | '、' | '！' | '？' | '，'))
+    .collect::<String>()
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ")
+}
@@ -1,2 +1,3 @@
+pub mod guards;
 pub mod whisper;
 pub mod whisper_processor;
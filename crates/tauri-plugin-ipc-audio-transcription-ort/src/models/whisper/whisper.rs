@@ -1,29 +1,22 @@
-use std::{borrow::Cow, collections::HashMap, path::PathBuf};
+use std::{borrow::Cow, collections::HashMap, path::Path};
 
+use airi_audio_models::{AudioError, ModelRepo, SessionOptions, create_session};
 use anyhow::{Result, anyhow};
 use clap::ValueEnum;
-use hf_hub::{
-  Repo,
-  RepoType,
-  api::sync::{Api, ApiBuilder},
-};
+use log::debug;
 use ndarray::{Array2, ArrayView3, Axis, s};
 use ort::{
-  execution_providers::{
-    CPUExecutionProvider,
-    CUDAExecutionProvider,
-    CoreMLExecutionProvider,
-    DirectMLExecutionProvider,
-  },
-  session::{Session, SessionInputValue, builder::GraphOptimizationLevel},
+  session::{Session, SessionInputValue},
   value::Value,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tauri::Runtime;
 use tokenizers::Tokenizer;
 
-use super::whisper_processor::WhisperProcessor;
-use crate::helpers::huggingface::create_progress_emitter;
+use super::{
+  guards::{Guard, GuardConfig, compression_ratio},
+  whisper_processor::{SAMPLE_RATE, WhisperProcessor},
+};
 
 // Helper function to provide a default value of true for serde
 const fn default_true() -> bool {
@@ -42,23 +35,96 @@ pub struct WhisperConfig {
   pub no_timestamps_token_id: Option<i64>,
   #[serde(default)]
   pub lang_to_id:             HashMap<String, i64>,
+  #[serde(default = "default_max_target_positions")]
+  pub max_target_positions:   usize,
+  #[serde(default)]
+  pub vocab_size:             Option<usize>,
+  // Every `<|...|>` token from tokenizer_config.json, e.g. <|startofprev|>, <|translate|>
+  #[serde(skip)]
+  pub special_tokens:         HashMap<String, i64>,
+}
+
+const fn default_max_target_positions() -> usize {
+  448
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Task {
+  #[default]
+  Transcribe,
+  Translate,
+}
+
+impl Task {
+  const fn token(self) -> &'static str {
+    match self {
+      Self::Transcribe => "<|transcribe|>",
+      Self::Translate => "<|translate|>",
+    }
+  }
+
+  // Fallback ids of the multilingual vocabulary, used when tokenizer_config.json lacks the tokens
+  const fn fallback_token_id(self) -> i64 {
+    match self {
+      Self::Transcribe => 50359,
+      Self::Translate => 50358,
+    }
+  }
 }
 
 #[derive(Debug)]
 pub struct GenerationConfig {
   pub language:          Option<String>,
-  pub task:              String,
+  pub task:              Task,
   pub return_timestamps: bool,
   pub max_new_tokens:    usize,
+  /// Text placed between `<|startofprev|>` and `<|startoftranscript|>` to condition the decoder,
+  /// e.g. previous context or spelling hints for character names.
+  pub initial_prompt:    Option<String>,
+  /// Words or phrases the model should be biased towards. Prepended to the initial prompt.
+  pub hotwords:          Vec<String>,
+  /// Text the transcript is forced to start with.
+  pub prefix:            Option<String>,
+  pub guards:            GuardConfig,
 }
 
 impl Default for GenerationConfig {
   fn default() -> Self {
     Self {
       language:          Some("en".to_string()),
-      task:              "transcribe".to_string(),
+      task:              Task::Transcribe,
       return_timestamps: true,
       max_new_tokens:    128,
+      initial_prompt:    None,
+      hotwords:          Vec::new(),
+      prefix:            None,
+      guards:            GuardConfig::default(),
+    }
+  }
+}
+
+impl GenerationConfig {
+  /// Builds the prompt text that goes after `<|startofprev|>`, if any.
+  pub fn prompt_text(&self) -> Option<String> {
+    let hotwords = self
+      .hotwords
+      .iter()
+      .map(|w| w.trim())
+      .filter(|w| !w.is_empty())
+      .collect::<Vec<_>>()
+      .join(", ");
+    let initial_prompt = self
+      .initial_prompt
+      .as_deref()
+      .map(str::trim)
+      .unwrap_or_default();
+
+    match (hotwords.is_empty(), initial_prompt.is_empty()) {
+      (true, true) => None,
+      (false, true) => Some(hotwords),
+      (true, false) => Some(initial_prompt.to_string()),
+      (false, false) => Some(format!("{hotwords} {initial_prompt}")),
     }
   }
 }
@@ -199,21 +265,8 @@ pub fn whisper_language_to_code(language: &str) -> Result<String> {
   Err(anyhow!("Language '{}' is not supported.", language))
 }
 
-fn get_or_download_file<R: Runtime>(
-  cache_repo: &hf_hub::CacheRepo,
-  repo: &hf_hub::api::sync::ApiRepo,
-  window: tauri::WebviewWindow<R>,
-  file_sub_path: &str,
-  event_name: &str,
-) -> Result<PathBuf, hf_hub::api::sync::ApiError> {
-  match cache_repo.get(file_sub_path) {
-    Some(p) => Ok(p),
-    None => repo.download_with_progress(
-      file_sub_path,
-      create_progress_emitter(window.clone(), event_name, file_sub_path.to_string()),
-    ),
-  }
-}
+const PROGRESS_EVENT: &str =
+  "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress";
 
 pub struct Whisper {
   encoder_session: Session,
@@ -223,64 +276,79 @@ pub struct Whisper {
 
 impl Whisper {
   pub fn new<R: Runtime>(
-    model_id: &str,
-    revision: &str,
+    repo: &ModelRepo,
+    model: WhichModel,
+    variant: ModelVariant,
     window: tauri::WebviewWindow<R>,
   ) -> Result<Self> {
-    let cache_api = hf_hub::Cache::from_env();
-    let cache_repo = cache_api.repo(Repo::with_revision(
-      model_id.to_string(),
-      RepoType::Model,
-      revision.to_string(),
-    ));
-
-    let api = ApiBuilder::new().with_progress(false).build()?;
-    let repo = api.repo(Repo::with_revision(
-      model_id.to_string(),
-      RepoType::Model,
-      revision.to_string(),
-    ));
-
-    let encoder_model_path = get_or_download_file(
-      &cache_repo,
-      &repo,
+    let encoder_model_path = repo.get(
+      &variant.onnx_file("encoder_model"),
       window.clone(),
-      "onnx/encoder_model.onnx",
-      "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress",
+      PROGRESS_EVENT,
     )?;
-
-    let decoder_model_path = get_or_download_file(
-      &cache_repo,
-      &repo,
+    let decoder_model_path = repo.get(
+      &variant.onnx_file("decoder_model"),
       window.clone(),
-      "onnx/decoder_model.onnx",
-      "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress",
+      PROGRESS_EVENT,
     )?;
 
-    let config_path = match cache_repo.get("config.json") {
-      Some(path) => path,
-      None => repo.download_with_progress(
-        "config.json",
-        create_progress_emitter(
-          window.clone(),
-          "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress",
-          "config.json".to_string(),
-        ),
-      )?,
-    };
+    // The external weights have to sit next to the model, when it has them
+    for data_file in model.external_data_files(variant) {
+      if repo
+        .get_optional(&data_file, window.clone(), PROGRESS_EVENT)?
+        .is_none()
+      {
+        debug!("{} has no external data", data_file);
+      }
+    }
 
-    let tokenizer_config_path = match cache_repo.get("tokenizer_config.json") {
-      Some(path) => path,
-      None => repo.download_with_progress(
-        "tokenizer_config.json",
-        create_progress_emitter(
-          window.clone(),
-          "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress",
-          "tokenizer_config.json".to_string(),
-        ),
-      )?,
-    };
+    let config_path = repo.get("config.json", window.clone(), PROGRESS_EVENT)?;
+    let tokenizer_config_path = repo.get("tokenizer_config.json", window, PROGRESS_EVENT)?;
+
+    Self::from_files(
+      &encoder_model_path,
+      &decoder_model_path,
+      &config_path,
+      &tokenizer_config_path,
+    )
+  }
 
+  /// Loads a model from a local directory laid out like the Hugging Face repositories, with
+  /// `config.json` and `tokenizer_config.json` at the top and the ONNX files in `onnx/`.
+  pub fn from_dir(
+    dir: &Path,
+    variant: ModelVariant,
+  ) -> Result<Self> {
+    let files = [
+      dir.join(variant.onnx_file("encoder_model")),
+      dir.join(variant.onnx_file("decoder_model")),
+      dir.join("config.json"),
+      dir.join("tokenizer_config.json"),
+    ];
+    if let Some(missing) = files.iter().find(|file| !file.is_file()) {
+      return Err(anyhow!("{} does not exist", missing.display()));
+    }
+
+    let [
+      encoder_model_path,
+      decoder_model_path,
+      config_path,
+      tokenizer_config_path,
+    ] = &files;
+    Self::from_files(
+      encoder_model_path,
+      decoder_model_path,
+      config_path,
+      tokenizer_config_path,
+    )
+  }
+
+  fn from_files(
+    encoder_model_path: &Path,
+    decoder_model_path: &Path,
+    config_path: &Path,
+    tokenizer_config_path: &Path,
+  ) -> Result<Self> {
     let encoder_session = Self::create_optimized_session(encoder_model_path)?;
     let decoder_session = Self::create_optimized_session(decoder_model_path)?;
 
@@ -289,6 +357,15 @@ impl Whisper {
       serde_json::from_str(&std::fs::read_to_string(tokenizer_config_path)?)?;
 
     if let Some(added_tokens) = tokenizer_config["added_tokens"].as_array() {
+      config.special_tokens = added_tokens
+        .iter()
+        .filter_map(|token| {
+          let content = token["content"].as_str()?;
+          let id = token["id"].as_i64()?;
+          (content.starts_with("<|") && content.ends_with("|>")).then(|| (content.to_string(), id))
+        })
+        .collect();
+
       let lang_to_id = added_tokens
         .iter()
         .filter_map(|token| {
@@ -308,6 +385,18 @@ impl Whisper {
       config.lang_to_id = lang_to_id;
     }
 
+    // English-only checkpoints have the smaller GPT-2 vocabulary, without language tokens
+    if let Some(vocab_size) = config.vocab_size {
+      config.is_multilingual = vocab_size >= 51865;
+    }
+
+    if config.no_timestamps_token_id.is_none() {
+      config.no_timestamps_token_id = config
+        .special_tokens
+        .get("<|notimestamps|>")
+        .copied();
+    }
+
     Ok(Self {
       encoder_session,
       decoder_session,
@@ -315,34 +404,50 @@ impl Whisper {
     })
   }
 
-  fn create_optimized_session(model_path: PathBuf) -> Result<Session> {
-    let session = Session::builder()?
-      .with_optimization_level(GraphOptimizationLevel::Level3)?
-      .with_parallel_execution(true)?
-      .with_execution_providers([
-        CUDAExecutionProvider::default()
-          .with_device_id(0)
-          .build(),
-        CoreMLExecutionProvider::default().build(),
-        DirectMLExecutionProvider::default()
-          .with_device_id(0)
-          .build(),
-        CPUExecutionProvider::default().build(),
-      ])?
-      .commit_from_file(model_path)?;
-    Ok(session)
+  fn create_optimized_session(model_path: &Path) -> Result<Session> {
+    create_session(
+      model_path,
+      SessionOptions {
+        parallel_execution: true,
+        ..Default::default()
+      },
+    )
   }
 
+  fn special_token_id(
+    &self,
+    token: &str,
+  ) -> Option<i64> {
+    self.config.special_tokens.get(token).copied()
+  }
+
+  /// Builds the decoder prompt:
+  /// `[<|startofprev|>, prompt...] <|startoftranscript|> <|lang|> <|task|> [<|notimestamps|>] [prefix...]`
   fn retrieve_init_tokens(
     &self,
     gen_config: &GenerationConfig,
+    prompt_ids: &[i64],
+    prefix_ids: &[i64],
   ) -> Result<Vec<i64>> {
-    let mut init_tokens = vec![self.config.decoder_start_token_id];
-    let task_id = if gen_config.task == "translate" {
-      50358
-    } else {
-      50359
-    };
+    let mut init_tokens = Vec::new();
+
+    if !prompt_ids.is_empty() {
+      let start_of_prev_id = self
+        .special_token_id("<|startofprev|>")
+        .ok_or_else(|| {
+          anyhow!("Token <|startofprev|> not found, the model does not take prompts")
+        })?;
+
+      // Same as OpenAI's implementation, the prompt may take up at most half of the context,
+      // keeping the most recent tokens when it is too long.
+      let max_prompt_len = self.config.max_target_positions / 2 - 1;
+      let skip = prompt_ids.len().saturating_sub(max_prompt_len);
+
+      init_tokens.push(start_of_prev_id);
+      init_tokens.extend_from_slice(&prompt_ids[skip..]);
+    }
+
+    init_tokens.push(self.config.decoder_start_token_id);
 
     if self.config.is_multilingual {
       let lang = gen_config.language.as_deref().unwrap_or("en");
@@ -354,22 +459,39 @@ impl Whisper {
         .get(&lang_token)
         .ok_or_else(|| anyhow!("Language token not found for: {}", lang_token))?;
       init_tokens.push(*lang_token_id);
+
+      let task_id = self
+        .special_token_id(gen_config.task.token())
+        .unwrap_or_else(|| gen_config.task.fallback_token_id());
+      init_tokens.push(task_id);
+    } else if gen_config.task == Task::Translate {
+      // English-only models are not trained with task tokens
+      return Err(anyhow!(
+        "Translation is not supported by English-only models"
+      ));
     }
-    init_tokens.push(task_id);
 
     if !gen_config.return_timestamps
       && let Some(no_timestamps_id) = self.config.no_timestamps_token_id
     {
       init_tokens.push(no_timestamps_id);
     }
+
+    init_tokens.extend_from_slice(prefix_ids);
+
     Ok(init_tokens)
   }
 
+  /// Greedy decoding. `prompt_ids` and `prefix_ids` are already tokenized text (see
+  /// [`GenerationConfig::initial_prompt`] and [`GenerationConfig::prefix`]), the returned tokens
+  /// start with `prefix_ids`.
   pub fn generate(
     &mut self,
     input_features: ArrayView3<f32>,
     gen_config: &GenerationConfig,
-  ) -> Result<Vec<i64>> {
+    prompt_ids: &[i64],
+    prefix_ids: &[i64],
+  ) -> Result<Generation> {
     let (batch_size, num_mel_bins, sequence_length) = input_features.dim();
     let expected_mel_bins = usize::try_from(self.config.num_mel_bins)?;
 
@@ -383,14 +505,32 @@ impl Whisper {
       ));
     }
 
-    let mut decoder_input_ids = self.retrieve_init_tokens(gen_config)?;
+    let mut decoder_input_ids = self.retrieve_init_tokens(gen_config, prompt_ids, prefix_ids)?;
+    let max_new_tokens = gen_config.max_new_tokens.min(
+      self
+        .config
+        .max_target_positions
+        .saturating_sub(decoder_input_ids.len()),
+    );
+
+    // The model predicts `<|nospeech|>` right after `<|startoftranscript|>`
+    let start_of_transcript_index = decoder_input_ids
+      .iter()
+      .position(|&id| id == self.config.decoder_start_token_id)
+      .unwrap_or_default();
+    let no_speech_id = self
+      .special_token_id("<|nospeech|>")
+      .or_else(|| self.special_token_id("<|nocaptions|>"));
 
     let owned_input = input_features.to_owned();
     let inputs = vec![("input_features", Value::from_array(owned_input)?)];
     let encoder_outputs = self.encoder_session.run(inputs)?;
     let encoder_hidden_states = encoder_outputs.get("last_hidden_state").unwrap();
 
-    let mut generated_tokens = Vec::new();
+    let mut generated_tokens = prefix_ids.to_vec();
+    let mut no_speech_prob = None;
+    let mut sum_logprob = 0.0;
+    let mut repetition = false;
 
     // KV Cache
     // let num_decoder_layers = self.config.decoder_layers as usize;
@@ -399,7 +539,7 @@ impl Whisper {
     //   .map(|_| Array4::<f32>::zeros((1, self.config.decoder_attention_heads as usize, 0, head_dim as usize)))
     //   .collect();
 
-    for _step in 0..gen_config.max_new_tokens {
+    for step in 0..max_new_tokens {
       let decoder_input_ids_array =
         Array2::from_shape_vec((1, decoder_input_ids.len()), decoder_input_ids.clone())?
           .mapv(|x| x);
@@ -422,25 +562,75 @@ impl Whisper {
       let logits_view = logits_ref.try_extract_array::<f32>()?;
       let next_token_logits = logits_view.slice(s![0, -1, ..]);
 
-      let next_token = next_token_logits
+      if step == 0
+        && let Some(no_speech_id) = no_speech_id
+      {
+        let logits = logits_view.slice(s![0, start_of_transcript_index, ..]);
+        let log_sum_exp = log_sum_exp(logits.iter().copied());
+        no_speech_prob = logits
+          .get(usize::try_from(no_speech_id)?)
+          .map(|&logit| (logit - log_sum_exp).exp());
+      }
+
+      let (next_token, next_token_logit) = next_token_logits
         .iter()
         .enumerate()
         .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-        .map(|(index, _)| i64::try_from(index).unwrap())
+        .map(|(index, &logit)| (i64::try_from(index).unwrap(), logit))
         .unwrap();
+      sum_logprob += next_token_logit - log_sum_exp(next_token_logits.iter().copied());
 
       if next_token == self.config.eos_token_id {
         break;
       }
 
       generated_tokens.push(next_token);
-      decoder_input_ids = vec![next_token];
+      // decoder_model.onnx has no past_key_values inputs, so every step runs the whole sequence
+      // again: feeding only `[next_token]` would decode without the prompt, the prefix and the
+      // tokens so far. That makes a transcript quadratic in its length, which the merged decoder
+      // with a KV cache (see above) would make linear.
+      decoder_input_ids.push(next_token);
+
+      // Greedy decoding does not get out of a loop once it is in one
+      if let Some(cut) = gen_config
+        .guards
+        .repetition_cut(&generated_tokens[prefix_ids.len()..])
+      {
+        generated_tokens.truncate(prefix_ids.len() + cut);
+        repetition = true;
+        break;
+      }
     }
 
-    Ok(generated_tokens)
+    let num_generated = generated_tokens.len() - prefix_ids.len();
+    Ok(Generation {
+      tokens: generated_tokens,
+      no_speech_prob,
+      // The end of text token counts as well, so this is defined for empty outputs too
+      avg_logprob: sum_logprob / (num_generated + 1) as f32,
+      repetition,
+    })
   }
 }
 
+fn log_sum_exp(values: impl Iterator<Item = f32> + Clone) -> f32 {
+  let max = values.clone().fold(f32::NEG_INFINITY, f32::max);
+  max + values.map(|v| (v - max).exp()).sum::<f32>().ln()
+}
+
+/// Output of [`Whisper::generate`].
+#[derive(Clone, Debug)]
+pub struct Generation {
+  pub tokens:         Vec<i64>,
+  /// Probability of `<|nospeech|>` at the start of the transcript, `None` when the model does
+  /// not have the token
+  pub no_speech_prob: Option<f32>,
+  /// Average log probability of the generated tokens
+  pub avg_logprob:    f32,
+  /// Decoding was stopped in a repetition loop
+  pub repetition:     bool,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
 pub enum WhichModel {
   Tiny,
@@ -449,6 +639,9 @@ pub enum WhichModel {
   Medium,
   LargeV3,
   LargeV3Turbo,
+  DistilSmallEn,
+  DistilMediumEn,
+  DistilLargeV3,
 }
 
 impl WhichModel {
@@ -460,8 +653,235 @@ impl WhichModel {
       Self::Medium => ("onnx-community/whisper-medium-ONNX", "main"),
       Self::LargeV3 => ("onnx-community/whisper-large-v3-ONNX", "main"),
       Self::LargeV3Turbo => ("onnx-community/whisper-large-v3-turbo-ONNX", "main"),
+      // The Transformers.js exports, laid out like the onnx-community repositories
+      Self::DistilSmallEn => ("Xenova/distil-whisper-small.en", "main"),
+      Self::DistilMediumEn => ("Xenova/distil-whisper-medium.en", "main"),
+      Self::DistilLargeV3 => ("Xenova/distil-whisper-large-v3", "main"),
+    }
+  }
+
+  /// The repository of the model, where its files are looked up and downloaded to.
+  pub fn repo(self) -> Result<ModelRepo> {
+    let (model_id, revision) = self.model_and_revision();
+    ModelRepo::new(model_id, revision)
+  }
+
+  /// `.onnx_data` files the encoder and decoder of `variant` may keep their weights in. Files over
+  /// the 2 GB protobuf limit have to, which only the fp32 files of models over 2 GB in fp32 can
+  /// reach. Whether they do is up to the export.
+  pub fn external_data_files(
+    self,
+    variant: ModelVariant,
+  ) -> Vec<String> {
+    if variant != ModelVariant::Fp32 || variant.estimated_size(self) <= 2 << 30 {
+      return Vec::new();
+    }
+
+    ["encoder_model", "decoder_model"]
+      .iter()
+      .map(|name| format!("{}_data", variant.onnx_file(name)))
+      .collect()
+  }
+
+  /// The id used by `list_models` and accepted by `load_ort_model_whisper`.
+  pub const fn id(self) -> &'static str {
+    match self {
+      Self::Tiny => "whisper-tiny",
+      Self::Base => "whisper-base",
+      Self::Small => "whisper-small",
+      Self::Medium => "whisper-medium",
+      Self::LargeV3 => "whisper-large-v3",
+      Self::LargeV3Turbo => "whisper-large-v3-turbo",
+      Self::DistilSmallEn => "distil-whisper-small.en",
+      Self::DistilMediumEn => "distil-whisper-medium.en",
+      Self::DistilLargeV3 => "distil-whisper-large-v3",
+    }
+  }
+
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::Tiny => "Whisper Tiny",
+      Self::Base => "Whisper Base",
+      Self::Small => "Whisper Small",
+      Self::Medium => "Whisper Medium",
+      Self::LargeV3 => "Whisper Large v3",
+      Self::LargeV3Turbo => "Whisper Large v3 Turbo",
+      Self::DistilSmallEn => "Distil-Whisper Small (English)",
+      Self::DistilMediumEn => "Distil-Whisper Medium (English)",
+      Self::DistilLargeV3 => "Distil-Whisper Large v3",
+    }
+  }
+
+  /// Parameter count of the encoder and decoder together, as published with the models.
+  pub const fn parameters(self) -> u64 {
+    match self {
+      Self::Tiny => 39_000_000,
+      Self::Base => 74_000_000,
+      Self::Small => 244_000_000,
+      Self::Medium => 769_000_000,
+      Self::LargeV3 => 1_550_000_000,
+      Self::LargeV3Turbo => 809_000_000,
+      Self::DistilSmallEn => 166_000_000,
+      Self::DistilMediumEn => 394_000_000,
+      Self::DistilLargeV3 => 756_000_000,
+    }
+  }
+
+  pub const fn accuracy(self) -> Accuracy {
+    match self {
+      Self::Tiny => Accuracy::Low,
+      Self::Base | Self::Small | Self::DistilSmallEn | Self::DistilMediumEn => Accuracy::Medium,
+      Self::Medium | Self::LargeV3 | Self::LargeV3Turbo | Self::DistilLargeV3 => Accuracy::High,
+    }
+  }
+
+  /// Expected speed of the fp32 files on a CPU. The decoder runs without a KV cache, so every
+  /// generated token costs a pass over the whole sequence and the decoder size matters most:
+  /// large-v3-turbo and distil-large-v3 keep the large encoder but only have 4 and 2 decoder
+  /// layers.
+  pub const fn speed(self) -> Speed {
+    match self {
+      Self::Tiny | Self::Base | Self::DistilSmallEn => Speed::Fast,
+      Self::Small | Self::LargeV3Turbo | Self::DistilMediumEn | Self::DistilLargeV3 => {
+        Speed::Medium
+      },
+      Self::Medium | Self::LargeV3 => Speed::Slow,
+    }
+  }
+
+  /// Whether the model can transcribe other languages than English, and translate.
+  pub const fn is_multilingual(self) -> bool {
+    !matches!(self, Self::DistilSmallEn | Self::DistilMediumEn)
+  }
+
+  /// Accepts [`Self::id`] as well as the command line names, e.g. `large-v3` or `largev3`.
+  pub fn from_id(id: &str) -> Result<Self, AudioError> {
+    let normalize = |id: &str| {
+      id.to_lowercase()
+        .replace("whisper", "")
+        .replace(|c: char| !c.is_ascii_alphanumeric(), "")
+    };
+
+    let normalized = normalize(id);
+    Self::value_variants()
+      .iter()
+      .copied()
+      .find(|model| normalize(model.id()) == normalized)
+      .ok_or_else(|| AudioError::InvalidInput(format!("Unknown Whisper model: {}", id)))
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Accuracy {
+  Low,
+  Medium,
+  High,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Speed {
+  Fast,
+  Medium,
+  Slow,
+}
+
+/// Precision of the ONNX files to load. Every variant comes from the same repository, e.g.
+/// `onnx/encoder_model_q4.onnx` next to `onnx/encoder_model.onnx`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelVariant {
+  #[default]
+  Fp32,
+  /// Half the size of fp32, only faster on GPUs, CPUs usually run it slower than fp32
+  Fp16,
+  /// Dynamically quantized weights, a quarter of fp32 and faster on CPUs
+  Int8,
+  Uint8,
+  /// 4-bit weights for the matrix multiplications, the smallest download with the largest
+  /// accuracy loss
+  Q4,
+}
+
+impl ModelVariant {
+  pub const ALL: [Self; 5] = [Self::Fp32, Self::Fp16, Self::Int8, Self::Uint8, Self::Q4];
+
+  pub const fn file_suffix(self) -> &'static str {
+    match self {
+      Self::Fp32 => "",
+      Self::Fp16 => "_fp16",
+      Self::Int8 => "_int8",
+      Self::Uint8 => "_uint8",
+      Self::Q4 => "_q4",
+    }
+  }
+
+  /// Path of an ONNX file in the repository, `name` is e.g. `encoder_model`.
+  pub fn onnx_file(
+    self,
+    name: &str,
+  ) -> String {
+    format!("onnx/{}{}.onnx", name, self.file_suffix())
+  }
+
+  /// Rough bytes per weight on disk. Quantized files keep some tensors like embeddings and
+  /// layer norms in fp32, so they are a bit larger than the bit width alone suggests.
+  pub const fn bytes_per_parameter(self) -> f64 {
+    match self {
+      Self::Fp32 => 4.0,
+      Self::Fp16 => 2.0,
+      Self::Int8 | Self::Uint8 => 1.1,
+      Self::Q4 => 0.75,
     }
   }
+
+  /// Approximate download size of the encoder and decoder of `model` in this variant.
+  pub fn estimated_size(
+    self,
+    model: WhichModel,
+  ) -> u64 {
+    (model.parameters() as f64 * self.bytes_per_parameter()) as u64
+  }
+}
+
+/// Seconds represented by one timestamp token step, `<|0.00|>`, `<|0.02|>`, ...
+const TIMESTAMP_PRECISION: f32 = 0.02;
+
+/// A piece of transcript delimited by timestamp tokens.
+#[derive(Clone, Debug)]
+pub struct Segment {
+  /// Start time in seconds, relative to the beginning of the transcribed audio
+  pub start:  f32,
+  /// End time in seconds, relative to the beginning of the transcribed audio
+  pub end:    f32,
+  pub text:   String,
+  /// Text tokens of the segment, without timestamp or other special tokens
+  pub tokens: Vec<i64>,
+}
+
+/// Result of [`WhisperPipeline::transcribe_segments`].
+#[derive(Clone, Debug)]
+pub struct Transcription {
+  pub segments:       Vec<Segment>,
+  /// The last guard that changed the result, if any
+  pub guard:          Option<Guard>,
+  pub no_speech_prob: Option<f32>,
+  pub avg_logprob:    f32,
+}
+
+/// Result of [`WhisperPipeline::transcribe`].
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptionResult {
+  /// Empty when the transcript was rejected by a guard
+  pub text:              String,
+  /// The guard that changed the result, if any
+  pub guard:             Option<Guard>,
+  pub no_speech_prob:    Option<f32>,
+  pub avg_logprob:       f32,
+  /// Compression ratio of the text before it was checked, see [`GuardConfig`]
+  pub compression_ratio: f32,
 }
 
 /// A pipeline that encapsulates the full Whisper transcription process.
@@ -473,37 +893,35 @@ pub struct WhisperPipeline {
 
 impl WhisperPipeline {
   pub fn new<R: Runtime>(
-    which_model: WhichModel,
-    model_id: &str,
-    revision: &str,
+    model: WhichModel,
+    variant: ModelVariant,
     window: tauri::WebviewWindow<R>,
   ) -> Result<Self> {
-    let model = Whisper::new(model_id, revision, window.clone())?;
-
-    // Initialize our new processor
-    let processor = WhisperProcessor::new(which_model)?;
-
-    let cache_api = hf_hub::Cache::from_env();
-    let cache_repo = cache_api.repo(Repo::with_revision(
-      model_id.to_string(),
-      RepoType::Model,
-      revision.to_string(),
-    ));
-
-    let api = Api::new()?;
-    let repo = api.repo(hf_hub::Repo::with_revision(
-      model_id.to_string(),
-      hf_hub::RepoType::Model,
-      revision.to_string(),
-    ));
-
-    let tokenizer_path = get_or_download_file(
-      &cache_repo,
-      &repo,
-      window.clone(),
-      "tokenizer.json",
-      "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:load-model-whisper-progress",
-    )?;
+    let repo = model.repo()?;
+    let model = Whisper::new(&repo, model, variant, window.clone())?;
+    let tokenizer_path = repo.get("tokenizer.json", window, PROGRESS_EVENT)?;
+
+    Self::with_model(model, &tokenizer_path)
+  }
+
+  /// Loads a pipeline from a local directory, see [`Whisper::from_dir`]. `tokenizer.json` is
+  /// expected next to `config.json`. Nothing is downloaded.
+  pub fn from_dir(
+    dir: impl AsRef<Path>,
+    variant: ModelVariant,
+  ) -> Result<Self> {
+    let dir = dir.as_ref();
+    let model = Whisper::from_dir(dir, variant)?;
+
+    Self::with_model(model, &dir.join("tokenizer.json"))
+  }
+
+  fn with_model(
+    model: Whisper,
+    tokenizer_path: &Path,
+  ) -> Result<Self> {
+    // The mel front end has to produce as many bins as the encoder was trained with
+    let processor = WhisperProcessor::new(usize::try_from(model.config.num_mel_bins)?)?;
 
     let tokenizer = Tokenizer::from_file(tokenizer_path)
       .map_err(|e| anyhow!("Failed to load tokenizer: {}", e))?;
@@ -519,29 +937,189 @@ impl WhisperPipeline {
     &mut self,
     audio: &[f32],
     gen_config: &GenerationConfig,
-  ) -> Result<String> {
+  ) -> Result<TranscriptionResult> {
+    let generation = self.generate_tokens(audio, gen_config)?;
+    let text = self.decode_tokens(&generation.tokens)?;
+    let compression_ratio = compression_ratio(&text);
+
+    let guard = if gen_config
+      .guards
+      .is_no_speech(generation.no_speech_prob, generation.avg_logprob)
+    {
+      Some(Guard::NoSpeech)
+    } else {
+      gen_config.guards.check_text(&text)
+    };
+
+    let (text, guard) = match guard {
+      // Rejected outright, nothing of the text is kept
+      Some(guard) => (String::new(), Some(guard)),
+      None => (text, generation.repetition.then_some(Guard::Repetition)),
+    };
+
+    Ok(TranscriptionResult {
+      text,
+      guard,
+      no_speech_prob: generation.no_speech_prob,
+      avg_logprob: generation.avg_logprob,
+      compression_ratio,
+    })
+  }
+
+  /// Transcribes audio and splits the result by the timestamp tokens Whisper emits when
+  /// [`GenerationConfig::return_timestamps`] is enabled. Without any timestamp tokens in the
+  /// output, a single segment spanning the whole audio is returned.
+  ///
+  /// Nothing is returned when the audio is considered silence, and segments rejected by
+  /// [`GuardConfig::check_text`] are left out.
+  pub fn transcribe_segments(
+    &mut self,
+    audio: &[f32],
+    gen_config: &GenerationConfig,
+  ) -> Result<Transcription> {
+    let generation = self.generate_tokens(audio, gen_config)?;
+    let mut transcription = Transcription {
+      segments:       Vec::new(),
+      guard:          generation.repetition.then_some(Guard::Repetition),
+      no_speech_prob: generation.no_speech_prob,
+      avg_logprob:    generation.avg_logprob,
+    };
+
+    if gen_config
+      .guards
+      .is_no_speech(generation.no_speech_prob, generation.avg_logprob)
+    {
+      transcription.guard = Some(Guard::NoSpeech);
+      return Ok(transcription);
+    }
+
+    for segment in self.split_segments(audio, generation.tokens)? {
+      match gen_config.guards.check_text(&segment.text) {
+        Some(guard) => {
+          debug!("Dropped segment {:?}: {:?}", guard, segment.text);
+          transcription.guard = Some(guard);
+        },
+        None => transcription.segments.push(segment),
+      }
+    }
+
+    Ok(transcription)
+  }
+
+  fn split_segments(
+    &self,
+    audio: &[f32],
+    generated_tokens: Vec<i64>,
+  ) -> Result<Vec<Segment>> {
+    let audio_duration = audio.len() as f32 / SAMPLE_RATE as f32;
+
+    let Some(timestamp_begin) = self
+      .model
+      .config
+      .no_timestamps_token_id
+      .map(|id| id + 1)
+    else {
+      return Ok(vec![Segment {
+        start:  0.0,
+        end:    audio_duration,
+        text:   self.decode_tokens(&generated_tokens)?,
+        tokens: generated_tokens,
+      }]);
+    };
+
+    let mut segments = Vec::new();
+    let mut start = 0.0;
+    let mut tokens = Vec::new();
+    for token in generated_tokens {
+      if token < timestamp_begin {
+        tokens.push(token);
+        continue;
+      }
+
+      let time = (token - timestamp_begin) as f32 * TIMESTAMP_PRECISION;
+      if !tokens.is_empty() {
+        segments.push(Segment {
+          start,
+          end: time,
+          text: self.decode_tokens(&tokens)?,
+          tokens: std::mem::take(&mut tokens),
+        });
+      }
+      start = time;
+    }
+
+    // Whisper did not close the last segment, it lasts until the end of the audio
+    if !tokens.is_empty() {
+      segments.push(Segment {
+        start,
+        end: audio_duration.max(start),
+        text: self.decode_tokens(&tokens)?,
+        tokens,
+      });
+    }
+
+    Ok(segments)
+  }
+
+  fn generate_tokens(
+    &mut self,
+    audio: &[f32],
+    gen_config: &GenerationConfig,
+  ) -> Result<Generation> {
     // 1. Process the raw audio into a mel spectrogram with the correct shape [80, 3000] for normal, and [128, 3000] for large-v3
     let input_features = self.processor.process(audio);
 
     // 2. Add the batch dimension, making the shape [1, 80, 3000] for normal, and [1, 128, 3000] for large-v3
     let input_features = input_features.insert_axis(Axis(0));
 
-    // 3. Generate tokens. This will now work without a shape error.
-    let generated_tokens = self
+    // 3. Tokenize the prompt and the forced prefix, if any
+    let prompt_ids = match gen_config.prompt_text() {
+      Some(prompt) => self.encode_text(&prompt)?,
+      None => Vec::new(),
+    };
+    let prefix_ids = match gen_config.prefix.as_deref() {
+      Some(prefix) if !prefix.trim().is_empty() => self.encode_text(prefix)?,
+      _ => Vec::new(),
+    };
+
+    // 4. Generate tokens. This will now work without a shape error.
+    self
       .model
-      .generate(input_features.view(), gen_config)?;
+      .generate(input_features.view(), gen_config, &prompt_ids, &prefix_ids)
+  }
 
-    // The rest of the function remains the same...
-    let generated_tokens_u32: Vec<u32> = generated_tokens
+  /// Decodes tokens into text, skipping special and timestamp tokens.
+  pub fn decode_tokens(
+    &self,
+    tokens: &[i64],
+  ) -> Result<String> {
+    let tokens_u32: Vec<u32> = tokens
       .iter()
       .map(|&x| u32::try_from(x).unwrap())
       .collect();
 
-    let transcript = self
+    self
       .tokenizer
-      .decode(&generated_tokens_u32, true)
-      .map_err(|e| anyhow!("Failed to decode tokens: {}", e))?;
+      .decode(&tokens_u32, true)
+      .map_err(|e| anyhow!("Failed to decode tokens: {}", e))
+  }
+
+  /// Tokenizes text the way Whisper expects it inside prompts, with a leading space.
+  fn encode_text(
+    &self,
+    text: &str,
+  ) -> Result<Vec<i64>> {
+    let encoding = self
+      .tokenizer
+      .encode(format!(" {}", text.trim()), false)
+      .map_err(|e| anyhow!("Failed to encode text: {}", e))?;
 
-    Ok(transcript)
+    Ok(
+      encoding
+        .get_ids()
+        .iter()
+        .map(|&id| i64::from(id))
+        .collect(),
+    )
   }
 }
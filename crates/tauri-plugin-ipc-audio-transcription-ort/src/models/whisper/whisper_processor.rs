@@ -1,134 +1,199 @@
-use anyhow::{Result, anyhow};
-use byteorder::{ByteOrder, LittleEndian};
-use ndarray::{Array1, Array2, s};
-use rustfft::{FftPlanner, num_complex::Complex};
+use std::sync::Arc;
 
-use super::whisper::WhichModel;
+use anyhow::{Result, anyhow};
+use ndarray::{Array2, s};
+use realfft::{RealFftPlanner, RealToComplex};
 
 // Constants from the Whisper paper/implementation
-const SAMPLE_RATE: usize = 16000;
-const N_FFT: usize = 400;
-const N_MELS: usize = 80; // <--- Crucial: Use 80 for base, 128 for large-v3
-const HOP_LENGTH: usize = 160;
+pub const SAMPLE_RATE: usize = 16000;
+pub const N_FFT: usize = 400;
+pub const HOP_LENGTH: usize = 160;
 const CHUNK_LENGTH: usize = 30;
-const N_SAMPLES: usize = CHUNK_LENGTH * SAMPLE_RATE; // 480000 samples
-const N_FRAMES: usize = N_SAMPLES / HOP_LENGTH; // 3000 frames
+pub const N_SAMPLES: usize = CHUNK_LENGTH * SAMPLE_RATE; // 480000 samples
+pub const N_FRAMES: usize = N_SAMPLES / HOP_LENGTH; // 3000 frames
+const N_FREQS: usize = N_FFT / 2 + 1; // 201 bins
 
 pub struct WhisperProcessor {
   mel_filters: Array2<f32>,
+  window:      Vec<f32>,
+  fft:         Arc<dyn RealToComplex<f32>>,
 }
 
 impl WhisperProcessor {
-  pub fn new(which_model: WhichModel) -> Result<Self> {
-    // Load the 80-bin mel filters
-    if which_model == WhichModel::LargeV3 || which_model == WhichModel::LargeV3Turbo {
-      let mel_bytes = include_bytes!("./melfilters128.bytes");
-      let mut mel_filters_vec = vec![0f32; mel_bytes.len() / 4];
-      LittleEndian::read_f32_into(mel_bytes, &mut mel_filters_vec);
-
-      // The shape is [80, 201]. N_FFT/2 + 1 = 201.
-      let mel_filters = Array2::from_shape_vec((N_MELS, N_FFT / 2 + 1), mel_filters_vec)
-        .map_err(|e| anyhow!("Failed to create mel filters array: {}", e))?;
-
-      Ok(Self { mel_filters })
-    } else {
-      let mel_bytes = include_bytes!("./melfilters.bytes");
-      let mut mel_filters_vec = vec![0f32; mel_bytes.len() / 4];
-      LittleEndian::read_f32_into(mel_bytes, &mut mel_filters_vec);
+  /// Creates a processor for `num_mel_bins` mel bins, which comes from `config.json` of the model:
+  /// 80 for most Whisper models, 128 for large-v3 and large-v3-turbo.
+  pub fn new(num_mel_bins: usize) -> Result<Self> {
+    if num_mel_bins == 0 {
+      return Err(anyhow!("num_mel_bins must be greater than zero"));
+    }
 
-      // The shape is [80, 201]. N_FFT/2 + 1 = 201.
-      let mel_filters = Array2::from_shape_vec((N_MELS, N_FFT / 2 + 1), mel_filters_vec)
-        .map_err(|e| anyhow!("Failed to create mel filters array: {}", e))?;
+    // Periodic Hann window, the same as torch.hann_window
+    let window = (0..N_FFT)
+      .map(|i| 0.5 * (1.0 - (2.0 * std::f32::consts::PI * i as f32 / N_FFT as f32).cos()))
+      .collect();
 
-      Ok(Self { mel_filters })
-    }
+    Ok(Self {
+      mel_filters: mel_filters(SAMPLE_RATE, N_FFT, num_mel_bins),
+      window,
+      fft: RealFftPlanner::<f32>::new().plan_fft_forward(N_FFT),
+    })
+  }
+
+  pub fn num_mel_bins(&self) -> usize {
+    self.mel_filters.nrows()
   }
 
-  /// Processes raw audio PCM data into a mel spectrogram.
+  /// Processes raw audio PCM data into a `[num_mel_bins, 3000]` log-mel spectrogram. The audio is
+  /// padded with silence or truncated to 30 seconds, like Whisper expects.
   pub fn process(
     &self,
     audio: &[f32],
   ) -> Array2<f32> {
-    // 1. Pad or truncate the audio to 30 seconds
-    let mut pcm_data = audio.to_vec();
-    if pcm_data.len() < N_SAMPLES {
-      pcm_data.resize(N_SAMPLES, 0.0);
-    } else {
-      pcm_data.truncate(N_SAMPLES);
-    }
-    let pcm_data = Array1::from_vec(pcm_data);
+    let audio = &audio[..audio.len().min(N_SAMPLES)];
 
-    // 2. Compute the Short-Time Fourier Transform (STFT)
-    let stft = self.stft(&pcm_data);
-    // 3. Apply the mel filter bank
-    let mel_spectrogram = self.mel_filters.dot(&stft);
+    // 1. Only frames that overlap the audio are computed, the rest of the 30 seconds is silence
+    let n_frames = if audio.len() == N_SAMPLES {
+      N_FRAMES
+    } else {
+      (audio.len() + N_FFT / 2)
+        .div_ceil(HOP_LENGTH)
+        .min(N_FRAMES)
+    };
+
+    // 2. Compute the power spectrogram, [n_frames, 201]
+    let power = self.power_spectrogram(audio, n_frames);
+    // 3. Apply the mel filter bank, [n_frames, num_mel_bins]
+    let mel_spectrogram = power.dot(&self.mel_filters.t());
     // 4. Apply logarithmic scaling
     self.log_mel_spectrogram(&mel_spectrogram)
   }
 
-  /// Computes the Short-Time Fourier Transform (STFT) of the input audio.
-  fn stft(
+  /// Computes the power of the Short-Time Fourier Transform for the first `n_frames` frames.
+  fn power_spectrogram(
     &self,
-    pcm_data: &Array1<f32>,
+    audio: &[f32],
+    n_frames: usize,
   ) -> Array2<f32> {
-    // Create a Hann window
-    let window: Array1<f32> = Array1::from_shape_fn(N_FFT, |i| {
-      0.5 * (1.0 - (2.0 * std::f32::consts::PI * i as f32 / N_FFT as f32).cos())
-    });
-
-    // Pad the input data
-    let mut padded_data = Array1::zeros(pcm_data.len() + N_FFT);
-
-    let end = padded_data.len() - N_FFT / 2;
-    padded_data
-      .slice_mut(s![N_FFT / 2..end])
-      .assign(pcm_data);
-
-    let frames = padded_data
-      .windows(N_FFT)
-      .into_iter()
-      .step_by(HOP_LENGTH);
-
-    let mut planner = FftPlanner::<f32>::new();
-    let fft = planner.plan_fft_forward(N_FFT);
-
-    // Process each frame
-    let mut stft_result = Array2::zeros((N_FFT / 2 + 1, N_FRAMES));
-    for (i, frame) in frames.enumerate() {
-      if i >= N_FRAMES {
-        break;
+    // Center the frames on the samples by reflecting the 30 second signal at both ends, like
+    // torch.stft. Audio shorter than that is followed by zeros, so only the start is reflected.
+    let pad = N_FFT / 2;
+    let padded_len = (n_frames - 1) * HOP_LENGTH + N_FFT;
+    let mut padded = vec![0.0; padded_len];
+    for (i, sample) in padded.iter_mut().enumerate() {
+      let i = i as isize - pad as isize;
+      let i = if i < 0 {
+        -i
+      } else if i >= N_SAMPLES as isize {
+        2 * (N_SAMPLES as isize - 1) - i
+      } else {
+        i
+      } as usize;
+
+      if let Some(&value) = audio.get(i) {
+        *sample = value;
       }
+    }
 
-      // Apply window
-      let windowed_frame = frame.to_owned() * window.view();
-
-      // Prepare buffer for FFT
-      let mut buffer: Vec<Complex<f32>> = windowed_frame
-        .iter()
-        .map(|&x| Complex { re: x, im: 0.0 })
-        .collect();
+    let mut input = self.fft.make_input_vec();
+    let mut output = self.fft.make_output_vec();
+    let mut scratch = self.fft.make_scratch_vec();
+
+    let mut power = Array2::zeros((n_frames, N_FREQS));
+    for (frame, mut row) in power.outer_iter_mut().enumerate() {
+      let start = frame * HOP_LENGTH;
+      for ((input, &sample), &window) in input
+        .iter_mut()
+        .zip(&padded[start..start + N_FFT])
+        .zip(&self.window)
+      {
+        *input = sample * window;
+      }
 
-      // Perform FFT
-      fft.process(&mut buffer);
+      // The buffers are made by the plan itself, their lengths always match
+      self
+        .fft
+        .process_with_scratch(&mut input, &mut output, &mut scratch)
+        .expect("FFT buffers have the planned lengths");
 
-      // Compute magnitude and store it
-      for j in 0..=(N_FFT / 2) {
-        stft_result[[j, i]] = buffer[j].norm_sqr().sqrt();
+      for (power, bin) in row.iter_mut().zip(&output) {
+        *power = bin.norm_sqr();
       }
     }
 
-    stft_result
+    power
   }
 
-  /// Converts a mel spectrogram to a log-scaled mel spectrogram.
+  /// Converts a `[n_frames, num_mel_bins]` mel spectrogram to a `[num_mel_bins, 3000]`
+  /// log-scaled one, frames past `n_frames` are silence.
   fn log_mel_spectrogram(
     &self,
     mel_spec: &Array2<f32>,
   ) -> Array2<f32> {
-    let mut log_spec = mel_spec.mapv(|x| x.max(1e-10).log10());
-    log_spec =
-      log_spec.mapv(|x| x.max(log_spec.fold(f32::NEG_INFINITY, |acc, &v| acc.max(v)) - 8.0));
-    log_spec = (log_spec + 4.0) / 4.0;
-    log_spec
+    let (n_frames, num_mel_bins) = mel_spec.dim();
+    let log_spec = mel_spec.mapv(|x| x.max(1e-10).log10());
+
+    // Silent frames are log10(1e-10) before clamping, they take part in the maximum as well
+    let mut max = log_spec.fold(f32::NEG_INFINITY, |acc, &v| acc.max(v));
+    if n_frames < N_FRAMES {
+      max = max.max(-10.0);
+    }
+    let floor = max - 8.0;
+
+    let mut result = Array2::from_elem((num_mel_bins, N_FRAMES), (floor.max(-10.0) + 4.0) / 4.0);
+    result
+      .slice_mut(s![.., ..n_frames])
+      .assign(&log_spec.t().mapv(|x| (x.max(floor) + 4.0) / 4.0));
+    result
+  }
+}
+
+/// Builds a `[num_mel_bins, n_fft / 2 + 1]` mel filterbank with the Slaney mel scale and area
+/// normalization, the same as `librosa.filters.mel` that OpenAI used for Whisper.
+pub fn mel_filters(
+  sample_rate: usize,
+  n_fft: usize,
+  num_mel_bins: usize,
+) -> Array2<f32> {
+  let n_freqs = n_fft / 2 + 1;
+  let fft_freqs: Vec<f64> = (0..n_freqs)
+    .map(|i| (i * sample_rate) as f64 / n_fft as f64)
+    .collect();
+
+  // Band edges, evenly spaced on the mel scale between 0 Hz and Nyquist
+  let max_mel = hz_to_mel(sample_rate as f64 / 2.0);
+  let mel_freqs: Vec<f64> = (0..num_mel_bins + 2)
+    .map(|i| mel_to_hz(max_mel * i as f64 / (num_mel_bins + 1) as f64))
+    .collect();
+
+  Array2::from_shape_fn((num_mel_bins, n_freqs), |(i, j)| {
+    let lower = (fft_freqs[j] - mel_freqs[i]) / (mel_freqs[i + 1] - mel_freqs[i]);
+    let upper = (mel_freqs[i + 2] - fft_freqs[j]) / (mel_freqs[i + 2] - mel_freqs[i + 1]);
+    let enorm = 2.0 / (mel_freqs[i + 2] - mel_freqs[i]);
+    (lower.min(upper).max(0.0) * enorm) as f32
+  })
+}
+
+// Slaney mel scale: linear below 1 kHz, logarithmic above
+const F_SP: f64 = 200.0 / 3.0;
+const MIN_LOG_HZ: f64 = 1000.0;
+const MIN_LOG_MEL: f64 = MIN_LOG_HZ / F_SP;
+
+fn log_step() -> f64 {
+  6.4f64.ln() / 27.0
+}
+
+fn hz_to_mel(hz: f64) -> f64 {
+  if hz >= MIN_LOG_HZ {
+    MIN_LOG_MEL + (hz / MIN_LOG_HZ).ln() / log_step()
+  } else {
+    hz / F_SP
+  }
+}
+
+fn mel_to_hz(mel: f64) -> f64 {
+  if mel >= MIN_LOG_MEL {
+    MIN_LOG_HZ * (log_step() * (mel - MIN_LOG_MEL)).exp()
+  } else {
+    mel * F_SP
   }
 }
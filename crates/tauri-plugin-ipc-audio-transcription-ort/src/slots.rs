@@ -0,0 +1,152 @@
+use std::sync::{
+  Arc,
+  Mutex,
+  RwLock,
+  atomic::{AtomicU64, Ordering},
+};
+
+use airi_audio_models::AudioError;
+use log::error;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Runtime};
+
+use crate::models::whisper::whisper::{ModelVariant, WhichModel, WhisperPipeline};
+
+pub const MODEL_STATE_EVENT: &str =
+  "tauri-plugins:tauri-plugin-ipc-audio-transcription-ort:model-state";
+
+/// Two models can be loaded at once, an accurate one and a fast one for streaming partials.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelSlot {
+  /// Used for one-shot transcriptions, jobs and the final results of streaming sessions
+  #[default]
+  Default,
+  /// Optional faster model for the partial results of streaming sessions
+  Partial,
+}
+
+pub struct LoadedWhisper {
+  pub model:    WhichModel,
+  pub variant:  ModelVariant,
+  pub pipeline: Mutex<WhisperPipeline>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "state", rename_all = "kebab-case")]
+pub enum ModelState {
+  #[serde(rename_all = "camelCase")]
+  Loading {
+    model_id: String,
+    variant:  ModelVariant,
+  },
+  #[serde(rename_all = "camelCase")]
+  Ready {
+    model_id: String,
+    variant:  ModelVariant,
+  },
+  /// Loading failed, the slot keeps the model it had before.
+  #[serde(rename_all = "camelCase")]
+  Failed {
+    model_id: String,
+    variant:  ModelVariant,
+    error:    String,
+  },
+  Unloaded,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ModelStateEvent {
+  pub slot:  ModelSlot,
+  #[serde(flatten)]
+  pub state: ModelState,
+}
+
+/// Loaded models by slot. Callers clone the `Arc` of a slot and lock its pipeline, so swapping a
+/// slot never waits for a transcription: work that already started finishes on the old model,
+/// which is dropped once the last caller is done with it.
+#[derive(Default)]
+pub struct AppDataWhisperProcessor {
+  default:     RwLock<Option<Arc<LoadedWhisper>>>,
+  partial:     RwLock<Option<Arc<LoadedWhisper>>>,
+  // Bumped by every load request, a finished load only takes the slot if no newer one started
+  generations: [AtomicU64; 2],
+}
+
+impl AppDataWhisperProcessor {
+  fn slot(
+    &self,
+    slot: ModelSlot,
+  ) -> &RwLock<Option<Arc<LoadedWhisper>>> {
+    match slot {
+      ModelSlot::Default => &self.default,
+      ModelSlot::Partial => &self.partial,
+    }
+  }
+
+  pub fn get(
+    &self,
+    slot: ModelSlot,
+  ) -> Option<Arc<LoadedWhisper>> {
+    self.slot(slot).read().unwrap().clone()
+  }
+
+  pub fn default_model(&self) -> Result<Arc<LoadedWhisper>, AudioError> {
+    self
+      .get(ModelSlot::Default)
+      .ok_or_else(|| AudioError::ModelNotLoaded("Whisper model is not loaded".to_string()))
+  }
+
+  pub fn loaded(
+    &self,
+    slot: ModelSlot,
+  ) -> Option<(WhichModel, ModelVariant)> {
+    self
+      .get(slot)
+      .map(|loaded| (loaded.model, loaded.variant))
+  }
+
+  /// Marks the start of a load into `slot`, the returned generation is passed to
+  /// [`Self::finish_load`].
+  pub fn begin_load(
+    &self,
+    slot: ModelSlot,
+  ) -> u64 {
+    self.generations[slot as usize].fetch_add(1, Ordering::SeqCst) + 1
+  }
+
+  /// Swaps the loaded model in, unless another load into the same slot started in the meantime.
+  pub fn finish_load(
+    &self,
+    slot: ModelSlot,
+    generation: u64,
+    loaded: LoadedWhisper,
+  ) -> bool {
+    let mut current = self.slot(slot).write().unwrap();
+    if self.generations[slot as usize].load(Ordering::SeqCst) != generation {
+      return false;
+    }
+
+    *current = Some(Arc::new(loaded));
+    true
+  }
+
+  pub fn unload(
+    &self,
+    slot: ModelSlot,
+  ) -> bool {
+    // Pending loads into this slot are dropped as well
+    self.generations[slot as usize].fetch_add(1, Ordering::SeqCst);
+    self.slot(slot).write().unwrap().take().is_some()
+  }
+}
+
+pub fn emit_model_state<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  slot: ModelSlot,
+  state: ModelState,
+) {
+  if let Err(err) = app.emit(MODEL_STATE_EVENT, ModelStateEvent { slot, state }) {
+    error!("Failed to emit model-state: {:?}", err);
+  }
+}
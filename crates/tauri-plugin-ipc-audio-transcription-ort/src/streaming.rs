@@ -0,0 +1,407 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::models::whisper::{
+  whisper::{GenerationConfig, Segment, Task, WhisperPipeline},
+  whisper_processor::SAMPLE_RATE,
+};
+
+const DEFAULT_PARTIAL_INTERVAL_MS: u32 = 1000;
+const DEFAULT_FINAL_INTERVAL_MS: u32 = 3000;
+const DEFAULT_MAX_BUFFER_SECS: f32 = 15.0;
+const HARD_MAX_BUFFER_SECS: f32 = 28.0;
+// Amount of confirmed text fed back as prompt, in characters
+const MAX_CONTEXT_CHARS: usize = 200;
+// Longest run of confirmed tokens looked for when a hypothesis words them differently
+const MAX_ANCHOR_TOKENS: usize = 8;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamingOptions {
+  pub language:            Option<String>,
+  pub task:                Option<Task>,
+  pub initial_prompt:      Option<String>,
+  pub hotwords:            Option<Vec<String>>,
+  /// How much new audio has to arrive before the model runs again, in milliseconds
+  pub partial_interval_ms: Option<u32>,
+  /// How often the default model confirms text when a separate partial model is loaded, in
+  /// milliseconds. The partial model fills the updates in between.
+  pub final_interval_ms:   Option<u32>,
+  /// Audio buffer length after which confirmed segments are dropped from it, in seconds
+  pub max_buffer_secs:     Option<f32>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum TranscriptionSessionEvent {
+  /// Unconfirmed text following everything confirmed so far, may change with more audio.
+  Partial { text: String },
+  /// Text confirmed by two consecutive hypotheses, it will not change anymore. `start` and `end`
+  /// are seconds since the session started.
+  Final {
+    text:  String,
+    start: f32,
+    end:   f32,
+  },
+}
+
+/// Anything that can produce timestamped segments, implemented by [`WhisperPipeline`].
+pub trait Transcriber {
+  fn transcribe_segments(
+    &mut self,
+    audio: &[f32],
+    gen_config: &GenerationConfig,
+  ) -> Result<Vec<Segment>>;
+
+  fn decode_tokens(
+    &self,
+    tokens: &[i64],
+  ) -> Result<String>;
+}
+
+impl Transcriber for WhisperPipeline {
+  fn transcribe_segments(
+    &mut self,
+    audio: &[f32],
+    gen_config: &GenerationConfig,
+  ) -> Result<Vec<Segment>> {
+    WhisperPipeline::transcribe_segments(self, audio, gen_config).map(|t| t.segments)
+  }
+
+  fn decode_tokens(
+    &self,
+    tokens: &[i64],
+  ) -> Result<String> {
+    WhisperPipeline::decode_tokens(self, tokens)
+  }
+}
+
+/// Incremental transcription over a growing audio buffer using the LocalAgreement-2 policy
+/// from "Turning Whisper into Real-Time Transcription System" (Macháček et al., 2023): the
+/// buffer is re-transcribed every time enough new audio arrived, and the prefix two consecutive
+/// hypotheses agree on is confirmed. Confirmed segments are then cut from the buffer and their
+/// text is used as prompt for the following audio.
+pub struct StreamingTranscriber {
+  options:          StreamingOptions,
+  partial_interval: usize,
+  final_interval:   usize,
+  max_buffer:       usize,
+  buffer:           Vec<f32>,
+  // Samples dropped from the front of the buffer since the session started
+  buffer_offset:    usize,
+  // Samples received since the model last ran
+  pending:          usize,
+  // Samples received since the hypotheses were last compared
+  pending_final:    usize,
+  // Tokens confirmed for the audio still in the buffer, as the last hypothesis worded them
+  committed:        Vec<i64>,
+  // Where the confirmed audio ends in the buffer, in seconds
+  committed_until:  f32,
+  // Unconfirmed tail of the previous hypothesis
+  previous_tail:    Vec<i64>,
+  // Confirmed text of audio that is no longer in the buffer
+  context:          String,
+  transcript:       String,
+}
+
+impl StreamingTranscriber {
+  pub fn new(options: StreamingOptions) -> Self {
+    let partial_interval = options
+      .partial_interval_ms
+      .unwrap_or(DEFAULT_PARTIAL_INTERVAL_MS) as usize
+      * SAMPLE_RATE
+      / 1000;
+    let final_interval = options
+      .final_interval_ms
+      .unwrap_or(DEFAULT_FINAL_INTERVAL_MS) as usize
+      * SAMPLE_RATE
+      / 1000;
+    let max_buffer_secs = options
+      .max_buffer_secs
+      .unwrap_or(DEFAULT_MAX_BUFFER_SECS)
+      .clamp(1.0, HARD_MAX_BUFFER_SECS);
+
+    Self {
+      options,
+      partial_interval: partial_interval.max(1),
+      final_interval,
+      max_buffer: (max_buffer_secs * SAMPLE_RATE as f32) as usize,
+      buffer: Vec::new(),
+      buffer_offset: 0,
+      pending: 0,
+      pending_final: 0,
+      committed: Vec::new(),
+      committed_until: 0.0,
+      previous_tail: Vec::new(),
+      context: String::new(),
+      transcript: String::new(),
+    }
+  }
+
+  /// Appends 16 kHz mono samples, returns whether enough audio arrived to run the model again.
+  pub fn append(
+    &mut self,
+    samples: &[f32],
+  ) -> bool {
+    self.buffer.extend_from_slice(samples);
+    self.pending += samples.len();
+    self.pending_final += samples.len();
+    self.pending >= self.partial_interval
+  }
+
+  /// Whether enough audio arrived since the last [`Self::process`] to confirm text again. Only
+  /// matters when partials come from a separate model through [`Self::process_partial`].
+  pub fn final_due(&self) -> bool {
+    self.pending_final >= self.final_interval
+      || self.buffer.len() as f32 >= HARD_MAX_BUFFER_SECS * SAMPLE_RATE as f32
+  }
+
+  /// Runs a faster model over the buffer for an updated partial result, without confirming
+  /// anything. The confirmed text is forced as prefix so the model only fills in the rest.
+  /// Timestamps are off for it: with them the model expects a timestamp token where the prefix
+  /// starts, and a partial result has no use for segment times anyway.
+  pub fn process_partial(
+    &mut self,
+    transcriber: &mut impl Transcriber,
+  ) -> Result<Vec<TranscriptionSessionEvent>> {
+    self.pending = 0;
+
+    let confirmed = transcriber.decode_tokens(&self.committed)?;
+    let gen_config = GenerationConfig {
+      prefix: Some(confirmed.clone()).filter(|c| !c.trim().is_empty()),
+      return_timestamps: false,
+      ..self.generation_config()
+    };
+
+    let text: String = transcriber
+      .transcribe_segments(&self.buffer, &gen_config)?
+      .iter()
+      .map(|segment| segment.text.as_str())
+      .collect();
+    let text = text
+      .trim_start()
+      .strip_prefix(confirmed.trim())
+      .unwrap_or(&text)
+      .to_string();
+
+    Ok(vec![TranscriptionSessionEvent::Partial { text }])
+  }
+
+  /// Runs the model over the buffer and confirms what the last two hypotheses agree on.
+  pub fn process(
+    &mut self,
+    transcriber: &mut impl Transcriber,
+  ) -> Result<Vec<TranscriptionSessionEvent>> {
+    self.run(transcriber, false)
+  }
+
+  /// Confirms everything left in the buffer, used when the session ends.
+  pub fn flush(
+    &mut self,
+    transcriber: &mut impl Transcriber,
+  ) -> Result<Vec<TranscriptionSessionEvent>> {
+    if self.buffer.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    self.run(transcriber, true)
+  }
+
+  /// Everything confirmed since the session started.
+  pub fn transcript(&self) -> &str {
+    self.transcript.trim()
+  }
+
+  fn generation_config(&self) -> GenerationConfig {
+    let initial_prompt = [
+      self
+        .options
+        .initial_prompt
+        .as_deref()
+        .unwrap_or_default(),
+      tail_chars(&self.context, MAX_CONTEXT_CHARS),
+    ]
+    .join(" ");
+
+    GenerationConfig {
+      language: self.options.language.clone(),
+      task: self.options.task.unwrap_or_default(),
+      initial_prompt: Some(initial_prompt),
+      hotwords: self.options.hotwords.clone().unwrap_or_default(),
+      ..Default::default()
+    }
+  }
+
+  fn run(
+    &mut self,
+    transcriber: &mut impl Transcriber,
+    flush: bool,
+  ) -> Result<Vec<TranscriptionSessionEvent>> {
+    self.pending = 0;
+    self.pending_final = 0;
+
+    let segments = transcriber.transcribe_segments(&self.buffer, &self.generation_config())?;
+    let hypothesis: Vec<(i64, f32, f32)> = segments
+      .iter()
+      .flat_map(|segment| {
+        segment
+          .tokens
+          .iter()
+          .map(|&token| (token, segment.start, segment.end))
+      })
+      .collect();
+
+    let tokens: Vec<i64> = hypothesis
+      .iter()
+      .map(|(token, ..)| *token)
+      .collect();
+    let skip = committed_end(&self.committed, &tokens).unwrap_or_else(|| {
+      // Nothing confirmed is recognizable anymore: skip the segments of the confirmed audio, or
+      // as many tokens as were confirmed when it ends inside a segment
+      match hypothesis
+        .iter()
+        .take_while(|(.., end)| *end <= self.committed_until)
+        .count()
+      {
+        0 => self.committed.len().min(tokens.len()),
+        covered => covered,
+      }
+    });
+    let tail = &tokens[skip..];
+
+    // Whisper can not look further than 30 seconds, confirm everything before audio gets lost
+    let force = flush || self.buffer.len() as f32 >= HARD_MAX_BUFFER_SECS * SAMPLE_RATE as f32;
+    let mut agreed = if force {
+      tail.len()
+    } else {
+      common_prefix_len(tail, &self.previous_tail)
+    };
+    // Byte-level BPE may split a character over several tokens, never confirm half of one
+    while agreed > 0
+      && transcriber
+        .decode_tokens(&tail[..agreed])?
+        .ends_with('\u{FFFD}')
+    {
+      agreed -= 1;
+    }
+
+    let mut events = Vec::new();
+    if agreed > 0 {
+      let offset = self.buffer_offset as f32 / SAMPLE_RATE as f32;
+      let text = transcriber.decode_tokens(&tail[..agreed])?;
+
+      self.transcript.push_str(&text);
+      self.committed_until = hypothesis[skip + agreed - 1].2;
+      events.push(TranscriptionSessionEvent::Final {
+        text,
+        start: offset + hypothesis[skip].1,
+        end: offset + self.committed_until,
+      });
+    }
+    // The next hypothesis is most likely worded like this one
+    self.committed = tokens[..skip + agreed].to_vec();
+
+    self.previous_tail = tail[agreed..].to_vec();
+    if !flush {
+      events.push(TranscriptionSessionEvent::Partial {
+        text: transcriber.decode_tokens(&self.previous_tail)?,
+      });
+    }
+
+    if force {
+      let tokens = std::mem::take(&mut self.committed);
+      self
+        .context
+        .push_str(&transcriber.decode_tokens(&tokens)?);
+      self.buffer_offset += self.buffer.len();
+      self.buffer.clear();
+      self.committed_until = 0.0;
+      self.previous_tail.clear();
+    } else if self.buffer.len() > self.max_buffer {
+      self.trim_buffer(transcriber, &segments)?;
+    }
+
+    Ok(events)
+  }
+
+  /// Drops the audio of segments that are completely confirmed from the front of the buffer.
+  fn trim_buffer(
+    &mut self,
+    transcriber: &mut impl Transcriber,
+    segments: &[Segment],
+  ) -> Result<()> {
+    let mut confirmed_tokens = 0;
+    let mut cut = None;
+    for segment in segments {
+      if confirmed_tokens + segment.tokens.len() > self.committed.len() {
+        break;
+      }
+
+      confirmed_tokens += segment.tokens.len();
+      cut = Some((segment.end, confirmed_tokens));
+    }
+
+    if let Some((end, confirmed_tokens)) = cut
+      && end > 0.0
+    {
+      let samples = ((end * SAMPLE_RATE as f32) as usize).min(self.buffer.len());
+      let tokens: Vec<i64> = self.committed.drain(..confirmed_tokens).collect();
+
+      self
+        .context
+        .push_str(&transcriber.decode_tokens(&tokens)?);
+      self.buffer.drain(..samples);
+      self.buffer_offset += samples;
+      self.committed_until = (self.committed_until - end).max(0.0);
+    }
+
+    Ok(())
+  }
+}
+
+/// Where the tokens confirmed earlier end in a new hypothesis of the same audio. Whisper may word
+/// the confirmed part differently this time, then the longest run of the last confirmed tokens
+/// is looked for, nearest to where it would end unchanged. `None` when it is not found.
+fn committed_end(
+  committed: &[i64],
+  hypothesis: &[i64],
+) -> Option<usize> {
+  if committed.is_empty() || hypothesis.starts_with(committed) {
+    return Some(committed.len());
+  }
+
+  // A single token such as " the" is found almost anywhere
+  let min_len = committed.len().min(2);
+  (min_len..=committed.len().min(MAX_ANCHOR_TOKENS))
+    .rev()
+    .find_map(|len| {
+      let anchor = &committed[committed.len() - len..];
+      hypothesis
+        .windows(len)
+        .enumerate()
+        .filter(|(_, window)| *window == anchor)
+        .map(|(start, _)| start + len)
+        .min_by_key(|end| end.abs_diff(committed.len()))
+    })
+}
+
+fn common_prefix_len(
+  a: &[i64],
+  b: &[i64],
+) -> usize {
+  a.iter()
+    .zip(b)
+    .take_while(|(a, b)| a == b)
+    .count()
+}
+
+fn tail_chars(
+  text: &str,
+  max_chars: usize,
+) -> &str {
+  let skip = text.chars().count().saturating_sub(max_chars);
+  text
+    .char_indices()
+    .nth(skip)
+    .map_or("", |(index, _)| &text[index..])
+}
@@ -0,0 +1,94 @@
+"""Generates the reference log-mel spectrograms used by tests/mel.rs.
+
+Follows openai-whisper `log_mel_spectrogram` step by step with the filterbanks OpenAI ships
+(the same values as src/models/whisper/melfilters*.bytes), in plain Python so it runs without
+numpy or torch:
+
+    python3 tests/fixtures/generate_log_mel.py
+"""
+
+import cmath
+import math
+import struct
+from pathlib import Path
+
+SAMPLE_RATE = 16000
+N_FFT = 400
+HOP_LENGTH = 160
+N_SAMPLES = 30 * SAMPLE_RATE
+# Frames covering the test signal, every later frame only sees the zero padding
+N_REFERENCE_FRAMES = 60
+
+HERE = Path(__file__).parent
+FILTERS = HERE.parent.parent / "src" / "models" / "whisper"
+
+
+def read_f32(path):
+    data = path.read_bytes()
+    return list(struct.unpack(f"<{len(data) // 4}f", data))
+
+
+def write_f32(path, values):
+    path.write_bytes(struct.pack(f"<{len(values)}f", *values))
+
+
+def test_signal():
+    # Half a second of two tones and a chirp, loud enough to set the global maximum
+    samples = []
+    for i in range(SAMPLE_RATE // 2):
+        t = i / SAMPLE_RATE
+        samples.append(
+            0.4 * math.sin(2 * math.pi * 440 * t)
+            + 0.2 * math.sin(2 * math.pi * 3000 * t)
+            + 0.1 * math.sin(2 * math.pi * (200 + 4000 * t) * t)
+        )
+    # Round trip through f32, the Rust side reads the same bytes
+    return list(struct.unpack(f"<{len(samples)}f", struct.pack(f"<{len(samples)}f", *samples)))
+
+
+def power_spectrogram(audio):
+    padded = audio + [0.0] * (N_SAMPLES - len(audio))
+    pad = N_FFT // 2
+    # torch.stft(center=True) uses reflect padding
+    padded = padded[pad:0:-1] + padded + padded[-2 : -pad - 2 : -1]
+    window = [0.5 * (1 - math.cos(2 * math.pi * i / N_FFT)) for i in range(N_FFT)]
+    twiddles = [cmath.exp(-2j * math.pi * k / N_FFT) for k in range(N_FFT)]
+
+    frames = []
+    for frame in range(N_REFERENCE_FRAMES):
+        start = frame * HOP_LENGTH
+        windowed = [padded[start + n] * window[n] for n in range(N_FFT)]
+        bins = []
+        for k in range(N_FFT // 2 + 1):
+            value = sum(windowed[n] * twiddles[(k * n) % N_FFT] for n in range(N_FFT))
+            bins.append(abs(value) ** 2)
+        frames.append(bins)
+    return frames
+
+
+def log_mel(frames, filters, n_mels):
+    n_freqs = N_FFT // 2 + 1
+    mel = [
+        [
+            math.log10(max(sum(filters[m * n_freqs + k] * frame[k] for k in range(n_freqs)), 1e-10))
+            for frame in frames
+        ]
+        for m in range(n_mels)
+    ]
+    # The padding frames are log10(1e-10), the maximum always lies within the signal
+    maximum = max(max(row) for row in mel)
+    return [(max(v, maximum - 8.0) + 4.0) / 4.0 for row in mel for v in row]
+
+
+def main():
+    audio = test_signal()
+    write_f32(HERE / "log_mel_input.bytes", audio)
+
+    frames = power_spectrogram(audio)
+    for n_mels, name in [(80, "melfilters.bytes"), (128, "melfilters128.bytes")]:
+        filters = read_f32(FILTERS / name)
+        write_f32(HERE / f"log_mel_{n_mels}.bytes", log_mel(frames, filters, n_mels))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,441 @@
+"""Generates the tiny Whisper-shaped model used by tests/pipeline.rs, and its expected outputs.
+
+The model has the inputs and outputs of the onnx-community exports, but random weights and a
+vocabulary of a few ASCII characters, special tokens and one second of timestamps:
+
+- encoder: `input_features` [1, 80, 3000] -> Conv(stride 2) -> Tanh -> `last_hidden_state` [1, 1500, 16]
+- decoder: `input_ids` [1, n], `encoder_hidden_states` [1, 1500, 16]
+  -> Tanh(Embedding(input_ids) + mean of the encoder states) x W -> `logits` [1, n, vocab]
+
+The decoder only looks at the current token, so greedy decoding follows a chain from the last
+prompt token. Seeds are tried until the chain after `<|transcribe|>` ends with `<|endoftext|>` and
+the chain after `<|notimestamps|>` loops, with a clear margin between the top logits at every step
+so float differences between this script and onnxruntime cannot change the result.
+
+Everything is written in plain Python, without numpy or the onnx package:
+
+    python3 tests/fixtures/generate_tiny_whisper.py
+"""
+
+import json
+import math
+import random
+import struct
+import sys
+from pathlib import Path
+
+HERE = Path(__file__).parent
+OUT = HERE / "tiny-whisper"
+sys.path.insert(0, str(HERE))
+
+import generate_log_mel  # noqa: E402
+
+N_MELS = 80
+N_FRAMES = 3000
+D_MODEL = 16
+KERNEL = 3
+MAX_NEW_TOKENS = 128
+MIN_MARGIN = 0.1
+ENCODER_SEED = 0
+
+TEXT = [" "] + [chr(c) for c in range(ord("a"), ord("z") + 1)] + list(".,!?'")
+SPECIAL = [
+    "<|endoftext|>",
+    "<|startoftranscript|>",
+    "<|en|>",
+    "<|de|>",
+    "<|translate|>",
+    "<|transcribe|>",
+    "<|startoflm|>",
+    "<|startofprev|>",
+    "<|nospeech|>",
+    "<|notimestamps|>",
+]
+TIMESTAMPS = [f"<|{i * 0.02:.2f}|>" for i in range(51)]
+
+
+def byte_level(text):
+    # GPT-2 maps the space byte to U+0120, printable ASCII maps to itself
+    return text.replace(" ", "Ġ")
+
+
+VOCAB = [byte_level(c) for c in TEXT] + SPECIAL + TIMESTAMPS
+ID = {token: i for i, token in enumerate(VOCAB)}
+TIMESTAMP_BEGIN = ID["<|0.00|>"]
+
+
+def f32(value):
+    return struct.unpack("<f", struct.pack("<f", value))[0]
+
+
+def gauss(rng, n, scale):
+    return [f32(rng.gauss(0.0, scale)) for _ in range(n)]
+
+
+# Protocol buffers, just the wire format parts ONNX needs
+
+
+def varint(value):
+    value &= (1 << 64) - 1
+    out = bytearray()
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        if value:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def field_varint(number, value):
+    return varint(number << 3) + varint(value)
+
+
+def field_bytes(number, value):
+    if isinstance(value, str):
+        value = value.encode()
+    return varint(number << 3 | 2) + varint(len(value)) + value
+
+
+def tensor(name, dims, values):
+    # TensorProto: dims = 1, data_type = 2 (FLOAT = 1), name = 8, raw_data = 9
+    return (
+        b"".join(field_varint(1, d) for d in dims)
+        + field_varint(2, 1)
+        + field_bytes(8, name)
+        + field_bytes(9, struct.pack(f"<{len(values)}f", *values))
+    )
+
+
+def value_info(name, elem_type, dims):
+    # TensorShapeProto.Dimension: dim_value = 1, dim_param = 2
+    shape = b"".join(
+        field_bytes(1, field_bytes(2, d) if isinstance(d, str) else field_varint(1, d)) for d in dims
+    )
+    # TypeProto.Tensor: elem_type = 1, shape = 2
+    tensor_type = field_varint(1, elem_type) + field_bytes(2, shape)
+    # ValueInfoProto: name = 1, type = 2 (TypeProto: tensor_type = 1)
+    return field_bytes(1, name) + field_bytes(2, field_bytes(1, tensor_type))
+
+
+def attribute_ints(name, values):
+    # AttributeProto: name = 1, ints = 8, type = 20 (INTS = 7)
+    return field_bytes(1, name) + b"".join(field_varint(8, v) for v in values) + field_varint(20, 7)
+
+
+def attribute_int(name, value):
+    # AttributeProto: name = 1, i = 3, type = 20 (INT = 2)
+    return field_bytes(1, name) + field_varint(3, value) + field_varint(20, 2)
+
+
+def node(op_type, inputs, outputs, attributes=()):
+    # NodeProto: input = 1, output = 2, name = 3, op_type = 4, attribute = 5
+    return (
+        b"".join(field_bytes(1, i) for i in inputs)
+        + b"".join(field_bytes(2, o) for o in outputs)
+        + field_bytes(3, outputs[0])
+        + field_bytes(4, op_type)
+        + b"".join(field_bytes(5, a) for a in attributes)
+    )
+
+
+def model(name, nodes, initializers, inputs, outputs):
+    # GraphProto: node = 1, name = 2, initializer = 5, input = 11, output = 12
+    graph = (
+        b"".join(field_bytes(1, n) for n in nodes)
+        + field_bytes(2, name)
+        + b"".join(field_bytes(5, t) for t in initializers)
+        + b"".join(field_bytes(11, i) for i in inputs)
+        + b"".join(field_bytes(12, o) for o in outputs)
+    )
+    # ModelProto: ir_version = 1, producer_name = 2, graph = 7, opset_import = 8 (version = 2)
+    return (
+        field_varint(1, 8)
+        + field_bytes(2, "generate_tiny_whisper.py")
+        + field_bytes(7, graph)
+        + field_bytes(8, field_varint(2, 17))
+    )
+
+
+# Reference forward pass
+
+
+def input_features():
+    audio = generate_log_mel.read_f32(HERE / "log_mel_input.bytes")
+    filters = generate_log_mel.read_f32(generate_log_mel.FILTERS / "melfilters.bytes")
+    frames = generate_log_mel.power_spectrogram(audio)
+    reference = generate_log_mel.log_mel(frames, filters, N_MELS)
+    n = generate_log_mel.N_REFERENCE_FRAMES
+    # Past the reference frames there is only silence, at the same floor as the last one
+    features = [
+        [reference[m * n + min(t, n - 1)] for t in range(N_FRAMES)] for m in range(N_MELS)
+    ]
+    return features, len(audio) / generate_log_mel.SAMPLE_RATE
+
+
+def encoder_mean(features, weight, bias):
+    # Conv with kernel 3, padding 1 and stride 2, then Tanh, averaged over time
+    floor = None
+    total = [0.0] * D_MODEL
+    for t in range(N_FRAMES // 2):
+        positions = [2 * t - 1 + k for k in range(KERNEL)]
+        # Frames past the signal are all the same, so are their outputs
+        is_floor = positions[0] >= generate_log_mel.N_REFERENCE_FRAMES
+        if is_floor and floor is not None:
+            column = floor
+        else:
+            column = []
+            for o in range(D_MODEL):
+                value = bias[o]
+                for c in range(N_MELS):
+                    for k, p in enumerate(positions):
+                        if 0 <= p < N_FRAMES:
+                            value += weight[(o * N_MELS + c) * KERNEL + k] * features[c][p]
+                column.append(math.tanh(value))
+            if is_floor:
+                floor = column
+        for o in range(D_MODEL):
+            total[o] += column[o]
+    return [v / (N_FRAMES // 2) for v in total]
+
+
+def logits(token, mean, embedding, projection):
+    hidden = [math.tanh(embedding[token * D_MODEL + i] + mean[i]) for i in range(D_MODEL)]
+    return [
+        sum(hidden[i] * projection[i * len(VOCAB) + v] for i in range(D_MODEL))
+        for v in range(len(VOCAB))
+    ]
+
+
+def log_softmax(values, index):
+    maximum = max(values)
+    return values[index] - maximum - math.log(sum(math.exp(v - maximum) for v in values))
+
+
+def repetition_cut(tokens, max_repeats=3, max_ngram=16, min_span=8):
+    # Same as GuardConfig::repetition_cut
+    for n in range(1, min(max_ngram, len(tokens) // 2) + 1):
+        ngram = tokens[-n:]
+        repeats = 0
+        while (repeats + 1) * n <= len(tokens) and tokens[
+            len(tokens) - (repeats + 1) * n : len(tokens) - repeats * n
+        ] == ngram:
+            repeats += 1
+        if repeats > max_repeats and repeats * n >= min_span:
+            return len(tokens) - (repeats - 1) * n
+    return None
+
+
+def greedy(init, mean, embedding, projection):
+    """Returns the generated tokens, their average log probability and whether decoding stopped in
+    a loop, or None when the top two logits are too close at some step."""
+    tokens = []
+    sum_logprob = 0.0
+    last = init[-1]
+    for _ in range(MAX_NEW_TOKENS):
+        step = logits(last, mean, embedding, projection)
+        ranked = sorted(range(len(VOCAB)), key=lambda v: step[v], reverse=True)
+        if step[ranked[0]] - step[ranked[1]] < MIN_MARGIN:
+            return None
+        last = ranked[0]
+        sum_logprob += log_softmax(step, last)
+        if last == ID["<|endoftext|>"]:
+            return tokens, sum_logprob / (len(tokens) + 1), False
+        tokens.append(last)
+        cut = repetition_cut(tokens)
+        if cut is not None:
+            return tokens[:cut], sum_logprob / (len(tokens) + 1), True
+    return tokens, sum_logprob / (len(tokens) + 1), False
+
+
+def decode(tokens):
+    return "".join(TEXT[t] for t in tokens if t < len(TEXT))
+
+
+def segments(tokens, duration):
+    out = []
+    start = 0.0
+    current = []
+    for token in tokens:
+        if token < TIMESTAMP_BEGIN:
+            current.append(token)
+            continue
+        time = (token - TIMESTAMP_BEGIN) * 0.02
+        if current:
+            out.append({"start": start, "end": time, "text": decode(current), "tokens": current})
+            current = []
+        start = time
+    if current:
+        out.append(
+            {"start": start, "end": max(duration, start), "text": decode(current), "tokens": current}
+        )
+    return out
+
+
+def is_text_or_timestamp(token):
+    return token < len(TEXT) or token >= TIMESTAMP_BEGIN
+
+
+def search(mean):
+    sot = [ID["<|startoftranscript|>"], ID["<|en|>"], ID["<|transcribe|>"]]
+    for seed in range(100_000):
+        rng = random.Random(seed)
+        embedding = gauss(rng, len(VOCAB) * D_MODEL, 1.0)
+        projection = gauss(rng, D_MODEL * len(VOCAB), 1.0)
+
+        timestamps = greedy(sot, mean, embedding, projection)
+        if timestamps is None or timestamps[2]:
+            continue
+        tokens = timestamps[0]
+        if (
+            not all(is_text_or_timestamp(t) for t in tokens)
+            or sum(t < len(TEXT) for t in tokens) < 3
+            or not any(t >= TIMESTAMP_BEGIN for t in tokens)
+        ):
+            continue
+
+        looping = greedy(sot + [ID["<|notimestamps|>"]], mean, embedding, projection)
+        if looping is None or not looping[2] or not all(t < len(TEXT) for t in looping[0]):
+            continue
+
+        return seed, embedding, projection, timestamps, looping
+    raise RuntimeError("No seed produced the wanted chains")
+
+
+def main():
+    rng = random.Random(ENCODER_SEED)
+    conv_weight = gauss(rng, D_MODEL * N_MELS * KERNEL, 1.0 / math.sqrt(N_MELS * KERNEL))
+    conv_bias = gauss(rng, D_MODEL, 0.1)
+    features, duration = input_features()
+    mean = encoder_mean(features, conv_weight, conv_bias)
+
+    seed, embedding, projection, timestamps, looping = search(mean)
+
+    first = logits(ID["<|startoftranscript|>"], mean, embedding, projection)
+    no_speech_prob = math.exp(log_softmax(first, ID["<|nospeech|>"]))
+
+    (OUT / "onnx").mkdir(parents=True, exist_ok=True)
+    (OUT / "onnx" / "encoder_model.onnx").write_bytes(
+        model(
+            "encoder",
+            [
+                node(
+                    "Conv",
+                    ["input_features", "conv.weight", "conv.bias"],
+                    ["conv"],
+                    [
+                        attribute_ints("kernel_shape", [KERNEL]),
+                        attribute_ints("pads", [1, 1]),
+                        attribute_ints("strides", [2]),
+                    ],
+                ),
+                node("Tanh", ["conv"], ["tanh"]),
+                node("Transpose", ["tanh"], ["last_hidden_state"], [attribute_ints("perm", [0, 2, 1])]),
+            ],
+            [
+                tensor("conv.weight", [D_MODEL, N_MELS, KERNEL], conv_weight),
+                tensor("conv.bias", [D_MODEL], conv_bias),
+            ],
+            [value_info("input_features", 1, ["batch_size", N_MELS, N_FRAMES])],
+            [value_info("last_hidden_state", 1, ["batch_size", N_FRAMES // 2, D_MODEL])],
+        )
+    )
+    (OUT / "onnx" / "decoder_model.onnx").write_bytes(
+        model(
+            "decoder",
+            [
+                node("Gather", ["embed_tokens", "input_ids"], ["embedded"], [attribute_int("axis", 0)]),
+                node(
+                    "ReduceMean",
+                    ["encoder_hidden_states"],
+                    ["audio"],
+                    [attribute_ints("axes", [1]), attribute_int("keepdims", 1)],
+                ),
+                node("Add", ["embedded", "audio"], ["sum"]),
+                node("Tanh", ["sum"], ["hidden"]),
+                node("MatMul", ["hidden", "proj_out"], ["logits"]),
+            ],
+            [
+                tensor("embed_tokens", [len(VOCAB), D_MODEL], embedding),
+                tensor("proj_out", [D_MODEL, len(VOCAB)], projection),
+            ],
+            [
+                value_info("input_ids", 7, ["batch_size", "decoder_sequence_length"]),
+                value_info("encoder_hidden_states", 1, ["batch_size", N_FRAMES // 2, D_MODEL]),
+            ],
+            [value_info("logits", 1, ["batch_size", "decoder_sequence_length", len(VOCAB)])],
+        )
+    )
+
+    added_tokens = [
+        {
+            "id": ID[token],
+            "content": token,
+            "single_word": False,
+            "lstrip": False,
+            "rstrip": False,
+            "normalized": False,
+            "special": True,
+        }
+        for token in SPECIAL + TIMESTAMPS
+    ]
+    byte_level_options = {"add_prefix_space": False, "trim_offsets": True, "use_regex": True}
+    tokenizer = {
+        "version": "1.0",
+        "truncation": None,
+        "padding": None,
+        "added_tokens": added_tokens,
+        "normalizer": None,
+        "pre_tokenizer": {"type": "ByteLevel", **byte_level_options},
+        "post_processor": None,
+        "decoder": {"type": "ByteLevel", **byte_level_options},
+        "model": {
+            "type": "BPE",
+            "dropout": None,
+            "unk_token": None,
+            "continuing_subword_prefix": None,
+            "end_of_word_suffix": None,
+            "fuse_unk": False,
+            "byte_fallback": False,
+            "ignore_merges": False,
+            "vocab": {token: i for i, token in enumerate(VOCAB[: len(TEXT)])},
+            "merges": [],
+        },
+    }
+    config = {
+        "model_type": "whisper",
+        "num_mel_bins": N_MELS,
+        "d_model": D_MODEL,
+        "decoder_start_token_id": ID["<|startoftranscript|>"],
+        "eos_token_id": ID["<|endoftext|>"],
+        "no_timestamps_token_id": ID["<|notimestamps|>"],
+        "max_target_positions": 448,
+    }
+    expected = {
+        "seed": seed,
+        "noSpeechProb": no_speech_prob,
+        "timestamps": {
+            "tokens": timestamps[0],
+            "text": decode(timestamps[0]),
+            "avgLogprob": timestamps[1],
+            "segments": segments(timestamps[0], duration),
+        },
+        "noTimestamps": {
+            "tokens": looping[0],
+            "text": decode(looping[0]),
+            "avgLogprob": looping[1],
+        },
+    }
+
+    for name, value in [
+        ("tokenizer.json", tokenizer),
+        ("tokenizer_config.json", {"added_tokens": added_tokens}),
+        ("config.json", config),
+        ("expected.json", expected),
+    ]:
+        (OUT / name).write_text(json.dumps(value, indent=2, ensure_ascii=False) + "\n")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,80 @@
+use tauri_plugin_ipc_audio_transcription_ort::{Guard, GuardConfig, compression_ratio};
+
+#[test]
+fn drops_known_hallucinations() {
+  let guards = GuardConfig::default();
+
+  assert_eq!(
+    guards.check_text(" Thank you for watching!"),
+    Some(Guard::Blocklist)
+  );
+  assert_eq!(
+    guards.check_text(" Sous-titrage Société Radio-Canada"),
+    Some(Guard::Blocklist)
+  );
+  assert_eq!(
+    guards.check_text(" ご視聴ありがとうございました。"),
+    Some(Guard::Blocklist)
+  );
+}
+
+#[test]
+fn keeps_text_that_only_contains_a_blocked_phrase() {
+  let guards = GuardConfig::default();
+
+  assert_eq!(
+    guards.check_text(" Thank you for watching the show with me."),
+    None
+  );
+  assert_eq!(guards.check_text(""), None);
+
+  let guards = GuardConfig {
+    blocklist: Vec::new(),
+    ..Default::default()
+  };
+  assert_eq!(guards.check_text(" Thank you for watching!"), None);
+}
+
+#[test]
+fn cuts_loops_after_their_first_occurrence() {
+  let guards = GuardConfig::default();
+
+  let tokens = [1, 2, 3, 7, 7, 7, 7, 7, 7, 7, 7, 7];
+  assert_eq!(guards.repetition_cut(&tokens), Some(4));
+
+  let tokens = [1, 2, 3, 4, 5, 2, 3, 4, 5, 2, 3, 4, 5, 2, 3, 4, 5];
+  assert_eq!(guards.repetition_cut(&tokens), Some(5));
+}
+
+#[test]
+fn keeps_short_and_rare_repetitions() {
+  let guards = GuardConfig::default();
+
+  // "no, no, no, no" is too short to be a loop
+  assert_eq!(guards.repetition_cut(&[5, 5, 5, 5]), None);
+  // Three times in a row is allowed
+  let tokens = [1, 2, 3, 4, 5, 2, 3, 4, 5, 2, 3, 4, 5];
+  assert_eq!(guards.repetition_cut(&tokens), None);
+  assert_eq!(guards.repetition_cut(&[]), None);
+
+  let guards = GuardConfig {
+    max_ngram_repeats: None,
+    ..Default::default()
+  };
+  assert_eq!(guards.repetition_cut(&[7; 32]), None);
+}
+
+#[test]
+fn measures_how_well_text_compresses() {
+  assert_eq!(compression_ratio(""), 0.0);
+
+  let sentence = "The quick brown fox jumps over the lazy dog near the river bank.";
+  assert!(compression_ratio(sentence) < 2.4);
+
+  let looped = "I don't know what to do. ".repeat(20);
+  assert!(compression_ratio(&looped) > 2.4);
+  assert_eq!(
+    GuardConfig::default().check_text(&looped),
+    Some(Guard::CompressionRatio)
+  );
+}
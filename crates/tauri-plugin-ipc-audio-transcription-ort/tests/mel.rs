@@ -0,0 +1,98 @@
+use byteorder::{ByteOrder, LittleEndian};
+use tauri_plugin_ipc_audio_transcription_ort::{WhisperProcessor, mel_filters};
+
+const N_REFERENCE_FRAMES: usize = 60;
+
+fn read_f32(bytes: &[u8]) -> Vec<f32> {
+  let mut values = vec![0f32; bytes.len() / 4];
+  LittleEndian::read_f32_into(bytes, &mut values);
+  values
+}
+
+fn assert_filters_match(
+  num_mel_bins: usize,
+  reference: &[u8],
+) {
+  let reference = read_f32(reference);
+  let filters = mel_filters(16000, 400, num_mel_bins);
+
+  assert_eq!(filters.dim(), (num_mel_bins, 201));
+  for (actual, expected) in filters.iter().zip(&reference) {
+    assert!(
+      (actual - expected).abs() < 1e-6,
+      "filter weight {actual} differs from {expected}"
+    );
+  }
+}
+
+fn assert_log_mel_matches(
+  num_mel_bins: usize,
+  reference: &[u8],
+) {
+  let audio = read_f32(include_bytes!("fixtures/log_mel_input.bytes"));
+  let reference = read_f32(reference);
+  let log_mel = WhisperProcessor::new(num_mel_bins)
+    .unwrap()
+    .process(&audio);
+
+  assert_eq!(log_mel.dim(), (num_mel_bins, 3000));
+  for mel in 0..num_mel_bins {
+    for frame in 0..N_REFERENCE_FRAMES {
+      let expected = reference[mel * N_REFERENCE_FRAMES + frame];
+      let actual = log_mel[[mel, frame]];
+      assert!(
+        (actual - expected).abs() < 1e-3,
+        "bin {mel}, frame {frame}: {actual} differs from {expected}"
+      );
+    }
+
+    // Past the signal only silence is left, which sits at the floor like the last reference frame
+    let floor = reference[mel * N_REFERENCE_FRAMES + N_REFERENCE_FRAMES - 1];
+    for frame in N_REFERENCE_FRAMES..3000 {
+      assert!((log_mel[[mel, frame]] - floor).abs() < 1e-3);
+    }
+  }
+}
+
+#[test]
+fn mel_filters_match_openai_80_bins() {
+  assert_filters_match(80, include_bytes!("../src/models/whisper/melfilters.bytes"));
+}
+
+#[test]
+fn mel_filters_match_openai_128_bins() {
+  assert_filters_match(
+    128,
+    include_bytes!("../src/models/whisper/melfilters128.bytes"),
+  );
+}
+
+#[test]
+fn log_mel_matches_reference_80_bins() {
+  assert_log_mel_matches(80, include_bytes!("fixtures/log_mel_80.bytes"));
+}
+
+#[test]
+fn log_mel_matches_reference_128_bins() {
+  assert_log_mel_matches(128, include_bytes!("fixtures/log_mel_128.bytes"));
+}
+
+#[test]
+fn processor_rejects_zero_mel_bins() {
+  assert!(WhisperProcessor::new(0).is_err());
+}
+
+#[test]
+fn short_audio_matches_explicit_padding() {
+  let audio = read_f32(include_bytes!("fixtures/log_mel_input.bytes"));
+  let mut padded = audio.clone();
+  padded.resize(30 * 16000, 0.0);
+
+  let processor = WhisperProcessor::new(80).unwrap();
+  let short = processor.process(&audio);
+  let full = processor.process(&padded);
+
+  for (short, full) in short.iter().zip(full.iter()) {
+    assert!((short - full).abs() < 1e-5);
+  }
+}
@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use byteorder::{ByteOrder, LittleEndian};
+use serde_json::Value;
+use tauri_plugin_ipc_audio_transcription_ort::{
+  GenerationConfig,
+  Guard,
+  GuardConfig,
+  ModelVariant,
+  WhisperPipeline,
+};
+
+// A random Whisper-shaped model, see tests/fixtures/generate_tiny_whisper.py
+fn fixture_dir() -> PathBuf {
+  PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tiny-whisper")
+}
+
+fn pipeline() -> WhisperPipeline {
+  WhisperPipeline::from_dir(fixture_dir(), ModelVariant::Fp32).unwrap()
+}
+
+fn audio() -> Vec<f32> {
+  let bytes = include_bytes!("fixtures/log_mel_input.bytes");
+  let mut samples = vec![0f32; bytes.len() / 4];
+  LittleEndian::read_f32_into(bytes, &mut samples);
+  samples
+}
+
+fn expected() -> Value {
+  serde_json::from_str(include_str!("fixtures/tiny-whisper/expected.json")).unwrap()
+}
+
+fn assert_close(
+  actual: f32,
+  expected: &Value,
+) {
+  let expected = expected.as_f64().unwrap() as f32;
+  assert!(
+    (actual - expected).abs() < 1e-3,
+    "{actual} differs from {expected}"
+  );
+}
+
+#[test]
+fn transcribes_segments_with_timestamps() {
+  let expected = expected();
+  let transcription = pipeline()
+    .transcribe_segments(&audio(), &GenerationConfig::default())
+    .unwrap();
+
+  assert_eq!(transcription.guard, None);
+  assert_close(
+    transcription.no_speech_prob.unwrap(),
+    &expected["noSpeechProb"],
+  );
+  assert_close(
+    transcription.avg_logprob,
+    &expected["timestamps"]["avgLogprob"],
+  );
+
+  let expected_segments = expected["timestamps"]["segments"]
+    .as_array()
+    .unwrap();
+  assert_eq!(transcription.segments.len(), expected_segments.len());
+  for (segment, expected) in transcription
+    .segments
+    .iter()
+    .zip(expected_segments)
+  {
+    assert_eq!(segment.text, expected["text"].as_str().unwrap());
+    assert_close(segment.start, &expected["start"]);
+    assert_close(segment.end, &expected["end"]);
+
+    let tokens: Vec<i64> = expected["tokens"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|token| token.as_i64().unwrap())
+      .collect();
+    assert_eq!(segment.tokens, tokens);
+  }
+}
+
+#[test]
+fn transcribes_text() {
+  let expected = expected();
+  let result = pipeline()
+    .transcribe(&audio(), &GenerationConfig::default())
+    .unwrap();
+
+  assert_eq!(result.guard, None);
+  assert_eq!(
+    result.text,
+    expected["timestamps"]["text"].as_str().unwrap()
+  );
+  assert_close(result.avg_logprob, &expected["timestamps"]["avgLogprob"]);
+}
+
+#[test]
+fn repetition_guard_stops_decoding_loops() {
+  let expected = expected();
+  let config = GenerationConfig {
+    return_timestamps: false,
+    ..Default::default()
+  };
+  let result = pipeline().transcribe(&audio(), &config).unwrap();
+
+  assert_eq!(result.guard, Some(Guard::Repetition));
+  assert_eq!(
+    result.text,
+    expected["noTimestamps"]["text"].as_str().unwrap()
+  );
+  assert_close(result.avg_logprob, &expected["noTimestamps"]["avgLogprob"]);
+}
+
+#[test]
+fn no_speech_guard_drops_the_transcript() {
+  let config = GenerationConfig {
+    guards: GuardConfig {
+      no_speech_threshold: Some(0.0),
+      logprob_threshold: 0.0,
+      ..Default::default()
+    },
+    ..Default::default()
+  };
+  let mut pipeline = pipeline();
+
+  let result = pipeline.transcribe(&audio(), &config).unwrap();
+  assert_eq!(result.guard, Some(Guard::NoSpeech));
+  assert_eq!(result.text, "");
+
+  let transcription = pipeline
+    .transcribe_segments(&audio(), &config)
+    .unwrap();
+  assert_eq!(transcription.guard, Some(Guard::NoSpeech));
+  assert!(transcription.segments.is_empty());
+}
+
+#[test]
+fn blocklist_guard_drops_the_transcript() {
+  let expected = expected();
+  let config = GenerationConfig {
+    guards: GuardConfig {
+      // Compared without punctuation and case
+      blocklist: vec![
+        expected["timestamps"]["text"]
+          .as_str()
+          .unwrap()
+          .to_uppercase(),
+      ],
+      ..Default::default()
+    },
+    ..Default::default()
+  };
+
+  let result = pipeline().transcribe(&audio(), &config).unwrap();
+  assert_eq!(result.guard, Some(Guard::Blocklist));
+  assert_eq!(result.text, "");
+}
+
+#[test]
+fn loading_reports_missing_files() {
+  let error = WhisperPipeline::from_dir(fixture_dir(), ModelVariant::Q4)
+    .err()
+    .unwrap();
+
+  assert!(
+    error
+      .to_string()
+      .contains("encoder_model_q4.onnx"),
+    "unexpected error: {error}"
+  );
+}
@@ -0,0 +1,46 @@
+use tauri_plugin_ipc_audio_transcription_ort::{TranscriptSegment, srt_timestamp, to_srt};
+
+#[test]
+fn formats_timestamps() {
+  assert_eq!(srt_timestamp(0.0), "00:00:00,000");
+  assert_eq!(srt_timestamp(61.25), "00:01:01,250");
+  assert_eq!(srt_timestamp(3723.5), "01:02:03,500");
+  // Starts before the audio, e.g. a segment moved back by an offset
+  assert_eq!(srt_timestamp(-1.0), "00:00:00,000");
+}
+
+#[test]
+fn rounds_to_the_millisecond() {
+  assert_eq!(srt_timestamp(1.0004), "00:00:01,000");
+  assert_eq!(srt_timestamp(1.0006), "00:00:01,001");
+  // Rounding up carries into the seconds and minutes
+  assert_eq!(srt_timestamp(59.9996), "00:01:00,000");
+}
+
+#[test]
+fn keeps_hours_past_99() {
+  assert_eq!(srt_timestamp(99.0 * 3600.0), "99:00:00,000");
+  assert_eq!(srt_timestamp(100.0 * 3600.0 + 1.5), "100:00:01,500");
+}
+
+#[test]
+fn numbers_the_cues() {
+  let segments = [
+    TranscriptSegment {
+      start: 0.0,
+      end:   1.5,
+      text:  "Hello.".to_string(),
+    },
+    TranscriptSegment {
+      start: 1.5,
+      end:   3.0,
+      text:  "How are you?".to_string(),
+    },
+  ];
+
+  assert_eq!(
+    to_srt(&segments),
+    "1\n00:00:00,000 --> 00:00:01,500\nHello.\n\n2\n00:00:01,500 --> 00:00:03,000\nHow are you?\n\n"
+  );
+  assert_eq!(to_srt(&[]), "");
+}
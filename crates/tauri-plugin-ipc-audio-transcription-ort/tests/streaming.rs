@@ -0,0 +1,245 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use tauri_plugin_ipc_audio_transcription_ort::{
+  GenerationConfig,
+  Segment,
+  StreamingOptions,
+  StreamingTranscriber,
+  Transcriber,
+  TranscriptionSessionEvent,
+};
+
+// Token ids are indices into this vocabulary
+const WORDS: [&str; 9] = [
+  " the", " cat", " sat", " on", " mat", " oh", " a", " dog", " ran",
+];
+
+/// Returns the hypotheses it was given one after another, whatever the audio is.
+#[derive(Default)]
+struct Scripted {
+  hypotheses: VecDeque<Vec<Segment>>,
+  /// `return_timestamps` and `prefix` of every run
+  runs:       Vec<(bool, Option<String>)>,
+}
+
+impl Scripted {
+  fn then(
+    mut self,
+    segments: Vec<Segment>,
+  ) -> Self {
+    self.hypotheses.push_back(segments);
+    self
+  }
+}
+
+impl Transcriber for Scripted {
+  fn transcribe_segments(
+    &mut self,
+    _audio: &[f32],
+    gen_config: &GenerationConfig,
+  ) -> Result<Vec<Segment>> {
+    self
+      .runs
+      .push((gen_config.return_timestamps, gen_config.prefix.clone()));
+    Ok(
+      self
+        .hypotheses
+        .pop_front()
+        .expect("no hypothesis left"),
+    )
+  }
+
+  fn decode_tokens(
+    &self,
+    tokens: &[i64],
+  ) -> Result<String> {
+    Ok(
+      tokens
+        .iter()
+        .map(|&token| WORDS[token as usize])
+        .collect(),
+    )
+  }
+}
+
+fn segment(
+  start: f32,
+  end: f32,
+  text: &str,
+) -> Segment {
+  let tokens = text
+    .split_whitespace()
+    .map(|word| {
+      WORDS
+        .iter()
+        .position(|w| w.trim() == word)
+        .unwrap() as i64
+    })
+    .collect();
+
+  Segment {
+    start,
+    end,
+    text: text.to_string(),
+    tokens,
+  }
+}
+
+fn transcriber() -> StreamingTranscriber {
+  StreamingTranscriber::new(StreamingOptions {
+    partial_interval_ms: Some(100),
+    ..Default::default()
+  })
+}
+
+/// Pushes 100 ms of audio and runs the model over it.
+fn step(
+  streaming: &mut StreamingTranscriber,
+  scripted: &mut Scripted,
+) -> (Vec<String>, Vec<String>) {
+  assert!(streaming.append(&[0.0; 1600]));
+
+  let mut finals = Vec::new();
+  let mut partials = Vec::new();
+  for event in streaming.process(scripted).unwrap() {
+    match event {
+      TranscriptionSessionEvent::Final { text, .. } => finals.push(text),
+      TranscriptionSessionEvent::Partial { text } => partials.push(text),
+    }
+  }
+  (finals, partials)
+}
+
+#[test]
+fn confirms_what_two_hypotheses_agree_on() {
+  let mut scripted = Scripted::default()
+    .then(vec![segment(0.0, 1.0, "the cat")])
+    .then(vec![segment(0.0, 1.5, "the cat sat")])
+    .then(vec![segment(0.0, 2.0, "the dog sat on")]);
+  let mut streaming = transcriber();
+
+  let (finals, partials) = step(&mut streaming, &mut scripted);
+  assert!(finals.is_empty());
+  assert_eq!(partials, [" the cat"]);
+
+  let (finals, partials) = step(&mut streaming, &mut scripted);
+  assert_eq!(finals, [" the cat"]);
+  assert_eq!(partials, [" sat"]);
+
+  // Confirmed text stays, even when the model changes its mind about it
+  let (finals, partials) = step(&mut streaming, &mut scripted);
+  assert_eq!(finals, [" sat"]);
+  assert_eq!(partials, [" on"]);
+  assert_eq!(streaming.transcript(), "the cat sat");
+}
+
+#[test]
+fn finds_confirmed_text_after_an_inserted_token() {
+  let mut scripted = Scripted::default()
+    .then(vec![segment(0.0, 1.0, "the cat sat")])
+    .then(vec![segment(0.0, 1.0, "the cat sat")])
+    .then(vec![segment(0.0, 2.0, "oh the cat sat on")])
+    .then(vec![segment(0.0, 2.5, "oh the cat sat on mat")]);
+  let mut streaming = transcriber();
+
+  step(&mut streaming, &mut scripted);
+  let (finals, _) = step(&mut streaming, &mut scripted);
+  assert_eq!(finals, [" the cat sat"]);
+
+  let (finals, partials) = step(&mut streaming, &mut scripted);
+  assert!(finals.is_empty());
+  assert_eq!(partials, [" on"]);
+
+  let (finals, partials) = step(&mut streaming, &mut scripted);
+  assert_eq!(finals, [" on"]);
+  assert_eq!(partials, [" mat"]);
+  assert_eq!(streaming.transcript(), "the cat sat on");
+}
+
+#[test]
+fn finds_confirmed_text_after_a_dropped_token() {
+  let mut scripted = Scripted::default()
+    .then(vec![segment(0.0, 1.0, "the cat sat")])
+    .then(vec![segment(0.0, 1.0, "the cat sat")])
+    .then(vec![segment(0.0, 2.0, "cat sat on mat")])
+    .then(vec![segment(0.0, 2.0, "cat sat on mat")]);
+  let mut streaming = transcriber();
+
+  step(&mut streaming, &mut scripted);
+  step(&mut streaming, &mut scripted);
+
+  let (_, partials) = step(&mut streaming, &mut scripted);
+  assert_eq!(partials, [" on mat"]);
+
+  let (finals, _) = step(&mut streaming, &mut scripted);
+  assert_eq!(finals, [" on mat"]);
+  assert_eq!(streaming.transcript(), "the cat sat on mat");
+}
+
+#[test]
+fn skips_reworded_text_by_its_timestamps() {
+  let mut scripted = Scripted::default()
+    .then(vec![segment(0.0, 1.0, "the cat"), segment(1.0, 2.0, "sat")])
+    .then(vec![segment(0.0, 1.0, "the cat"), segment(1.0, 2.0, "on")])
+    .then(vec![
+      segment(0.0, 1.0, "oh a dog"),
+      segment(1.0, 2.0, "ran on"),
+    ])
+    .then(vec![
+      segment(0.0, 1.0, "oh a dog"),
+      segment(1.0, 2.0, "ran on"),
+    ]);
+  let mut streaming = transcriber();
+
+  step(&mut streaming, &mut scripted);
+  let (finals, _) = step(&mut streaming, &mut scripted);
+  assert_eq!(finals, [" the cat"]);
+
+  // Nothing of " the cat" is left, the first segment covers the same audio
+  let (finals, partials) = step(&mut streaming, &mut scripted);
+  assert!(finals.is_empty());
+  assert_eq!(partials, [" ran on"]);
+
+  let (finals, _) = step(&mut streaming, &mut scripted);
+  assert_eq!(finals, [" ran on"]);
+  assert_eq!(streaming.transcript(), "the cat ran on");
+}
+
+#[test]
+fn flush_confirms_everything_left() {
+  let mut scripted = Scripted::default()
+    .then(vec![segment(0.0, 1.0, "the cat")])
+    .then(vec![segment(0.0, 1.5, "the cat sat")]);
+  let mut streaming = transcriber();
+
+  step(&mut streaming, &mut scripted);
+  let events = streaming.flush(&mut scripted).unwrap();
+  assert!(matches!(
+    events.as_slice(),
+    [TranscriptionSessionEvent::Final { text, start, end }]
+      if text == " the cat sat" && *start == 0.0 && *end == 1.5
+  ));
+  assert_eq!(streaming.transcript(), "the cat sat");
+  assert!(streaming.flush(&mut scripted).unwrap().is_empty());
+}
+
+#[test]
+fn partials_force_the_confirmed_text_without_timestamps() {
+  let mut scripted = Scripted::default()
+    .then(vec![segment(0.0, 1.0, "the cat")])
+    .then(vec![segment(0.0, 1.0, "the cat")])
+    .then(vec![segment(0.0, 1.5, "the cat sat")]);
+  let mut streaming = transcriber();
+
+  step(&mut streaming, &mut scripted);
+  step(&mut streaming, &mut scripted);
+
+  assert!(streaming.append(&[0.0; 1600]));
+  let events = streaming.process_partial(&mut scripted).unwrap();
+  assert!(matches!(
+    events.as_slice(),
+    [TranscriptionSessionEvent::Partial { text }] if text == " sat"
+  ));
+  assert_eq!(scripted.runs[2], (false, Some(" the cat".to_string())));
+}
@@ -2,8 +2,12 @@ const COMMANDS: &[&str] = &[
     "list_models",
     "list_voices",
     "load_model",
+    "cancel_model_download",
     "synthesize",
     "list_installed_models",
+    "playback_started",
+    "playback_finished",
+    "interrupt_playback",
 ];
 
 fn main() {
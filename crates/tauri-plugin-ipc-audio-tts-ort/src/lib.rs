@@ -2,18 +2,23 @@ use std::collections::HashMap;
 use std::sync::Mutex;
 use log::warn;
 
+use airi_audio_models::{AudioError, BARGE_IN_EVENT};
 use log::info;
 use serde::{Deserialize, Serialize};
 use tauri::{
     plugin::{Builder as PluginBuilder, TauriPlugin},
-    Manager, Runtime,
+    Emitter, Listener, Manager, Runtime,
 };
 
 mod models;
 mod audio;
+mod playback;
 
 use models::{ModelInfo, TtsModel, VoiceInfo};
 use models::is_model_installed;
+use playback::{Playback, INTERRUPTED_EVENT};
+
+pub use playback::Interrupted;
 
 #[derive(Default)]
 struct TtsState {
@@ -29,7 +34,7 @@ struct SynthesizeOptions {
 }
 
 #[tauri::command]
-async fn list_models() -> Result<Vec<ModelInfo>, String> {
+async fn list_models() -> Result<Vec<ModelInfo>, AudioError> {
     let kokoro_installed = is_model_installed("hexgrad/Kokoro-82M");
     Ok(vec![
         // Only Kokoro-82M is supported (ONNX Community version)
@@ -56,7 +61,7 @@ async fn list_models() -> Result<Vec<ModelInfo>, String> {
 #[tauri::command]
 async fn list_voices<R: Runtime>(
     app: tauri::AppHandle<R>,
-) -> Result<Vec<VoiceInfo>, String> {
+) -> Result<Vec<VoiceInfo>, AudioError> {
     let state = app.state::<Mutex<TtsState>>();
     let state = state.lock().unwrap();
 
@@ -102,7 +107,7 @@ async fn list_voices<R: Runtime>(
 #[tauri::command]
 async fn list_installed_models<R: Runtime>(
     app: tauri::AppHandle<R>,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<String>, AudioError> {
     let state = app.state::<Mutex<TtsState>>();
     let state = state.lock().unwrap();
 
@@ -122,7 +127,7 @@ async fn load_model<R: Runtime>(
     app: tauri::AppHandle<R>,
     window: tauri::WebviewWindow<R>,
     model_id: String,
-) -> Result<(), String> {
+) -> Result<(), AudioError> {
     info!("Loading TTS model: {}", model_id);
 
     let state = app.state::<Mutex<TtsState>>();
@@ -152,41 +157,22 @@ async fn load_model<R: Runtime>(
                     },
                     Err(e) => {
                         info!("Failed to load model {} from cache: {}, attempting re-download", model_id, e);
-                        // Fall back to network download if cache load fails
-                                        info!("Cache load failed for {}, will attempt re-download if needed", model_id);
-
-                // Clear the corrupted cache files
-                let _ = models::clear_model_cache(&model_id);
 
-                // Try to download with a timeout
-                let download_result = tokio::time::timeout(
-                    std::time::Duration::from_secs(60),
-                    models::load_onnx_model(&model_id, window)
-                ).await;
-
-                match download_result {
-                    Ok(Ok(m)) => {
-                        info!("Successfully re-downloaded model {} after cache failure", model_id);
-                        m
-                    },
-                    Ok(Err(e2)) => {
-                        warn!("Download failed for model {}: {}", model_id, e2);
-                        // Create a dummy model so voices still work
-                        if model_id == "hexgrad/Kokoro-82M" {
-                            warn!("Creating placeholder Kokoro model for voice listing");
-                            // We'll mark it as loaded even though it failed, so voices show up
-                            // The actual synthesis will fail gracefully
-                        }
-                        return Err(format!("Model {} could not be loaded but voices are available: {}", model_id, e2));
-                    },
-                    Err(_) => {
-                        warn!("Download timed out for model {}", model_id);
-                        if model_id == "hexgrad/Kokoro-82M" {
-                            warn!("Creating placeholder Kokoro model for voice listing after timeout");
+                        // Clear the corrupted cache files
+                        let _ = models::clear_model_cache(&model_id);
+
+                        // Slow downloads are left to the hub's read timeout and
+                        // `cancel_model_download`
+                        match models::load_onnx_model(&model_id, window).await {
+                            Ok(m) => {
+                                info!("Successfully re-downloaded model {} after cache failure", model_id);
+                                m
+                            },
+                            Err(e) => {
+                                warn!("Download failed for model {}: {}", model_id, e);
+                                return Err(AudioError::load_failed(e.context(format!("Failed to re-download model {}", model_id))));
+                            }
                         }
-                        return Err(format!("Model {} download timed out but voices are available", model_id));
-                    }
-                }
                     }
                 }
             } else {
@@ -198,7 +184,7 @@ async fn load_model<R: Runtime>(
                         m
                     },
                     Err(e) => {
-                        return Err(format!("Failed to load model {}: {}", model_id, e));
+                        return Err(AudioError::load_failed(e.context(format!("Failed to load model {}", model_id))));
                     }
                 }
             }
@@ -220,7 +206,7 @@ async fn reload_model<R: Runtime>(
     app: tauri::AppHandle<R>,
     window: tauri::WebviewWindow<R>,
     model_id: String,
-) -> Result<(), String> {
+) -> Result<(), AudioError> {
     info!("Force reloading TTS model: {}", model_id);
 
     // Clear the model from state first
@@ -244,18 +230,40 @@ async fn reload_model<R: Runtime>(
     load_model(app, window, model_id).await
 }
 
+/// Stops the download of a model being loaded, `load_model` then fails. Returns whether one was
+/// in progress.
+#[tauri::command]
+async fn cancel_model_download(model_id: String) -> Result<bool, AudioError> {
+    Ok(models::cancel_model_download(&model_id))
+}
+
+/// Synthesizes `text` into a WAV file. With an `utterance_id` the utterance is tracked until
+/// `playback_finished` and can be interrupted. Interrupting a synthesis that is running stops it
+/// after the sentence being synthesized, and the command rejects with `cancelled`.
 #[tauri::command]
 async fn synthesize<R: Runtime>(
     app: tauri::AppHandle<R>,
     text: String,
     voice_id: String,
     options: Option<SynthesizeOptions>,
-) -> Result<Vec<u8>, String> {
+    utterance_id: Option<String>,
+) -> Result<Vec<u8>, AudioError> {
     info!("Synthesizing text with voice: {}", voice_id);
 
+    // Tracked utterances can be interrupted while synthesizing or waiting for the model
+    if let Some(id) = &utterance_id {
+        app.state::<Mutex<Playback>>().lock().unwrap().begin(id, &text);
+    }
+
     let state = app.state::<Mutex<TtsState>>();
     let state = state.lock().unwrap();
 
+    if let Some(id) = &utterance_id {
+        if !app.state::<Mutex<Playback>>().lock().unwrap().is_active(id) {
+            return Err(AudioError::Cancelled("Synthesis interrupted".to_string()));
+        }
+    }
+
     // Find the model that contains this voice
     let model = state.loaded_models.values()
         .find(|m| m.has_voice(&voice_id))
@@ -265,33 +273,112 @@ async fn synthesize<R: Runtime>(
     let model = match model {
         Some(m) => m,
         None => {
-            // If we have a Kokoro voice but model isn't loaded, show clear error
-            if voice_id.starts_with("af") || voice_id.starts_with("am") || voice_id.starts_with("jf") ||
-               voice_id.starts_with("jm") || voice_id.starts_with("zf") || voice_id.starts_with("zm") {
-                return Err("Kokoro model is not loaded. Please ensure the model is installed and loaded properly.".to_string());
+            if let Some(id) = &utterance_id {
+                app.state::<Mutex<Playback>>().lock().unwrap().finished(id);
+            }
+
+            // Kokoro voices are known before the model is loaded
+            if models::get_kokoro_voices_static().iter().any(|v| v.id == voice_id) {
+                return Err(AudioError::ModelNotLoaded("Kokoro model is not loaded. Please ensure the model is installed and loaded properly.".to_string()));
             } else {
-                return Err("No suitable model loaded for voice".to_string());
+                return Err(AudioError::NotFound(format!("No loaded model has the voice {}", voice_id)));
             }
         }
     };
 
     // Synthesize audio
-    let audio = model.synthesize(&text, &voice_id, options.as_ref())
-        .map_err(|e| format!("Synthesis failed: {}", e))?;
+    let interrupted = || {
+        utterance_id.as_ref().is_some_and(|id| !app.state::<Mutex<Playback>>().lock().unwrap().is_active(id))
+    };
+    let audio = model.synthesize(&text, &voice_id, options.as_ref(), &interrupted)
+        .map_err(|e| {
+            if let Some(id) = &utterance_id {
+                app.state::<Mutex<Playback>>().lock().unwrap().finished(id);
+            }
+            AudioError::inference_failed(e.context("Synthesis failed"))
+        })?;
 
     // Convert to WAV format with correct sample rate (Kokoro uses 24kHz)
     let sample_rate = if voice_id.starts_with("espeak") { 22050 } else { 24000 };
+
+    if let Some(id) = &utterance_id {
+        if !app.state::<Mutex<Playback>>().lock().unwrap().synthesized(id, &audio, sample_rate) {
+            return Err(AudioError::Cancelled("Synthesis interrupted".to_string()));
+        }
+    }
     let wav_data = audio::to_wav(&audio, sample_rate)
-        .map_err(|e| format!("Failed to encode WAV: {}", e))?;
+        .map_err(|e| AudioError::Internal(format!("Failed to encode WAV: {}", e)))?;
 
     Ok(wav_data)
 }
 
+/// Marks the start of playing a synthesized utterance, how much of it was spoken is estimated
+/// from the time since. Its audio becomes the echo reference of the preprocess plugin.
+#[tauri::command]
+async fn playback_started<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    utterance_id: String,
+) -> Result<(), AudioError> {
+    let audio = app.state::<Mutex<Playback>>().lock().unwrap().started(&utterance_id);
+    if let Some((samples, sample_rate)) = audio {
+        let played = tauri_plugin_ipc_audio_preprocess::play_echo_reference(&app, samples, sample_rate, 1);
+        if let Err(e) = played {
+            warn!("Failed to play echo reference: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn playback_finished<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    utterance_id: String,
+) -> Result<(), AudioError> {
+    app.state::<Mutex<Playback>>().lock().unwrap().finished(&utterance_id);
+    Ok(())
+}
+
+#[tauri::command]
+async fn interrupt_playback<R: Runtime>(
+    app: tauri::AppHandle<R>,
+) -> Result<Vec<Interrupted>, AudioError> {
+    Ok(interrupt(&app))
+}
+
+/// Cancels every tracked utterance, those still synthesizing reject after their current sentence
+/// and those playing are reported through the interrupted event. Called when the VAD plugin detects a barge-in.
+pub fn interrupt<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<Interrupted> {
+    let interrupted = app.state::<Mutex<Playback>>().lock().unwrap().interrupt();
+    if !interrupted.is_empty() {
+        // What was still to be played does not come out of the speakers anymore
+        match tauri_plugin_ipc_audio_preprocess::echo_reference(app) {
+            Ok(reference) => reference.stop(),
+            Err(e) => warn!("Failed to stop echo reference: {}", e),
+        }
+    }
+
+    for utterance in &interrupted {
+        info!("Interrupted utterance {} after {} ms", utterance.utterance_id, utterance.spoken_ms);
+        if let Err(e) = app.emit(INTERRUPTED_EVENT, utterance) {
+            warn!("Failed to emit interrupted event: {}", e);
+        }
+    }
+
+    interrupted
+}
+
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
     PluginBuilder::new("ipc-audio-tts-ort")
         .setup(|app, _| {
             info!("Initializing TTS plugin...");
             app.manage(Mutex::new(TtsState::default()));
+            app.manage(Mutex::new(Playback::default()));
+
+            // The VAD plugin decides when the user talks over the assistant
+            let handle = app.clone();
+            app.listen_any(BARGE_IN_EVENT, move |_| {
+                interrupt(&handle);
+            });
 
             // Load eSpeak as default fallback
             let state = app.state::<Mutex<TtsState>>();
@@ -309,7 +396,11 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
             list_installed_models,
             load_model,
             reload_model,
+            cancel_model_download,
             synthesize,
+            playback_started,
+            playback_finished,
+            interrupt_playback,
         ])
         .build()
 }
@@ -1,19 +1,15 @@
+use airi_audio_models::{
+    create_session, AudioError, ExecutionProvider, GraphOptimizationLevel, ModelDownloadProgress, ModelRepo, ProgressEmitter,
+    SessionOptions,
+};
 use anyhow::{anyhow, Result};
 use log::{info, warn};
-use ort::{
-    execution_providers::CPUExecutionProvider,
-    session::{builder::GraphOptimizationLevel, Session},
-    util::Mutex,
-    value::Tensor,
-};
-use reqwest::Client;
+use ort::{session::Session, util::Mutex, value::Tensor};
 use serde::{Deserialize, Serialize};
 use std::{path::{Path, PathBuf}, sync::Arc};
-use tauri::{Emitter, Runtime};
+use tauri::Runtime;
 use tokenizers::Tokenizer;
 use serde_json::Value as JsonValue;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
 
 use crate::SynthesizeOptions;
 
@@ -72,9 +68,10 @@ impl TtsModel {
         self.get_voices().iter().any(|v| v.id == voice_id)
     }
 
-    pub fn synthesize(&self, text: &str, voice_id: &str, options: Option<&SynthesizeOptions>) -> Result<Vec<f32>> {
+    /// Stops with `AudioError::Cancelled` once `interrupted` returns true, checked between sentences.
+    pub fn synthesize(&self, text: &str, voice_id: &str, options: Option<&SynthesizeOptions>, interrupted: &dyn Fn() -> bool) -> Result<Vec<f32>> {
         match self {
-            TtsModel::Onnx(model) => model.synthesize(text, voice_id, options),
+            TtsModel::Onnx(model) => model.synthesize(text, voice_id, options, interrupted),
             TtsModel::ESpeak => synthesize_espeak(text, voice_id, options),
         }
     }
@@ -165,16 +162,29 @@ impl OnnxTtsModel {
         self.voices.clone()
     }
 
-    pub fn synthesize(&self, text: &str, voice_id: &str, options: Option<&SynthesizeOptions>) -> Result<Vec<f32>> {
+    pub fn synthesize(&self, text: &str, voice_id: &str, options: Option<&SynthesizeOptions>, interrupted: &dyn Fn() -> bool) -> Result<Vec<f32>> {
         // Input validation
         if text.trim().is_empty() {
-            return Err(anyhow!("Text input cannot be empty"));
+            return Err(AudioError::InvalidInput("Text input cannot be empty".to_string()).into());
         }
 
         if text.len() > 1000 {
-            return Err(anyhow!("Text too long (max 1000 characters)"));
+            return Err(AudioError::InvalidInput("Text too long (max 1000 characters)".to_string()).into());
+        }
+
+        // One sentence at a time, so an interrupted utterance stops after the sentence running
+        let mut audio_samples = Vec::new();
+        for sentence in split_sentences(text) {
+            if interrupted() {
+                return Err(AudioError::Cancelled("Synthesis interrupted".to_string()).into());
+            }
+            audio_samples.extend(self.synthesize_sentence(sentence, voice_id, options)?);
         }
 
+        Ok(audio_samples)
+    }
+
+    fn synthesize_sentence(&self, text: &str, voice_id: &str, options: Option<&SynthesizeOptions>) -> Result<Vec<f32>> {
         // Tokenize the input text
         let encoding = self.tokenizer.encode(text.trim(), false)
             .map_err(|e| anyhow!("Tokenization failed: {}", e))?;
@@ -186,7 +196,7 @@ impl OnnxTtsModel {
         }
 
         if tokens.len() > 512 {
-            return Err(anyhow!("Tokenized sequence too long (max 512 tokens)"));
+            return Err(AudioError::InvalidInput("Tokenized sequence too long (max 512 tokens)".to_string()).into());
         }
 
         // Convert tokens and validate
@@ -252,7 +262,7 @@ impl OnnxTtsModel {
         // Extract speed from options or use default, ensure it's in reasonable range
         let speed = options.and_then(|o| o.speed).unwrap_or(1.0);
         if !speed.is_finite() || speed <= 0.0 || speed > 3.0 {
-            return Err(anyhow!("Speed must be finite and between 0.0 and 3.0, got {}", speed));
+            return Err(AudioError::InvalidInput(format!("Speed must be finite and between 0.0 and 3.0, got {}", speed)).into());
         }
 
         info!("Creating tensors: input_ids shape=[1, {}], style shape=[1, 256], speed shape=[1] value={}", tokens_len, speed);
@@ -414,6 +424,31 @@ impl OnnxTtsModel {
     }
 }
 
+/// Splits after sentence-ending punctuation and line breaks. Periods, question and exclamation
+/// marks only end a sentence before whitespace, so numbers like "3.5" stay whole.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let ends = match c {
+            '\n' => true,
+            '.' | '!' | '?' => next.is_some_and(char::is_whitespace),
+            '。' | '！' | '？' => !matches!(next, Some('。' | '！' | '？')),
+            _ => false,
+        };
+        if ends {
+            let end = i + c.len_utf8();
+            sentences.push(&text[start..end]);
+            start = end;
+        }
+    }
+    sentences.push(&text[start..]);
+    sentences.retain(|sentence| !sentence.trim().is_empty());
+    sentences
+}
+
 fn synthesize_espeak(text: &str, _voice_id: &str, options: Option<&SynthesizeOptions>) -> Result<Vec<f32>> {
     // Generate simple sine wave as placeholder
     let sample_rate = 22050;
@@ -433,28 +468,69 @@ fn synthesize_espeak(text: &str, _voice_id: &str, options: Option<&SynthesizeOpt
     Ok(audio)
 }
 
-async fn download_file_with_progress(url: &str, filename: &str) -> Result<PathBuf> {
-    let client = Client::new();
-    let response = client.get(url).send().await?;
+const PROGRESS_EVENT: &str = "tauri-plugins:tauri-plugin-ipc-audio-tts-ort:load-model-progress";
+
+/// Kokoro ONNX community repository
+const KOKORO_REPO: &str = "onnx-community/Kokoro-82M-v1.0-ONNX";
 
-    if !response.status().is_success() {
-        return Err(anyhow!("Failed to download {}: HTTP {}", filename, response.status()));
+/// Files a Kokoro model needs
+const KOKORO_FILES: [&str; 4] = ["onnx/model.onnx", "config.json", "tokenizer.json", "tokenizer_config.json"];
+
+fn kokoro_repo(model_id: &str) -> Result<ModelRepo> {
+    match model_id {
+        "hexgrad/Kokoro-82M" => ModelRepo::new(KOKORO_REPO, "main"),
+        _ => Err(AudioError::InvalidInput(format!("Only Kokoro-82M is supported. Model ID: {}", model_id)).into()),
     }
+}
+
+/// Cancels the download of a model, named by its id or by the repository its progress events carry.
+/// Returns whether one was in progress.
+pub fn cancel_model_download(model_id: &str) -> bool {
+    let repo_id = kokoro_repo(model_id)
+        .map(|repo| repo.model_id().to_string())
+        .unwrap_or_else(|_| model_id.to_string());
+    airi_audio_models::cancel_download(&repo_id)
+}
+
+/// Directories older versions downloaded Kokoro into, before the Hugging Face cache was used:
+/// huggingface/transformers/kokoro/<model_id>/ and huggingface/transformers/ itself
+fn legacy_roots(model_id: &str) -> Vec<PathBuf> {
+    let Some(cache_base) = dirs::cache_dir() else {
+        return Vec::new();
+    };
+    let transformers = cache_base.join("huggingface").join("transformers");
+    vec![transformers.join("kokoro").join(model_id.replace('/', "_")), transformers]
+}
 
-    // Create cache directory structure similar to hf-hub
-    let cache_dir = dirs::cache_dir()
-        .ok_or_else(|| anyhow!("Could not find cache directory"))?
-        .join("huggingface")
-        .join("transformers");
+/// Path of a model file already on disk, in the Hugging Face cache or in one of the legacy layouts
+fn cached_file(repo: &ModelRepo, model_id: &str, file: &str) -> Option<PathBuf> {
+    repo.cached(file).or_else(|| {
+        let name = Path::new(file).file_name()?;
+        legacy_roots(model_id)
+            .into_iter()
+            .map(|root| root.join(name))
+            .find(|path| path.exists())
+    })
+}
 
-    tokio::fs::create_dir_all(&cache_dir).await?;
-    let file_path = cache_dir.join(filename);
+/// Removes a file of the Hugging Face cache together with the blob its snapshot entry points to
+fn remove_cached_file(path: &Path) {
+    if let Ok(blob) = std::fs::canonicalize(path) {
+        if blob != path {
+            let _ = std::fs::remove_file(&blob);
+        }
+    }
+    match std::fs::remove_file(path) {
+        Ok(_) => info!("Removed cached file: {:?}", path),
+        Err(e) => info!("Failed to remove cached file {:?}: {}", path, e),
+    }
+}
 
-    let bytes = response.bytes().await?;
-    let mut file = File::create(&file_path).await?;
-    file.write_all(&bytes).await?;
+/// For downloads nobody is waiting on
+struct Quiet;
 
-    Ok(file_path)
+impl ProgressEmitter for Quiet {
+    fn emit_progress(&self, _: &str, _: &ModelDownloadProgress) {}
 }
 
 /// Check whether a given model appears installed on disk (all key assets present).
@@ -583,32 +659,15 @@ fn load_tokenizer_from_cache_with_fallback(tokenizer_path: &Path) -> Result<Toke
 fn create_basic_tokenizer() -> Result<Tokenizer> {
     warn!("Tokenizer loading failed, downloading fresh Kokoro tokenizer directly");
 
-    // Use the Kokoro tokenizer from the official ONNX community repo
-    let tokenizer_url = "https://huggingface.co/onnx-community/Kokoro-82M-v1.0-ONNX/resolve/main/tokenizer.json";
-
-    // Create a temporary file to store the downloaded tokenizer
-    let temp_dir = std::env::temp_dir();
-    let temp_path = temp_dir.join("kokoro_fresh_tokenizer.json");
-
-    // Download the tokenizer synchronously using reqwest blocking
-    let client = reqwest::blocking::Client::new();
-    let response = client.get(tokenizer_url).send()
+    // Use the Kokoro tokenizer from the official ONNX community repo, replacing the cached one
+    let tokenizer_path = ModelRepo::new(KOKORO_REPO, "main")?
+        .download("tokenizer.json", Quiet, PROGRESS_EVENT)
         .map_err(|e| anyhow!("Failed to download Kokoro tokenizer: {}", e))?;
 
-    if !response.status().is_success() {
-        return Err(anyhow!("Failed to download tokenizer: HTTP {}", response.status()));
-    }
-
-    let tokenizer_bytes = response.bytes()
-        .map_err(|e| anyhow!("Failed to read tokenizer response: {}", e))?;
-
-    std::fs::write(&temp_path, &tokenizer_bytes)
-        .map_err(|e| anyhow!("Failed to write tokenizer file: {}", e))?;
-
-    info!("Downloaded fresh Kokoro tokenizer ({} bytes)", tokenizer_bytes.len());
+    info!("Downloaded fresh Kokoro tokenizer");
 
     // Try to load the fresh tokenizer
-    match Tokenizer::from_file(&temp_path) {
+    match Tokenizer::from_file(&tokenizer_path) {
         Ok(tokenizer) => {
             info!("Successfully loaded fresh Kokoro tokenizer with vocab size: {}", tokenizer.get_vocab_size(false));
             Ok(tokenizer)
@@ -616,7 +675,7 @@ fn create_basic_tokenizer() -> Result<Tokenizer> {
         Err(e) => {
             warn!("Fresh Kokoro tokenizer failed to load: {}", e);
             // Try to fix common issues with the downloaded tokenizer
-            fix_and_load_tokenizer(&temp_path)
+            fix_and_load_tokenizer(&tokenizer_path)
         }
     }
 }
@@ -669,45 +728,31 @@ fn fix_and_load_tokenizer(tokenizer_path: &Path) -> Result<Tokenizer> {
 pub fn clear_model_cache(model_id: &str) -> Result<()> {
     info!("Clearing cache for model: {}", model_id);
 
-    let cache_base = dirs::cache_dir()
-        .ok_or_else(|| anyhow!("Could not find cache directory"))?;
-
-    // New layout: huggingface/transformers/kokoro/<model_id>/
-    let new_root = cache_base
-        .join("huggingface")
-        .join("transformers")
-        .join("kokoro")
-        .join(model_id.replace('/', "_"));
-
-    // Legacy layout: huggingface/transformers/
-    let legacy_root = cache_base.join("huggingface").join("transformers");
-
-    let required = ["model.onnx", "config.json", "tokenizer.json", "tokenizer_config.json"];
-
-    // Clear new layout files
-    for name in required {
-        let path = new_root.join(name);
-        if path.exists() {
-            match std::fs::remove_file(&path) {
-                Ok(_) => info!("Removed cached file: {:?}", path),
-                Err(e) => info!("Failed to remove cached file {:?}: {}", path, e),
-            }
+    let repo = kokoro_repo(model_id)?;
+    for file in KOKORO_FILES {
+        if let Some(path) = repo.cached(file) {
+            remove_cached_file(&path);
         }
     }
 
-    // Clear legacy layout files
-    for name in required {
-        let path = legacy_root.join(name);
-        if path.exists() {
-            match std::fs::remove_file(&path) {
-                Ok(_) => info!("Removed legacy cached file: {:?}", path),
-                Err(e) => info!("Failed to remove legacy cached file {:?}: {}", path, e),
+    // Clear files of the legacy layouts
+    let roots = legacy_roots(model_id);
+    for root in &roots {
+        for file in KOKORO_FILES {
+            let path = root.join(Path::new(file).file_name().unwrap_or_default());
+            if path.exists() {
+                match std::fs::remove_file(&path) {
+                    Ok(_) => info!("Removed legacy cached file: {:?}", path),
+                    Err(e) => info!("Failed to remove legacy cached file {:?}: {}", path, e),
+                }
             }
         }
     }
 
-    // Try to remove the cache directory if it's empty
-    let _ = std::fs::remove_dir(&new_root);
+    // Try to remove the legacy cache directory if it's empty
+    if let Some(root) = roots.first() {
+        let _ = std::fs::remove_dir(root);
+    }
 
     Ok(())
 }
@@ -716,22 +761,17 @@ pub fn clear_model_cache(model_id: &str) -> Result<()> {
 pub fn clear_tokenizer_cache(model_id: &str) -> Result<()> {
     info!("Clearing corrupted tokenizer cache for model: {}", model_id);
 
-    let cache_base = dirs::cache_dir()
-        .ok_or_else(|| anyhow!("Could not find cache directory"))?;
-
-    // Clear from new layout
-    let new_root = cache_base
-        .join("huggingface")
-        .join("transformers")
-        .join("kokoro")
-        .join(model_id.replace('/', "_"));
-
-    // Clear from legacy layout
-    let legacy_root = cache_base.join("huggingface").join("transformers");
-
     let tokenizer_files = ["tokenizer.json", "tokenizer_config.json"];
 
-    for root in [&new_root, &legacy_root] {
+    let repo = kokoro_repo(model_id)?;
+    for name in tokenizer_files {
+        if let Some(path) = repo.cached(name) {
+            remove_cached_file(&path);
+            let _ = std::fs::remove_file(path.with_extension("fixed.json"));
+        }
+    }
+
+    for root in legacy_roots(model_id) {
         for name in tokenizer_files {
             let path = root.join(name);
             if path.exists() {
@@ -799,123 +839,58 @@ pub fn get_kokoro_voices_static() -> Vec<VoiceInfo> {
 }
 
 pub fn is_model_installed(model_id: &str) -> bool {
-    // Known required files for Kokoro ONNX community model
-    let required = ["model.onnx", "config.json", "tokenizer.json", "tokenizer_config.json"];
-
-    let cache_base = match dirs::cache_dir() { Some(p) => p, None => {
-        info!("Could not find cache directory for model check: {}", model_id);
-        return false;
-    }};
-
-    // New layout: huggingface/transformers/kokoro/<model_id>/
-    let new_root = cache_base
-        .join("huggingface")
-        .join("transformers")
-        .join("kokoro")
-        .join(model_id.replace('/', "_"));
-
-    info!("Checking new layout path for {}: {:?}", model_id, new_root);
-    let new_ok = required.iter().all(|name| {
-        let path = new_root.join(name);
-        let exists = path.exists();
-        info!("  File {}: exists={} at {:?}", name, exists, path);
-        exists
-    });
-
-    if new_ok {
-        info!("Model {} found in new layout", model_id);
-        return true;
-    }
+    let repo = match kokoro_repo(model_id) {
+        Ok(repo) => repo,
+        Err(e) => {
+            info!("Could not check model {}: {}", model_id, e);
+            return false;
+        }
+    };
 
-    // Backward compatibility: files might have been stored directly under huggingface/transformers
-    let legacy_root = cache_base.join("huggingface").join("transformers");
-    info!("Checking legacy layout path for {}: {:?}", model_id, legacy_root);
-    let legacy_ok = required.iter().all(|name| {
-        let path = legacy_root.join(name);
-        let exists = path.exists();
-        info!("  Legacy file {}: exists={} at {:?}", name, exists, path);
-        exists
+    let installed = KOKORO_FILES.iter().all(|file| {
+        let path = cached_file(&repo, model_id, file);
+        info!("  File {}: {:?}", file, path);
+        path.is_some()
     });
 
-    if legacy_ok {
-        info!("Model {} found in legacy layout", model_id);
+    if installed {
+        info!("Model {} found in cache", model_id);
     } else {
-        info!("Model {} not found in either layout", model_id);
+        info!("Model {} not found in cache", model_id);
     }
 
-    legacy_ok
+    installed
 }
 
 pub async fn load_onnx_model<R: Runtime>(
     model_id: &str,
     window: tauri::WebviewWindow<R>,
+) -> Result<TtsModel> {
+    let model_id = model_id.to_string();
+    tokio::task::spawn_blocking(move || download_onnx_model(&model_id, window)).await?
+}
+
+/// Downloads the files of a Kokoro model that are not on disk yet, then loads it
+fn download_onnx_model<R: Runtime>(
+    model_id: &str,
+    window: tauri::WebviewWindow<R>,
 ) -> Result<TtsModel> {
     info!("Loading ONNX TTS model: {}", model_id);
 
     // Only support Kokoro-82M (use ONNX community version)
-    let (repo_id, _revision) = match model_id {
-        "hexgrad/Kokoro-82M" => ("onnx-community/Kokoro-82M-v1.0-ONNX", "main"),
-        _ => return Err(anyhow!("Only Kokoro-82M is supported. Model ID: {}", model_id)),
-    };
-
-    // Skip hf-hub API and use direct HTTP downloads for better subdirectory support
-
-    // Emit progress events
-    let emit_progress = |filename: &str, progress: f32| {
-        let _ = window.emit(
-            "tauri-plugins:tauri-plugin-ipc-audio-tts-ort:load-model-progress",
-            (false, filename, progress, 100, (progress * 100.0) as usize),
-        );
-    };
-
-    emit_progress(model_id, 0.0);
-
-    // Target cache directory where we persist Kokoro assets, namespaced by model id
-    let cache_root = dirs::cache_dir()
-        .ok_or_else(|| anyhow!("Could not find cache directory"))?
-        .join("huggingface")
-        .join("transformers")
-        .join("kokoro")
-        .join(model_id.replace('/', "_"));
-    tokio::fs::create_dir_all(&cache_root).await?;
-
-    // Helper to resolve cached path and decide whether to download
-    let model_id_owned = model_id.to_string();
-    let ensure_file = |name: &str, url: String, progress: f32| {
-        let cache_root = cache_root.clone();
-        let name_owned = name.to_string();
-        let model_id_owned = model_id_owned.clone();
-        async move {
-            let path = cache_root.join(&name_owned);
-            if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
-                let downloaded = download_file_with_progress(&url, &name_owned).await?;
-                // Move from generic cache root to our subdir if needed
-                if downloaded != path {
-                    if let Some(parent) = path.parent() { tokio::fs::create_dir_all(parent).await.ok(); }
-                    tokio::fs::rename(&downloaded, &path).await.ok();
-                }
-            }
-            emit_progress(&model_id_owned, progress);
-            Result::<PathBuf>::Ok(path)
-        }
-    };
+    let repo = kokoro_repo(model_id)?;
 
     // Download Kokoro ONNX community model files or reuse cached ones
-    // onnx/model.onnx
-    let model_url = format!("https://huggingface.co/{}/resolve/main/onnx/model.onnx", repo_id);
-    let model_path = ensure_file("model.onnx", model_url, 40.0).await?;
-
-    // config.json
-    let config_url = format!("https://huggingface.co/{}/resolve/main/config.json", repo_id);
-    let config_path = ensure_file("config.json", config_url, 60.0).await?;
-
-    // tokenizer.json
-    let tokenizer_url = format!("https://huggingface.co/{}/resolve/main/tokenizer.json", repo_id);
-    let tokenizer_path = ensure_file("tokenizer.json", tokenizer_url, 80.0).await?;
-
-    // tokenizer_config.json
-    let tokenizer_config_url = format!("https://huggingface.co/{}/resolve/main/tokenizer_config.json", repo_id);
-    let _tokenizer_config_path = ensure_file("tokenizer_config.json", tokenizer_config_url, 90.0).await?;
+    let ensure_file = |file: &str| match cached_file(&repo, model_id, file) {
+        Some(path) => Ok(path),
+        None => repo.get(file, window.clone(), PROGRESS_EVENT),
+    };
+    let [model_path, config_path, tokenizer_path, _tokenizer_config_path] = [
+        ensure_file(KOKORO_FILES[0])?,
+        ensure_file(KOKORO_FILES[1])?,
+        ensure_file(KOKORO_FILES[2])?,
+        ensure_file(KOKORO_FILES[3])?,
+    ];
 
     // Load config
     let config_str = std::fs::read_to_string(config_path)?;
@@ -928,42 +903,32 @@ pub async fn load_onnx_model<R: Runtime>(
             warn!("Failed to load tokenizer with all fallbacks: {}", e);
             warn!("Attempting to download a fresh tokenizer from HuggingFace");
 
-            // Try downloading a fresh tokenizer using async
-            let tokenizer_url = format!("https://huggingface.co/{}/resolve/main/tokenizer.json", repo_id);
-            let runtime = tokio::runtime::Runtime::new()?;
-            let download_result = runtime.block_on(async {
-                let client = reqwest::Client::new();
-                client.get(&tokenizer_url).send().await
-            });
-
-            match download_result {
-                Ok(response) if response.status().is_success() => {
-                    let tokenizer_bytes = runtime.block_on(response.bytes())?;
-                    std::fs::write(&tokenizer_path, &tokenizer_bytes)?;
+            match repo.download("tokenizer.json", Quiet, PROGRESS_EVENT) {
+                Ok(tokenizer_path) => {
                     info!("Downloaded fresh tokenizer, attempting to load");
 
                     // Try the simple load first
-                                         match Tokenizer::from_file(&tokenizer_path) {
-                         Ok(t) => {
-                             info!("Successfully loaded fresh Kokoro tokenizer with vocab size: {}", t.get_vocab_size(false));
-                             t
-                         },
-                         Err(_) => {
-                             // If that fails, try to fix and load the downloaded tokenizer
-                             warn!("Fresh tokenizer loading failed, attempting to fix compatibility issues");
-                             match fix_and_load_tokenizer(&tokenizer_path) {
-                                 Ok(t) => t,
-                                 Err(_) => {
-                                     // Last resort - download a fresh tokenizer directly
-                                     warn!("All tokenizer methods failed, downloading fresh Kokoro tokenizer as last resort");
-                                     create_basic_tokenizer()?
-                                 }
-                             }
-                         }
-                     }
+                    match Tokenizer::from_file(&tokenizer_path) {
+                        Ok(t) => {
+                            info!("Successfully loaded fresh Kokoro tokenizer with vocab size: {}", t.get_vocab_size(false));
+                            t
+                        },
+                        Err(_) => {
+                            // If that fails, try to fix and load the downloaded tokenizer
+                            warn!("Fresh tokenizer loading failed, attempting to fix compatibility issues");
+                            match fix_and_load_tokenizer(&tokenizer_path) {
+                                Ok(t) => t,
+                                Err(_) => {
+                                    // Last resort - download a fresh tokenizer directly
+                                    warn!("All tokenizer methods failed, downloading fresh Kokoro tokenizer as last resort");
+                                    create_basic_tokenizer()?
+                                }
+                            }
+                        }
+                    }
                 }
-                _ => {
-                    warn!("Could not download fresh tokenizer, using minimal fallback");
+                Err(e) => {
+                    warn!("Could not download fresh tokenizer: {}, using minimal fallback", e);
                     create_basic_tokenizer()?
                 }
             }
@@ -971,14 +936,7 @@ pub async fn load_onnx_model<R: Runtime>(
     };
 
     // Create ONNX session
-    let session = create_optimized_session(model_path)?;
-    emit_progress(model_id, 100.0);
-
-    // Emit completion
-    let _ = window.emit(
-        "tauri-plugins:tauri-plugin-ipc-audio-tts-ort:load-model-progress",
-        (true, model_id, 100.0),
-    );
+    let session = create_optimized_session(&model_path)?;
 
     Ok(TtsModel::Onnx(OnnxTtsModel::new(session, config, model_id.to_string(), tokenizer)))
 }
@@ -989,50 +947,27 @@ pub fn load_onnx_model_from_cache(model_id: &str) -> Result<TtsModel> {
     info!("Loading ONNX TTS model from cache: {}", model_id);
 
     // Only support Kokoro-82M
-    if model_id != "hexgrad/Kokoro-82M" {
-        return Err(anyhow!("Only Kokoro-82M is supported. Model ID: {}", model_id));
-    }
+    let repo = kokoro_repo(model_id)?;
 
-    let cache_root = dirs::cache_dir()
-        .ok_or_else(|| anyhow!("Could not find cache directory"))?
-        .join("huggingface")
-        .join("transformers")
-        .join("kokoro")
-        .join(model_id.replace('/', "_"));
-
-    let model_path = cache_root.join("model.onnx");
-    let config_path = cache_root.join("config.json");
-    let tokenizer_path = cache_root.join("tokenizer.json");
-    let tokenizer_config_path = cache_root.join("tokenizer_config.json");
-
-    // Fallback to legacy layout
-    let legacy_root = dirs::cache_dir()
-        .ok_or_else(|| anyhow!("Could not find cache directory"))?
-        .join("huggingface")
-        .join("transformers");
-    let get_first_existing = |preferred: &Path, legacy_name: &str| {
-        if preferred.exists() { preferred.to_path_buf() } else { legacy_root.join(legacy_name) }
-    };
-
-    let model_path = get_first_existing(&model_path, "model.onnx");
-    let config_path = get_first_existing(&config_path, "config.json");
-    let tokenizer_path = get_first_existing(&tokenizer_path, "tokenizer.json");
-    let tokenizer_config_path = get_first_existing(&tokenizer_config_path, "tokenizer_config.json");
+    let [model_path, config_path, tokenizer_path, tokenizer_config_path] =
+        KOKORO_FILES.map(|file| cached_file(&repo, model_id, file));
 
     info!("Checking cache files: model={}, config={}, tokenizer={}, tokenizer_config={}",
-          model_path.exists(), config_path.exists(), tokenizer_path.exists(), tokenizer_config_path.exists());
+          model_path.is_some(), config_path.is_some(), tokenizer_path.is_some(), tokenizer_config_path.is_some());
 
-    if !model_path.exists() || !config_path.exists() || !tokenizer_path.exists() || !tokenizer_config_path.exists() {
+    let (Some(model_path), Some(config_path), Some(tokenizer_path), Some(_)) =
+        (&model_path, &config_path, &tokenizer_path, &tokenizer_config_path)
+    else {
         return Err(anyhow!("Cached model files not found for {}. Missing files: model={}, config={}, tokenizer={}, tokenizer_config={}",
-                   model_id, !model_path.exists(), !config_path.exists(), !tokenizer_path.exists(), !tokenizer_config_path.exists()));
-    }
+                   model_id, model_path.is_none(), config_path.is_none(), tokenizer_path.is_none(), tokenizer_config_path.is_none()));
+    };
 
-        let config_str = std::fs::read_to_string(&config_path)?;
+    let config_str = std::fs::read_to_string(config_path)?;
     let config: TtsConfig = serde_json::from_str(&config_str)?;
     info!("Successfully loaded config for {}", model_id);
 
     // Load tokenizer with fallback for compatibility
-    let tokenizer = load_tokenizer_from_cache_with_fallback(&tokenizer_path)
+    let tokenizer = load_tokenizer_from_cache_with_fallback(tokenizer_path)
         .map_err(|e| anyhow!("Failed to load tokenizer from cache: {}", e))?;
     info!("Successfully loaded tokenizer for {}", model_id);
 
@@ -1042,15 +977,12 @@ pub fn load_onnx_model_from_cache(model_id: &str) -> Result<TtsModel> {
     Ok(TtsModel::Onnx(OnnxTtsModel::new(session, config, model_id.to_string(), tokenizer)))
 }
 
-fn create_optimized_session(model_path: PathBuf) -> Result<Session> {
-    // Try CPU-only first to avoid DirectML/CUDA issues with Kokoro model
-    let session = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level1)?  // Reduce optimization level
-        .with_parallel_execution(false)?  // Disable parallel execution for stability
-        .with_execution_providers([
-            CPUExecutionProvider::default().build(),
-        ])?
-        .commit_from_file(model_path)?;
-
-    Ok(session)
+fn create_optimized_session(model_path: &Path) -> Result<Session> {
+    // CPU only to avoid DirectML/CUDA issues with Kokoro model
+    create_session(model_path, SessionOptions {
+        optimization_level: GraphOptimizationLevel::Level1, // Reduce optimization level
+        parallel_execution: false, // Disable parallel execution for stability
+        execution_providers: vec![ExecutionProvider::Cpu],
+        ..Default::default()
+    })
 }
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Emitted once for every utterance that was cut off, so the caller can stop playing it and
+/// trim what the assistant said from the conversation.
+pub const INTERRUPTED_EVENT: &str = "tauri-plugins:tauri-plugin-ipc-audio-tts-ort:interrupted";
+
+/// Utterances the caller never reported finished are forgotten after this long, e.g. when the
+/// webview dropped their audio or was reloaded.
+const UTTERANCE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How long past the end of its audio a started utterance is still considered playing
+const PLAYBACK_GRACE: Duration = Duration::from_secs(5);
+
+struct Utterance {
+    text: String,
+    begun: Instant,
+    /// Known once synthesized
+    duration_ms: Option<u64>,
+    /// Set when the caller reports that playback started
+    started: Option<Instant>,
+    /// Mono samples and their rate, fed to echo cancellation once playback starts
+    audio: Option<(Vec<f32>, u32)>,
+}
+
+impl Utterance {
+    /// Whether the caller lost track of the utterance, or its audio must have been played by now
+    fn is_stale(&self) -> bool {
+        let played = match (self.started, self.duration_ms) {
+            (Some(started), Some(duration_ms)) => {
+                started.elapsed() > Duration::from_millis(duration_ms) + PLAYBACK_GRACE
+            }
+            _ => false,
+        };
+        played || self.begun.elapsed() > UTTERANCE_TTL
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Interrupted {
+    pub utterance_id: String,
+    pub text: String,
+    /// Estimated from the time played, cut at a word boundary when the text has spaces
+    pub spoken_text: String,
+    pub spoken_ms: u64,
+    /// None when synthesis was still running or waiting
+    pub duration_ms: Option<u64>,
+}
+
+/// Utterances from the start of their synthesis until they finished playing or were interrupted.
+/// Playback happens in the webview, which reports when it starts and ends.
+#[derive(Default)]
+pub struct Playback {
+    utterances: HashMap<String, Utterance>,
+}
+
+impl Playback {
+    pub fn begin(&mut self, utterance_id: &str, text: &str) {
+        self.utterances.retain(|_, utterance| !utterance.is_stale());
+        self.utterances.insert(utterance_id.to_string(), Utterance {
+            text: text.to_string(),
+            begun: Instant::now(),
+            duration_ms: None,
+            started: None,
+            audio: None,
+        });
+    }
+
+    /// False when the utterance was interrupted, its audio is not wanted anymore
+    pub fn is_active(&self, utterance_id: &str) -> bool {
+        self.utterances.contains_key(utterance_id)
+    }
+
+    pub fn synthesized(&mut self, utterance_id: &str, audio: &[f32], sample_rate: u32) -> bool {
+        match self.utterances.get_mut(utterance_id) {
+            Some(utterance) => {
+                utterance.duration_ms = Some(audio.len() as u64 * 1000 / sample_rate as u64);
+                utterance.audio = Some((audio.to_vec(), sample_rate));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the audio of the utterance, which is now coming out of the speakers
+    pub fn started(&mut self, utterance_id: &str) -> Option<(Vec<f32>, u32)> {
+        let utterance = self.utterances.get_mut(utterance_id)?;
+        utterance.started = Some(Instant::now());
+        utterance.audio.take()
+    }
+
+    pub fn finished(&mut self, utterance_id: &str) {
+        self.utterances.remove(utterance_id);
+    }
+
+    /// Forgets every utterance, returning how much of each was spoken. Those that must have
+    /// finished playing already are left out.
+    pub fn interrupt(&mut self) -> Vec<Interrupted> {
+        self.utterances.retain(|_, utterance| !utterance.is_stale());
+        self.utterances
+            .drain()
+            .map(|(utterance_id, utterance)| {
+                let elapsed_ms = utterance
+                    .started
+                    .map_or(0, |started| started.elapsed().as_millis() as u64);
+                let spoken_ms = match utterance.duration_ms {
+                    Some(duration_ms) => elapsed_ms.min(duration_ms),
+                    None => 0,
+                };
+                let spoken_text = match utterance.duration_ms {
+                    Some(duration_ms) if duration_ms > 0 => {
+                        spoken_prefix(&utterance.text, spoken_ms as f64 / duration_ms as f64)
+                    }
+                    _ => String::new(),
+                };
+
+                Interrupted {
+                    utterance_id,
+                    text: utterance.text,
+                    spoken_text,
+                    spoken_ms,
+                    duration_ms: utterance.duration_ms,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The share of `text` said in `ratio` of the audio, assuming an even speaking rate
+fn spoken_prefix(text: &str, ratio: f64) -> String {
+    let chars = text.chars().count();
+    let spoken = ((chars as f64 * ratio).floor() as usize).min(chars);
+    if spoken == chars {
+        return text.to_string();
+    }
+
+    let cut = text.char_indices().nth(spoken).map_or(text.len(), |(i, _)| i);
+    let prefix = &text[..cut];
+    // A word that was only partly said is dropped, texts without spaces are cut anywhere
+    if text[cut..].starts_with(char::is_whitespace) {
+        return prefix.trim_end().to_string();
+    }
+    match prefix.rfind(char::is_whitespace) {
+        Some(space) => prefix[..space].trim_end().to_string(),
+        None if text.contains(char::is_whitespace) => String::new(),
+        None => prefix.to_string(),
+    }
+}
@@ -1,4 +1,15 @@
-const COMMANDS: &[&str] = &["load_ort_model_silero_vad", "ipc_audio_vad"];
+const COMMANDS: &[&str] = &[
+  "load_ort_model_silero_vad",
+  "cancel_model_download",
+  "get_vad_backend",
+  "ipc_audio_vad",
+  "create_vad_session",
+  "push_samples",
+  "reset_vad_session",
+  "set_barge_in_options",
+  "destroy_vad_session",
+  "detect_speech_segments",
+];
 
 fn main() {
   tauri_plugin::Builder::new(COMMANDS).build();
@@ -0,0 +1,33 @@
+pub use airi_audio_models::BARGE_IN_EVENT;
+use serde::{Deserialize, Serialize};
+
+/// When speech detected by a session counts as the user interrupting, shared by all sessions.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BargeInOptions {
+  pub enabled:         bool,
+  /// Mean speech probability of the windows since the segment started
+  pub min_probability: f32,
+  /// Time since the segment started, padding included. Coughs and short noises stay below it.
+  pub min_duration_ms: u32,
+}
+
+impl Default for BargeInOptions {
+  fn default() -> Self {
+    Self {
+      enabled:         true,
+      min_probability: 0.7,
+      min_duration_ms: 400,
+    }
+  }
+}
+
+/// Speech that went on long and confidently enough to interrupt, reported once per segment.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BargeIn {
+  /// Position of the segment start in samples since the session started
+  pub start:       u64,
+  pub duration_ms: u64,
+  pub probability: f32,
+}
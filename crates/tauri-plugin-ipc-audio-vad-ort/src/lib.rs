@@ -1,86 +1,342 @@
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, atomic::Ordering};
 
-use log::info;
+use airi_audio_models::{AudioError, audio};
+use log::{error, info};
 use tauri::{
+  Emitter,
   Manager,
   Runtime,
   plugin::{Builder as PluginBuilder, TauriPlugin},
 };
+use tauri_plugin_ipc_audio_preprocess::PreprocessOptions;
 
-mod helpers;
+mod barge_in;
 mod models;
+pub mod offline;
+mod segmenter;
+mod sessions;
 
-use crate::models::{
-  new_silero_vad_processor,
-  silero_vad::{VADInferenceInput, VADInferenceResult},
+pub use barge_in::{BARGE_IN_EVENT, BargeIn, BargeInOptions};
+pub use models::{
+  VadBackend,
+  VadBackendKind,
+  energy_vad::Detector as EnergyDetector,
+  silero_vad::Processor as SileroVad,
+};
+pub use segmenter::{SegmenterOptions, SpeechEvent, SpeechSegmenter};
+pub use sessions::{VadFrame, VadPushResult, VadSession};
+
+use crate::{
+  models::{
+    new_vad_backend,
+    silero_vad::{VADInferenceInput, VADInferenceResult},
+  },
+  offline::{SAMPLE_RATE, SpeechSegments},
+  sessions::AppDataVadSessions,
 };
 
 #[derive(Default)]
-struct AppDataSileroVadProcessor {
-  silero_vad_processor: Option<crate::models::silero_vad::Processor>,
+struct AppDataVadBackend {
+  backend: Option<Box<dyn VadBackend>>,
+}
+
+impl AppDataVadBackend {
+  fn get(&self) -> Result<&dyn VadBackend, AudioError> {
+    self
+      .backend
+      .as_deref()
+      .ok_or_else(|| AudioError::ModelNotLoaded("VAD model is not loaded".to_string()))
+  }
 }
 
+/// Loads Silero VAD, or the energy detector when it cannot be loaded. Returns the backend in
+/// use, or rejects with `cancelled` when the download was cancelled. Loading again after the
+/// fallback retries Silero, e.g. once the network is back.
 #[tauri::command]
 async fn load_ort_model_silero_vad<R: Runtime>(
   app: tauri::AppHandle<R>,
   window: tauri::WebviewWindow<R>,
-) -> Result<(), String> {
+) -> Result<VadBackendKind, AudioError> {
   info!("Loading models...");
 
   {
-    let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
+    let data = app.state::<Mutex<AppDataVadBackend>>();
     let data = data.lock().unwrap();
-    if data.silero_vad_processor.is_some() {
-      info!("Silero VAD model already loaded, skipping...");
-      return Ok(());
+    if let Some(backend) = &data.backend
+      && backend.kind() == VadBackendKind::Silero
+    {
+      info!("Silero VAD already loaded, skipping...");
+      return Ok(VadBackendKind::Silero);
     }
   }
 
-  match new_silero_vad_processor(window) {
-    Ok(p) => {
-      let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
-      let mut data = data.lock().unwrap();
-      data.silero_vad_processor = Some(p);
-      info!("Silero VAD model loaded successfully");
-    },
-    Err(e) => {
-      let error_message = format!("Failed to load Silero VAD model: {}", e);
-      info!("{}", error_message);
-      return Err(error_message);
-    },
+  let backend = new_vad_backend(window)?;
+  let kind = backend.kind();
+  {
+    let data = app.state::<Mutex<AppDataVadBackend>>();
+    let mut data = data.lock().unwrap();
+    // A load running at the same time may have got Silero, which beats the fallback
+    if let Some(loaded) = &data.backend
+      && loaded.kind() == VadBackendKind::Silero
+    {
+      return Ok(VadBackendKind::Silero);
+    }
+    data.backend = Some(backend);
   }
 
-  info!("All models loaded successfully");
-  Ok(())
+  info!("VAD backend {:?} loaded successfully", kind);
+  Ok(kind)
+}
+
+/// Stops the Silero VAD download started by `load_ort_model_silero_vad`, with the model id of its
+/// progress events. Returns whether it was still downloading.
+#[tauri::command]
+async fn cancel_model_download(model_id: String) -> Result<bool, AudioError> {
+  Ok(airi_audio_models::cancel_download(&model_id))
+}
+
+/// Returns the backend in use, if one was loaded.
+#[tauri::command]
+async fn get_vad_backend<R: Runtime>(
+  app: tauri::AppHandle<R>
+) -> Result<Option<VadBackendKind>, AudioError> {
+  let data = app.state::<Mutex<AppDataVadBackend>>();
+  let data = data.lock().unwrap();
+  Ok(
+    data
+      .backend
+      .as_ref()
+      .map(|backend| backend.kind()),
+  )
 }
 
+/// Stateless inference that matches the JavaScript interface, returns both the output and the
+/// updated state.
 #[tauri::command]
 async fn ipc_audio_vad<R: Runtime>(
   app: tauri::AppHandle<R>,
   input_data: VADInferenceInput,
-) -> Result<VADInferenceResult, String> {
-  let data = app.state::<Mutex<AppDataSileroVadProcessor>>();
+) -> Result<VADInferenceResult, AudioError> {
+  let data = app.state::<Mutex<AppDataVadBackend>>();
   let data = data.lock().unwrap();
+  let backend = data.get()?;
 
-  if let Some(processor) = &data.silero_vad_processor {
-    processor
-      .inference(input_data)
-      .map_err(|e| e.to_string())
-  } else {
-    Err("Silero VAD model is not loaded".to_string())
+  let mut state = input_data.state;
+  if state.len() != backend.state_size() {
+    return Err(AudioError::InvalidInput(format!(
+      "State must have {} elements, got {}",
+      backend.state_size(),
+      state.len()
+    )));
   }
+
+  let output = backend
+    .infer_window(&input_data.input, input_data.sr, &mut state)
+    .map_err(AudioError::inference_failed)?;
+
+  Ok(VADInferenceResult {
+    output: vec![output],
+    state,
+  })
+}
+
+/// Starts a VAD session that keeps the model state between calls, `sample_rate` is 16000 when
+/// not given. `options` configure how speech segments are detected, `preprocess` cleans the
+/// audio up before the model sees it. Returns the session id, the session is destroyed after
+/// five minutes without audio.
+#[tauri::command]
+async fn create_vad_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  sample_rate: Option<u32>,
+  options: Option<SegmenterOptions>,
+  preprocess: Option<PreprocessOptions>,
+) -> Result<u32, AudioError> {
+  let sample_rate = sample_rate.unwrap_or(16000);
+  let mut session = VadSession::new(sample_rate, &options.unwrap_or_default())
+    .map_err(|e| AudioError::InvalidInput(e.to_string()))?;
+  if let Some(preprocess) = preprocess {
+    session = session.with_preprocessor(tauri_plugin_ipc_audio_preprocess::new_preprocessor(
+      &app,
+      sample_rate,
+      &preprocess,
+    )?);
+  }
+
+  let data = app.state::<AppDataVadSessions>();
+  data.remove_idle();
+  let session_id = data.next_id.fetch_add(1, Ordering::Relaxed);
+  data
+    .sessions
+    .lock()
+    .unwrap()
+    .insert(session_id, Arc::new(Mutex::new(session)));
+
+  info!("VAD session {} created", session_id);
+  Ok(session_id)
+}
+
+/// Appends audio of any length to a session, returns the speech probability of every window
+/// completed by it and the speech starts and ends detected so far.
+#[tauri::command]
+async fn push_samples<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+  samples: Vec<f32>,
+) -> Result<VadPushResult, AudioError> {
+  let session = app
+    .state::<AppDataVadSessions>()
+    .get(session_id)?;
+  let mut session = session.lock().unwrap();
+
+  push_to_session(&app, &mut session, &samples)
+}
+
+#[tauri::command]
+async fn reset_vad_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+) -> Result<(), AudioError> {
+  let session = app
+    .state::<AppDataVadSessions>()
+    .get(session_id)?;
+  session.lock().unwrap().reset();
+
+  Ok(())
+}
+
+/// Configures when speech in any session interrupts the assistant, see [`BARGE_IN_EVENT`].
+#[tauri::command]
+async fn set_barge_in_options<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  options: BargeInOptions,
+) -> Result<(), AudioError> {
+  info!("Barge-in options set to {:?}", options);
+  *app
+    .state::<Mutex<BargeInOptions>>()
+    .lock()
+    .unwrap() = options;
+
+  Ok(())
+}
+
+/// Ends a session, returns the end of the speech segment still going on, if any.
+#[tauri::command]
+async fn destroy_vad_session<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  session_id: u32,
+) -> Result<Vec<SpeechEvent>, AudioError> {
+  let session = app
+    .state::<AppDataVadSessions>()
+    .sessions
+    .lock()
+    .unwrap()
+    .remove(&session_id)
+    .ok_or_else(|| AudioError::NotFound(format!("VAD session {} does not exist", session_id)))?;
+
+  info!("VAD session {} destroyed", session_id);
+  Ok(session.lock().unwrap().finish())
+}
+
+/// Finds the speech in a whole recording, given either as interleaved PCM `samples` at any
+/// rate or as the `path` of an audio file. Timestamps are sample offsets at 16 kHz, the rate
+/// everything is resampled to. With `return_audio` the speech is also returned without the
+/// silence in between.
+#[tauri::command]
+async fn detect_speech_segments<R: Runtime>(
+  app: tauri::AppHandle<R>,
+  samples: Option<Vec<f32>>,
+  sample_rate: Option<u32>,
+  channels: Option<u16>,
+  path: Option<String>,
+  options: Option<SegmenterOptions>,
+  return_audio: Option<bool>,
+) -> Result<SpeechSegments, AudioError> {
+  // Decoding and resampling happen before the model lock is taken
+  let audio = match (samples, path) {
+    (Some(samples), None) => audio::to_mono(
+      samples,
+      sample_rate.unwrap_or(SAMPLE_RATE as u32),
+      channels.unwrap_or(1) as usize,
+      SAMPLE_RATE,
+    ),
+    (None, Some(path)) => audio::decode_file(path.as_ref()).and_then(|a| a.into_mono(SAMPLE_RATE)),
+    _ => {
+      return Err(AudioError::InvalidInput(
+        "Exactly one of `samples` or `path` must be provided".to_string(),
+      ));
+    },
+  }
+  .map_err(|e| AudioError::InvalidInput(format!("Failed to prepare audio: {}", e)))?;
+
+  let data = app.state::<Mutex<AppDataVadBackend>>();
+  let data = data.lock().unwrap();
+
+  let segments = offline::detect_speech_segments(data.get()?, &audio, &options.unwrap_or_default())
+    .map_err(AudioError::inference_failed)?;
+  info!("Detected {} speech segments", segments.len());
+
+  Ok(SpeechSegments {
+    sample_rate: SAMPLE_RATE as u32,
+    audio: return_audio
+      .unwrap_or_default()
+      .then(|| offline::collect_speech(&audio, &segments)),
+    segments,
+  })
+}
+
+/// Pushes audio into a session with the loaded backend, for plugins that capture audio in-process
+/// instead of sending it over IPC. Emits [`BARGE_IN_EVENT`] when the speech going on qualifies.
+pub fn push_to_session<R: Runtime>(
+  app: &tauri::AppHandle<R>,
+  session: &mut VadSession,
+  samples: &[f32],
+) -> Result<VadPushResult, AudioError> {
+  let result = {
+    let data = app.state::<Mutex<AppDataVadBackend>>();
+    let data = data.lock().unwrap();
+
+    session
+      .push(data.get()?, samples)
+      .map_err(AudioError::inference_failed)?
+  };
+
+  let options = app
+    .state::<Mutex<BargeInOptions>>()
+    .lock()
+    .unwrap()
+    .clone();
+  if options.enabled
+    && let Some(barge_in) = session.barge_in(&options)
+  {
+    info!("Barge-in after {} ms of speech", barge_in.duration_ms);
+    if let Err(e) = app.emit(BARGE_IN_EVENT, &barge_in) {
+      error!("Failed to emit barge-in event: {}", e);
+    }
+  }
+
+  Ok(result)
 }
 
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
   PluginBuilder::new("ipc-audio-vad-ort")
     .setup(|app, _| {
       info!("Initializing audio VAD plugin...");
-      app.manage(Mutex::new(AppDataSileroVadProcessor::default()));
+      app.manage(Mutex::new(AppDataVadBackend::default()));
+      app.manage(AppDataVadSessions::default());
+      app.manage(Mutex::new(BargeInOptions::default()));
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
       load_ort_model_silero_vad,
-      ipc_audio_vad
+      cancel_model_download,
+      get_vad_backend,
+      ipc_audio_vad,
+      create_vad_session,
+      push_samples,
+      reset_vad_session,
+      set_barge_in_options,
+      destroy_vad_session,
+      detect_speech_segments
     ])
     .build()
 }
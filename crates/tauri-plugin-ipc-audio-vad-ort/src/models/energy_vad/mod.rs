@@ -0,0 +1,189 @@
+use std::f32::consts::PI;
+
+use anyhow::{Result, anyhow};
+
+use crate::models::{VadBackend, VadBackendKind};
+
+/// Noise floor in dB and the number of windows seen, capped
+const STATE_SIZE: usize = 2;
+
+/// Voices carry most of their energy between these frequencies, fundamentals included
+const VOICE_BAND_LOW_HZ: f32 = 80.0;
+const VOICE_BAND_HIGH_HZ: f32 = 3400.0;
+/// Windows quieter than this are silence, whatever the noise floor
+const SILENCE_DB: f32 = -65.0;
+/// Signal to noise ratio in dB at which the probability is 0.5, and how quickly it rises
+const SNR_MIDPOINT_DB: f32 = 9.0;
+const SNR_SLOPE_DB: f32 = 2.0;
+/// Share of the energy in the voice band that white noise has, and above which sounds are
+/// treated as voice. The probability is scaled down in between.
+const NOISE_VOICE_RATIO: f32 = 0.45;
+const SPEECH_VOICE_RATIO: f32 = 0.75;
+/// The noise floor follows quieter windows quickly, louder non-speech windows slowly, and creeps
+/// up under speech so a steady loud noise is learned eventually
+const FLOOR_FALL: f32 = 0.3;
+const FLOOR_RISE: f32 = 0.05;
+const FLOOR_CREEP_DB: f32 = 0.01;
+/// The floor tracks everything closely for the first windows, about 0.2 seconds
+const WARMUP_WINDOWS: f32 = 6.0;
+
+/// Voice activity from the loudness of the voice band over an adaptive noise floor. Needs no
+/// model, but tells speech from other sounds in that band far worse than Silero.
+pub struct Detector;
+
+impl VadBackend for Detector {
+  fn kind(&self) -> VadBackendKind {
+    VadBackendKind::Energy
+  }
+
+  fn state_size(&self) -> usize {
+    STATE_SIZE
+  }
+
+  fn infer_window(
+    &self,
+    input: &[f32],
+    sr: i64,
+    state: &mut Vec<f32>,
+  ) -> Result<f32> {
+    let (window_size, context_size) = match sr {
+      16000 => (512, 64),
+      8000 => (256, 32),
+      _ => return Err(anyhow!("Unsupported sample rate {}", sr)),
+    };
+    if input.len() != context_size + window_size || state.len() != STATE_SIZE {
+      return Err(anyhow!(
+        "Expected {} samples and {} state values, got {} and {}",
+        context_size + window_size,
+        STATE_SIZE,
+        input.len(),
+        state.len()
+      ));
+    }
+
+    // The context settles the filters before the window starts
+    let mut high_pass = Biquad::high_pass(VOICE_BAND_LOW_HZ, sr as f32);
+    let mut low_pass = Biquad::low_pass(VOICE_BAND_HIGH_HZ, sr as f32);
+    let mut total_energy = 0.0;
+    let mut voice_energy = 0.0;
+    for (i, &sample) in input.iter().enumerate() {
+      let voice = low_pass.process(high_pass.process(sample));
+      if i >= context_size {
+        total_energy += sample * sample;
+        voice_energy += voice * voice;
+      }
+    }
+
+    let energy_db = 10.0 * (voice_energy / window_size as f32 + 1e-10).log10();
+    let voice_ratio = if total_energy > 0.0 {
+      voice_energy / total_energy
+    } else {
+      0.0
+    };
+
+    let (noise_floor, windows_seen) = (state[0], state[1]);
+    let noise_floor = if windows_seen == 0.0 {
+      energy_db
+    } else {
+      noise_floor
+    };
+
+    let probability = if energy_db < SILENCE_DB {
+      0.0
+    } else {
+      let snr = energy_db - noise_floor;
+      let loudness = 1.0 / (1.0 + (-(snr - SNR_MIDPOINT_DB) / SNR_SLOPE_DB).exp());
+      let voicing = (voice_ratio - NOISE_VOICE_RATIO) / (SPEECH_VOICE_RATIO - NOISE_VOICE_RATIO);
+      loudness * voicing.clamp(0.0, 1.0)
+    };
+
+    let noise_floor = if windows_seen < WARMUP_WINDOWS {
+      noise_floor + (energy_db - noise_floor) / (windows_seen + 1.0)
+    } else if energy_db < noise_floor {
+      noise_floor + (energy_db - noise_floor) * FLOOR_FALL
+    } else if probability < 0.5 {
+      noise_floor + (energy_db - noise_floor) * FLOOR_RISE
+    } else {
+      (noise_floor + FLOOR_CREEP_DB).min(energy_db)
+    };
+
+    state[0] = noise_floor;
+    state[1] = (windows_seen + 1.0).min(WARMUP_WINDOWS);
+
+    Ok(probability)
+  }
+}
+
+/// Second order filter from the Audio EQ Cookbook, Q of 1/sqrt(2).
+struct Biquad {
+  b:  [f32; 3],
+  a:  [f32; 2],
+  x1: f32,
+  x2: f32,
+  y1: f32,
+  y2: f32,
+}
+
+impl Biquad {
+  fn high_pass(
+    cutoff: f32,
+    sample_rate: f32,
+  ) -> Self {
+    let (cos, alpha) = Self::coefficients(cutoff, sample_rate);
+    Self::new(
+      [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+      cos,
+      alpha,
+    )
+  }
+
+  fn low_pass(
+    cutoff: f32,
+    sample_rate: f32,
+  ) -> Self {
+    let (cos, alpha) = Self::coefficients(cutoff, sample_rate);
+    Self::new(
+      [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+      cos,
+      alpha,
+    )
+  }
+
+  fn coefficients(
+    cutoff: f32,
+    sample_rate: f32,
+  ) -> (f32, f32) {
+    let omega = 2.0 * PI * cutoff / sample_rate;
+    (omega.cos(), omega.sin() / 2.0_f32.sqrt())
+  }
+
+  fn new(
+    b: [f32; 3],
+    cos: f32,
+    alpha: f32,
+  ) -> Self {
+    let a0 = 1.0 + alpha;
+    Self {
+      b:  b.map(|b| b / a0),
+      a:  [-2.0 * cos / a0, (1.0 - alpha) / a0],
+      x1: 0.0,
+      x2: 0.0,
+      y1: 0.0,
+      y2: 0.0,
+    }
+  }
+
+  fn process(
+    &mut self,
+    x: f32,
+  ) -> f32 {
+    let y = self.b[0] * x + self.b[1] * self.x1 + self.b[2] * self.x2
+      - self.a[0] * self.y1
+      - self.a[1] * self.y2;
+    self.x2 = self.x1;
+    self.x1 = x;
+    self.y2 = self.y1;
+    self.y1 = y;
+    y
+  }
+}
@@ -1,10 +1,90 @@
+pub mod energy_vad;
 pub mod silero_vad;
-use log::info;
+use airi_audio_models::AudioError;
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use serde::Serialize;
 use tauri::Runtime;
 
-pub fn new_silero_vad_processor<R: Runtime>(
+/// Which detector scores the audio.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VadBackendKind {
+  Silero,
+  /// Used when the Silero model cannot be loaded, e.g. without network on first launch
+  Energy,
+}
+
+/// Scores windows of 16 kHz or 8 kHz mono audio with a speech probability, carrying whatever
+/// it needs from one window to the next in a state owned by the caller.
+pub trait VadBackend: Send {
+  fn kind(&self) -> VadBackendKind;
+
+  /// Number of values in the state of one stream, a new stream starts with zeros
+  fn state_size(&self) -> usize;
+
+  /// Scores the window at the end of `input`, which starts with the end of the previous window
+  /// as context. `state` is replaced with the updated one.
+  fn infer_window(
+    &self,
+    input: &[f32],
+    sr: i64,
+    state: &mut Vec<f32>,
+  ) -> Result<f32>;
+
+  /// Scores `batch` windows of independent streams at once, laid out one after the other in
+  /// `input`. `state` holds `batch` states in a layout of the backend's choosing, one after the
+  /// other unless overridden.
+  fn infer_batch(
+    &self,
+    input: &[f32],
+    batch: usize,
+    sr: i64,
+    state: &mut Vec<f32>,
+  ) -> Result<Vec<f32>> {
+    let state_size = self.state_size();
+    if batch == 0 || input.len() % batch != 0 || state.len() != batch * state_size {
+      return Err(anyhow!(
+        "Expected {} windows of equal length and their states, got {} samples and {} state values",
+        batch,
+        input.len(),
+        state.len()
+      ));
+    }
+
+    let mut lane_state = Vec::with_capacity(state_size);
+    input
+      .chunks_exact(input.len() / batch)
+      .zip(state.chunks_exact_mut(state_size))
+      .map(|(input, state)| {
+        lane_state.clear();
+        lane_state.extend_from_slice(state);
+        let probability = self.infer_window(input, sr, &mut lane_state)?;
+        state.copy_from_slice(&lane_state);
+        Ok(probability)
+      })
+      .collect()
+  }
+}
+
+/// Loads Silero VAD, downloading it when needed. Falls back to the energy detector when that
+/// fails, so voice input keeps working, but not when the download was cancelled.
+pub fn new_vad_backend<R: Runtime>(
   window: tauri::WebviewWindow<R>
-) -> anyhow::Result<silero_vad::Processor> {
+) -> Result<Box<dyn VadBackend>, AudioError> {
   info!("Loading VAD model");
-  silero_vad::Processor::new(window)
+  match silero_vad::Processor::new(window) {
+    Ok(processor) => Ok(Box::new(processor)),
+    Err(e) => {
+      let error = AudioError::load_failed(e);
+      if matches!(error, AudioError::Cancelled(_)) {
+        return Err(error);
+      }
+      warn!(
+        "Failed to load Silero VAD model, falling back to the energy detector: {}",
+        error
+      );
+      Ok(Box::new(energy_vad::Detector))
+    },
+  }
 }
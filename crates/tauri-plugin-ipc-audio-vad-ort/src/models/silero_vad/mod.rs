@@ -1,23 +1,16 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{path::Path, sync::Arc};
 
+use airi_audio_models::{ModelRepo, SessionOptions, create_session};
 use anyhow::Result;
-use hf_hub::Repo;
 use log::info;
-use ort::{
-  execution_providers::{
-    CPUExecutionProvider,
-    CUDAExecutionProvider,
-    CoreMLExecutionProvider,
-    DirectMLExecutionProvider,
-  },
-  session::{Session, builder::GraphOptimizationLevel},
-  util::Mutex,
-  value::Tensor,
-};
+use ort::{session::Session, util::Mutex, value::Tensor};
 use serde::{Deserialize, Serialize};
 use tauri::Runtime;
 
-use crate::helpers::huggingface::create_progress_emitter;
+use crate::models::{VadBackend, VadBackendKind};
+
+/// Length of the recurrent state, shaped [2, 1, 128]
+pub const STATE_SIZE: usize = 2 * 128;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct VADInferenceResult {
@@ -41,34 +34,18 @@ impl Processor {
     let model_id = "onnx-community/silero-vad";
     let revision = "main";
 
-    let cache_api = hf_hub::Cache::from_env();
-    let cache_repo = cache_api.repo(Repo::with_revision(
-      model_id.into(),
-      hf_hub::RepoType::Model,
-      revision.into(),
-    ));
-
-    let api = hf_hub::api::sync::ApiBuilder::new().build()?;
-    let repo = api.repo(hf_hub::Repo::with_revision(
-      model_id.into(),
-      hf_hub::RepoType::Model,
-      revision.into(),
-    ));
-
-    let model_path_sub_name = "onnx/model.onnx";
-    let model_path = match cache_repo.get(model_path_sub_name) {
-      Some(path) => path,
-      None => repo.download_with_progress(
-        model_path_sub_name,
-        create_progress_emitter(
-          window.clone(),
-          "tauri-plugins:tauri-plugin-ipc-audio-vad-ort:load-model-silero-vad-progress",
-          model_path_sub_name.to_string(),
-        ),
-      )?,
-    };
-
-    let session = Self::create_optimized_session(model_path.clone())?;
+    let model_path = ModelRepo::new(model_id, revision)?.get(
+      "onnx/model.onnx",
+      window,
+      "tauri-plugins:tauri-plugin-ipc-audio-vad-ort:load-model-silero-vad-progress",
+    )?;
+
+    Self::from_file(&model_path)
+  }
+
+  /// Loads the model from a local ONNX file, without looking at the hub.
+  pub fn from_file(model_path: &Path) -> Result<Self> {
+    let session = Self::create_optimized_session(model_path)?;
 
     Ok(Self {
       session: Arc::new(Mutex::new(session)),
@@ -76,74 +53,94 @@ impl Processor {
   }
 
   /// Create an optimized ONNX session with hardware acceleration
-  fn create_optimized_session(model_path: PathBuf) -> Result<Session> {
-    let builder = Session::builder()?
-      .with_optimization_level(GraphOptimizationLevel::Level3)?
-      .with_intra_threads(1)?;
-
-    let session = builder
-      .with_execution_providers(vec![
-        CUDAExecutionProvider::default()
-          .with_device_id(0)
-          .build(),
-        CoreMLExecutionProvider::default().build(),
-        DirectMLExecutionProvider::default()
-          .with_device_id(0)
-          .build(),
-        CPUExecutionProvider::default().build(),
-      ])?
-      .commit_from_file(model_path)?;
+  fn create_optimized_session(model_path: &Path) -> Result<Session> {
+    let session = create_session(
+      model_path,
+      SessionOptions {
+        intra_threads: Some(1),
+        ..Default::default()
+      },
+    )?;
     info!("VAD model loaded successfully");
 
     Ok(session)
   }
 
-  /// Stateless inference that matches JavaScript interface
-  /// Returns both output and updated state like the JS version
-  pub fn inference(
+  fn run(
     &self,
-    input_data: VADInferenceInput,
-  ) -> Result<VADInferenceResult> {
-    // Validate input dimensions
-    if input_data.state.len() != 2 * 1 * 128 {
-      return Err(anyhow::anyhow!(
-        "State must have 256 elements (2*1*128), got {}",
-        input_data.state.len()
-      ));
-    }
-
+    input: Vec<f32>,
+    batch: usize,
+    sr: i64,
+    state: Vec<f32>,
+  ) -> Result<(Vec<f32>, Vec<f32>)> {
     // Create input tensors for the ONNX model
     let inputs = vec![
       (
         "input",
-        Tensor::from_array((vec![1, input_data.input.len()], input_data.input.clone()))?.into_dyn(),
-      ),
-      (
-        "sr",
-        Tensor::from_array(([1], vec![input_data.sr]))?.into_dyn(),
+        Tensor::from_array((vec![batch, input.len() / batch], input))?.into_dyn(),
       ),
+      ("sr", Tensor::from_array(([1], vec![sr]))?.into_dyn()),
       (
         "state",
-        Tensor::from_array((vec![2, 1, 128], input_data.state.clone()))?.into_dyn(),
+        Tensor::from_array((vec![2, batch, 128], state))?.into_dyn(),
       ),
     ];
 
     // Run inference and extract data while session is still locked
-    let (state_data, speech_data) = {
-      let mut session = self.session.lock();
-      let outputs = session.run(inputs)?;
+    let mut session = self.session.lock();
+    let outputs = session.run(inputs)?;
 
-      // Extract and clone the data immediately while session is locked
-      let (_state_shape, state_slice) = outputs[1].try_extract_tensor::<f32>()?;
-      let (_speech_shape, speech_slice) = outputs[0].try_extract_tensor::<f32>()?;
+    // Extract and clone the data immediately while session is locked
+    let (_state_shape, state_slice) = outputs[1].try_extract_tensor::<f32>()?;
+    let (_speech_shape, speech_slice) = outputs[0].try_extract_tensor::<f32>()?;
 
-      // Clone the data to owned vectors before the session lock is released
-      (state_slice.to_vec(), speech_slice.to_vec())
-    };
+    // Clone the data to owned vectors before the session lock is released
+    Ok((speech_slice.to_vec(), state_slice.to_vec()))
+  }
+}
 
-    Ok(VADInferenceResult {
-      output: speech_data,
-      state:  state_data,
-    })
+impl VadBackend for Processor {
+  fn kind(&self) -> VadBackendKind {
+    VadBackendKind::Silero
+  }
+
+  fn state_size(&self) -> usize {
+    STATE_SIZE
+  }
+
+  fn infer_window(
+    &self,
+    input: &[f32],
+    sr: i64,
+    state: &mut Vec<f32>,
+  ) -> Result<f32> {
+    let mut probabilities = self.infer_batch(input, 1, sr, state)?;
+    probabilities
+      .pop()
+      .ok_or_else(|| anyhow::anyhow!("Silero VAD returned no output"))
+  }
+
+  /// States are shaped [2, batch, 128], the layout the model takes.
+  fn infer_batch(
+    &self,
+    input: &[f32],
+    batch: usize,
+    sr: i64,
+    state: &mut Vec<f32>,
+  ) -> Result<Vec<f32>> {
+    if batch == 0 || input.len() % batch != 0 || state.len() != batch * STATE_SIZE {
+      return Err(anyhow::anyhow!(
+        "Expected {} windows of equal length and their states, got {} samples and {} state values",
+        batch,
+        input.len(),
+        state.len()
+      ));
+    }
+
+    // The state is only replaced once the model ran, a failed run leaves the stream as it was
+    let (speech_data, state_data) = self.run(input.to_vec(), batch, sr, state.clone())?;
+    *state = state_data;
+
+    Ok(speech_data)
   }
 }
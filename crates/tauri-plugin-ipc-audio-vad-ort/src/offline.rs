@@ -0,0 +1,142 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+  models::VadBackend,
+  segmenter::{SegmenterOptions, SpeechEvent, SpeechSegmenter},
+};
+
+/// Offline detection always runs Silero VAD at 16 kHz
+pub const SAMPLE_RATE: usize = 16000;
+
+const WINDOW_SIZE: usize = 512;
+const CONTEXT_SIZE: usize = 64;
+/// Most lanes run through the model at once
+const MAX_BATCH_SIZE: usize = 16;
+/// Lanes are not split shorter than this, about 10 seconds
+const MIN_LANE_WINDOWS: usize = 312;
+/// Every lane but the first starts this early, about 1 second, to build up a model state before
+/// its own windows come. Probabilities of these windows are dropped.
+const WARMUP_WINDOWS: usize = 32;
+
+/// Sample offsets into the 16 kHz audio, padding included.
+#[derive(Clone, Debug, Serialize)]
+pub struct SpeechTimestamp {
+  pub start: u64,
+  pub end:   u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeechSegments {
+  pub sample_rate: u32,
+  pub segments:    Vec<SpeechTimestamp>,
+  /// The speech segments one after the other, when asked for
+  pub audio:       Option<Vec<f32>>,
+}
+
+/// Finds the speech in 16 kHz mono audio with the rules of Silero's `get_speech_timestamps`.
+/// Segments less than a window apart after padding are merged.
+///
+/// Audio longer than about 10 seconds is scored in lanes, see `speech_probabilities`, so its
+/// segments can differ slightly from those of a run from the start.
+pub fn detect_speech_segments(
+  backend: &dyn VadBackend,
+  audio: &[f32],
+  options: &SegmenterOptions,
+) -> Result<Vec<SpeechTimestamp>> {
+  let mut segmenter = SpeechSegmenter::new(SAMPLE_RATE as u32, WINDOW_SIZE, options);
+
+  let mut events = Vec::new();
+  for probability in speech_probabilities(backend, audio)? {
+    events.extend(segmenter.push(probability));
+  }
+  events.extend(segmenter.finish(audio.len() as u64));
+
+  let mut segments: Vec<SpeechTimestamp> = Vec::new();
+  for event in events {
+    let SpeechEvent::SpeechEnd { start, end } = event else {
+      continue;
+    };
+
+    match segments.last_mut() {
+      Some(last) if start < last.end + WINDOW_SIZE as u64 => last.end = end,
+      _ => segments.push(SpeechTimestamp { start, end }),
+    }
+  }
+
+  Ok(segments)
+}
+
+/// Cuts the segments out of `audio` and joins them.
+pub fn collect_speech(
+  audio: &[f32],
+  segments: &[SpeechTimestamp],
+) -> Vec<f32> {
+  segments
+    .iter()
+    .flat_map(|segment| &audio[segment.start as usize..segment.end as usize])
+    .copied()
+    .collect()
+}
+
+/// Speech probability of every window of `audio`, the last one zero padded.
+///
+/// Backends carry state from window to window, so the audio is split into lanes that run side by
+/// side in one batch, each with its own state. A lane starts from a fresh state
+/// [`WARMUP_WINDOWS`] before its own windows, which gets its state close to that of a run from
+/// the start but not the same: probabilities differ a little, and one close to the threshold
+/// can end up on the other side of it. Audio of up to [`MIN_LANE_WINDOWS`] runs in a single
+/// lane, window by window like `get_speech_timestamps`.
+fn speech_probabilities(
+  backend: &dyn VadBackend,
+  audio: &[f32],
+) -> Result<Vec<f32>> {
+  let windows = audio.len().div_ceil(WINDOW_SIZE);
+  if windows == 0 {
+    return Ok(Vec::new());
+  }
+
+  let batch_size = windows
+    .div_ceil(MIN_LANE_WINDOWS)
+    .clamp(1, MAX_BATCH_SIZE);
+  let lane_windows = windows.div_ceil(batch_size);
+  let warmup = if batch_size > 1 {
+    WARMUP_WINDOWS
+  } else {
+    0
+  };
+
+  // Lane `l` owns the windows from `l * lane_windows` on, and all but the first one start
+  // `warmup` windows before them. The first lane runs on into the next instead.
+  let first_window = |lane: usize| (lane * lane_windows).saturating_sub(warmup);
+
+  let mut probabilities = vec![0.0; windows];
+  let mut state = vec![0.0; batch_size * backend.state_size()];
+  let mut input = Vec::with_capacity(batch_size * (CONTEXT_SIZE + WINDOW_SIZE));
+  for step in 0..warmup + lane_windows {
+    input.clear();
+    for lane in 0..batch_size {
+      let start = (first_window(lane) + step) * WINDOW_SIZE;
+      // The context in front of the very first window is silence, so is the padding at the end
+      for position in start as isize - CONTEXT_SIZE as isize..(start + WINDOW_SIZE) as isize {
+        let sample = usize::try_from(position)
+          .ok()
+          .and_then(|position| audio.get(position));
+        input.push(sample.copied().unwrap_or(0.0));
+      }
+    }
+
+    let output = backend.infer_batch(&input, batch_size, SAMPLE_RATE as i64, &mut state)?;
+
+    for (lane, probability) in output.into_iter().enumerate() {
+      let index = first_window(lane) + step;
+      let owned = lane * lane_windows..((lane + 1) * lane_windows).min(windows);
+      if owned.contains(&index) {
+        probabilities[index] = probability;
+      }
+    }
+  }
+
+  Ok(probabilities)
+}
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+
+/// Rules that turn speech probabilities into segments, named and defaulted like the parameters
+/// of Silero's `get_speech_timestamps`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SegmenterOptions {
+  /// Windows at or above this probability are speech
+  pub threshold:               f32,
+  /// Speech only ends below this probability, `threshold - 0.15` when not set
+  pub neg_threshold:           Option<f32>,
+  /// Shorter segments are dropped
+  pub min_speech_duration_ms:  u32,
+  /// Silence that has to pass before a segment ends
+  pub min_silence_duration_ms: u32,
+  /// Added to both sides of every segment
+  pub speech_pad_ms:           u32,
+  /// Longer segments are split, at the last silence of more than 98 ms if there was one
+  pub max_speech_duration_s:   Option<f32>,
+}
+
+impl Default for SegmenterOptions {
+  fn default() -> Self {
+    Self {
+      threshold:               0.5,
+      neg_threshold:           None,
+      min_speech_duration_ms:  250,
+      min_silence_duration_ms: 100,
+      speech_pad_ms:           30,
+      max_speech_duration_s:   None,
+    }
+  }
+}
+
+/// Positions are sample offsets from the start of the audio, padding included.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum SpeechEvent {
+  SpeechStart { start: u64 },
+  SpeechEnd { start: u64, end: u64 },
+}
+
+/// A segment that ended, waiting for the start of the next one to know how much of the silence
+/// in between it gets.
+struct ClosedSpeech {
+  start:   u64,
+  raw_end: u64,
+}
+
+/// The state machine of `get_speech_timestamps`, fed one window probability at a time. Events
+/// are emitted as soon as they can no longer change: a start once the segment is long enough to
+/// be kept, an end once the padding it gets is known.
+pub struct SpeechSegmenter {
+  window_size:                       u64,
+  threshold:                         f32,
+  neg_threshold:                     f32,
+  min_speech_samples:                u64,
+  min_silence_samples:               u64,
+  min_silence_samples_at_max_speech: u64,
+  speech_pad_samples:                u64,
+  max_speech_samples:                f64,
+
+  index:         u64,
+  triggered:     bool,
+  current_start: Option<u64>,
+  temp_end:      u64,
+  prev_end:      u64,
+  next_start:    u64,
+  /// Padded start of the current segment, once its start was emitted
+  emitted_start: Option<u64>,
+  closed:        Option<ClosedSpeech>,
+}
+
+impl SpeechSegmenter {
+  pub fn new(
+    sample_rate: u32,
+    window_size: usize,
+    options: &SegmenterOptions,
+  ) -> Self {
+    let samples = |ms: u32| u64::from(sample_rate) * u64::from(ms) / 1000;
+    let speech_pad_samples = samples(options.speech_pad_ms);
+    let max_speech_samples = options
+      .max_speech_duration_s
+      .map_or(f64::INFINITY, |secs| {
+        f64::from(sample_rate) * f64::from(secs)
+          - window_size as f64
+          - 2.0 * speech_pad_samples as f64
+      });
+
+    Self {
+      window_size: window_size as u64,
+      threshold: options.threshold,
+      neg_threshold: options
+        .neg_threshold
+        .unwrap_or((options.threshold - 0.15).max(0.01)),
+      min_speech_samples: samples(options.min_speech_duration_ms),
+      min_silence_samples: samples(options.min_silence_duration_ms),
+      min_silence_samples_at_max_speech: samples(98),
+      speech_pad_samples,
+      max_speech_samples,
+      index: 0,
+      triggered: false,
+      current_start: None,
+      temp_end: 0,
+      prev_end: 0,
+      next_start: 0,
+      emitted_start: None,
+      closed: None,
+    }
+  }
+
+  /// Feeds the speech probability of the next window.
+  pub fn push(
+    &mut self,
+    probability: f32,
+  ) -> Vec<SpeechEvent> {
+    let mut events = Vec::new();
+    let position = self.index * self.window_size;
+    self.index += 1;
+
+    self.step(position, probability, &mut events);
+
+    // A kept segment is known once it is long enough that no way of ending it drops it
+    if let Some(start) = self.current_start
+      && self.emitted_start.is_none()
+    {
+      let kept = if self.temp_end != 0 {
+        self.temp_end - start > self.min_speech_samples
+      } else {
+        position - start >= self.min_speech_samples
+      };
+      if kept {
+        self.start_speech(start, &mut events);
+      }
+    }
+
+    // The next segment cannot start before the current one, or past this window when there is
+    // none. Once that is twice the padding away, the previous segment gets the full padding.
+    if let Some(closed) = &self.closed {
+      let earliest_next_start = self.current_start.unwrap_or(position);
+      if earliest_next_start - closed.raw_end >= 2 * self.speech_pad_samples {
+        events.push(SpeechEvent::SpeechEnd {
+          start: closed.start,
+          end:   closed.raw_end + self.speech_pad_samples,
+        });
+        self.closed = None;
+      }
+    }
+
+    events
+  }
+
+  /// Ends the audio after `audio_length` samples, closing the segment still going on.
+  pub fn finish(
+    &mut self,
+    audio_length: u64,
+  ) -> Vec<SpeechEvent> {
+    let mut events = Vec::new();
+
+    if let Some(start) = self.current_start.take()
+      && audio_length.saturating_sub(start) > self.min_speech_samples
+    {
+      self.close_speech(start, audio_length, &mut events);
+    }
+
+    if let Some(closed) = self.closed.take() {
+      events.push(SpeechEvent::SpeechEnd {
+        start: closed.start,
+        end:   (closed.raw_end + self.speech_pad_samples).min(audio_length),
+      });
+    }
+
+    self.reset();
+    events
+  }
+
+  pub fn reset(&mut self) {
+    self.index = 0;
+    self.triggered = false;
+    self.current_start = None;
+    self.temp_end = 0;
+    self.prev_end = 0;
+    self.next_start = 0;
+    self.emitted_start = None;
+    self.closed = None;
+  }
+
+  /// One iteration of the loop in `get_speech_timestamps`.
+  fn step(
+    &mut self,
+    position: u64,
+    probability: f32,
+    events: &mut Vec<SpeechEvent>,
+  ) {
+    if probability >= self.threshold && self.temp_end != 0 {
+      self.temp_end = 0;
+      if self.next_start < self.prev_end {
+        self.next_start = position;
+      }
+    }
+
+    if probability >= self.threshold && !self.triggered {
+      self.triggered = true;
+      self.current_start = Some(position);
+      return;
+    }
+
+    if let Some(start) = self.current_start
+      && self.triggered
+      && (position - start) as f64 > self.max_speech_samples
+    {
+      if self.prev_end != 0 {
+        self.close_speech(start, self.prev_end, events);
+        // Still speech since the last silence, the next segment starts right after it
+        if self.next_start < self.prev_end {
+          self.triggered = false;
+        } else {
+          self.current_start = Some(self.next_start);
+        }
+        self.prev_end = 0;
+        self.next_start = 0;
+        self.temp_end = 0;
+      } else {
+        self.close_speech(start, position, events);
+        self.prev_end = 0;
+        self.next_start = 0;
+        self.temp_end = 0;
+        self.triggered = false;
+        return;
+      }
+    }
+
+    if probability < self.neg_threshold && self.triggered {
+      if self.temp_end == 0 {
+        self.temp_end = position;
+      }
+      if position - self.temp_end > self.min_silence_samples_at_max_speech {
+        self.prev_end = self.temp_end;
+      }
+      if position - self.temp_end < self.min_silence_samples {
+        return;
+      }
+
+      if let Some(start) = self.current_start.take()
+        && self.temp_end - start > self.min_speech_samples
+      {
+        self.close_speech(start, self.temp_end, events);
+      }
+      // A dropped segment never had its start emitted
+      self.emitted_start = None;
+      self.prev_end = 0;
+      self.next_start = 0;
+      self.temp_end = 0;
+      self.triggered = false;
+    }
+  }
+
+  /// Emits the start of the segment beginning at `start`, and settles the end of the previous
+  /// one: the padding of both is cut to half the silence when they would overlap.
+  fn start_speech(
+    &mut self,
+    start: u64,
+    events: &mut Vec<SpeechEvent>,
+  ) {
+    let padded_start = match self.closed.take() {
+      Some(closed) if start - closed.raw_end < 2 * self.speech_pad_samples => {
+        let half_silence = (start - closed.raw_end) / 2;
+        events.push(SpeechEvent::SpeechEnd {
+          start: closed.start,
+          end:   closed.raw_end + half_silence,
+        });
+        start.saturating_sub(half_silence)
+      },
+      closed => {
+        if let Some(closed) = closed {
+          events.push(SpeechEvent::SpeechEnd {
+            start: closed.start,
+            end:   closed.raw_end + self.speech_pad_samples,
+          });
+        }
+        start.saturating_sub(self.speech_pad_samples)
+      },
+    };
+
+    self.emitted_start = Some(padded_start);
+    events.push(SpeechEvent::SpeechStart {
+      start: padded_start,
+    });
+  }
+
+  /// Ends the current segment at `raw_end`, before padding.
+  fn close_speech(
+    &mut self,
+    start: u64,
+    raw_end: u64,
+    events: &mut Vec<SpeechEvent>,
+  ) {
+    if self.emitted_start.is_none() {
+      self.start_speech(start, events);
+    }
+
+    self.closed = self
+      .emitted_start
+      .take()
+      .map(|start| ClosedSpeech { start, raw_end });
+    self.current_start = None;
+  }
+}
@@ -0,0 +1,288 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::{Arc, Mutex, atomic::AtomicU32},
+  time::{Duration, Instant},
+};
+
+use airi_audio_models::AudioError;
+use anyhow::{Result, anyhow};
+use log::info;
+use serde::Serialize;
+use tauri_plugin_ipc_audio_preprocess::Preprocessor;
+
+use crate::{
+  barge_in::{BargeIn, BargeInOptions},
+  models::VadBackend,
+  segmenter::{SegmenterOptions, SpeechEvent, SpeechSegmenter},
+};
+
+/// Probabilities kept for the speech that has not been confirmed yet, about 10 seconds at 16 kHz
+const RECENT_WINDOWS: usize = 320;
+/// Sessions that got no audio for this long are destroyed, for clients that never call
+/// `destroy_vad_session`
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Speech probability of one window of a session.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VadFrame {
+  /// Position of the first sample of the window, counted from the start of the session
+  pub start:       u64,
+  pub probability: f32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct VadPushResult {
+  pub frames: Vec<VadFrame>,
+  /// Speech segment boundaries settled by the pushed audio
+  pub events: Vec<SpeechEvent>,
+}
+
+/// The speech segment going on, from its start event until its end event.
+struct CurrentSpeech {
+  start:           u64,
+  probability_sum: f32,
+  windows:         usize,
+  barged_in:       bool,
+}
+
+/// Keeps everything the VAD carries over between windows, so callers can push audio of any
+/// length without round-tripping the model state.
+pub struct VadSession {
+  sample_rate:  u32,
+  window_size:  usize,
+  /// The model sees the end of the previous window in front of every new one
+  context:      Vec<f32>,
+  /// Sized for the backend on the first push
+  state:        Vec<f32>,
+  /// Samples that do not fill a window yet
+  pending:      Vec<f32>,
+  processed:    u64,
+  segmenter:    SpeechSegmenter,
+  /// Probabilities of the latest windows, the start of a segment is only known windows later
+  recent:       VecDeque<f32>,
+  speech:       Option<CurrentSpeech>,
+  /// Cleans pushed audio up before the model sees it
+  preprocessor: Option<Preprocessor>,
+  last_used:    Instant,
+}
+
+impl VadSession {
+  /// Silero VAD takes 512 samples at 16 kHz and 256 at 8 kHz, other rates are not supported.
+  pub fn new(
+    sample_rate: u32,
+    options: &SegmenterOptions,
+  ) -> Result<Self> {
+    let (window_size, context_size) = match sample_rate {
+      16000 => (512, 64),
+      8000 => (256, 32),
+      _ => {
+        return Err(anyhow!(
+          "Unsupported sample rate {}, expected 8000 or 16000",
+          sample_rate
+        ));
+      },
+    };
+
+    Ok(Self {
+      sample_rate,
+      window_size,
+      context: vec![0.0; context_size],
+      state: Vec::new(),
+      pending: Vec::with_capacity(window_size),
+      processed: 0,
+      segmenter: SpeechSegmenter::new(sample_rate, window_size, options),
+      recent: VecDeque::with_capacity(RECENT_WINDOWS),
+      speech: None,
+      preprocessor: None,
+      last_used: Instant::now(),
+    })
+  }
+
+  /// Runs pushed audio through `preprocessor` first.
+  pub fn with_preprocessor(
+    mut self,
+    preprocessor: Preprocessor,
+  ) -> Self {
+    self.preprocessor = Some(preprocessor);
+    self
+  }
+
+  /// Runs every complete window in `samples` and whatever was left over from the previous call.
+  /// Returns one frame per window, samples of an incomplete window wait for the next call.
+  pub fn push(
+    &mut self,
+    backend: &dyn VadBackend,
+    samples: &[f32],
+  ) -> Result<VadPushResult> {
+    self.last_used = Instant::now();
+    match &mut self.preprocessor {
+      Some(preprocessor) => self.pending.extend(preprocessor.process(samples)),
+      None => self.pending.extend_from_slice(samples),
+    }
+    if self.state.len() != backend.state_size() {
+      self.state = vec![0.0; backend.state_size()];
+    }
+
+    let mut frames = Vec::with_capacity(self.pending.len() / self.window_size);
+    let mut events = Vec::new();
+    let mut input = Vec::with_capacity(self.context.len() + self.window_size);
+    let mut consumed = 0;
+    while self.pending.len() - consumed >= self.window_size {
+      let window = &self.pending[consumed..consumed + self.window_size];
+
+      input.clear();
+      input.extend_from_slice(&self.context);
+      input.extend_from_slice(window);
+      let probability =
+        match backend.infer_window(&input, i64::from(self.sample_rate), &mut self.state) {
+          Ok(probability) => probability,
+          Err(e) => {
+            // Windows that went through already are not run again
+            self.pending.drain(..consumed);
+            return Err(e);
+          },
+        };
+
+      let context_size = self.context.len();
+      self
+        .context
+        .copy_from_slice(&window[self.window_size - context_size..]);
+      frames.push(VadFrame {
+        start: self.processed,
+        probability,
+      });
+      let window_events = self.segmenter.push(probability);
+
+      self.processed += self.window_size as u64;
+      consumed += self.window_size;
+      // Segment positions are compared with the end of the window
+      self.track_speech(probability, &window_events);
+      events.extend(window_events);
+    }
+
+    self.pending.drain(..consumed);
+    Ok(VadPushResult { frames, events })
+  }
+
+  fn track_speech(
+    &mut self,
+    probability: f32,
+    events: &[SpeechEvent],
+  ) {
+    if self.recent.len() == RECENT_WINDOWS {
+      self.recent.pop_front();
+    }
+    self.recent.push_back(probability);
+    if let Some(speech) = &mut self.speech {
+      speech.probability_sum += probability;
+      speech.windows += 1;
+    }
+
+    for event in events {
+      match *event {
+        SpeechEvent::SpeechStart { start } => {
+          // Every window overlapping the segment so far, the padding before it included
+          let windows = (self.processed - start).div_ceil(self.window_size as u64) as usize;
+          let windows = windows.clamp(1, self.recent.len());
+          self.speech = Some(CurrentSpeech {
+            start,
+            probability_sum: self.recent.iter().rev().take(windows).sum(),
+            windows,
+            barged_in: false,
+          });
+        },
+        SpeechEvent::SpeechEnd { .. } => self.speech = None,
+      }
+    }
+  }
+
+  /// Returns the speech going on the first time it meets `options`, then nothing until the next
+  /// segment.
+  pub fn barge_in(
+    &mut self,
+    options: &BargeInOptions,
+  ) -> Option<BargeIn> {
+    let speech = self.speech.as_mut()?;
+    if speech.barged_in {
+      return None;
+    }
+
+    let duration_ms = (self.processed - speech.start) * 1000 / u64::from(self.sample_rate);
+    let probability = speech.probability_sum / speech.windows as f32;
+    if duration_ms < u64::from(options.min_duration_ms) || probability < options.min_probability {
+      return None;
+    }
+
+    speech.barged_in = true;
+    Some(BargeIn {
+      start: speech.start,
+      duration_ms,
+      probability,
+    })
+  }
+
+  /// Ends the audio, closing the speech segment still going on. The session starts over.
+  pub fn finish(&mut self) -> Vec<SpeechEvent> {
+    let events = self
+      .segmenter
+      .finish(self.processed + self.pending.len() as u64);
+    self.reset();
+    events
+  }
+
+  /// Forgets the audio seen so far, as if the session was just created.
+  pub fn reset(&mut self) {
+    self.context.fill(0.0);
+    self.state.clear();
+    self.pending.clear();
+    self.processed = 0;
+    self.segmenter.reset();
+    self.recent.clear();
+    self.speech = None;
+    if let Some(preprocessor) = &mut self.preprocessor {
+      preprocessor.reset();
+    }
+  }
+}
+
+#[derive(Default)]
+pub struct AppDataVadSessions {
+  pub next_id:  AtomicU32,
+  pub sessions: Mutex<HashMap<u32, Arc<Mutex<VadSession>>>>,
+}
+
+impl AppDataVadSessions {
+  pub fn get(
+    &self,
+    session_id: u32,
+  ) -> Result<Arc<Mutex<VadSession>>, AudioError> {
+    self.remove_idle();
+    self
+      .sessions
+      .lock()
+      .unwrap()
+      .get(&session_id)
+      .cloned()
+      .ok_or_else(|| AudioError::NotFound(format!("VAD session {} does not exist", session_id)))
+  }
+
+  /// Destroys the sessions that got no audio within [`SESSION_IDLE_TIMEOUT`], checked whenever
+  /// sessions are created or used.
+  pub fn remove_idle(&self) {
+    self
+      .sessions
+      .lock()
+      .unwrap()
+      .retain(|session_id, session| {
+        // A session that is running the model right now is not idle
+        let idle = session
+          .try_lock()
+          .is_ok_and(|session| session.last_used.elapsed() > SESSION_IDLE_TIMEOUT);
+        if idle {
+          info!("VAD session {} destroyed after being idle", session_id);
+        }
+        !idle
+      });
+  }
+}
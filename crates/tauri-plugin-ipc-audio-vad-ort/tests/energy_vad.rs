@@ -0,0 +1,131 @@
+use std::f32::consts::PI;
+
+use tauri_plugin_ipc_audio_vad_ort::{EnergyDetector, VadBackend};
+
+/// xorshift32, the tests need reproducible noise and nothing more
+struct Noise(u32);
+
+impl Noise {
+  fn next(&mut self) -> f32 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 17;
+    self.0 ^= self.0 << 5;
+    self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+  }
+}
+
+/// Scores `audio` window by window, the way a VAD session feeds the detector.
+fn probabilities(
+  state: &mut Vec<f32>,
+  audio: &[f32],
+  sample_rate: i64,
+) -> Vec<f32> {
+  let (window_size, context_size) = if sample_rate == 16000 {
+    (512, 64)
+  } else {
+    (256, 32)
+  };
+
+  let mut context = vec![0.0; context_size];
+  audio
+    .chunks_exact(window_size)
+    .map(|window| {
+      let input = [context.as_slice(), window].concat();
+      context.copy_from_slice(&window[window_size - context_size..]);
+      EnergyDetector
+        .infer_window(&input, sample_rate, state)
+        .unwrap()
+    })
+    .collect()
+}
+
+/// A voice-like tone: 150 Hz and its harmonics up to 3 kHz.
+fn voice(
+  seconds: f32,
+  sample_rate: i64,
+) -> Vec<f32> {
+  (0..(seconds * sample_rate as f32) as usize)
+    .map(|i| {
+      let phase = 2.0 * PI * 150.0 * i as f32 / sample_rate as f32;
+      0.1
+        * (1..=20)
+          .map(|harmonic| (harmonic as f32 * phase).sin() / harmonic as f32)
+          .sum::<f32>()
+    })
+    .collect()
+}
+
+fn noise(
+  seconds: f32,
+  sample_rate: i64,
+  amplitude: f32,
+  seed: u32,
+) -> Vec<f32> {
+  let mut noise = Noise(seed);
+  (0..(seconds * sample_rate as f32) as usize)
+    .map(|_| amplitude * noise.next())
+    .collect()
+}
+
+#[test]
+fn silence_is_not_speech() {
+  let mut state = vec![0.0; EnergyDetector.state_size()];
+
+  let probabilities = probabilities(&mut state, &[0.0; 16000], 16000);
+  assert!(probabilities.iter().all(|&p| p == 0.0));
+}
+
+#[test]
+fn voice_over_quiet_noise_is_speech() {
+  for sample_rate in [16000, 8000] {
+    let mut state = vec![0.0; EnergyDetector.state_size()];
+
+    let quiet = probabilities(&mut state, &noise(1.0, sample_rate, 0.003, 1), sample_rate);
+    assert!(quiet.iter().all(|&p| p < 0.1), "{quiet:?}");
+
+    let mut audio = voice(1.0, sample_rate);
+    for (sample, noise) in audio
+      .iter_mut()
+      .zip(noise(1.0, sample_rate, 0.003, 2))
+    {
+      *sample += noise;
+    }
+    let speech = probabilities(&mut state, &audio, sample_rate);
+    assert!(speech.iter().all(|&p| p > 0.9), "{speech:?}");
+
+    // Back to the noise, the floor did not move up to the voice
+    let quiet = probabilities(&mut state, &noise(0.5, sample_rate, 0.003, 3), sample_rate);
+    assert!(quiet.iter().skip(1).all(|&p| p < 0.1), "{quiet:?}");
+  }
+}
+
+#[test]
+fn loud_broadband_noise_is_not_speech() {
+  let mut state = vec![0.0; EnergyDetector.state_size()];
+  probabilities(&mut state, &noise(1.0, 16000, 0.003, 1), 16000);
+
+  // As loud as the voice, but most of its energy is outside the voice band
+  let loud = probabilities(&mut state, &noise(1.0, 16000, 0.3, 4), 16000);
+  assert!(loud.iter().all(|&p| p < 0.2), "{loud:?}");
+}
+
+#[test]
+fn rejects_windows_it_cannot_score() {
+  let mut state = vec![0.0; EnergyDetector.state_size()];
+
+  assert!(
+    EnergyDetector
+      .infer_window(&[0.0; 576], 44100, &mut state)
+      .is_err()
+  );
+  assert!(
+    EnergyDetector
+      .infer_window(&[0.0; 512], 16000, &mut state)
+      .is_err()
+  );
+  assert!(
+    EnergyDetector
+      .infer_window(&[0.0; 576], 16000, &mut vec![0.0; 256])
+      .is_err()
+  );
+}
@@ -0,0 +1,214 @@
+"""Generates the speech timestamps tests/segmenter.rs compares the segmenter with.
+
+`speech_timestamps` is the part of `get_speech_timestamps` from silero-vad 5.1.2
+(src/silero_vad/utils_vad.py) that runs after the model, copied as is apart from taking the
+probabilities instead of computing them. The probabilities are made up of blocks of speech and
+silence that hit the rules of the segmenter: short segments, short pauses, merged padding and
+segments split at their maximum length.
+
+    python3 tests/fixtures/generate_segmenter_reference.py
+"""
+
+import json
+from pathlib import Path
+
+HERE = Path(__file__).parent
+
+SPEECH = 0.9
+UNSURE = 0.42
+SILENCE = 0.05
+
+
+def speech_timestamps(
+    speech_probs,
+    audio_length_samples,
+    threshold=0.5,
+    sampling_rate=16000,
+    min_speech_duration_ms=250,
+    max_speech_duration_s=float("inf"),
+    min_silence_duration_ms=100,
+    speech_pad_ms=30,
+    neg_threshold=None,
+):
+    window_size_samples = 512 if sampling_rate == 16000 else 256
+
+    min_speech_samples = sampling_rate * min_speech_duration_ms / 1000
+    speech_pad_samples = sampling_rate * speech_pad_ms / 1000
+    max_speech_samples = sampling_rate * max_speech_duration_s - window_size_samples - 2 * speech_pad_samples
+    min_silence_samples = sampling_rate * min_silence_duration_ms / 1000
+    min_silence_samples_at_max_speech = sampling_rate * 98 / 1000
+
+    triggered = False
+    speeches = []
+    current_speech = {}
+
+    if neg_threshold is None:
+        neg_threshold = max(threshold - 0.15, 0.01)
+    temp_end = 0  # to save potential segment end (and tolerate some silence)
+    prev_end = next_start = 0  # to save potential segment limits in case of maximum segment size reached
+
+    for i, speech_prob in enumerate(speech_probs):
+        if (speech_prob >= threshold) and temp_end:
+            temp_end = 0
+            if next_start < prev_end:
+                next_start = window_size_samples * i
+
+        if (speech_prob >= threshold) and not triggered:
+            triggered = True
+            current_speech["start"] = window_size_samples * i
+            continue
+
+        if triggered and (window_size_samples * i) - current_speech["start"] > max_speech_samples:
+            if prev_end:
+                current_speech["end"] = prev_end
+                speeches.append(current_speech)
+                current_speech = {}
+                if next_start < prev_end:  # previously reached silence (< neg_thres) and is still not speech (< thres)
+                    triggered = False
+                else:
+                    current_speech["start"] = next_start
+                prev_end = next_start = temp_end = 0
+            else:
+                current_speech["end"] = window_size_samples * i
+                speeches.append(current_speech)
+                current_speech = {}
+                prev_end = next_start = temp_end = 0
+                triggered = False
+                continue
+
+        if (speech_prob < neg_threshold) and triggered:
+            if not temp_end:
+                temp_end = window_size_samples * i
+            if ((window_size_samples * i) - temp_end) > min_silence_samples_at_max_speech:  # condition to avoid cutting in very short silence
+                prev_end = temp_end
+            if (window_size_samples * i) - temp_end < min_silence_samples:
+                continue
+            else:
+                current_speech["end"] = temp_end
+                if (current_speech["end"] - current_speech["start"]) > min_speech_samples:
+                    speeches.append(current_speech)
+                current_speech = {}
+                prev_end = next_start = temp_end = 0
+                triggered = False
+                continue
+
+    if current_speech and (audio_length_samples - current_speech["start"]) > min_speech_samples:
+        current_speech["end"] = audio_length_samples
+        speeches.append(current_speech)
+
+    for i, speech in enumerate(speeches):
+        if i == 0:
+            speech["start"] = int(max(0, speech["start"] - speech_pad_samples))
+        if i != len(speeches) - 1:
+            silence_duration = speeches[i + 1]["start"] - speech["end"]
+            if silence_duration < 2 * speech_pad_samples:
+                speech["end"] += int(silence_duration // 2)
+                speeches[i + 1]["start"] = int(max(0, speeches[i + 1]["start"] - silence_duration // 2))
+            else:
+                speech["end"] = int(min(audio_length_samples, speech["end"] + speech_pad_samples))
+                speeches[i + 1]["start"] = int(max(0, speeches[i + 1]["start"] - speech_pad_samples))
+        else:
+            speech["end"] = int(min(audio_length_samples, speech["end"] + speech_pad_samples))
+
+    return speeches
+
+
+def probabilities(blocks):
+    # A little jitter, far from any threshold
+    return [
+        round(level + ((len(blocks) + i) * 37 % 11 - 5) / 1000, 3)
+        for i, level in enumerate(level for count, level in blocks for _ in range(count))
+    ]
+
+
+CASES = [
+    {
+        "name": "defaults",
+        "options": {},
+        "blocks": [
+            (20, SILENCE),
+            (30, SPEECH),
+            (2, UNSURE),  # not speech, but not silence either
+            (20, SPEECH),
+            (5, SILENCE),
+            (3, SPEECH),  # too short
+            (10, SILENCE),
+            (15, SPEECH),
+            (2, SILENCE),  # too short a pause
+            (15, SPEECH),
+            (6, SILENCE),
+            (12, SPEECH),
+            (30, SILENCE),
+            (25, SPEECH),  # runs to the end
+        ],
+        "cut": 100,
+    },
+    {
+        "name": "short-pauses",
+        "options": {"minSilenceDurationMs": 50, "speechPadMs": 60},
+        "blocks": [
+            (3, SPEECH),  # padding stops at the start of the audio
+            (10, SPEECH),
+            (3, SILENCE),
+            (12, SPEECH),
+            (4, SILENCE),
+            (12, SPEECH),
+            (20, SILENCE),
+        ],
+        "cut": 0,
+    },
+    {
+        "name": "max-speech",
+        "options": {
+            "threshold": 0.6,
+            "negThreshold": 0.3,
+            "minSilenceDurationMs": 300,
+            "maxSpeechDurationS": 2.0,
+        },
+        "blocks": [
+            (10, SILENCE),
+            (40, SPEECH),
+            (5, SILENCE),  # long enough to split at, too short to end the segment
+            (40, SPEECH),
+            (5, SILENCE),
+            (3, SPEECH),
+            (20, SILENCE),
+            (150, SPEECH),  # no pause to split at
+            (20, SILENCE),
+        ],
+        "cut": 300,
+    },
+]
+
+PYTHON_OPTIONS = {
+    "threshold": "threshold",
+    "negThreshold": "neg_threshold",
+    "minSpeechDurationMs": "min_speech_duration_ms",
+    "minSilenceDurationMs": "min_silence_duration_ms",
+    "speechPadMs": "speech_pad_ms",
+    "maxSpeechDurationS": "max_speech_duration_s",
+}
+
+
+def main():
+    cases = []
+    for case in CASES:
+        probs = probabilities(case["blocks"])
+        # The last window is zero padded, the audio ends before it does
+        audio_length = len(probs) * 512 - case["cut"]
+        options = {PYTHON_OPTIONS[key]: value for key, value in case["options"].items()}
+        cases.append(
+            {
+                "name": case["name"],
+                "options": case["options"],
+                "audioLength": audio_length,
+                "probabilities": probs,
+                "speeches": speech_timestamps(probs, audio_length, **options),
+            }
+        )
+
+    (HERE / "segmenter_reference.json").write_text(json.dumps(cases, indent=2) + "\n")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,116 @@
+"""Generates the tiny Silero-shaped model used by tests/silero_vad.rs.
+
+The model has the inputs and outputs of the onnx-community export, but no weights, and takes
+16 kHz windows only:
+
+- `input` [batch, 576], `sr` [1] (unused), `state` [2, batch, 128]
+- `output` [batch, 1] = Sigmoid(mean of the input), `stateN` [2, batch, 128] = Sigmoid(state)
+
+Everything is written in plain Python, without the onnx package:
+
+    python3 tests/fixtures/generate_tiny_silero_vad.py
+"""
+
+from pathlib import Path
+
+HERE = Path(__file__).parent
+
+# 64 samples of context in front of a 512 sample window
+INPUT_SIZE = 64 + 512
+STATE_SIZE = 128
+
+# Protocol buffers, just the wire format parts ONNX needs
+
+
+def varint(value):
+    value &= (1 << 64) - 1
+    out = bytearray()
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        if value:
+            out.append(byte | 0x80)
+        else:
+            out.append(byte)
+            return bytes(out)
+
+
+def field_varint(number, value):
+    return varint(number << 3) + varint(value)
+
+
+def field_bytes(number, value):
+    if isinstance(value, str):
+        value = value.encode()
+    return varint(number << 3 | 2) + varint(len(value)) + value
+
+
+def value_info(name, elem_type, dims):
+    # TensorShapeProto.Dimension: dim_value = 1, dim_param = 2
+    shape = b"".join(
+        field_bytes(1, field_bytes(2, d) if isinstance(d, str) else field_varint(1, d)) for d in dims
+    )
+    # TypeProto.Tensor: elem_type = 1, shape = 2
+    tensor_type = field_varint(1, elem_type) + field_bytes(2, shape)
+    # ValueInfoProto: name = 1, type = 2 (TypeProto: tensor_type = 1)
+    return field_bytes(1, name) + field_bytes(2, field_bytes(1, tensor_type))
+
+
+def attribute_ints(name, values):
+    # AttributeProto: name = 1, ints = 8, type = 20 (INTS = 7)
+    return field_bytes(1, name) + b"".join(field_varint(8, v) for v in values) + field_varint(20, 7)
+
+
+def node(op_type, inputs, outputs, attributes=()):
+    # NodeProto: input = 1, output = 2, name = 3, op_type = 4, attribute = 5
+    return (
+        b"".join(field_bytes(1, i) for i in inputs)
+        + b"".join(field_bytes(2, o) for o in outputs)
+        + field_bytes(3, outputs[0])
+        + field_bytes(4, op_type)
+        + b"".join(field_bytes(5, a) for a in attributes)
+    )
+
+
+def model(name, nodes, inputs, outputs):
+    # GraphProto: node = 1, name = 2, input = 11, output = 12
+    graph = (
+        b"".join(field_bytes(1, n) for n in nodes)
+        + field_bytes(2, name)
+        + b"".join(field_bytes(11, i) for i in inputs)
+        + b"".join(field_bytes(12, o) for o in outputs)
+    )
+    # ModelProto: ir_version = 1, producer_name = 2, graph = 7, opset_import = 8 (version = 2)
+    return (
+        field_varint(1, 8)
+        + field_bytes(2, "generate_tiny_silero_vad.py")
+        + field_bytes(7, graph)
+        + field_bytes(8, field_varint(2, 17))
+    )
+
+
+def main():
+    (HERE / "tiny-silero-vad.onnx").write_bytes(
+        model(
+            "tiny-silero-vad",
+            [
+                node("ReduceMean", ["input"], ["mean"], [attribute_ints("axes", [1])]),
+                node("Sigmoid", ["mean"], ["output"]),
+                node("Sigmoid", ["state"], ["stateN"]),
+            ],
+            [
+                # FLOAT = 1, INT64 = 7
+                value_info("input", 1, ["batch_size", INPUT_SIZE]),
+                value_info("sr", 7, [1]),
+                value_info("state", 1, [2, "batch_size", STATE_SIZE]),
+            ],
+            [
+                value_info("output", 1, ["batch_size", 1]),
+                value_info("stateN", 1, [2, "batch_size", STATE_SIZE]),
+            ],
+        )
+    )
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,164 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Result;
+use serde_json::Value;
+use tauri_plugin_ipc_audio_vad_ort::{
+  SegmenterOptions,
+  SpeechEvent,
+  SpeechSegmenter,
+  VadBackend,
+  VadBackendKind,
+  offline::{SpeechTimestamp, collect_speech, detect_speech_segments},
+};
+
+const WINDOW_SIZE: usize = 512;
+const CONTEXT_SIZE: usize = 64;
+
+/// Scores a window with the mean of its samples. Keeps no state, so lanes score every window
+/// the same as a run from the start.
+#[derive(Default)]
+struct Level {
+  max_batch: AtomicUsize,
+}
+
+impl VadBackend for Level {
+  fn kind(&self) -> VadBackendKind {
+    VadBackendKind::Energy
+  }
+
+  fn state_size(&self) -> usize {
+    1
+  }
+
+  fn infer_window(
+    &self,
+    input: &[f32],
+    _sr: i64,
+    _state: &mut Vec<f32>,
+  ) -> Result<f32> {
+    Ok(input[CONTEXT_SIZE..].iter().sum::<f32>() / WINDOW_SIZE as f32)
+  }
+
+  fn infer_batch(
+    &self,
+    input: &[f32],
+    batch: usize,
+    sr: i64,
+    state: &mut Vec<f32>,
+  ) -> Result<Vec<f32>> {
+    self.max_batch.fetch_max(batch, Ordering::Relaxed);
+    input
+      .chunks_exact(input.len() / batch)
+      .map(|window| self.infer_window(window, sr, state))
+      .collect()
+  }
+}
+
+// Probabilities and what `get_speech_timestamps` makes of them, see
+// tests/fixtures/generate_segmenter_reference.py
+fn reference() -> Value {
+  let cases: Vec<Value> =
+    serde_json::from_str(include_str!("fixtures/segmenter_reference.json")).unwrap();
+  cases[0].clone()
+}
+
+/// Audio whose windows have the given levels.
+fn audio(levels: &[f32]) -> Vec<f32> {
+  levels
+    .iter()
+    .flat_map(|&level| [level; WINDOW_SIZE])
+    .collect()
+}
+
+fn pairs(segments: &[SpeechTimestamp]) -> Vec<(u64, u64)> {
+  segments
+    .iter()
+    .map(|segment| (segment.start, segment.end))
+    .collect()
+}
+
+#[test]
+fn finds_the_segments_of_get_speech_timestamps() {
+  let reference = reference();
+  let levels: Vec<f32> = serde_json::from_value(reference["probabilities"].clone()).unwrap();
+  let mut audio = audio(&levels);
+  audio.truncate(reference["audioLength"].as_u64().unwrap() as usize);
+
+  let backend = Level::default();
+  let segments = detect_speech_segments(&backend, &audio, &SegmenterOptions::default()).unwrap();
+
+  let expected: Vec<(u64, u64)> = reference["speeches"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|speech| {
+      (
+        speech["start"].as_u64().unwrap(),
+        speech["end"].as_u64().unwrap(),
+      )
+    })
+    .collect();
+  assert_eq!(pairs(&segments), expected);
+  // Short enough for a single lane
+  assert_eq!(backend.max_batch.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn scores_long_audio_in_lanes() {
+  let reference = reference();
+  let levels: Vec<f32> = serde_json::from_value(reference["probabilities"].clone()).unwrap();
+  // About a minute
+  let levels = levels.repeat(10);
+
+  let backend = Level::default();
+  let options = SegmenterOptions::default();
+  let segments = detect_speech_segments(&backend, &audio(&levels), &options).unwrap();
+  assert!(backend.max_batch.load(Ordering::Relaxed) > 1);
+
+  // Window by window from the start
+  let mut segmenter = SpeechSegmenter::new(16000, WINDOW_SIZE, &options);
+  let mut events = Vec::new();
+  for &level in &levels {
+    events.extend(segmenter.push(level));
+  }
+  events.extend(segmenter.finish((levels.len() * WINDOW_SIZE) as u64));
+  let expected: Vec<(u64, u64)> = events
+    .into_iter()
+    .filter_map(|event| match event {
+      SpeechEvent::SpeechEnd { start, end } => Some((start, end)),
+      SpeechEvent::SpeechStart { .. } => None,
+    })
+    .collect();
+
+  assert_eq!(expected.len(), 40);
+  assert_eq!(pairs(&segments), expected);
+}
+
+#[test]
+fn finds_nothing_in_silence() {
+  let backend = Level::default();
+  let options = SegmenterOptions::default();
+
+  assert!(
+    detect_speech_segments(&backend, &[], &options)
+      .unwrap()
+      .is_empty()
+  );
+  assert!(
+    detect_speech_segments(&backend, &[0.0; 16000], &options)
+      .unwrap()
+      .is_empty()
+  );
+}
+
+#[test]
+fn collects_the_speech() {
+  let audio: Vec<f32> = (0..10).map(|i| i as f32).collect();
+  let segments = [
+    SpeechTimestamp { start: 2, end: 4 },
+    SpeechTimestamp { start: 6, end: 7 },
+  ];
+
+  assert_eq!(collect_speech(&audio, &segments), [2.0, 3.0, 6.0]);
+  assert!(collect_speech(&audio, &[]).is_empty());
+}
@@ -0,0 +1,96 @@
+use serde_json::Value;
+use tauri_plugin_ipc_audio_vad_ort::{SegmenterOptions, SpeechEvent, SpeechSegmenter};
+
+// Probabilities and what `get_speech_timestamps` makes of them, see
+// tests/fixtures/generate_segmenter_reference.py
+fn cases() -> Vec<Value> {
+  serde_json::from_str(include_str!("fixtures/segmenter_reference.json")).unwrap()
+}
+
+fn speeches(
+  options: &SegmenterOptions,
+  probabilities: &[f32],
+  audio_length: u64,
+) -> Vec<(u64, u64)> {
+  let mut segmenter = SpeechSegmenter::new(16000, 512, options);
+
+  let mut events = Vec::new();
+  for &probability in probabilities {
+    events.extend(segmenter.push(probability));
+  }
+  events.extend(segmenter.finish(audio_length));
+
+  // Every end comes after the start of its segment, which is where the end says it started
+  let mut started = None;
+  let mut speeches = Vec::new();
+  for event in events {
+    match event {
+      SpeechEvent::SpeechStart { start } => {
+        assert_eq!(started.replace(start), None, "speech started twice");
+      },
+      SpeechEvent::SpeechEnd { start, end } => {
+        assert_eq!(started.take(), Some(start));
+        speeches.push((start, end));
+      },
+    }
+  }
+  assert_eq!(started, None, "speech did not end");
+
+  speeches
+}
+
+#[test]
+fn matches_get_speech_timestamps() {
+  for case in cases() {
+    let options: SegmenterOptions = serde_json::from_value(case["options"].clone()).unwrap();
+    let probabilities: Vec<f32> = serde_json::from_value(case["probabilities"].clone()).unwrap();
+    let expected: Vec<(u64, u64)> = case["speeches"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|speech| {
+        (
+          speech["start"].as_u64().unwrap(),
+          speech["end"].as_u64().unwrap(),
+        )
+      })
+      .collect();
+
+    assert_eq!(
+      speeches(
+        &options,
+        &probabilities,
+        case["audioLength"].as_u64().unwrap()
+      ),
+      expected,
+      "{}",
+      case["name"]
+    );
+  }
+}
+
+#[test]
+fn starts_again_after_a_reset() {
+  let case = &cases()[0];
+  let options = SegmenterOptions::default();
+  let probabilities: Vec<f32> = serde_json::from_value(case["probabilities"].clone()).unwrap();
+  let audio_length = case["audioLength"].as_u64().unwrap();
+
+  let mut segmenter = SpeechSegmenter::new(16000, 512, &options);
+  for &probability in &probabilities[..40] {
+    segmenter.push(probability);
+  }
+  segmenter.reset();
+
+  let mut events = Vec::new();
+  for &probability in &probabilities {
+    events.extend(segmenter.push(probability));
+  }
+  events.extend(segmenter.finish(audio_length));
+
+  let ends = events
+    .iter()
+    .filter(|event| matches!(event, SpeechEvent::SpeechEnd { .. }))
+    .count();
+  assert_eq!(ends, case["speeches"].as_array().unwrap().len());
+}
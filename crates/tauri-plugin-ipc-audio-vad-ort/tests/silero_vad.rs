@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+
+use tauri_plugin_ipc_audio_vad_ort::{SileroVad, VadBackend};
+
+// A Silero-shaped model without weights, see tests/fixtures/generate_tiny_silero_vad.py
+fn model() -> SileroVad {
+  let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tiny-silero-vad.onnx");
+  SileroVad::from_file(&path).unwrap()
+}
+
+#[test]
+fn replaces_the_state_after_a_window() {
+  let vad = model();
+  let mut state = vec![0.0; vad.state_size()];
+
+  let probability = vad
+    .infer_window(&[0.0; 576], 16000, &mut state)
+    .unwrap();
+
+  assert!((probability - 0.5).abs() < 1e-6);
+  assert!(
+    state
+      .iter()
+      .all(|&value| (value - 0.5).abs() < 1e-6)
+  );
+}
+
+#[test]
+fn keeps_the_state_when_inference_fails() {
+  let vad = model();
+  let mut state = vec![0.25; vad.state_size()];
+
+  // The model only takes windows of 576 samples, context included
+  assert!(
+    vad
+      .infer_window(&[0.0; 100], 16000, &mut state)
+      .is_err()
+  );
+  assert_eq!(state, vec![0.25; vad.state_size()]);
+}
@@ -1,5 +1,6 @@
 export * from './audio'
 export * from './llmmarkerParser'
+export * from './local-speech'
 export * from './markdown'
 export * from './micvad'
 export * from './queue'
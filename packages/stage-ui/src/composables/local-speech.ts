@@ -0,0 +1,82 @@
+import { onUnmounted } from 'vue'
+
+export const LOCAL_SPEECH_PROVIDER_ID = 'app-local-audio-speech'
+
+/** An utterance cut off by a barge-in, as emitted by the TTS plugin */
+export interface LocalSpeechInterrupted {
+  utteranceId: string
+  text: string
+  spokenText: string
+  spokenMs: number
+  durationMs?: number | null
+}
+
+/**
+ * Reports the playback of utterances synthesized by the local TTS plugin, so that it knows what
+ * is coming out of the speakers (for echo cancellation) and can cut it off when the user talks
+ * over it. Outside of Tauri every call does nothing.
+ */
+export function useLocalSpeechPlayback(options: { onInterrupted?: (interrupted: LocalSpeechInterrupted) => void } = {}) {
+  const interrupted = new Set<string>()
+  let unlisten: (() => void) | undefined
+  let disposed = false
+
+  async function invoke(cmd: string, args: Record<string, unknown>) {
+    try {
+      const core = await import('@tauri-apps/api/core')
+      await core.invoke(`plugin:ipc-audio-tts-ort|${cmd}`, args)
+    }
+    catch (error) {
+      console.warn(`Failed to report ${cmd} to the local TTS:`, error)
+    }
+  }
+
+  async function listenInterrupted() {
+    try {
+      const { listen } = await import('@tauri-apps/api/event')
+      const stop = await listen<LocalSpeechInterrupted>('tauri-plugins:tauri-plugin-ipc-audio-tts-ort:interrupted', (event) => {
+        interrupted.add(event.payload.utteranceId)
+        options.onInterrupted?.(event.payload)
+      })
+      if (disposed)
+        stop()
+      else
+        unlisten = stop
+    }
+    catch {
+      // Not running in Tauri, nothing can be interrupted
+    }
+  }
+
+  function newUtteranceId() {
+    return crypto.randomUUID()
+  }
+
+  /** Whether the utterance was cut off, its audio should not be played anymore */
+  function isInterrupted(utteranceId: string) {
+    return interrupted.has(utteranceId)
+  }
+
+  async function started(utteranceId: string) {
+    await invoke('playback_started', { utteranceId })
+  }
+
+  /** Also to be called when the audio is dropped without being played */
+  async function finished(utteranceId: string) {
+    interrupted.delete(utteranceId)
+    await invoke('playback_finished', { utteranceId })
+  }
+
+  listenInterrupted()
+  onUnmounted(() => {
+    disposed = true
+    unlisten?.()
+  })
+
+  return {
+    newUtteranceId,
+    isInterrupted,
+    started,
+    finished,
+  }
+}
@@ -285,7 +285,9 @@ export const useProvidersStore = defineStore('providers', () => {
         return {
           speech: (model: string, options: Record<string, any>) => ({
             model,
-            generateSpeech: async ({ input, voice }: { input: string, voice: string }) => {
+            // With an `utteranceId` the plugin tracks the utterance until its playback is reported
+            // finished, and rejects with `cancelled` if it is interrupted before the audio is ready
+            generateSpeech: async ({ input, voice, utteranceId }: { input: string, voice: string, utteranceId?: string }) => {
               try {
                 // Validate inputs at provider level
                 if (!input || typeof input !== 'string') {
@@ -314,6 +316,7 @@ export const useProvidersStore = defineStore('providers', () => {
                   text: input,
                   voiceId: voice,
                   options: synthesisOptions,
+                  utteranceId,
                 }) as number[]
 
                 // Validate result
@@ -338,6 +341,10 @@ export const useProvidersStore = defineStore('providers', () => {
                   if (typeof error === 'string') {
                     return error
                   }
+                  // The plugin rejects with `{ code, message }`
+                  if (typeof error === 'object' && 'message' in error && typeof error.message === 'string') {
+                    return error.message
+                  }
                   try {
                     return String(error)
                   }
@@ -346,7 +353,8 @@ export const useProvidersStore = defineStore('providers', () => {
                   }
                 })()
 
-                throw new Error(errorMessage)
+                // Keeps the `{ code, message }` of the plugin, e.g. to tell an interruption apart
+                throw new Error(errorMessage, { cause: error })
               }
             },
           }),
@@ -488,14 +496,13 @@ export const useProvidersStore = defineStore('providers', () => {
             model,
             transcribe: async ({ audio, language }: { audio: File | Blob, language?: string }) => {
               const arrayBuffer = await audio.arrayBuffer()
-              const float32Array = new Float32Array(arrayBuffer)
 
               const result = await invoke('plugin:ipc-audio-transcription-ort|ipc_audio_transcription', {
-                chunk: Array.from(float32Array),
+                encoded: Array.from(new Uint8Array(arrayBuffer)),
                 language: language || 'en',
-              }) as string
+              }) as { text: string }
 
-              return { text: result }
+              return { text: result.text }
             },
           }),
         }
@@ -550,9 +557,9 @@ export const useProvidersStore = defineStore('providers', () => {
           })
 
           try {
-            const modelType = (config.modelId as string | undefined)?.replace('whisper-', '').replace('-', '') || 'base'
             await invoke('plugin:ipc-audio-transcription-ort|load_ort_model_whisper', {
-              modelType,
+              modelType: (config.modelId as string | undefined) || 'whisper-base',
+              variant: config.variant as string | undefined,
             })
           }
           finally {